@@ -0,0 +1,186 @@
+//! Statistics about the fetched roads, mostly for data-art purposes.
+
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use crate::{LatLon, Road};
+
+/// A named street made of one or more connected ways.
+#[derive(Debug, Clone)]
+pub struct Street {
+    pub name: String,
+
+    /// Total length of the ways on the ground, in meters.
+    pub length: f64,
+
+    /// Ids of the ways of this street.
+    pub ways: Vec<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ranking {
+    Longest,
+    Shortest,
+}
+
+/// Which streets to highlight, e.g. `longest 5` or `shortest 3`. A count of zero, written as
+/// `none`, highlights nothing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreetHighlight {
+    pub ranking: Ranking,
+    pub count: usize,
+}
+
+/// Group the named roads into streets.
+///
+/// Ways with the same name are part of the same street only if they're connected, so that
+/// homonymous streets in different neighborhoods are kept apart.
+pub fn streets(roads: &[Road]) -> Vec<Street> {
+    let mut by_name: BTreeMap<&str, Vec<&Road>> = BTreeMap::new();
+    for r in roads {
+        if let Some(name) = r.tags.get("name") {
+            if r.tags.contains_key("highway") && r.points.len() > 1 {
+                by_name.entry(name).or_default().push(r);
+            }
+        }
+    }
+
+    let mut streets = vec![];
+    for (name, mut ways) in by_name {
+        while let Some(first) = ways.pop() {
+            let mut component = vec![first];
+
+            // grow the component until no remaining way touches it
+            let mut i = 0;
+            while i < component.len() {
+                let (touching, rest) = ways
+                    .into_iter()
+                    .partition::<Vec<_>, _>(|w| touches(component[i], w));
+                component.extend(touching);
+                ways = rest;
+                i += 1;
+            }
+
+            streets.push(Street {
+                name: name.to_string(),
+                length: component.iter().map(|w| ground_length(&w.points)).sum(),
+                ways: component.iter().map(|w| w.id).collect(),
+            });
+        }
+    }
+
+    streets
+}
+
+/// The streets to highlight in order of ranking.
+pub fn rank(mut streets: Vec<Street>, highlight: StreetHighlight) -> Vec<Street> {
+    streets.sort_by(|a, b| match highlight.ranking {
+        Ranking::Longest => b.length.total_cmp(&a.length),
+        Ranking::Shortest => a.length.total_cmp(&b.length),
+    });
+    streets.truncate(highlight.count);
+    streets
+}
+
+/// The length in meters of the given path in Web Mercator, which stretches distances by the
+/// secant of the latitude.
+fn ground_length(points: &[(f64, f64)]) -> f64 {
+    points
+        .windows(2)
+        .map(|w| LatLon::from_xy(w[0]).distance(LatLon::from_xy(w[1])))
+        .sum()
+}
+
+fn touches(a: &Road, b: &Road) -> bool {
+    let ends = |r: &Road| [r.points[0], r.points[r.points.len() - 1]];
+    ends(a).iter().any(|p| ends(b).contains(p))
+}
+
+impl StreetHighlight {
+    pub fn none() -> Self {
+        StreetHighlight {
+            ranking: Ranking::Longest,
+            count: 0,
+        }
+    }
+
+    pub fn is_none(&self) -> bool {
+        self.count == 0
+    }
+}
+
+impl fmt::Display for Street {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.length >= 1000.0 {
+            write!(f, "{} ({:.1} km)", self.name, self.length / 1000.0)
+        } else {
+            write!(f, "{} ({:.0} m)", self.name, self.length)
+        }
+    }
+}
+
+impl FromStr for StreetHighlight {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s == "none" {
+            return Ok(Self::none());
+        }
+
+        let (ranking, count) = s.split_once(' ').unwrap_or((s, "1"));
+        let ranking = match ranking {
+            "longest" => Ranking::Longest,
+            "shortest" => Ranking::Shortest,
+            _ => return Err(format!("invalid street ranking '{}'", ranking)),
+        };
+        let count = count
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid number of streets '{}'", count.trim()))?;
+
+        Ok(StreetHighlight { ranking, count })
+    }
+}
+
+impl fmt::Display for StreetHighlight {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_none() {
+            return f.write_str("none");
+        }
+
+        let ranking = match self.ranking {
+            Ranking::Longest => "longest",
+            Ranking::Shortest => "shortest",
+        };
+        write!(f, "{} {}", ranking, self.count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lengths_are_on_the_ground() {
+        // a kilometer eastwards at 45°, where web mercator stretches it to ~1.41 km
+        let start = LatLon::new(45.0, 7.0);
+        let end = LatLon::new(45.0, 7.0 + 1000.0 / (111_319.5 * 45_f64.to_radians().cos()));
+        let road = Road {
+            id: 1,
+            tags: [("highway", "residential"), ("name", "Via Roma")]
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            points: vec![start.to_xy(), end.to_xy()],
+            holes: vec![],
+        };
+
+        let streets = streets(&[road]);
+        assert_eq!(streets.len(), 1);
+        assert!(
+            (streets[0].length - 1000.0).abs() < 1.0,
+            "{}",
+            streets[0].length
+        );
+    }
+}
@@ -0,0 +1,87 @@
+//! On-disk cache of the Nominatim and Overpass responses, so that rendering the same place again
+//! with different options doesn't download everything again.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::util::{fnv1a, write_file_atomic};
+
+/// A directory of responses keyed by the hash of the request that produced them.
+///
+/// Entries older than the time to live are ignored and overwritten by the next response. Failing
+/// to read or write an entry is never an error, the request is just sent again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+impl Cache {
+    /// How long entries are kept by default, OpenStreetMap data changes slowly enough.
+    pub const DEFAULT_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+    pub fn new(dir: impl Into<PathBuf>, ttl: Duration) -> Self {
+        Cache {
+            dir: dir.into(),
+            ttl,
+        }
+    }
+
+    /// The cache in the user cache directory, if there's one.
+    pub fn user() -> Option<Self> {
+        Some(Self::new(Self::default_dir()?, Self::DEFAULT_TTL))
+    }
+
+    /// `$XDG_CACHE_HOME/roads`, `~/.cache/roads` or `%LOCALAPPDATA%\roads`.
+    pub fn default_dir() -> Option<PathBuf> {
+        let var = |name| std::env::var_os(name).filter(|v| !v.is_empty());
+
+        let base = var("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| var("HOME").map(|h| Path::new(&h).join(".cache")))
+            .or_else(|| var("LOCALAPPDATA").map(PathBuf::from))?;
+
+        Some(base.join("roads"))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// The response stored for the given request, unless it expired.
+    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let path = self.path(key);
+
+        let age = fs::metadata(&path).ok()?.modified().ok()?.elapsed().ok()?;
+        if age > self.ttl {
+            return None;
+        }
+
+        fs::read(path).ok()
+    }
+
+    pub fn put(&self, key: &str, response: &[u8]) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        write_file_atomic(self.path(key), response)
+    }
+
+    /// Remove all the entries.
+    pub fn clear(&self) -> io::Result<()> {
+        match fs::remove_dir_all(&self.dir) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            r => r,
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir
+            .join(format!("{:016x}.json", fnv1a(key.as_bytes())))
+    }
+}
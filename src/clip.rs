@@ -0,0 +1,232 @@
+//! Clipping of paths against arbitrary polygons, possibly with holes.
+
+use crate::{multipolygon, pipeline::PathTransform};
+
+/// Keeps only the parts of the paths that are inside the given rings, using the even-odd rule so
+/// that holes are honored.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clip {
+    rings: Vec<Vec<(f64, f64)>>,
+}
+
+impl Clip {
+    pub fn new(rings: Vec<Vec<(f64, f64)>>) -> Self {
+        Clip { rings }
+    }
+
+    /// Whether the given point is inside the clipping area.
+    pub fn contains(&self, p: (f64, f64)) -> bool {
+        self.rings
+            .iter()
+            .filter(|r| multipolygon::contains(r, p))
+            .count()
+            % 2
+            == 1
+    }
+
+    /// Split the given path into the pieces that are inside the clipping area.
+    pub fn clip_path(&self, path: &[(f64, f64)]) -> Vec<Vec<(f64, f64)>> {
+        if path.len() < 2 {
+            return if path.iter().all(|p| self.contains(*p)) {
+                vec![path.to_vec()]
+            } else {
+                vec![]
+            };
+        }
+
+        let mut pieces = vec![];
+        let mut current: Vec<(f64, f64)> = vec![];
+
+        for w in path.windows(2) {
+            let (a, b) = (w[0], w[1]);
+
+            let mut ts = vec![0.0, 1.0];
+            ts.extend(self.crossings(a, b));
+            ts.sort_by(f64::total_cmp);
+            ts.dedup();
+
+            for t in ts.windows(2) {
+                let p0 = lerp(a, b, t[0]);
+                let p1 = lerp(a, b, t[1]);
+
+                if self.contains(lerp(a, b, (t[0] + t[1]) / 2.0)) {
+                    if current.last() != Some(&p0) {
+                        if current.len() > 1 {
+                            pieces.push(std::mem::take(&mut current));
+                        }
+                        current = vec![p0];
+                    }
+                    current.push(p1);
+                } else if current.len() > 1 {
+                    pieces.push(std::mem::take(&mut current));
+                } else {
+                    current.clear();
+                }
+            }
+        }
+
+        if current.len() > 1 {
+            pieces.push(current);
+        }
+
+        pieces
+    }
+
+    /// Parallel lines the given distance apart filling the clipping area, at the given angle in
+    /// degrees counterclockwise from the x axis. Handy to shade areas with a pen plotter.
+    pub fn hatch(&self, spacing: f64, angle: f64) -> Vec<Vec<(f64, f64)>> {
+        let points = self.rings.iter().flatten();
+        if spacing <= 0.0 || self.rings.is_empty() {
+            return vec![];
+        }
+
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let along = |(x, y): &(f64, f64)| x * cos + y * sin;
+        let across = |(x, y): &(f64, f64)| y * cos - x * sin;
+
+        let range = |f: &dyn Fn(&(f64, f64)) -> f64| {
+            points
+                .clone()
+                .map(f)
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| {
+                    (lo.min(v), hi.max(v))
+                })
+        };
+        let (start, end) = range(&along);
+        let (lo, hi) = range(&across);
+
+        let first = (lo / spacing).ceil() as i64;
+        let last = (hi / spacing).floor() as i64;
+        (first..=last)
+            .flat_map(|i| {
+                let o = i as f64 * spacing;
+                let point = |a: f64| (a * cos - o * sin, a * sin + o * cos);
+                self.clip_path(&[point(start), point(end)])
+            })
+            .collect()
+    }
+
+    /// The parameters along `a -> b` where the segment crosses the boundary of the clipping area.
+    fn crossings(&self, a: (f64, f64), b: (f64, f64)) -> Vec<f64> {
+        let mut ts = vec![];
+
+        for ring in &self.rings {
+            for (i, &c) in ring.iter().enumerate() {
+                let d = ring[(i + 1) % ring.len()];
+                if let Some(t) = intersection(a, b, c, d) {
+                    ts.push(t);
+                }
+            }
+        }
+
+        ts
+    }
+}
+
+impl PathTransform for Clip {
+    fn apply(&self, paths: Vec<Vec<(f64, f64)>>) -> Vec<Vec<(f64, f64)>> {
+        paths.iter().flat_map(|p| self.clip_path(p)).collect()
+    }
+}
+
+/// The parameter along `a -> b` of the intersection with the segment `c -> d`, if any.
+fn intersection(a: (f64, f64), b: (f64, f64), c: (f64, f64), d: (f64, f64)) -> Option<f64> {
+    let r = (b.0 - a.0, b.1 - a.1);
+    let s = (d.0 - c.0, d.1 - c.1);
+
+    let den = r.0 * s.1 - r.1 * s.0;
+    if den == 0.0 {
+        return None;
+    }
+
+    let t = ((c.0 - a.0) * s.1 - (c.1 - a.1) * s.0) / den;
+    let u = ((c.0 - a.0) * r.1 - (c.1 - a.1) * r.0) / den;
+
+    if (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u) {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+fn lerp(a: (f64, f64), b: (f64, f64), t: f64) -> (f64, f64) {
+    (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(x: f64, y: f64, size: f64) -> Vec<(f64, f64)> {
+        vec![
+            (x, y),
+            (x + size, y),
+            (x + size, y + size),
+            (x, y + size),
+            (x, y),
+        ]
+    }
+
+    /// A 10 by 10 square with a 2 by 2 hole.
+    fn holed() -> Clip {
+        Clip::new(vec![square(0.0, 0.0, 10.0), square(4.0, 4.0, 2.0)])
+    }
+
+    #[test]
+    fn contains() {
+        let clip = holed();
+        assert!(clip.contains((1.0, 1.0)));
+        assert!(clip.contains((8.0, 5.0)));
+        assert!(!clip.contains((5.0, 5.0)));
+        assert!(!clip.contains((20.0, 20.0)));
+    }
+
+    #[test]
+    fn path_inside_or_outside() {
+        let clip = holed();
+        let inside = vec![(1.0, 1.0), (3.0, 3.0), (1.0, 8.0)];
+        assert_eq!(clip.clip_path(&inside), vec![inside]);
+        assert!(clip.clip_path(&[(20.0, 0.0), (30.0, 0.0)]).is_empty());
+        assert!(clip.clip_path(&[(4.5, 4.5), (5.5, 5.5)]).is_empty());
+
+        assert_eq!(clip.clip_path(&[(1.0, 1.0)]), vec![vec![(1.0, 1.0)]]);
+        assert!(clip.clip_path(&[(5.0, 5.0)]).is_empty());
+    }
+
+    #[test]
+    fn path_crossing_the_boundary() {
+        let clip = holed();
+        assert_eq!(
+            clip.clip_path(&[(-4.0, 1.0), (4.0, 1.0)]),
+            vec![vec![(0.0, 1.0), (4.0, 1.0)]]
+        );
+
+        // in and out of the square and of its hole
+        assert_eq!(
+            clip.clip_path(&[(-4.0, 5.0), (12.0, 5.0)]),
+            vec![vec![(0.0, 5.0), (4.0, 5.0)], vec![(6.0, 5.0), (10.0, 5.0)]]
+        );
+
+        // the pieces of consecutive segments inside are joined
+        assert_eq!(
+            clip.clip_path(&[(1.0, -1.0), (1.0, 2.0), (12.0, 2.0)]),
+            vec![vec![(1.0, 0.0), (1.0, 2.0), (10.0, 2.0)]]
+        );
+    }
+
+    #[test]
+    fn hatch() {
+        let clip = Clip::new(vec![square(-1.0, -1.0, 10.0), square(3.0, 4.0, 2.0)]);
+        assert_eq!(
+            clip.hatch(5.0, 0.0),
+            vec![
+                vec![(-1.0, 0.0), (9.0, 0.0)],
+                vec![(-1.0, 5.0), (3.0, 5.0)],
+                vec![(5.0, 5.0), (9.0, 5.0)],
+            ]
+        );
+
+        assert!(clip.hatch(0.0, 0.0).is_empty());
+        assert!(Clip::new(vec![]).hatch(1.0, 45.0).is_empty());
+    }
+}
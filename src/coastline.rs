@@ -0,0 +1,305 @@
+//! Land and sea polygons built from the [coastline] of OpenStreetMap, so that the sea of seaside
+//! places can be told apart from the land.
+//!
+//! The coastline is made of ways having the land on their left and the water on their right. The
+//! ones crossing the frame of the map are closed against its sides, going around the frame
+//! clockwise from where a coastline leaves it to where the next one enters it, while the closed
+//! ones inside the frame are islands.
+//!
+//! [coastline]: https://wiki.openstreetmap.org/wiki/Tag:natural%3Dcoastline
+
+use crate::{clip::Clip, Road};
+
+/// Selector of the coastline ways.
+pub const COASTLINE_SELECTOR: &str = "way[natural=coastline]";
+
+/// Whether the given road is part of the coastline.
+pub fn is_coastline(r: &Road) -> bool {
+    r.tags.get("natural").is_some_and(|v| v == "coastline")
+}
+
+/// The rings of the sea inside the rectangle going from `min` to `max`, to be filled with the
+/// even-odd rule, given the coastline ways in the same coordinates with the y axis pointing
+/// north.
+///
+/// Without any coastline crossing the frame, the frame is all sea or all land depending on which
+/// side of the closest coastline it is. It's all land if there's no coastline at all.
+pub fn sea(
+    coastlines: &[Vec<(f64, f64)>],
+    min: (f64, f64),
+    max: (f64, f64),
+) -> Vec<Vec<(f64, f64)>> {
+    let frame = Frame { min, max };
+    let chains = join_directed(coastlines.iter().filter(|c| c.len() > 1).cloned().collect());
+    if chains.is_empty() || frame.perimeter() <= 0.0 {
+        return vec![];
+    }
+
+    let clip = Clip::new(vec![frame.ring()]);
+    let pieces = join_directed(chains.iter().flat_map(|c| clip.clip_path(c)).collect());
+    let (islands, open): (Vec<_>, Vec<_>) = pieces
+        .into_iter()
+        .partition(|p| p.len() > 3 && p.first() == p.last());
+
+    // the islands never reach the sides of the frame, so a corner is on the same side as the
+    // whole frame
+    let mut rings = if open.is_empty() {
+        if is_water(&chains, min) {
+            vec![frame.ring()]
+        } else {
+            vec![]
+        }
+    } else {
+        frame.close(open)
+    };
+
+    rings.extend(islands);
+    rings
+}
+
+/// The rectangle the coastline is closed against.
+struct Frame {
+    min: (f64, f64),
+    max: (f64, f64),
+}
+
+/// A piece of coastline crossing the frame, along with where it enters and leaves it as
+/// distances along the sides of the frame, see [`Frame::position`].
+struct Piece {
+    path: Vec<(f64, f64)>,
+    entry: f64,
+    exit: f64,
+}
+
+impl Frame {
+    fn size(&self) -> (f64, f64) {
+        (self.max.0 - self.min.0, self.max.1 - self.min.1)
+    }
+
+    fn perimeter(&self) -> f64 {
+        let (w, h) = self.size();
+        2.0 * (w + h)
+    }
+
+    fn ring(&self) -> Vec<(f64, f64)> {
+        let (x0, y0) = self.min;
+        let (x1, y1) = self.max;
+        vec![(x0, y1), (x1, y1), (x1, y0), (x0, y0), (x0, y1)]
+    }
+
+    /// Distance along the sides of the frame of the point of the sides closest to the given one,
+    /// clockwise from the top left corner.
+    fn position(&self, (x, y): (f64, f64)) -> f64 {
+        let (x0, y0) = self.min;
+        let (x1, y1) = self.max;
+        let (w, h) = self.size();
+        let x = x.clamp(x0, x1);
+        let y = y.clamp(y0, y1);
+
+        let sides = [
+            (y1 - y, x - x0),
+            (x1 - x, w + y1 - y),
+            (y - y0, w + h + x1 - x),
+            (x - x0, 2.0 * w + h + y - y0),
+        ];
+        sides
+            .into_iter()
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map_or(0.0, |(_, t)| t)
+    }
+
+    /// The point of the sides of the frame at the given distance, see [`Frame::position`].
+    fn point(&self, t: f64) -> (f64, f64) {
+        let (x0, y0) = self.min;
+        let (x1, y1) = self.max;
+        let (w, h) = self.size();
+        let t = t.rem_euclid(self.perimeter());
+
+        if t < w {
+            (x0 + t, y1)
+        } else if t < w + h {
+            (x1, y1 - (t - w))
+        } else if t < 2.0 * w + h {
+            (x1 - (t - w - h), y0)
+        } else {
+            (x0, y0 + (t - 2.0 * w - h))
+        }
+    }
+
+    /// How far `b` is from `a` going clockwise along the sides.
+    fn distance(&self, a: f64, b: f64) -> f64 {
+        (b - a).rem_euclid(self.perimeter())
+    }
+
+    /// Close the given pieces of coastline into the rings of the sea by following the sides of
+    /// the frame clockwise, i.e. keeping the water on the right.
+    fn close(&self, open: Vec<Vec<(f64, f64)>>) -> Vec<Vec<(f64, f64)>> {
+        let pieces = open
+            .into_iter()
+            .map(|mut path| {
+                // pieces ending inside the frame, because the coastline is incomplete, are
+                // extended to the closest side
+                let entry = self.position(path[0]);
+                let exit = self.position(path[path.len() - 1]);
+                path.insert(0, self.point(entry));
+                path.push(self.point(exit));
+                path.dedup();
+                Piece { path, entry, exit }
+            })
+            .collect::<Vec<_>>();
+
+        let (w, h) = self.size();
+        let corners = [0.0, w, w + h, 2.0 * w + h];
+
+        let mut used = vec![false; pieces.len()];
+        let mut rings = vec![];
+        for start in 0..pieces.len() {
+            if used[start] {
+                continue;
+            }
+
+            let mut ring: Vec<(f64, f64)> = vec![];
+            let mut i = start;
+            loop {
+                used[i] = true;
+                ring.extend(&pieces[i].path);
+
+                let exit = pieces[i].exit;
+                let next = (0..pieces.len())
+                    .filter(|&j| !used[j] || j == start)
+                    .min_by(|&a, &b| {
+                        let da = self.distance(exit, pieces[a].entry);
+                        let db = self.distance(exit, pieces[b].entry);
+                        da.total_cmp(&db)
+                    })
+                    .unwrap_or(start);
+
+                let gap = self.distance(exit, pieces[next].entry);
+                let mut turns = corners
+                    .iter()
+                    .map(|&c| (self.distance(exit, c), c))
+                    .filter(|&(d, _)| d > 0.0 && d < gap)
+                    .collect::<Vec<_>>();
+                turns.sort_by(|a, b| a.0.total_cmp(&b.0));
+                ring.extend(turns.into_iter().map(|(_, c)| self.point(c)));
+
+                if next == start {
+                    break;
+                }
+                i = next;
+            }
+
+            ring.push(ring[0]);
+            rings.push(ring);
+        }
+
+        rings
+    }
+}
+
+/// Whether the given point is on the water side of the closest segment of the coastline.
+fn is_water(chains: &[Vec<(f64, f64)>], p: (f64, f64)) -> bool {
+    let closest = chains
+        .iter()
+        .flat_map(|c| c.windows(2))
+        .map(|s| (distance_to_segment(p, s[0], s[1]), s[0], s[1]))
+        .min_by(|a, b| a.0.total_cmp(&b.0));
+
+    match closest {
+        None => false,
+        Some((_, a, b)) => (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0) < 0.0,
+    }
+}
+
+fn distance_to_segment(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let d = (b.0 - a.0, b.1 - a.1);
+    let len2 = d.0 * d.0 + d.1 * d.1;
+    let t = if len2 == 0.0 {
+        0.0
+    } else {
+        (((p.0 - a.0) * d.0 + (p.1 - a.1) * d.1) / len2).clamp(0.0, 1.0)
+    };
+
+    (p.0 - a.0 - t * d.0).hypot(p.1 - a.1 - t * d.1)
+}
+
+/// Like [`crate::multipolygon::join_paths`], but the paths are never reversed since the
+/// direction of the coastline tells where the water is.
+fn join_directed(mut paths: Vec<Vec<(f64, f64)>>) -> Vec<Vec<(f64, f64)>> {
+    let mut joined = vec![];
+
+    paths.retain(|p| !p.is_empty());
+    paths.reverse();
+
+    while let Some(mut path) = paths.pop() {
+        loop {
+            let (first, last) = (path[0], path[path.len() - 1]);
+            if path.len() > 1 && first == last {
+                break;
+            }
+
+            if let Some(i) = paths.iter().position(|p| p[0] == last) {
+                let next = paths.remove(i);
+                path.extend_from_slice(&next[1..]);
+            } else if let Some(i) = paths.iter().position(|p| p[p.len() - 1] == first) {
+                let mut prev = paths.remove(i);
+                prev.extend_from_slice(&path[1..]);
+                path = prev;
+            } else {
+                break;
+            }
+        }
+
+        joined.push(path);
+    }
+
+    joined
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area(ring: &[(f64, f64)]) -> f64 {
+        ring.windows(2)
+            .map(|w| w[0].0 * w[1].1 - w[1].0 * w[0].1)
+            .sum::<f64>()
+            .abs()
+            / 2.0
+    }
+
+    #[test]
+    fn coast_crossing_the_frame() {
+        // heading east with the land to the north, so the southern half is sea
+        let coast = vec![vec![(-5.0, 0.0), (0.0, 0.0), (15.0, 0.0)]];
+        let rings = sea(&coast, (0.0, -10.0), (10.0, 10.0));
+
+        assert_eq!(rings.len(), 1);
+        assert_eq!(area(&rings[0]), 100.0);
+        assert!(rings[0].iter().all(|p| p.1 <= 0.0));
+    }
+
+    #[test]
+    fn bay_between_two_crossings() {
+        let coast = vec![vec![(3.0, -1.0), (3.0, 5.0), (7.0, 5.0), (7.0, -1.0)]];
+        let rings = sea(&coast, (0.0, 0.0), (10.0, 10.0));
+
+        assert_eq!(rings.len(), 1);
+        assert_eq!(area(&rings[0]), 20.0);
+    }
+
+    #[test]
+    fn island_in_the_sea() {
+        let island = vec![(4.0, 4.0), (6.0, 4.0), (6.0, 6.0), (4.0, 6.0), (4.0, 4.0)];
+        let rings = sea(std::slice::from_ref(&island), (0.0, 0.0), (10.0, 10.0));
+
+        assert_eq!(rings.len(), 2);
+        assert_eq!(area(&rings[0]), 100.0);
+        assert_eq!(rings[1], island);
+    }
+
+    #[test]
+    fn no_coastline_is_land() {
+        assert!(sea(&[], (0.0, 0.0), (10.0, 10.0)).is_empty());
+    }
+}
@@ -0,0 +1,306 @@
+use std::{fmt, str::FromStr};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// An opaque sRGB color or no color at all.
+///
+/// It can be parsed from the usual css notations like `#222`, `#1e1e1e`, `rgb(30, 30, 30)`, a
+/// few named colors and `none`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Color {
+    None,
+    Rgb(u8, u8, u8),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseColorError(String);
+
+/// The color space the vector exports are written in for print shops, the colors are always
+/// picked in sRGB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorSpace {
+    #[default]
+    Srgb,
+
+    /// The colors are converted with [`Color::cmyk`], which is only an approximation without
+    /// the ICC profile of the press.
+    Cmyk,
+}
+
+/// Names of the inks printing some of the colors as spot colors, e.g. `#1e1e1e=PANTONE-Black-C`.
+///
+/// The names are identifiers since they end up in the paints of the svg, e.g. the one above is
+/// written as `#1e1e1e icc-color(PANTONE-Black-C, 1)`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SpotColors(Vec<(Color, String)>);
+
+impl Color {
+    pub const BLACK: Color = Color::Rgb(0, 0, 0);
+    pub const WHITE: Color = Color::Rgb(255, 255, 255);
+
+    const NAMED: [(&'static str, Color); 12] = [
+        ("black", Color::BLACK),
+        ("white", Color::WHITE),
+        ("red", Color::Rgb(255, 0, 0)),
+        ("green", Color::Rgb(0, 128, 0)),
+        ("blue", Color::Rgb(0, 0, 255)),
+        ("yellow", Color::Rgb(255, 255, 0)),
+        ("cyan", Color::Rgb(0, 255, 255)),
+        ("magenta", Color::Rgb(255, 0, 255)),
+        ("orange", Color::Rgb(255, 165, 0)),
+        ("gray", Color::Rgb(128, 128, 128)),
+        ("grey", Color::Rgb(128, 128, 128)),
+        ("transparent", Color::None),
+    ];
+
+    pub fn rgb(self) -> Option<(u8, u8, u8)> {
+        match self {
+            Color::None => None,
+            Color::Rgb(r, g, b) => Some((r, g, b)),
+        }
+    }
+
+    /// The cyan, magenta, yellow and black components of the color between 0 and 1, with the
+    /// naive conversion that puts all the gray in the black ink.
+    pub fn cmyk(self) -> Option<(f64, f64, f64, f64)> {
+        let (r, g, b) = self.rgb()?;
+        let (r, g, b) = (
+            f64::from(r) / 255.0,
+            f64::from(g) / 255.0,
+            f64::from(b) / 255.0,
+        );
+
+        let k = 1.0 - r.max(g).max(b);
+        if k >= 1.0 {
+            return Some((0.0, 0.0, 0.0, 1.0));
+        }
+        let ink = |c: f64| (1.0 - c - k) / (1.0 - k);
+        Some((ink(r), ink(g), ink(b), k))
+    }
+}
+
+impl SpotColors {
+    /// The name of the spot color printing the given color, if any.
+    pub fn get(&self, c: Color) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(spot, _)| *spot == c)
+            .map(|(_, name)| name.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Color, &str)> {
+        self.0.iter().map(|(c, name)| (*c, name.as_str()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl FromStr for ColorSpace {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "srgb" => Ok(ColorSpace::Srgb),
+            "cmyk" => Ok(ColorSpace::Cmyk),
+            _ => Err(format!("invalid color space '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for ColorSpace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ColorSpace::Srgb => "srgb",
+            ColorSpace::Cmyk => "cmyk",
+        })
+    }
+}
+
+impl FromStr for SpotColors {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid =
+            |spot: &str| format!("invalid spot color '{}', expected e.g. #222=Black", spot);
+
+        let mut spots = vec![];
+        for spot in s.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let (color, name) = spot.split_once('=').ok_or_else(|| invalid(spot))?;
+            let color = color.parse::<Color>().map_err(|e| e.to_string())?;
+            let name = name.trim();
+
+            let is_ident = name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+            if color == Color::None || name.is_empty() || !is_ident {
+                return Err(invalid(spot));
+            }
+            spots.push((color, name.to_string()));
+        }
+
+        Ok(SpotColors(spots))
+    }
+}
+
+impl fmt::Display for SpotColors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (c, name)) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{}={}", c, name)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Color {
+    type Err = ParseColorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        let err = || ParseColorError(format!("invalid color '{}'", s));
+
+        if s == "none" {
+            return Ok(Color::None);
+        }
+
+        if let Some((_, c)) = Self::NAMED.iter().find(|(n, _)| *n == s) {
+            return Ok(*c);
+        }
+
+        if let Some(hex) = s.strip_prefix('#') {
+            let digits = hex
+                .chars()
+                .map(|c| c.to_digit(16).map(|d| d as u8))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(err)?;
+
+            return match digits[..] {
+                [r, g, b] => Ok(Color::Rgb(r * 17, g * 17, b * 17)),
+                [r1, r0, g1, g0, b1, b0] => {
+                    Ok(Color::Rgb(r1 * 16 + r0, g1 * 16 + g0, b1 * 16 + b0))
+                }
+                _ => Err(err()),
+            };
+        }
+
+        if let Some(args) = s.strip_prefix("rgb(").and_then(|s| s.strip_suffix(')')) {
+            let c = args
+                .split(',')
+                .map(|c| c.trim().parse::<u8>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| err())?;
+
+            return match c[..] {
+                [r, g, b] => Ok(Color::Rgb(r, g, b)),
+                _ => Err(err()),
+            };
+        }
+
+        Err(err())
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Color::None => f.write_str("none"),
+            Color::Rgb(r, g, b) => write!(f, "#{:02x}{:02x}{:02x}", r, g, b),
+        }
+    }
+}
+
+impl fmt::Display for ParseColorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ParseColorError {}
+
+/// Serialized as its css notation, e.g. `#1e1e1e`.
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        String::deserialize(d)?.parse().map_err(de::Error::custom)
+    }
+}
+
+/// Serialized like the option, e.g. `#1e1e1e=PANTONE-Black-C,#4a80c0=PANTONE-2925-C`.
+impl Serialize for SpotColors {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for SpotColors {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        String::deserialize(d)?.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_color() {
+        assert_eq!("#1e1e1e".parse(), Ok(Color::Rgb(30, 30, 30)));
+        assert_eq!(" #A0C8F0 ".parse(), Ok(Color::Rgb(160, 200, 240)));
+        assert_eq!("#222".parse(), Ok(Color::Rgb(34, 34, 34)));
+        assert_eq!("rgb(30, 60,90)".parse(), Ok(Color::Rgb(30, 60, 90)));
+        assert_eq!("Orange".parse(), Ok(Color::Rgb(255, 165, 0)));
+        assert_eq!("none".parse(), Ok(Color::None));
+        assert_eq!("transparent".parse(), Ok(Color::None));
+
+        for invalid in ["#22", "#ggg", "rgb(300, 0, 0)", "rgb(1, 2)", "teal", ""] {
+            assert!(invalid.parse::<Color>().is_err(), "{}", invalid);
+        }
+        assert_eq!(
+            "#GGG".parse::<Color>().unwrap_err().to_string(),
+            "invalid color '#ggg'"
+        );
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for c in [Color::Rgb(30, 30, 30), Color::WHITE, Color::None] {
+            assert_eq!(c.to_string().parse(), Ok(c));
+        }
+    }
+
+    #[test]
+    fn parse_spot_colors() {
+        let spots = "#1e1e1e=PANTONE-Black-C, #4a80c0 = PANTONE_2925_C,"
+            .parse::<SpotColors>()
+            .unwrap();
+        assert_eq!(spots.get(Color::Rgb(30, 30, 30)), Some("PANTONE-Black-C"));
+        assert_eq!(spots.get(Color::Rgb(74, 128, 192)), Some("PANTONE_2925_C"));
+        assert_eq!(spots.get(Color::BLACK), None);
+        assert_eq!(
+            spots.to_string(),
+            "#1e1e1e=PANTONE-Black-C,#4a80c0=PANTONE_2925_C"
+        );
+
+        assert!("".parse::<SpotColors>().unwrap().is_empty());
+
+        let invalid = "invalid spot color '#222', expected e.g. #222=Black";
+        assert_eq!("#222".parse::<SpotColors>(), Err(invalid.to_string()));
+        for spot in ["none=Black", "#222=", "#222=Pantone Black", "#222=a(1)"] {
+            assert!(spot.parse::<SpotColors>().is_err(), "{}", spot);
+        }
+        assert_eq!(
+            "fuchsia=Pink".parse::<SpotColors>(),
+            Err("invalid color 'fuchsia'".to_string())
+        );
+    }
+}
@@ -0,0 +1,71 @@
+//! User defaults for the options, stored in a toml file like
+//!
+//! ```toml
+//! [options]
+//! Width = "297mm"
+//! Height = "210mm"
+//! "Road classes" = "motorway,trunk,primary"
+//! "Output directory" = "/home/me/maps"
+//! ```
+//!
+//! The values use the same format accepted when editing the options in the TUI.
+
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::util::write_file_atomic;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Config {
+    /// Values of the options by name.
+    #[serde(default)]
+    pub options: BTreeMap<String, String>,
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/roads/config.toml`, `~/.config/roads/config.toml` or
+    /// `%APPDATA%\roads\config.toml`.
+    pub fn default_path() -> Option<PathBuf> {
+        let var = |name| std::env::var_os(name).filter(|v| !v.is_empty());
+
+        let base = var("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| var("HOME").map(|h| Path::new(&h).join(".config")))
+            .or_else(|| var("APPDATA").map(PathBuf::from))?;
+
+        Some(base.join("roads").join("config.toml"))
+    }
+
+    /// Load the config at the given path, a missing file is an empty config.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+
+        let data = match fs::read_to_string(path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+            r => r?,
+        };
+
+        toml::from_str(&data).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid config {}: {}", path.display(), e),
+            )
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let data = toml::to_string(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        write_file_atomic(path, data)
+    }
+}
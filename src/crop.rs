@@ -0,0 +1,356 @@
+//! Framing of the map in canvases with a fixed aspect ratio, like the square or portrait posts of
+//! social media, cropping it around its most interesting part rather than shrinking it to fit.
+
+use std::{fmt, str::FromStr};
+
+use crate::{clip::Clip, render::pdf::PageSize, units::Length, viewport::bounds, Road};
+
+/// Size of the canvas, either set by hand or following a preset.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CanvasPreset {
+    /// The given width and height.
+    Custom,
+
+    /// The given width and the height giving this ratio of width to height, e.g. `16:9`.
+    Ratio(u32, u32),
+
+    /// A paper size, e.g. `a4` or `a3-landscape`, regardless of the given width and height.
+    Page(PageSize),
+}
+
+/// Where to center the crop of the map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Crop {
+    /// Fit the whole map in the canvas.
+    #[default]
+    Off,
+
+    /// The centroid of the boundary of the place, or the center of the map without one.
+    Centroid,
+
+    /// The area with the most points, i.e. the densest part of the network.
+    Densest,
+}
+
+/// Number of cells along each axis of the grid the points are counted in to find the densest
+/// area.
+const DENSITY_CELLS: usize = 64;
+
+impl CanvasPreset {
+    /// The width and height of the canvas given the ones set by hand.
+    pub fn size(self, width: Length, height: Length) -> (Length, Length) {
+        match self {
+            CanvasPreset::Custom => (width, height),
+            CanvasPreset::Ratio(w, h) => (
+                width,
+                Length {
+                    value: width.value * f64::from(h) / f64::from(w),
+                    ..width
+                },
+            ),
+            CanvasPreset::Page(page) => (Length::mm(page.width), Length::mm(page.height)),
+        }
+    }
+}
+
+/// The rectangle with the given ratio of width to height to crop the map made of the given
+/// points to, as `(min, max)`. It's as big as the bounds of the points allow and it's centered
+/// where the crop says, but never past the bounds.
+///
+/// `boundary` are the rings of the boundary of the place, if any.
+pub fn window(
+    crop: Crop,
+    aspect: f64,
+    points: &[(f64, f64)],
+    boundary: &[Vec<(f64, f64)>],
+) -> Option<((f64, f64), (f64, f64))> {
+    let (min, max) = bounds(points)?;
+    if crop == Crop::Off || !aspect.is_finite() || aspect <= 0.0 {
+        return None;
+    }
+
+    let (bw, bh) = (max.0 - min.0, max.1 - min.1);
+    let size = if bw > bh * aspect {
+        (bh * aspect, bh)
+    } else {
+        (bw, bw / aspect)
+    };
+
+    let center = match crop {
+        Crop::Off => unreachable!(),
+        Crop::Centroid => centroid(boundary),
+        Crop::Densest => densest(points, (min, max), size),
+    };
+    let (cx, cy) = center.unwrap_or(((min.0 + max.0) / 2.0, (min.1 + max.1) / 2.0));
+
+    let x = (cx - size.0 / 2.0).clamp(min.0, max.0 - size.0);
+    let y = (cy - size.1 / 2.0).clamp(min.1, max.1 - size.1);
+    Some(((x, y), (x + size.0, y + size.1)))
+}
+
+/// Crop the given way to the rectangle going from `min` to `max`. The rings of filled ways stay
+/// closed by following the sides of the rectangle, while the other ways are split into the
+/// pieces inside it.
+pub fn crop(road: Road, (min, max): ((f64, f64), (f64, f64)), filled: bool) -> Vec<Road> {
+    let inside = |(x, y): &(f64, f64)| *x >= min.0 && *x <= max.0 && *y >= min.1 && *y <= max.1;
+    if road.points.iter().all(inside) && road.holes.iter().flatten().all(inside) {
+        return vec![road];
+    }
+
+    if filled && road.is_closed() {
+        let points = clip_ring(&road.points, min, max);
+        if points.len() < 4 {
+            return vec![];
+        }
+
+        let holes = road
+            .holes
+            .iter()
+            .map(|h| clip_ring(h, min, max))
+            .filter(|h| h.len() > 3)
+            .collect();
+        return vec![Road {
+            points,
+            holes,
+            ..road
+        }];
+    }
+
+    let frame = Clip::new(vec![vec![
+        (min.0, min.1),
+        (max.0, min.1),
+        (max.0, max.1),
+        (min.0, max.1),
+        (min.0, min.1),
+    ]]);
+    frame
+        .clip_path(&road.points)
+        .into_iter()
+        .map(|points| Road {
+            points,
+            holes: vec![],
+            ..road.clone()
+        })
+        .collect()
+}
+
+/// Clip the given closed ring to the rectangle with the Sutherland-Hodgman algorithm, which keeps
+/// it closed since the rectangle is convex.
+fn clip_ring(ring: &[(f64, f64)], min: (f64, f64), max: (f64, f64)) -> Vec<(f64, f64)> {
+    // each side keeps the points on its inner side, i.e. with a positive distance from it
+    let sides = [
+        (0, min.0, 1.0),
+        (0, max.0, -1.0),
+        (1, min.1, 1.0),
+        (1, max.1, -1.0),
+    ];
+
+    let mut points = ring[..ring.len() - 1].to_vec();
+    for (axis, at, sign) in sides {
+        let side = |p: (f64, f64)| sign * ([p.0, p.1][axis] - at);
+        if points.is_empty() {
+            break;
+        }
+
+        let mut kept = vec![];
+        for (i, &b) in points.iter().enumerate() {
+            let a = points[(i + points.len() - 1) % points.len()];
+            let (da, db) = (side(a), side(b));
+            if (da >= 0.0) != (db >= 0.0) {
+                let t = da / (da - db);
+                kept.push((a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t));
+            }
+            if db >= 0.0 {
+                kept.push(b);
+            }
+        }
+        points = kept;
+    }
+
+    if let Some(&first) = points.first() {
+        points.push(first);
+    }
+    points
+}
+
+/// The centroid of the area of the given rings, if they have any.
+fn centroid(rings: &[Vec<(f64, f64)>]) -> Option<(f64, f64)> {
+    let (mut area, mut cx, mut cy) = (0.0, 0.0, 0.0);
+    for ring in rings {
+        for w in ring.windows(2) {
+            let (a, b) = (w[0], w[1]);
+            let cross = a.0 * b.1 - b.0 * a.1;
+            area += cross;
+            cx += (a.0 + b.0) * cross;
+            cy += (a.1 + b.1) * cross;
+        }
+    }
+
+    // rings going the other way, like holes, have a negative area and are subtracted
+    (area.abs() > f64::EPSILON).then(|| (cx / (3.0 * area), cy / (3.0 * area)))
+}
+
+/// The center of the rectangle of the given size, inside the given bounds, with the most points.
+fn densest(
+    points: &[(f64, f64)],
+    (min, max): ((f64, f64), (f64, f64)),
+    size: (f64, f64),
+) -> Option<(f64, f64)> {
+    let n = DENSITY_CELLS;
+    let cell = ((max.0 - min.0) / n as f64, (max.1 - min.1) / n as f64);
+    if cell.0 <= 0.0 || cell.1 <= 0.0 {
+        return None;
+    }
+
+    // summed area table of the number of points in each cell
+    let mut sums = vec![vec![0_usize; n + 1]; n + 1];
+    for (x, y) in points {
+        let i = (((x - min.0) / cell.0) as usize).min(n - 1);
+        let j = (((y - min.1) / cell.1) as usize).min(n - 1);
+        sums[i + 1][j + 1] += 1;
+    }
+    for i in 1..=n {
+        for j in 1..=n {
+            sums[i][j] += sums[i - 1][j] + sums[i][j - 1] - sums[i - 1][j - 1];
+        }
+    }
+
+    let w = ((size.0 / cell.0).round() as usize).clamp(1, n);
+    let h = ((size.1 / cell.1).round() as usize).clamp(1, n);
+    let (i, j) = (0..=n - w)
+        .flat_map(|i| (0..=n - h).map(move |j| (i, j)))
+        .max_by_key(|&(i, j)| sums[i + w][j + h] + sums[i][j] - sums[i][j + h] - sums[i + w][j])?;
+
+    Some((
+        min.0 + (i as f64 + w as f64 / 2.0) * cell.0,
+        min.1 + (j as f64 + h as f64 / 2.0) * cell.1,
+    ))
+}
+
+/// `custom`, a ratio like `1:1`, `4:5` or `16:9`, or a paper size like `a4` or `a3-landscape`.
+impl FromStr for CanvasPreset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s == "custom" {
+            return Ok(CanvasPreset::Custom);
+        }
+
+        if let Some((w, h)) = s.split_once(':') {
+            let parse = |v: &str| v.trim().parse::<u32>().ok().filter(|v| *v > 0);
+            return match (parse(w), parse(h)) {
+                (Some(w), Some(h)) => Ok(CanvasPreset::Ratio(w, h)),
+                _ => Err(format!("invalid aspect ratio '{}'", s)),
+            };
+        }
+
+        s.parse::<PageSize>()
+            .map(CanvasPreset::Page)
+            .map_err(|_| format!("invalid canvas preset '{}'", s))
+    }
+}
+
+impl fmt::Display for CanvasPreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CanvasPreset::Custom => f.write_str("custom"),
+            CanvasPreset::Ratio(w, h) => write!(f, "{}:{}", w, h),
+            CanvasPreset::Page(page) => write!(f, "{}", page),
+        }
+    }
+}
+
+impl FromStr for Crop {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "off" => Ok(Crop::Off),
+            "centroid" => Ok(Crop::Centroid),
+            "densest" => Ok(Crop::Densest),
+            _ => Err(format!("invalid crop '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for Crop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Crop::Off => "off",
+            Crop::Centroid => "centroid",
+            Crop::Densest => "densest",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn road(points: Vec<(f64, f64)>) -> Road {
+        Road {
+            id: 1,
+            tags: BTreeMap::new(),
+            points,
+            holes: vec![],
+        }
+    }
+
+    #[test]
+    fn square_window_around_the_densest_area() {
+        // a long map whose points are all on its right end
+        let mut points = vec![(0.0, 0.0), (100.0, 10.0)];
+        points.extend((0..50).map(|i| (95.0 + f64::from(i) / 10.0, 5.0)));
+
+        let (min, max) = window(Crop::Densest, 1.0, &points, &[]).unwrap();
+        assert_eq!(max.0 - min.0, 10.0);
+        assert_eq!(max.1 - min.1, 10.0);
+        assert!(min.0 >= 88.0 && max.0 <= 100.0);
+
+        let (min, max) = window(Crop::Centroid, 1.0, &points, &[]).unwrap();
+        assert_eq!((min, max), ((45.0, 0.0), (55.0, 10.0)));
+    }
+
+    #[test]
+    fn filled_rings_stay_closed() {
+        let square = road(vec![
+            (0.0, 0.0),
+            (4.0, 0.0),
+            (4.0, 4.0),
+            (0.0, 4.0),
+            (0.0, 0.0),
+        ]);
+
+        let filled = crop(square.clone(), ((2.0, -1.0), (6.0, 5.0)), true);
+        assert_eq!(filled.len(), 1);
+        assert_eq!(filled[0].points.first(), filled[0].points.last());
+        assert!(filled[0].points.iter().all(|p| p.0 >= 2.0));
+
+        let outline = crop(square, ((2.0, -1.0), (6.0, 5.0)), false);
+        assert_eq!(outline.len(), 1);
+        assert_eq!(
+            outline[0].points,
+            vec![(2.0, 0.0), (4.0, 0.0), (4.0, 4.0), (2.0, 4.0)]
+        );
+    }
+
+    #[test]
+    fn presets() {
+        let (w, h) = "4:5"
+            .parse::<CanvasPreset>()
+            .unwrap()
+            .size(Length::px(1080.0), Length::px(1080.0));
+        assert_eq!((w.to_px(), h.to_px()), (1080.0, 1350.0));
+
+        let a4 = "a4".parse::<CanvasPreset>().unwrap();
+        assert_eq!(a4.to_string(), "a4");
+        assert_eq!(
+            a4.size(Length::px(1.0), Length::px(1.0)).1,
+            Length::mm(297.0)
+        );
+        assert!("0:1".parse::<CanvasPreset>().is_err());
+    }
+}
@@ -0,0 +1,120 @@
+//! Comparison of two snapshots of the same area, e.g. fetched at different dates with
+//! [`attic_query`](crate::overpass::attic_query) or saved in two projects, to show how its map
+//! changed.
+//!
+//! Ways are matched by id and geometry, so a way whose tags changed but that didn't move is
+//! unchanged, while a way that moved is both removed, where it was, and added, where it is.
+
+use std::collections::HashMap;
+
+use crate::Road;
+
+/// The ways of two snapshots split by how they changed from the first to the second.
+#[derive(Debug, Clone, Default)]
+pub struct Diff {
+    /// The ways only in the second snapshot, as they are in it.
+    pub added: Vec<Road>,
+
+    /// The ways only in the first snapshot, as they were in it.
+    pub removed: Vec<Road>,
+
+    /// The ways in both snapshots, as they are in the second one.
+    pub unchanged: Vec<Road>,
+}
+
+/// Compare the ways of the `before` snapshot with the ones of the `after` snapshot, which must be
+/// projected the same way.
+pub fn diff(before: Vec<Road>, after: Vec<Road>) -> Diff {
+    let mut remaining = HashMap::<_, usize>::new();
+    for r in &before {
+        *remaining.entry(key(r)).or_default() += 1;
+    }
+
+    let mut diff = Diff::default();
+    let mut matched = HashMap::<_, usize>::new();
+    for r in after {
+        let k = key(&r);
+        match remaining.get_mut(&k).filter(|n| **n > 0) {
+            Some(n) => {
+                *n -= 1;
+                *matched.entry(k).or_default() += 1;
+                diff.unchanged.push(r);
+            }
+            None => diff.added.push(r),
+        }
+    }
+
+    for r in before {
+        match matched.get_mut(&key(&r)).filter(|n| **n > 0) {
+            Some(n) => *n -= 1,
+            None => diff.removed.push(r),
+        }
+    }
+
+    diff
+}
+
+/// What identifies a way across snapshots, the exact coordinates are compared since the same
+/// data projected the same way gives the same points.
+fn key(r: &Road) -> (i64, Vec<(u64, u64)>) {
+    let points = r
+        .points
+        .iter()
+        .map(|(x, y)| (x.to_bits(), y.to_bits()))
+        .collect();
+    (r.id, points)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn road(id: i64, points: Vec<(f64, f64)>) -> Road {
+        Road {
+            id,
+            tags: BTreeMap::new(),
+            points,
+            holes: vec![],
+        }
+    }
+
+    fn ids(roads: &[Road]) -> Vec<i64> {
+        roads.iter().map(|r| r.id).collect()
+    }
+
+    #[test]
+    fn added_removed_and_unchanged() {
+        let before = vec![
+            road(1, vec![(0.0, 0.0), (1.0, 0.0)]),
+            road(2, vec![(0.0, 1.0), (1.0, 1.0)]),
+            road(3, vec![(0.0, 2.0), (1.0, 2.0)]),
+        ];
+        let after = vec![
+            road(1, vec![(0.0, 0.0), (1.0, 0.0)]),
+            road(3, vec![(0.0, 2.0), (2.0, 2.0)]),
+            road(4, vec![(0.0, 3.0), (1.0, 3.0)]),
+        ];
+
+        let diff = diff(before, after);
+        assert_eq!(ids(&diff.unchanged), vec![1]);
+        assert_eq!(ids(&diff.added), vec![3, 4]);
+        assert_eq!(ids(&diff.removed), vec![2, 3]);
+        assert_eq!(diff.added[0].points[1], (2.0, 2.0));
+        assert_eq!(diff.removed[1].points[1], (1.0, 2.0));
+    }
+
+    #[test]
+    fn retagged_ways_are_unchanged() {
+        let before = vec![road(1, vec![(0.0, 0.0), (1.0, 0.0)])];
+        let mut renamed = before[0].clone();
+        renamed
+            .tags
+            .insert("name".to_string(), "Via Roma".to_string());
+
+        let diff = diff(before, vec![renamed]);
+        assert!(diff.added.is_empty() && diff.removed.is_empty());
+        assert_eq!(diff.unchanged[0].tags["name"], "Via Roma");
+    }
+}
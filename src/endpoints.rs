@@ -0,0 +1,356 @@
+//! The servers used to geocode places and to download the roads.
+
+use std::{fmt, str::FromStr};
+
+use crate::{
+    cache::Cache,
+    overpass::{FetchStrategy, OverpassClient, QueryLimits},
+    polite::PoliteMode,
+    progress::ProgressObserver,
+    retry::RetryPolicy,
+};
+
+/// Base urls of the Nominatim and Overpass instances to talk to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoints {
+    /// Nominatim base url, e.g. `https://nominatim.openstreetmap.org`.
+    pub nominatim: String,
+
+    /// Overpass interpreter url, e.g. `https://overpass-api.de/api/interpreter`.
+    pub overpass: String,
+
+    /// Overpass instances tried in order when the main one is overloaded.
+    pub overpass_mirrors: Vec<String>,
+
+    pub nominatim_auth: Option<Auth>,
+
+    /// Credentials of the main Overpass instance, never sent to the mirrors.
+    pub overpass_auth: Option<Auth>,
+
+    /// How to retry the requests when the servers are overloaded.
+    pub retry: RetryPolicy,
+
+    /// Told how the Overpass downloads are going.
+    pub progress: ProgressObserver,
+
+    /// How Overpass sends the geometry of the elements.
+    pub strategy: FetchStrategy,
+
+    /// How much time and memory Overpass may spend on each query.
+    pub limits: QueryLimits,
+
+    /// User-Agent sent with every request.
+    pub client: ClientConfig,
+
+    /// Where to keep the responses to avoid downloading them again, if anywhere.
+    pub cache: Option<Cache>,
+}
+
+/// How roads introduces itself to the servers.
+///
+/// The [Nominatim usage policy] requires an User-Agent identifying the application, the contact
+/// details let the server operators reach out before blocking abusive clients.
+///
+/// [Nominatim usage policy]: https://operations.osmfoundation.org/policies/nominatim/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientConfig {
+    pub user_agent: String,
+
+    /// Email or url of whoever runs the application, if any.
+    pub contact: Option<String>,
+}
+
+/// Credentials attached to the requests, as needed by commercial providers.
+///
+/// It can be parsed from:
+///
+/// - `Bearer <token>` for an `Authorization: Bearer <token>` header
+/// - `<name>: <value>` for a custom header like `X-Api-Key: 1234`
+/// - `?<name>=<value>` for a query parameter like `?apiKey=1234`
+#[derive(Clone, PartialEq, Eq)]
+pub enum Auth {
+    Header(String, String),
+    QueryParam(String, String),
+}
+
+impl Endpoints {
+    pub const PUBLIC_NOMINATIM: &'static str = "https://nominatim.openstreetmap.org";
+    pub const PUBLIC_OVERPASS: &'static str = "https://overpass-api.de/api/interpreter";
+    pub const PUBLIC_OVERPASS_MIRRORS: [&'static str; 1] =
+        ["https://overpass.kumi.systems/api/interpreter"];
+
+    /// Environment variables holding the credentials of the services, see [`Auth`].
+    pub const NOMINATIM_AUTH_ENV: &'static str = "ROADS_NOMINATIM_AUTH";
+    pub const OVERPASS_AUTH_ENV: &'static str = "ROADS_OVERPASS_AUTH";
+
+    /// The public OpenStreetMap instances.
+    pub fn public() -> Self {
+        Self::self_hosted(Self::PUBLIC_NOMINATIM, Self::PUBLIC_OVERPASS)
+            .with_overpass_mirrors(Self::PUBLIC_OVERPASS_MIRRORS)
+    }
+
+    pub fn self_hosted(nominatim: impl Into<String>, overpass: impl Into<String>) -> Self {
+        Endpoints {
+            nominatim: nominatim.into().trim_end_matches('/').to_string(),
+            overpass: overpass.into(),
+            overpass_mirrors: vec![],
+            nominatim_auth: None,
+            overpass_auth: None,
+            retry: RetryPolicy::default(),
+            progress: ProgressObserver::none(),
+            strategy: FetchStrategy::Geom,
+            limits: QueryLimits::default(),
+            client: ClientConfig::default(),
+            cache: Cache::user(),
+        }
+    }
+
+    pub fn with_overpass_mirrors(
+        mut self,
+        mirrors: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.overpass_mirrors = mirrors.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn with_client(mut self, client: ClientConfig) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Use the given cache, `None` disables caching.
+    pub fn with_cache(mut self, cache: Option<Cache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_progress(mut self, progress: ProgressObserver) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    pub fn with_strategy(mut self, strategy: FetchStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: QueryLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn with_nominatim_auth(mut self, auth: Auth) -> Self {
+        self.nominatim_auth = Some(auth);
+        self
+    }
+
+    pub fn with_overpass_auth(mut self, auth: Auth) -> Self {
+        self.overpass_auth = Some(auth);
+        self
+    }
+
+    /// Fill the missing credentials from the [`Self::NOMINATIM_AUTH_ENV`] and
+    /// [`Self::OVERPASS_AUTH_ENV`] environment variables, if set.
+    pub fn with_env_auth(mut self) -> Result<Self, String> {
+        let var = |name| match std::env::var(name) {
+            Ok(v) if !v.trim().is_empty() => v.parse().map(Some),
+            _ => Ok(None),
+        };
+
+        if self.nominatim_auth.is_none() {
+            self.nominatim_auth = var(Self::NOMINATIM_AUTH_ENV)?;
+        }
+        if self.overpass_auth.is_none() {
+            self.overpass_auth = var(Self::OVERPASS_AUTH_ENV)?;
+        }
+
+        Ok(self)
+    }
+
+    /// Whether any of the endpoints is one of the public instances.
+    pub fn is_public(&self) -> bool {
+        self.nominatim == Self::PUBLIC_NOMINATIM
+            || std::iter::once(&self.overpass)
+                .chain(&self.overpass_mirrors)
+                .any(|u| {
+                    u == Self::PUBLIC_OVERPASS
+                        || Self::PUBLIC_OVERPASS_MIRRORS.contains(&u.as_str())
+                })
+    }
+
+    /// The limits to respect when talking to these endpoints given the ones the user asked for.
+    ///
+    /// Private instances are not throttled at all.
+    pub fn polite_mode(&self, requested: PoliteMode) -> PoliteMode {
+        if self.is_public() {
+            requested
+        } else {
+            PoliteMode::off()
+        }
+    }
+
+    /// Client talking to the Overpass instance, failing over to the mirrors if needed.
+    pub fn overpass_client(&self) -> OverpassClient {
+        let client =
+            OverpassClient::new(std::iter::once(&self.overpass).chain(&self.overpass_mirrors))
+                .with_retry(self.retry.clone())
+                .with_progress(self.progress.clone())
+                .with_strategy(self.strategy)
+                .with_limits(self.limits)
+                .with_client(self.client.clone())
+                .with_cache(self.cache.clone());
+
+        match &self.overpass_auth {
+            None => client,
+            Some(auth) => client.with_auth(auth.clone()),
+        }
+    }
+
+    pub(crate) fn nominatim_request(&self, path: &str) -> reqwest::RequestBuilder {
+        let client = reqwest::Client::new();
+        request(
+            client.get(format!("{}{}", self.nominatim, path)),
+            &self.client,
+            self.nominatim_auth.as_ref(),
+        )
+    }
+}
+
+impl ClientConfig {
+    /// Environment variables overriding the user agent and the contact.
+    pub const USER_AGENT_ENV: &'static str = "ROADS_USER_AGENT";
+    pub const CONTACT_ENV: &'static str = "ROADS_CONTACT";
+
+    pub fn new(user_agent: impl Into<String>) -> Self {
+        ClientConfig {
+            user_agent: user_agent.into(),
+            contact: None,
+        }
+    }
+
+    pub fn with_contact(mut self, contact: impl Into<String>) -> Self {
+        self.contact = Some(contact.into());
+        self
+    }
+
+    /// Override the fields with the [`Self::USER_AGENT_ENV`] and [`Self::CONTACT_ENV`]
+    /// environment variables, if set.
+    pub fn with_env(mut self) -> Self {
+        let var = |name| std::env::var(name).ok().filter(|v| !v.trim().is_empty());
+
+        if let Some(ua) = var(Self::USER_AGENT_ENV) {
+            self.user_agent = ua;
+        }
+        if let Some(contact) = var(Self::CONTACT_ENV) {
+            self.contact = Some(contact);
+        }
+
+        self
+    }
+
+    /// The full User-Agent header, e.g. `roads/0.1.0 (me@example.com)`.
+    pub fn user_agent_header(&self) -> String {
+        match &self.contact {
+            None => self.user_agent.clone(),
+            Some(c) => format!("{} ({})", self.user_agent, c),
+        }
+    }
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self::new(format!("roads/{}", env!("CARGO_PKG_VERSION")))
+    }
+}
+
+/// Add the headers shared by all the requests and the credentials, if any.
+pub(crate) fn request(
+    rb: reqwest::RequestBuilder,
+    client: &ClientConfig,
+    auth: Option<&Auth>,
+) -> reqwest::RequestBuilder {
+    let rb = rb.header(reqwest::header::USER_AGENT, client.user_agent_header());
+
+    match auth {
+        None => rb,
+        Some(Auth::Header(k, v)) => rb.header(k.as_str(), v.as_str()),
+        Some(Auth::QueryParam(k, v)) => rb.query(&[(k, v)]),
+    }
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Self::public()
+    }
+}
+
+impl FromStr for Auth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        if let Some(token) = s.strip_prefix("Bearer ") {
+            return Ok(Auth::Header(
+                "Authorization".to_string(),
+                format!("Bearer {}", token.trim()),
+            ));
+        }
+
+        if let Some((k, v)) = s.strip_prefix('?').and_then(|q| q.split_once('=')) {
+            return Ok(Auth::QueryParam(k.to_string(), v.to_string()));
+        }
+
+        match s.split_once(':') {
+            Some((k, v)) if !k.trim().is_empty() => {
+                Ok(Auth::Header(k.trim().to_string(), v.trim().to_string()))
+            }
+            _ => Err(format!("invalid credentials '{}'", s)),
+        }
+    }
+}
+
+/// The name of the header or parameter with the secret redacted, so it never ends up in logs,
+/// errors or saved files.
+impl fmt::Display for Auth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Auth::Header(k, _) => write!(f, "{}: ***", k),
+            Auth::QueryParam(k, _) => write!(f, "?{}=***", k),
+        }
+    }
+}
+
+/// Never leak the secrets in logs or error messages.
+impl fmt::Debug for Auth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Auth::Header(k, _) => write!(f, "Header({:?}, ***)", k),
+            Auth::QueryParam(k, _) => write!(f, "QueryParam({:?}, ***)", k),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secrets_are_redacted() {
+        let bearer = "Bearer s3cret".parse::<Auth>().unwrap();
+        assert_eq!(
+            bearer,
+            Auth::Header("Authorization".to_string(), "Bearer s3cret".to_string())
+        );
+        assert_eq!(bearer.to_string(), "Authorization: ***");
+        assert!(!format!("{:?}", bearer).contains("s3cret"));
+
+        let key = "?api_key=s3cret".parse::<Auth>().unwrap();
+        assert_eq!(key.to_string(), "?api_key=***");
+    }
+}
@@ -0,0 +1,386 @@
+//! The errors of the requests sent to Nominatim and Overpass.
+
+use std::{fmt, time::Duration};
+
+use reqwest::{Response, StatusCode};
+use serde::de::DeserializeOwned;
+
+use crate::{filter::HighwayClass, polite::BudgetExceeded, retry, LatLon};
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Which server a request was sent to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Service {
+    Nominatim,
+    Overpass,
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The server could not be reached at all, e.g. because the network is down.
+    Network {
+        service: Service,
+        source: reqwest::Error,
+    },
+
+    /// The server is overloaded and it still asks to slow down after all the retries.
+    RateLimited {
+        service: Service,
+        retry_after: Option<Duration>,
+    },
+
+    /// The server refused the request, e.g. because the Overpass query is invalid.
+    Status {
+        service: Service,
+        status: StatusCode,
+        message: Option<String>,
+    },
+
+    /// Overpass gave up on the query because it took too long.
+    Timeout { remark: String },
+
+    /// Overpass gave up on the query because it needed too much memory.
+    QueryTooLarge { remark: String },
+
+    /// Overpass failed to run the query for some other reason.
+    Overpass { remark: String },
+
+    /// The connection dropped before the whole response was received.
+    Truncated {
+        service: Service,
+
+        /// Bytes received.
+        received: u64,
+
+        /// Bytes announced by the server, if it did.
+        expected: Option<u64>,
+    },
+
+    /// The response is not what was expected.
+    Decode {
+        service: Service,
+        source: serde_json::Error,
+    },
+
+    /// Nominatim found no place matching the search.
+    NotFound { place: String },
+
+    /// The OpenStreetMap element has no area to search in, only relations and ways do.
+    NoArea { osm_type: String, osm_id: i64 },
+
+    /// No more queries can be sent in this run, see [`PoliteMode`](crate::polite::PoliteMode).
+    BudgetExceeded(BudgetExceeded),
+
+    /// The rectangle to search in is empty or outside of the world, its min corner must be
+    /// south west of the max one.
+    InvalidBbox { min: LatLon, max: LatLon },
+
+    /// Overpass ran the query successfully, but it matched no elements.
+    Empty {
+        query: String,
+
+        /// The id of the Overpass area the elements were searched in, if the query searched by
+        /// area rather than by bounding box.
+        area: Option<u64>,
+
+        /// Likely explanations and what to try next.
+        hints: Vec<String>,
+    },
+}
+
+/// A problem that didn't stop the server from answering, but that likely makes the result
+/// incomplete.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Warning {
+    /// Overpass ran out of time or memory midway and returned only the elements found so far.
+    Partial { remark: String },
+
+    /// Only the most important highway classes were fetched to keep the number of ways within a
+    /// budget, see [`RoadFilter::most_important`](crate::filter::RoadFilter::most_important).
+    Trimmed {
+        kept: Vec<HighwayClass>,
+        max_ways: u64,
+    },
+}
+
+impl Error {
+    pub(crate) fn from_reqwest(service: Service, e: reqwest::Error) -> Self {
+        match e.status() {
+            Some(status) if retry::is_overloaded(status) => Error::RateLimited {
+                service,
+                retry_after: None,
+            },
+            Some(status) => Error::Status {
+                service,
+                status,
+                message: None,
+            },
+            None => Error::Network { service, source: e },
+        }
+    }
+
+    /// The error of a query that matched nothing, guessing why from the query itself. For the
+    /// callers of [`OverpassClient::fetch_elements`](crate::overpass::OverpassClient::fetch_elements)
+    /// that need some elements.
+    pub fn empty(query: &str) -> Self {
+        const BBOX: &str = "try the bbox mode";
+
+        let area = area_id(query);
+
+        let mut hints = vec![];
+        match area {
+            Some(id) if id >= 3_600_000_000 => hints.push(format!(
+                "relation {} has no mapped elements of this kind or no area in Overpass; {}",
+                id - 3_600_000_000,
+                BBOX
+            )),
+            Some(id) if id >= 2_400_000_000 => hints.push(format!(
+                "way {} has no area in Overpass, only closed ways tagged as areas do; {}",
+                id - 2_400_000_000,
+                BBOX
+            )),
+            Some(_) => hints.push(format!("the area has no mapped elements; {}", BBOX)),
+            None if query.contains("[bbox:") => {
+                hints.push("the bounding box may be too small; try a positive padding".to_string())
+            }
+            None => {}
+        }
+        if query.contains("[name") {
+            hints.push("no street matches the name filter; check its spelling".to_string());
+        }
+        hints.push("the tag filters may be too strict; try more road classes".to_string());
+
+        Error::Empty {
+            query: query.to_string(),
+            area,
+            hints,
+        }
+    }
+
+    /// Classify the runtime error Overpass reported in the `remark` of a response.
+    pub(crate) fn from_remark(remark: String) -> Self {
+        if remark.contains("timed out") {
+            Error::Timeout { remark }
+        } else if remark.contains("out of memory") {
+            Error::QueryTooLarge { remark }
+        } else {
+            Error::Overpass { remark }
+        }
+    }
+}
+
+/// Turn an unsuccessful response into the matching error, keeping the explanation sent by the
+/// server if any.
+pub(crate) async fn check_status(service: Service, r: Response) -> Result<Response> {
+    let status = r.status();
+    if status.is_success() {
+        return Ok(r);
+    }
+
+    if retry::is_overloaded(status) {
+        return Err(Error::RateLimited {
+            service,
+            retry_after: retry::retry_after(&r),
+        });
+    }
+
+    let message = r.text().await.ok().and_then(|body| error_message(&body));
+    Err(Error::Status {
+        service,
+        status,
+        message,
+    })
+}
+
+/// The id in the first `area(<id>)` statement of the given query.
+fn area_id(query: &str) -> Option<u64> {
+    let (_, rest) = query.split_once("area(")?;
+    let (id, _) = rest.split_once(')')?;
+    id.trim().parse().ok()
+}
+
+/// Decode the json body of a response.
+pub(crate) fn decode<T: DeserializeOwned>(service: Service, body: &[u8]) -> Result<T> {
+    serde_json::from_slice(body).map_err(|source| {
+        // a valid prefix of a response is what's left when the connection drops, while nothing
+        // at all is what some proxies send instead of the response
+        let is_blank = body.iter().all(u8::is_ascii_whitespace);
+        if source.is_eof() && !is_blank {
+            Error::Truncated {
+                service,
+                received: body.len() as u64,
+                expected: None,
+            }
+        } else {
+            Error::Decode { service, source }
+        }
+    })
+}
+
+/// Extract the explanation from an error page, Overpass sends html with the errors in lines
+/// starting with `Error:` while Nominatim sends json or plain text.
+fn error_message(body: &str) -> Option<String> {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in body.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+
+    let errors = text
+        .lines()
+        .filter_map(|l| l.trim().strip_prefix("Error:"))
+        .map(str::trim)
+        .collect::<Vec<_>>();
+    if !errors.is_empty() {
+        return Some(errors.join("; "));
+    }
+
+    if let Ok(json) = serde_json::from_str::<serde_json::Value>(body) {
+        let error = &json["error"];
+        return error["message"]
+            .as_str()
+            .or_else(|| error.as_str())
+            .map(str::to_string);
+    }
+
+    let line = text.lines().map(str::trim).find(|l| !l.is_empty())?;
+    Some(line.chars().take(200).collect())
+}
+
+impl fmt::Display for Service {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Service::Nominatim => f.write_str("Nominatim"),
+            Service::Overpass => f.write_str("Overpass"),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Network { service, source } => write!(
+                f,
+                "cannot reach {}, check the connection and the url ({})",
+                service, source
+            ),
+            Error::RateLimited {
+                service,
+                retry_after,
+            } => {
+                write!(f, "{} is overloaded, try again", service)?;
+                match retry_after {
+                    Some(d) => write!(f, " in {}s", d.as_secs())?,
+                    None => f.write_str(" later")?,
+                }
+                if *service == Service::Overpass {
+                    f.write_str(" or add an Overpass mirror")?;
+                }
+                Ok(())
+            }
+            Error::Status {
+                service,
+                status,
+                message,
+            } => {
+                write!(f, "{} answered {}", service, status)?;
+                if let Some(m) = message {
+                    write!(f, ": {}", m)?;
+                }
+                Ok(())
+            }
+            Error::Timeout { remark } => write!(
+                f,
+                "the Overpass query timed out, try a smaller place, fewer layers or a longer timeout ({})",
+                remark
+            ),
+            Error::QueryTooLarge { remark } => write!(
+                f,
+                "the Overpass query needs too much memory, try a smaller place, fewer layers or a bigger maxsize ({})",
+                remark
+            ),
+            Error::Overpass { remark } => write!(f, "Overpass failed to run the query: {}", remark),
+            Error::Truncated {
+                service,
+                received,
+                expected,
+            } => {
+                write!(f, "the response of {} was cut short after {} bytes", service, received)?;
+                if let Some(expected) = expected {
+                    write!(f, " of {}", expected)?;
+                }
+                f.write_str(", the connection dropped; try again")
+            }
+            Error::Decode { service, source } => {
+                write!(f, "unexpected response from {} ({})", service, source)
+            }
+            Error::NotFound { place } => write!(f, "no place found for '{}'", place),
+            Error::NoArea { osm_type, osm_id } => write!(
+                f,
+                "{} {} has no area to search in, only relations and ways do",
+                osm_type, osm_id
+            ),
+            Error::BudgetExceeded(e) => write!(f, "{}", e),
+            Error::InvalidBbox { min, max } => write!(
+                f,
+                "invalid bounding box from {},{} to {},{}, the first corner must be south west of the second",
+                min.lat, min.lon, max.lat, max.lon
+            ),
+            Error::Empty { query, area, hints } => {
+                f.write_str("Overpass found nothing")?;
+                if let Some(area) = area {
+                    write!(f, " in area {}", area)?;
+                }
+                writeln!(f)?;
+                for h in hints {
+                    writeln!(f, "- {}", h)?;
+                }
+                write!(f, "\nquery:\n{}", query.trim_end())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::Partial { remark } => {
+                write!(f, "Overpass returned partial results ({})", remark)
+            }
+            Warning::Trimmed { kept, max_ways } => {
+                let kept = kept.iter().map(|c| c.tag()).collect::<Vec<_>>();
+                write!(
+                    f,
+                    "only {} fetched to stay within {} ways",
+                    kept.join(", "),
+                    max_ways
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Network { source, .. } => Some(source),
+            Error::Decode { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<BudgetExceeded> for Error {
+    fn from(e: BudgetExceeded) -> Self {
+        Error::BudgetExceeded(e)
+    }
+}
@@ -0,0 +1,8 @@
+//! Writing the styled paths of a map to the supported formats, along with their georeferencing.
+
+pub use crate::{
+    georef::WorldFile,
+    raster,
+    render::{dxf, gcode, pdf, svg},
+    style::{Style, StyledPaths},
+};
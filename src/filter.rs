@@ -0,0 +1,427 @@
+//! Selection of the kinds of roads to download.
+
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use regex::Regex;
+
+/// The value of the `highway` tag of a road, see
+/// <https://wiki.openstreetmap.org/wiki/Key:highway>.
+///
+/// Each class also matches its `_link` variant, if any.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HighwayClass {
+    Motorway,
+    Trunk,
+    Primary,
+    Secondary,
+    Tertiary,
+    Unclassified,
+    Residential,
+    LivingStreet,
+    Service,
+    Pedestrian,
+    Track,
+    Footway,
+    Cycleway,
+    Path,
+    Steps,
+}
+
+/// Which highway classes to fetch, all of them by default.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RoadFilter {
+    classes: Vec<HighwayClass>,
+    name: Option<NamePattern>,
+    exclusions: TagExclusions,
+}
+
+/// Tags that exclude the roads carrying them, e.g. `access=private` or just `area:highway` to
+/// exclude any value of the key.
+///
+/// They're translated into the Overpass query, so the excluded roads are never downloaded.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TagExclusions {
+    tags: Vec<(String, Option<String>)>,
+}
+
+/// Case insensitive regular expression matched against the `name` of the roads.
+///
+/// The same pattern is used by Overpass to filter the roads server side, thus it's better to stick
+/// to the POSIX extended syntax supported by both.
+#[derive(Debug, Clone)]
+pub struct NamePattern {
+    regex: Regex,
+    pattern: String,
+}
+
+impl HighwayClass {
+    pub const ALL: [HighwayClass; 15] = [
+        HighwayClass::Motorway,
+        HighwayClass::Trunk,
+        HighwayClass::Primary,
+        HighwayClass::Secondary,
+        HighwayClass::Tertiary,
+        HighwayClass::Unclassified,
+        HighwayClass::Residential,
+        HighwayClass::LivingStreet,
+        HighwayClass::Service,
+        HighwayClass::Pedestrian,
+        HighwayClass::Track,
+        HighwayClass::Footway,
+        HighwayClass::Cycleway,
+        HighwayClass::Path,
+        HighwayClass::Steps,
+    ];
+
+    pub fn tag(self) -> &'static str {
+        match self {
+            HighwayClass::Motorway => "motorway",
+            HighwayClass::Trunk => "trunk",
+            HighwayClass::Primary => "primary",
+            HighwayClass::Secondary => "secondary",
+            HighwayClass::Tertiary => "tertiary",
+            HighwayClass::Unclassified => "unclassified",
+            HighwayClass::Residential => "residential",
+            HighwayClass::LivingStreet => "living_street",
+            HighwayClass::Service => "service",
+            HighwayClass::Pedestrian => "pedestrian",
+            HighwayClass::Track => "track",
+            HighwayClass::Footway => "footway",
+            HighwayClass::Cycleway => "cycleway",
+            HighwayClass::Path => "path",
+            HighwayClass::Steps => "steps",
+        }
+    }
+
+    /// The class of the given `highway` tag value, if known.
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let tag = tag.strip_suffix("_link").unwrap_or(tag);
+        Self::ALL.iter().copied().find(|c| c.tag() == tag)
+    }
+}
+
+impl RoadFilter {
+    /// Fetch all the roads, even the ones whose class is not in [`HighwayClass`].
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    pub fn only(classes: &[HighwayClass]) -> Self {
+        let mut f = Self::default();
+        for c in classes {
+            f = f.with(*c);
+        }
+        f
+    }
+
+    pub fn with(mut self, class: HighwayClass) -> Self {
+        if let Err(i) = self.classes.binary_search(&class) {
+            self.classes.insert(i, class);
+        }
+        self
+    }
+
+    /// Only fetch the roads whose name matches the given pattern.
+    pub fn with_name(mut self, pattern: NamePattern) -> Self {
+        self.name = Some(pattern);
+        self
+    }
+
+    /// Never fetch the roads with any of the given tags.
+    pub fn without(mut self, exclusions: TagExclusions) -> Self {
+        self.exclusions = exclusions;
+        self
+    }
+
+    pub fn is_all(&self) -> bool {
+        self.classes.is_empty() && self.name.is_none() && self.exclusions.is_empty()
+    }
+
+    pub fn name(&self) -> Option<&NamePattern> {
+        self.name.as_ref()
+    }
+
+    pub fn classes(&self) -> &[HighwayClass] {
+        &self.classes
+    }
+
+    /// The classes of the roads kept by this filter, from the most important, the ones of
+    /// [`HighwayClass::ALL`] for the filters of all the roads.
+    pub fn ranked_classes(&self) -> &[HighwayClass] {
+        if self.classes.is_empty() {
+            &HighwayClass::ALL[..]
+        } else {
+            &self.classes[..]
+        }
+    }
+
+    /// The same filter keeping only its `n` most important classes, e.g. to fetch fewer roads.
+    /// Filters of all the roads keep the first `n` of [`HighwayClass::ALL`], leaving out the
+    /// highways of no class too, unless `n` keeps every class and the filter is left as it is.
+    pub fn most_important(&self, n: usize) -> Self {
+        let classes = self.ranked_classes();
+        if n >= classes.len() {
+            return self.clone();
+        }
+
+        RoadFilter {
+            classes: classes[..n.min(classes.len())].to_vec(),
+            ..self.clone()
+        }
+    }
+
+    pub fn exclusions(&self) -> &TagExclusions {
+        &self.exclusions
+    }
+
+    /// The Overpass selector of the ways matching this filter.
+    pub fn selector(&self) -> String {
+        let mut selector = if self.classes.is_empty() {
+            "way[highway]".to_string()
+        } else {
+            let classes = self
+                .classes
+                .iter()
+                .map(|c| c.tag())
+                .collect::<Vec<_>>()
+                .join("|");
+
+            format!(r#"way[highway~"^({})(_link)?$"]"#, classes)
+        };
+
+        if let Some(name) = &self.name {
+            selector += &name.selector();
+        }
+        selector += &self.exclusions.selector();
+
+        selector
+    }
+}
+
+impl TagExclusions {
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Exclude the roads with the given tag, or with any value of the key if `value` is `None`.
+    pub fn with(mut self, key: &str, value: Option<&str>) -> Self {
+        let tag = (key.to_string(), value.map(str::to_string));
+        if !self.tags.contains(&tag) {
+            self.tags.push(tag);
+        }
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+    }
+
+    /// Whether a road with the given tags is excluded.
+    pub fn excludes(&self, tags: &BTreeMap<String, String>) -> bool {
+        self.tags.iter().any(|(k, v)| match (tags.get(k), v) {
+            (Some(_), None) => true,
+            (Some(actual), Some(v)) => actual == v,
+            (None, _) => false,
+        })
+    }
+
+    /// The Overpass tag filters dropping the excluded roads, e.g.
+    /// `["access"!="private"][!"area:highway"]`.
+    pub fn selector(&self) -> String {
+        self.tags
+            .iter()
+            .map(|(k, v)| match v {
+                Some(v) => format!("[{}!={}]", quote(k), quote(v)),
+                None => format!("[!{}]", quote(k)),
+            })
+            .collect()
+    }
+}
+
+impl NamePattern {
+    pub fn new(pattern: &str) -> Result<Self, regex::Error> {
+        Ok(NamePattern {
+            regex: Regex::new(&format!("(?i){}", pattern))?,
+            pattern: pattern.to_string(),
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    pub fn is_match(&self, name: &str) -> bool {
+        self.regex.is_match(name)
+    }
+
+    /// The Overpass tag filter matching the names, e.g. `[name~"^via",i]`.
+    pub fn selector(&self) -> String {
+        format!(
+            r#"[name~"{}",i]"#,
+            self.pattern.replace('\\', "\\\\").replace('"', "\\\"")
+        )
+    }
+}
+
+/// Quote the given key or value for an Overpass tag filter.
+pub(crate) fn quote(s: &str) -> String {
+    format!(r#""{}""#, s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl PartialEq for NamePattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.pattern == other.pattern
+    }
+}
+
+impl Eq for NamePattern {}
+
+impl FromStr for NamePattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s).map_err(|e| e.to_string())
+    }
+}
+
+impl fmt::Display for NamePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.pattern)
+    }
+}
+
+impl FromStr for HighwayClass {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_tag(s.trim()).ok_or_else(|| format!("unknown highway class '{}'", s.trim()))
+    }
+}
+
+impl fmt::Display for HighwayClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.tag())
+    }
+}
+
+/// Parse a comma separated list of highway classes or `all`, the name pattern is not part of the
+/// textual representation.
+impl FromStr for RoadFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim() == "all" {
+            return Ok(RoadFilter::all());
+        }
+
+        let classes = s
+            .split(',')
+            .map(str::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(RoadFilter::only(&classes))
+    }
+}
+
+impl fmt::Display for RoadFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.classes.is_empty() {
+            return f.write_str("all");
+        }
+
+        for (i, c) in self.classes.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{}", c)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parse a comma separated list of `key=value` tags or bare keys, e.g.
+/// `access=private,service=driveway,area:highway`. An empty string excludes nothing.
+impl FromStr for TagExclusions {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut exclusions = TagExclusions::none();
+
+        for tag in s.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            let (key, value) = match tag.split_once('=') {
+                Some((k, v)) => (k.trim(), Some(v.trim())),
+                None => (tag, None),
+            };
+            if key.is_empty() || value.is_some_and(str::is_empty) {
+                return Err(format!("invalid tag '{}'", tag));
+            }
+
+            exclusions = exclusions.with(key, value);
+        }
+
+        Ok(exclusions)
+    }
+}
+
+impl fmt::Display for TagExclusions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (k, v)) in self.tags.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            f.write_str(k)?;
+            if let Some(v) = v {
+                write!(f, "={}", v)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serde")]
+crate::util::serde_via_str!(HighwayClass, RoadFilter);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn most_important() {
+        let all = RoadFilter::all();
+        assert_eq!(all.most_important(HighwayClass::ALL.len()), all);
+        assert_eq!(all.most_important(usize::MAX).selector(), "way[highway]");
+        assert_eq!(
+            all.most_important(2).classes(),
+            &[HighwayClass::Motorway, HighwayClass::Trunk]
+        );
+
+        let some = RoadFilter::only(&[HighwayClass::Residential, HighwayClass::Primary]);
+        assert_eq!(some.most_important(2), some);
+        assert_eq!(some.most_important(1).classes(), &[HighwayClass::Primary]);
+        assert_eq!(some.ranked_classes().len(), 2);
+    }
+
+    #[test]
+    fn exclusions_match_the_selector() {
+        let exclusions = "access=private,area:highway"
+            .parse::<TagExclusions>()
+            .unwrap();
+        assert_eq!(
+            exclusions.selector(),
+            r#"["access"!="private"][!"area:highway"]"#
+        );
+
+        let tags = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<BTreeMap<_, _>>()
+        };
+        assert!(exclusions.excludes(&tags(&[("access", "private")])));
+        assert!(exclusions.excludes(&tags(&[("area:highway", "footway")])));
+        assert!(!exclusions.excludes(&tags(&[("access", "yes")])));
+        assert!(!exclusions.excludes(&tags(&[("highway", "residential")])));
+    }
+}
@@ -0,0 +1,132 @@
+//! Alphabetical index of the street names of a map, the gazetteer at the end of road atlases.
+
+use std::fmt;
+
+use crate::{text, Road};
+
+/// The distinct names of the streets of a map, sorted alphabetically ignoring case and accents.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StreetIndex {
+    names: Vec<String>,
+}
+
+/// A line of the index page.
+enum Entry<'a> {
+    /// The initial of the names that follow.
+    Heading(char),
+    Name(&'a str),
+}
+
+impl StreetIndex {
+    /// The index of the named highways among the given roads.
+    pub fn new(roads: &[Road]) -> Self {
+        let mut names = roads
+            .iter()
+            .filter(|r| r.tags.contains_key("highway"))
+            .filter_map(|r| r.tags.get("name"))
+            .map(|n| n.trim().to_string())
+            .filter(|n| !n.is_empty())
+            .collect::<Vec<_>>();
+
+        names.sort_by_cached_key(|n| (text::fold(n), n.clone()));
+        names.dedup();
+
+        StreetIndex { names }
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Engrave the index in columns on a page of the given size, headed by the initial of each
+    /// group of names. The text is as big as `max_size`, or smaller if needed to fit the page.
+    /// The y axis grows downwards like in SVG.
+    pub fn page(&self, (w, h): (f64, f64), max_size: f64) -> Vec<Vec<(f64, f64)>> {
+        let mut size = max_size;
+        loop {
+            let columns = self.columns(h, size);
+            let width = columns.iter().map(|c| c.1).sum::<f64>() + size * 2.0;
+
+            if width <= w || size < max_size / 20.0 {
+                return self.engrave(&columns, size);
+            }
+            size *= 0.9;
+        }
+    }
+
+    /// The entries split in columns fitting the page height along with the width of each column.
+    fn columns(&self, h: f64, size: f64) -> Vec<(Vec<Entry<'_>>, f64)> {
+        let line_height = size * 1.6;
+        let lines = ((h - size * 4.0) / line_height).floor().max(1.0) as usize;
+
+        let mut entries = vec![];
+        let mut initial = None;
+        for n in &self.names {
+            let c = text::fold(n).chars().next().unwrap_or(' ');
+            if initial != Some(c) {
+                initial = Some(c);
+                entries.push(Entry::Heading(c));
+            }
+            entries.push(Entry::Name(n));
+        }
+
+        let mut columns = vec![];
+        let mut entries = entries.into_iter().peekable();
+        while entries.peek().is_some() {
+            let column = entries.by_ref().take(lines).collect::<Vec<_>>();
+            let width = column
+                .iter()
+                .map(|e| match e {
+                    Entry::Heading(c) => text::width(&c.to_string(), size),
+                    Entry::Name(n) => text::width(n, size),
+                })
+                .fold(0.0, f64::max);
+
+            columns.push((column, width + size * 2.0));
+        }
+
+        columns
+    }
+
+    fn engrave(&self, columns: &[(Vec<Entry<'_>>, f64)], size: f64) -> Vec<Vec<(f64, f64)>> {
+        let line_height = size * 1.6;
+
+        let mut paths = vec![];
+        let mut x = size * 2.0;
+        for (column, width) in columns {
+            for (i, e) in column.iter().enumerate() {
+                let baseline = size * 2.0 + size + i as f64 * line_height;
+                match e {
+                    // headings are underlined to stand out, the font has a single weight
+                    Entry::Heading(c) => {
+                        let c = c.to_string();
+                        paths.extend(text::engrave(&c, (x, baseline), size));
+                        let underline = baseline + size * 0.3;
+                        paths.push(vec![(x, underline), (x + text::width(&c, size), underline)]);
+                    }
+                    Entry::Name(n) => {
+                        paths.extend(text::engrave(n, (x + size, baseline), size));
+                    }
+                }
+            }
+
+            x += width;
+        }
+
+        paths
+    }
+}
+
+/// One name per line.
+impl fmt::Display for StreetIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for n in &self.names {
+            writeln!(f, "{}", n)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,99 @@
+//! Conversions of the fetched and processed geometries to [geo-types], to plug them into the
+//! rest of the [geo] ecosystem.
+//!
+//! Planar points keep their coordinates, i.e. meters in the projection they're in, while
+//! [`LatLon`]s become `x = longitude` and `y = latitude` as customary.
+//!
+//! ```
+//! use geo_types::{LineString, MultiLineString};
+//!
+//! let paths = vec![vec![(0.0, 0.0), (1.0, 1.0)], vec![(2.0, 0.0), (3.0, 0.0)]];
+//! let lines: MultiLineString = roads::geo::multi_line_string(&paths);
+//!
+//! assert_eq!(lines.0.len(), 2);
+//! assert_eq!(lines.0[0], LineString::from(vec![(0.0, 0.0), (1.0, 1.0)]));
+//! ```
+//!
+//! [geo-types]: https://docs.rs/geo-types
+//! [geo]: https://docs.rs/geo
+
+use geo_types::{Coord, LineString, MultiLineString, Point};
+
+use crate::{multipolygon, style::StyledPaths, LatLon, Road};
+
+/// The given path as a line string.
+pub fn line_string(path: &[(f64, f64)]) -> LineString<f64> {
+    path.iter().copied().collect()
+}
+
+/// The given paths as a single multi line string, e.g. all the roads of a layer.
+pub fn multi_line_string(paths: &[Vec<(f64, f64)>]) -> MultiLineString<f64> {
+    paths.iter().map(|p| line_string(p)).collect()
+}
+
+/// The given geographic paths, e.g. the result of [`crate::fetch_roads`], in longitude and
+/// latitude.
+pub fn geographic(paths: &[Vec<LatLon>]) -> MultiLineString<f64> {
+    paths
+        .iter()
+        .map(|p| LineString::new(p.iter().copied().map(Coord::from).collect()))
+        .collect()
+}
+
+/// The paths of the given geometries, e.g. the roads of a place, in a single multi line string.
+pub fn roads(roads: &[Road]) -> MultiLineString<f64> {
+    roads.iter().map(LineString::from).collect()
+}
+
+impl From<LatLon> for Coord<f64> {
+    fn from(ll: LatLon) -> Self {
+        Coord {
+            x: ll.lon,
+            y: ll.lat,
+        }
+    }
+}
+
+impl From<LatLon> for Point<f64> {
+    fn from(ll: LatLon) -> Self {
+        Point(ll.into())
+    }
+}
+
+impl From<Coord<f64>> for LatLon {
+    fn from(c: Coord<f64>) -> Self {
+        LatLon { lat: c.y, lon: c.x }
+    }
+}
+
+/// The outer path of the road, the holes of the areas are dropped.
+impl From<&Road> for LineString<f64> {
+    fn from(road: &Road) -> Self {
+        line_string(&road.points)
+    }
+}
+
+/// The road as an area, which makes sense only for closed ones like buildings.
+impl From<&Road> for geo_types::Polygon<f64> {
+    fn from(road: &Road) -> Self {
+        geo_types::Polygon::new(
+            line_string(&road.points),
+            road.holes.iter().map(|h| line_string(h)).collect(),
+        )
+    }
+}
+
+impl From<&multipolygon::Polygon> for geo_types::Polygon<f64> {
+    fn from(p: &multipolygon::Polygon) -> Self {
+        geo_types::Polygon::new(
+            line_string(&p.outer),
+            p.holes.iter().map(|h| line_string(h)).collect(),
+        )
+    }
+}
+
+impl From<&StyledPaths> for MultiLineString<f64> {
+    fn from(layer: &StyledPaths) -> Self {
+        multi_line_string(&layer.paths)
+    }
+}
@@ -0,0 +1,6 @@
+//! Finding places by name with [Nominatim](https://nominatim.org/) and picking the one to draw.
+
+pub use crate::{
+    best_entry, is_ambiguous, parse_coordinates, search, search_structured, search_with,
+    search_with_options, NominatimEntry, SearchOptions, SearchQuery,
+};
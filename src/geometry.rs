@@ -0,0 +1,12 @@
+//! The shapes fetched from OpenStreetMap and how they're turned into planar paths.
+
+pub use crate::{
+    circle_ring,
+    multipolygon::Polygon,
+    project,
+    projection::{
+        reproject, AzimuthalEquidistant, Equirectangular, Projection, ProjectionKind, Utm,
+        WebMercator,
+    },
+    to_lat_lon, LatLon, Road, RoadSegment,
+};
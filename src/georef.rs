@@ -0,0 +1,71 @@
+//! Georeferencing of the exported drawings, so that a raster rendering of them can be loaded as a
+//! layer in a GIS like QGIS.
+
+use std::fmt;
+
+use crate::viewport::Viewport;
+
+/// The coordinate system of the fetched points, i.e. Web Mercator (EPSG:3857), as the ESRI WKT
+/// expected in `.prj` files.
+pub const WEB_MERCATOR_WKT: &str = r#"PROJCS["WGS_1984_Web_Mercator_Auxiliary_Sphere",GEOGCS["GCS_WGS_1984",DATUM["D_WGS_1984",SPHEROID["WGS_1984",6378137.0,298.257223563]],PRIMEM["Greenwich",0.0],UNIT["Degree",0.0174532925199433]],PROJECTION["Mercator_Auxiliary_Sphere"],PARAMETER["False_Easting",0.0],PARAMETER["False_Northing",0.0],PARAMETER["Central_Meridian",0.0],PARAMETER["Standard_Parallel_1",0.0],PARAMETER["Auxiliary_Sphere_Type",0.0],UNIT["Meter",1.0]]"#;
+
+/// The EPSG code of [`WEB_MERCATOR_WKT`].
+pub const WEB_MERCATOR_EPSG: &str = "EPSG:3857";
+
+/// An [ESRI world file](https://en.wikipedia.org/wiki/World_file), mapping the pixels of a
+/// north-up raster to world coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorldFile {
+    /// Size of a pixel in world units.
+    pub pixel_size: f64,
+
+    /// World coordinates of the center of the top left pixel.
+    pub top_left: (f64, f64),
+}
+
+impl WorldFile {
+    /// The world file of a raster with one pixel per canvas unit covering the drawing of the given
+    /// viewport plus a margin on every side, like an svg written by [`crate::render::svg::write`].
+    ///
+    /// The y axis of the canvas must grow downwards, otherwise the raster is upside down.
+    /// Per-layer transforms are not taken into account.
+    pub fn for_drawing(viewport: &Viewport, margin: f64) -> Self {
+        let pixel_size = 1.0 / viewport.scale;
+        let margin = margin * pixel_size;
+
+        WorldFile {
+            pixel_size,
+            top_left: (
+                viewport.min.0 - margin + pixel_size / 2.0,
+                viewport.max.1 + margin - pixel_size / 2.0,
+            ),
+        }
+    }
+
+    /// The world file of a raster with `scale` pixels per canvas unit covering exactly the
+    /// drawing of the given viewport, like the images of [`crate::raster::render`].
+    pub fn for_raster(viewport: &Viewport, scale: f64) -> Self {
+        let pixel_size = 1.0 / (viewport.scale * scale);
+
+        WorldFile {
+            pixel_size,
+            top_left: (
+                viewport.min.0 + pixel_size / 2.0,
+                viewport.max.1 - pixel_size / 2.0,
+            ),
+        }
+    }
+}
+
+/// The six lines of the world file: pixel width, the two rotation terms, negated pixel height
+/// and the coordinates of the top left pixel.
+impl fmt::Display for WorldFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.pixel_size)?;
+        writeln!(f, "0.0")?;
+        writeln!(f, "0.0")?;
+        writeln!(f, "{}", -self.pixel_size)?;
+        writeln!(f, "{}", self.top_left.0)?;
+        writeln!(f, "{}", self.top_left.1)
+    }
+}
@@ -1,7 +1,68 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    str::FromStr,
+};
+
 use serde::{Deserialize, Serialize};
 
+use endpoints::Endpoints;
+use error::{check_status, decode, Service};
+use filter::{HighwayClass, RoadFilter};
+use multipolygon::Polygon;
+use overpass::{area_id, OutputMode, QueryBuilder, SearchArea};
+use projection::{Projection, WebMercator};
+use units::Padding;
+
+pub mod analysis;
+pub mod cache;
+pub mod clip;
+pub mod coastline;
+pub mod color;
+pub mod config;
+pub mod crop;
+pub mod diff;
+pub mod endpoints;
+pub mod error;
+pub mod export;
+pub mod filter;
+pub mod gazetteer;
+#[cfg(feature = "geo")]
+pub mod geo;
+pub mod geocode;
+pub mod geometry;
+pub mod georef;
+pub mod lod;
+pub mod marks;
+pub mod multipolygon;
+pub mod neighborhood;
+pub mod optimize;
+pub mod overpass;
+pub mod pipeline;
+pub mod polite;
+pub mod prelude;
+pub mod preview;
+pub mod progress;
+pub mod project;
+pub mod projection;
+pub mod raster;
+pub mod render;
+pub mod retry;
+pub mod rules;
+#[cfg(feature = "scripting")]
+pub mod script;
 pub mod simplify;
+pub mod split;
+pub mod style;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod text;
+pub mod transform;
+pub mod units;
 pub mod util;
+pub mod viewport;
+
+pub use error::{Error, Result, Warning};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NominatimEntry {
@@ -10,10 +71,115 @@ pub struct NominatimEntry {
     pub osm_id: i64,
     pub display_name: String,
     pub importance: f64,
+
+    /// South, north, west and east, as sent by Nominatim. A malformed one fails the decoding of
+    /// the entry, rather than searching the place around the null island.
+    #[serde(deserialize_with = "bounding_box")]
     pub boundingbox: [String; 4],
+
+    /// Main OpenStreetMap tag of the place, called `category` in the `jsonv2` format.
+    #[serde(default, alias = "category")]
+    pub class: String,
     pub r#type: String,
 }
 
+fn bounding_box<'de, D: serde::Deserializer<'de>>(
+    d: D,
+) -> std::result::Result<[String; 4], D::Error> {
+    let bbox = <[String; 4]>::deserialize(d)?;
+    match bbox
+        .iter()
+        .find(|c| !c.parse::<f64>().is_ok_and(f64::is_finite))
+    {
+        Some(c) => Err(serde::de::Error::custom(format!(
+            "malformed bounding box coordinate {:?}",
+            c
+        ))),
+        None => Ok(bbox),
+    }
+}
+
+/// The outcome of an Overpass query along with the problems that didn't stop Overpass from
+/// answering, but that likely make the result incomplete.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fetched<T> {
+    pub value: T,
+    pub warnings: Vec<Warning>,
+}
+
+/// Tweaks to the Nominatim searches.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchOptions {
+    /// Maximum number of results, Nominatim defaults to 10. It's capped at
+    /// [`SearchOptions::MAX_LIMIT`] like Nominatim does.
+    pub limit: Option<u32>,
+
+    /// Preferred languages of the names of the results, in the format of the `Accept-Language`
+    /// header, e.g. `de` or `fr,en`. Nominatim guesses them from the request otherwise.
+    pub language: Option<String>,
+
+    /// Places to leave out of the results, by place id. Nominatim has no offset, so the next
+    /// page of results is found by leaving out the places already found, see
+    /// [`SearchOptions::after`].
+    pub exclude_place_ids: Vec<i64>,
+}
+
+/// A search by parts of the address rather than by free text, to tell apart the places sharing
+/// the same name, see <https://nominatim.org/release-docs/latest/api/Search/#structured-query>.
+///
+/// It's written as `<part>=<value>` pairs separated by commas or semicolons, e.g.
+/// `city=Springfield, state=Illinois, country=USA`. Where free text is searched too, it's told
+/// apart by [`SearchQuery::PREFIX`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchQuery {
+    /// House number and street name, e.g. `10 Downing Street`.
+    pub street: Option<String>,
+    pub city: Option<String>,
+    pub county: Option<String>,
+    pub state: Option<String>,
+    pub country: Option<String>,
+    pub postalcode: Option<String>,
+}
+
+/// A single OpenStreetMap way, or node, along with its tags, already projected.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Road {
+    pub id: i64,
+    pub tags: BTreeMap<String, String>,
+    pub points: Vec<(f64, f64)>,
+
+    /// Inner rings of multipolygons, empty for everything else.
+    pub holes: Vec<Vec<(f64, f64)>>,
+}
+
+/// A [`Road`] as it is on the globe, with the coordinates sent by Overpass untouched, to be
+/// projected afterwards, see [`GeoRoad::project`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeoRoad {
+    pub id: i64,
+    pub tags: BTreeMap<String, String>,
+    pub points: Vec<LatLon>,
+
+    /// Inner rings of multipolygons, empty for everything else.
+    pub holes: Vec<Vec<LatLon>>,
+}
+
+/// A road along with its most useful tags already decoded.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RoadSegment {
+    pub id: i64,
+
+    /// `None` if the road is not a highway or its class is unknown.
+    pub highway_class: Option<HighwayClass>,
+    pub name: Option<String>,
+    pub oneway: bool,
+    pub bridge: bool,
+    pub tunnel: bool,
+    pub points: Vec<(f64, f64)>,
+}
+
 #[derive(Serialize, Deserialize)]
 struct OverpassForm {
     data: String,
@@ -22,88 +188,1299 @@ struct OverpassForm {
 #[derive(Serialize, Deserialize)]
 struct OverpassResponse {
     elements: Vec<OverpassElement>,
+
+    /// Set when the query failed or was cut short on the server.
+    #[serde(default)]
+    remark: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
 struct OverpassElement {
+    #[serde(rename = "type", default)]
+    kind: String,
     id: i64,
+    #[serde(default)]
+    tags: BTreeMap<String, String>,
+    #[serde(default)]
     geometry: Vec<LatLon>,
+
+    // only set for nodes
+    lat: Option<f64>,
+    lon: Option<f64>,
+
+    // only set for ways output without their geometry
+    #[serde(default)]
+    nodes: Vec<i64>,
+
+    // only set for relations
+    #[serde(default)]
+    members: Vec<OverpassMember>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct OverpassMember {
+    #[serde(rename = "type", default)]
+    kind: String,
+    #[serde(rename = "ref", default)]
+    id: i64,
+    role: String,
+    #[serde(default)]
+    geometry: Vec<LatLon>,
+}
+
+/// A point on the Earth, in degrees.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LatLon {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+pub async fn search(place: &str) -> Result<Vec<NominatimEntry>> {
+    search_with(&Endpoints::public(), place).await
+}
+
+/// Like [`search`], but query the given Nominatim instance.
+pub async fn search_with(endpoints: &Endpoints, place: &str) -> Result<Vec<NominatimEntry>> {
+    search_with_options(endpoints, place, &SearchOptions::default()).await
+}
+
+/// Like [`search_with`], but with full control over the search.
+pub async fn search_with_options(
+    endpoints: &Endpoints,
+    place: &str,
+    options: &SearchOptions,
+) -> Result<Vec<NominatimEntry>> {
+    nominatim_search(endpoints, vec![("q", place.to_string())], options).await
+}
+
+/// Like [`search_with_options`], but search by the parts of the address.
+pub async fn search_structured(
+    endpoints: &Endpoints,
+    query: &SearchQuery,
+    options: &SearchOptions,
+) -> Result<Vec<NominatimEntry>> {
+    nominatim_search(endpoints, query.params(), options).await
+}
+
+async fn nominatim_search(
+    endpoints: &Endpoints,
+    mut params: Vec<(&str, String)>,
+    options: &SearchOptions,
+) -> Result<Vec<NominatimEntry>> {
+    params.push(("format", "jsonv2".to_string()));
+    if let Some(limit) = options.limit {
+        let limit = limit.min(SearchOptions::MAX_LIMIT);
+        params.push(("limit", limit.to_string()));
+    }
+    if let Some(language) = &options.language {
+        params.push(("accept-language", language.clone()));
+    }
+    if !options.exclude_place_ids.is_empty() {
+        let ids = options
+            .exclude_place_ids
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>();
+        params.push(("exclude_place_ids", ids.join(",")));
+    }
+
+    let key = format!(
+        "{}/search?{}",
+        endpoints.nominatim,
+        params
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>()
+            .join("&")
+    );
+    let cached = endpoints.cache.as_ref().and_then(|c| c.get(&key));
+    if let Some(Ok(entries)) = cached.map(|body| decode(Service::Nominatim, &body)) {
+        return Ok(entries);
+    }
+
+    let r = endpoints
+        .retry
+        .send(|| endpoints.nominatim_request("/search").query(&params))
+        .await
+        .map_err(|e| Error::from_reqwest(Service::Nominatim, e))?;
+    let body = check_status(Service::Nominatim, r)
+        .await?
+        .bytes()
+        .await
+        .map_err(|e| Error::from_reqwest(Service::Nominatim, e))?;
+
+    let entries = decode(Service::Nominatim, &body)?;
+    if let Some(cache) = &endpoints.cache {
+        let _ = cache.put(&key, &body);
+    }
+
+    Ok(entries)
 }
 
-#[derive(Clone, Copy, Serialize, Deserialize)]
-struct LatLon {
+/// Pick the entry that most likely is the place the user meant, without asking.
+///
+/// Administrative boundaries of cities and towns are preferred over everything else, ties are
+/// broken by importance and then by the smallest enclosing area.
+pub fn best_entry(entries: &[NominatimEntry]) -> Option<&NominatimEntry> {
+    entries.iter().min_by(|a, b| {
+        b.rank()
+            .cmp(&a.rank())
+            .then(b.importance.total_cmp(&a.importance))
+            .then(a.bbox_area().total_cmp(&b.bbox_area()))
+    })
+}
+
+/// Whether [`best_entry`] had to choose between entries that are equally good candidates.
+pub fn is_ambiguous(entries: &[NominatimEntry]) -> bool {
+    let best = match best_entry(entries) {
+        None => return false,
+        Some(b) => b,
+    };
+
+    entries.iter().any(|e| {
+        e.place_id != best.place_id
+            && e.rank() == best.rank()
+            && (e.importance - best.importance).abs() < 0.05
+    })
+}
+
+/// Fetch the roads of the given entry whose class is selected by the given filter, as they are
+/// on the globe with the coordinates sent by Overpass. Use [`project`] to draw them.
+///
+/// Check the warnings of the result to know whether Overpass returned only part of the roads.
+pub async fn fetch_roads(
+    entry: &NominatimEntry,
+    filter: &RoadFilter,
+) -> Result<Fetched<Vec<Vec<LatLon>>>> {
+    fetch_roads_with(&Endpoints::public(), entry, filter).await
+}
+
+/// Like [`fetch_roads`], but query the given Overpass instance.
+pub async fn fetch_roads_with(
+    endpoints: &Endpoints,
+    entry: &NominatimEntry,
+    filter: &RoadFilter,
+) -> Result<Fetched<Vec<Vec<LatLon>>>> {
+    fetch_paths(endpoints, &roads_query(entry, filter)).await
+}
+
+/// Like [`fetch_roads`], but in the rectangle with the given corners rather than in a place,
+/// e.g. to draw an area that isn't a named place without geocoding it.
+pub async fn fetch_roads_in_bbox(
+    min_lat: f64,
+    min_lon: f64,
+    max_lat: f64,
+    max_lon: f64,
+    filter: &RoadFilter,
+) -> Result<Fetched<Vec<Vec<LatLon>>>> {
+    fetch_roads_in_bbox_with(
+        &Endpoints::public(),
+        min_lat,
+        min_lon,
+        max_lat,
+        max_lon,
+        filter,
+    )
+    .await
+}
+
+/// Like [`fetch_roads_in_bbox`], but query the given Overpass instance.
+pub async fn fetch_roads_in_bbox_with(
+    endpoints: &Endpoints,
+    min_lat: f64,
+    min_lon: f64,
+    max_lat: f64,
+    max_lon: f64,
+    filter: &RoadFilter,
+) -> Result<Fetched<Vec<Vec<LatLon>>>> {
+    let query = bbox_roads_query(min_lat, min_lon, max_lat, max_lon, filter)?;
+    fetch_paths(endpoints, &query).await
+}
+
+/// Like [`fetch_roads`], but within the given radius in meters of a point, e.g. for a print of
+/// one's neighborhood, which neither an administrative boundary nor a bounding box crop well.
+pub async fn fetch_roads_around(
     lat: f64,
     lon: f64,
+    radius: f64,
+    filter: &RoadFilter,
+) -> Result<Fetched<Vec<Vec<LatLon>>>> {
+    fetch_roads_around_with(&Endpoints::public(), lat, lon, radius, filter).await
+}
+
+/// Like [`fetch_roads_around`], but query the given Overpass instance.
+pub async fn fetch_roads_around_with(
+    endpoints: &Endpoints,
+    lat: f64,
+    lon: f64,
+    radius: f64,
+    filter: &RoadFilter,
+) -> Result<Fetched<Vec<Vec<LatLon>>>> {
+    fetch_paths(endpoints, &around_roads_query(lat, lon, radius, filter)).await
+}
+
+/// The Overpass query used to fetch the roads within the given radius in meters of a point.
+pub fn around_roads_query(lat: f64, lon: f64, radius: f64, filter: &RoadFilter) -> String {
+    elements_query_around(lat, lon, radius, &[&filter.selector()])
 }
 
-pub async fn search(place: &str) -> reqwest::Result<Vec<NominatimEntry>> {
-    reqwest::Client::new()
-        .get("https://nominatim.openstreetmap.org/search")
-        .query(&[("q", place), ("format", "json")])
-        .header(
-            reqwest::header::USER_AGENT,
-            format!("roads/{}", env!("CARGO_PKG_VERSION")),
-        )
-        .send()
+/// Like [`fetch_roads`], but in the relation or the way with the given id, e.g. a city whose id
+/// is known, without searching it by name.
+pub async fn fetch_roads_for_osm_id(
+    osm_type: &str,
+    osm_id: i64,
+    filter: &RoadFilter,
+) -> Result<Fetched<Vec<Vec<LatLon>>>> {
+    fetch_roads_for_osm_id_with(&Endpoints::public(), osm_type, osm_id, filter).await
+}
+
+/// Like [`fetch_roads_for_osm_id`], but query the given Overpass instance.
+pub async fn fetch_roads_for_osm_id_with(
+    endpoints: &Endpoints,
+    osm_type: &str,
+    osm_id: i64,
+    filter: &RoadFilter,
+) -> Result<Fetched<Vec<Vec<LatLon>>>> {
+    fetch_paths(endpoints, &osm_id_roads_query(osm_type, osm_id, filter)?).await
+}
+
+/// The Overpass query used to fetch the roads in the relation or the way with the given id.
+pub fn osm_id_roads_query(osm_type: &str, osm_id: i64, filter: &RoadFilter) -> Result<String> {
+    let area = area_id(osm_type, osm_id).ok_or_else(|| Error::NoArea {
+        osm_type: osm_type.to_string(),
+        osm_id,
+    })?;
+
+    Ok(QueryBuilder::new()
+        .area(area)
+        .select(filter.selector())
+        .build())
+}
+
+/// The Overpass query used to fetch the roads in the rectangle with the given corners, which
+/// must be a non empty rectangle in the world.
+pub fn bbox_roads_query(
+    min_lat: f64,
+    min_lon: f64,
+    max_lat: f64,
+    max_lon: f64,
+    filter: &RoadFilter,
+) -> Result<String> {
+    let is_valid = (-90.0..=90.0).contains(&min_lat)
+        && (-90.0..=90.0).contains(&max_lat)
+        && (-180.0..=180.0).contains(&min_lon)
+        && (-180.0..=180.0).contains(&max_lon)
+        && min_lat < max_lat
+        && min_lon < max_lon;
+    if !is_valid {
+        return Err(Error::InvalidBbox {
+            min: LatLon::new(min_lat, min_lon),
+            max: LatLon::new(max_lat, max_lon),
+        });
+    }
+
+    Ok(QueryBuilder::new()
+        .bbox(min_lat, min_lon, max_lat, max_lon)
+        .select(filter.selector())
+        .build())
+}
+
+/// The paths of the elements output by the given query, as Overpass sent them.
+async fn fetch_paths(endpoints: &Endpoints, query: &str) -> Result<Fetched<Vec<Vec<LatLon>>>> {
+    Ok(endpoints
+        .overpass_client()
+        .fetch_geo_query(query)
         .await?
-        .json()
-        .await
+        .map(|roads| roads.into_iter().map(|r| r.points).collect()))
 }
 
-pub async fn fetch_roads(entry: &NominatimEntry) -> reqwest::Result<Vec<Vec<(f64, f64)>>> {
-    let query = if entry.osm_type != "relation" && entry.osm_type != "way" {
-        format!(
-            r#"[out:json][timeout:60][bbox:{},{},{},{}];
-// way[highway~"^(motorway|primary|secondary|tertiary)|residential"];
-way[highway];
-out geom;"#,
-            entry.boundingbox[0], entry.boundingbox[2], entry.boundingbox[1], entry.boundingbox[3],
-        )
-    } else {
-        format!(
-            r#"[out:json][timeout:60];
-area({})->.a;
-// way(area.a)[highway~"^(motorway|primary|secondary|tertiary)|residential"];
-way(area.a)[highway];
-out geom;"#,
-            if entry.osm_type == "relation" {
-                3_600_000_000 + entry.osm_id
-            } else if entry.osm_type == "way" {
-                2_400_000_000 + entry.osm_id
-            } else {
-                unreachable!()
-            },
-        )
+/// Like [`fetch_roads`], but then [projected](project) in [`WebMercator`] like the rest of the
+/// fetched elements.
+pub async fn fetch_roads_mercator(
+    entry: &NominatimEntry,
+    filter: &RoadFilter,
+) -> Result<Fetched<Vec<Vec<(f64, f64)>>>> {
+    Ok(fetch_roads(entry, filter)
+        .await?
+        .map(|roads| project(&roads, &WebMercator)))
+}
+
+/// The planar coordinates of the given paths in the given projection.
+pub fn project(
+    paths: &[Vec<LatLon>],
+    projection: &(impl Projection + ?Sized),
+) -> Vec<Vec<(f64, f64)>> {
+    paths
+        .iter()
+        .map(|p| {
+            p.iter()
+                .map(|ll| projection.project(ll.lat, ll.lon))
+                .collect()
+        })
+        .collect()
+}
+
+/// Like [`fetch_roads`], but keep the id and the tags of each way.
+pub async fn fetch_tagged_roads(
+    entry: &NominatimEntry,
+    filter: &RoadFilter,
+) -> Result<Fetched<Vec<Road>>> {
+    fetch_tagged_roads_with(&Endpoints::public(), entry, filter).await
+}
+
+/// Like [`fetch_tagged_roads`], but query the given Overpass instance.
+pub async fn fetch_tagged_roads_with(
+    endpoints: &Endpoints,
+    entry: &NominatimEntry,
+    filter: &RoadFilter,
+) -> Result<Fetched<Vec<Road>>> {
+    fetch_query_with(endpoints, &roads_query(entry, filter)).await
+}
+
+/// Like [`fetch_roads`], but decode the most common tags of each road.
+pub async fn fetch_roads_detailed(
+    entry: &NominatimEntry,
+    filter: &RoadFilter,
+) -> Result<Fetched<Vec<RoadSegment>>> {
+    Ok(fetch_tagged_roads(entry, filter)
+        .await?
+        .map(|roads| roads.into_iter().map(RoadSegment::from).collect()))
+}
+
+/// The Overpass query used to fetch the roads of the given entry.
+pub fn roads_query(entry: &NominatimEntry, filter: &RoadFilter) -> String {
+    elements_query(entry, &[&filter.selector()])
+}
+
+/// Overpass selector of rivers, streams, canals and the like.
+pub const WATERWAYS_SELECTOR: &str = "way[waterway]";
+
+/// Fetch the rivers, streams and canals of the given place, see [`WATERWAYS_SELECTOR`]. The
+/// paths are in Web Mercator like the points of [`Road`].
+pub async fn fetch_waterways(entry: &NominatimEntry) -> Result<Fetched<Vec<Vec<(f64, f64)>>>> {
+    fetch_waterways_with(&Endpoints::public(), entry).await
+}
+
+/// Like [`fetch_waterways`], but query the given Overpass instance.
+pub async fn fetch_waterways_with(
+    endpoints: &Endpoints,
+    entry: &NominatimEntry,
+) -> Result<Fetched<Vec<Vec<(f64, f64)>>>> {
+    Ok(fetch_features(endpoints, &waterways_query(entry))
+        .await?
+        .map(|roads| roads.into_iter().map(|r| r.points).collect()))
+}
+
+/// The Overpass query used to fetch the waterways of the given entry.
+pub fn waterways_query(entry: &NominatimEntry) -> String {
+    elements_query(entry, &[WATERWAYS_SELECTOR])
+}
+
+/// Fetch the actual outline of the given place, outer rings and holes alike.
+///
+/// Only relations and ways have one, the result is empty for everything else.
+pub async fn fetch_boundary(entry: &NominatimEntry) -> Result<Fetched<Vec<Vec<(f64, f64)>>>> {
+    fetch_boundary_with(&Endpoints::public(), entry).await
+}
+
+/// Like [`fetch_boundary`], but query the given Overpass instance.
+pub async fn fetch_boundary_with(
+    endpoints: &Endpoints,
+    entry: &NominatimEntry,
+) -> Result<Fetched<Vec<Vec<(f64, f64)>>>> {
+    let query = match boundary_query(entry) {
+        None => return Ok(Fetched::complete(vec![])),
+        Some(q) => q,
     };
 
-    let client = reqwest::Client::new();
-    let r: OverpassResponse = client
-        .post("https://overpass-api.de/api/interpreter")
-        .form(&OverpassForm { data: query })
-        .header(reqwest::header::CONTENT_TYPE, "application/osm3s+xml")
-        .send()
+    Ok(fetch_features(endpoints, &query).await?.map(|roads| {
+        roads
+            .into_iter()
+            .flat_map(|r| std::iter::once(r.points).chain(r.holes))
+            .collect()
+    }))
+}
+
+/// Name of the output set of the boundary in the queries of [`with_boundary`].
+pub const BOUNDARY_SET: &str = "boundary";
+
+/// The given query also outputting the boundary of the given entry as the [`BOUNDARY_SET`]
+/// output set, so that both come with a single request. `None` if the entry has no boundary, like
+/// for [`boundary_query`].
+///
+/// The elements of the boundary are tagged with [`SET_TAG`](overpass::SET_TAG).
+pub fn with_boundary(query: &str, entry: &NominatimEntry) -> Option<String> {
+    if entry.osm_type != "relation" && entry.osm_type != "way" {
+        return None;
+    }
+
+    Some(format!(
+        "{}\n{}{}({});\nout geom;",
+        query.trim_end(),
+        overpass::set_marker(BOUNDARY_SET),
+        entry.osm_type,
+        entry.osm_id
+    ))
+}
+
+/// The Overpass query used to fetch the outline of the given entry, see [`fetch_boundary`].
+///
+/// `None` for nodes and the rest of the entries that aren't relations or ways, since they have no
+/// outline to fetch.
+pub fn boundary_query(entry: &NominatimEntry) -> Option<String> {
+    if entry.osm_type != "relation" && entry.osm_type != "way" {
+        return None;
+    }
+
+    Some(
+        QueryBuilder::new()
+            .element(&entry.osm_type, entry.osm_id)
+            .build(),
+    )
+}
+
+/// Overpass selector of the heavy rail, tram and subway tracks.
+pub const RAILWAYS_SELECTOR: &str = r#"way[railway~"^(rail|tram|subway)$"]"#;
+
+/// Fetch the railway, tram and subway tracks of the given place, see [`RAILWAYS_SELECTOR`]. The
+/// paths are in Web Mercator like the points of [`Road`].
+pub async fn fetch_railways(entry: &NominatimEntry) -> Result<Fetched<Vec<Vec<(f64, f64)>>>> {
+    fetch_railways_with(&Endpoints::public(), entry).await
+}
+
+/// Like [`fetch_railways`], but query the given Overpass instance.
+pub async fn fetch_railways_with(
+    endpoints: &Endpoints,
+    entry: &NominatimEntry,
+) -> Result<Fetched<Vec<Vec<(f64, f64)>>>> {
+    Ok(fetch_features(endpoints, &railways_query(entry))
+        .await?
+        .map(|roads| roads.into_iter().map(|r| r.points).collect()))
+}
+
+/// The Overpass query used to fetch the railways of the given entry.
+pub fn railways_query(entry: &NominatimEntry) -> String {
+    elements_query(entry, &[RAILWAYS_SELECTOR])
+}
+
+/// Overpass selectors of the building footprints, both simple ways and multipolygons.
+pub const BUILDINGS_SELECTORS: [&str; 2] =
+    ["way[building]", "relation[building][type=multipolygon]"];
+
+/// Fetch the footprints of the buildings, only closed polygons are returned.
+pub async fn fetch_buildings(entry: &NominatimEntry) -> Result<Fetched<Vec<Polygon>>> {
+    fetch_buildings_with(&Endpoints::public(), entry).await
+}
+
+/// Like [`fetch_buildings`], but query the given Overpass instance.
+pub async fn fetch_buildings_with(
+    endpoints: &Endpoints,
+    entry: &NominatimEntry,
+) -> Result<Fetched<Vec<Polygon>>> {
+    Ok(fetch_features(endpoints, &buildings_query(entry))
         .await?
-        .json()
-        .await?;
+        .map(|roads| {
+            roads
+                .into_iter()
+                .filter(Road::is_closed)
+                .map(|r| Polygon {
+                    outer: r.points,
+                    holes: r.holes,
+                })
+                .collect()
+        }))
+}
+
+/// The Overpass query used to fetch the buildings of the given entry.
+pub fn buildings_query(entry: &NominatimEntry) -> String {
+    elements_query(entry, &BUILDINGS_SELECTORS)
+}
+
+/// Fetch the nodes matching the given tag filter (e.g. `natural=tree` or
+/// `[highway=street_lamp]`) as single point roads.
+pub async fn fetch_points(entry: &NominatimEntry, filter: &str) -> Result<Fetched<Vec<Road>>> {
+    fetch_points_with(&Endpoints::public(), entry, filter).await
+}
+
+/// Like [`fetch_points`], but query the given Overpass instance.
+pub async fn fetch_points_with(
+    endpoints: &Endpoints,
+    entry: &NominatimEntry,
+    filter: &str,
+) -> Result<Fetched<Vec<Road>>> {
+    fetch_features(endpoints, &elements_query(entry, &[&node_selector(filter)])).await
+}
+
+/// The Overpass selector of the nodes matching the given tag filter.
+pub fn node_selector(filter: &str) -> String {
+    let filter = filter.trim();
+    if filter.starts_with('[') {
+        format!("node{}", filter)
+    } else {
+        format!("node[{}]", filter)
+    }
+}
+
+/// Build the query to fetch all the elements matching any of the given Overpass selectors (e.g.
+/// `way[highway]`) inside the given entry.
+///
+/// Relations and ways are searched by area, everything else by bounding box.
+pub fn elements_query(entry: &NominatimEntry, selectors: &[&str]) -> String {
+    elements_query_in(search_area(entry), selectors)
+}
+
+/// Like [`elements_query`], but always search by the bounding box of the entry, e.g. because it
+/// has been [padded](NominatimEntry::padded).
+pub fn elements_query_in_bbox(entry: &NominatimEntry, selectors: &[&str]) -> String {
+    elements_query_in(search_area_of(entry, false), selectors)
+}
+
+/// Like [`elements_query`], but search within the given radius in meters of a point. Ways
+/// crossing the circle are returned whole.
+pub fn elements_query_around(lat: f64, lon: f64, radius: f64, selectors: &[&str]) -> String {
+    elements_query_in(SearchArea::Around { lat, lon, radius }, selectors)
+}
+
+/// Where [`elements_query`] searches the elements of the given entry.
+pub fn search_area(entry: &NominatimEntry) -> SearchArea {
+    search_area_of(
+        entry,
+        entry.osm_type == "relation" || entry.osm_type == "way",
+    )
+}
+
+fn search_area_of(entry: &NominatimEntry, by_area: bool) -> SearchArea {
+    match area_id(&entry.osm_type, entry.osm_id).filter(|_| by_area) {
+        Some(id) => SearchArea::Area(id),
+        None => {
+            let (s, w, n, e) = entry.bounds();
+            SearchArea::Bbox(s, w, n, e)
+        }
+    }
+}
+
+/// The query fetching the elements matching any of the given selectors in the given area.
+pub fn elements_query_in(search: SearchArea, selectors: &[&str]) -> String {
+    selectors
+        .iter()
+        .fold(QueryBuilder::new().search(search), |q, s| q.select(s))
+        .build()
+}
+
+/// The given query outputting the number of matched elements rather than their geometry, see
+/// [`OverpassClient::count`](overpass::OverpassClient::count).
+pub fn count_query(query: &str) -> String {
+    replace_output(query, OutputMode::Count)
+}
+
+/// The given query outputting the matched elements as a skeleton, i.e. the ids of the nodes of
+/// the ways followed by the nodes themselves, see
+/// [`FetchStrategy::Skeleton`](overpass::FetchStrategy::Skeleton).
+pub fn skeleton_query(query: &str) -> String {
+    replace_output(query, OutputMode::Skeleton)
+}
+
+/// The given query with every `out geom;` replaced by the given output, one per output set, or
+/// with the output appended if there's none.
+fn replace_output(query: &str, output: OutputMode) -> String {
+    if query.contains("out geom;") {
+        query.replace("out geom;", &output.to_string())
+    } else {
+        format!("{}\n{}", query.trim_end(), output)
+    }
+}
+
+/// Run the given Overpass query and return the ways and nodes it outputs.
+///
+/// The query must output json along with the geometry of the ways, i.e. `[out:json]` and `out
+/// geom`. Nodes are returned as roads made of a single point.
+///
+/// When Overpass runs out of time or memory midway it may still answer with the elements found
+/// so far, in that case the result carries a [`Warning::Partial`]. A query matching nothing at all
+/// fails with [`Error::Empty`], whose hints suggest what to change.
+pub async fn fetch_query(query: &str) -> Result<Fetched<Vec<Road>>> {
+    fetch_query_with(&Endpoints::public(), query).await
+}
+
+/// Like [`fetch_query`], but query the given Overpass instance.
+pub async fn fetch_query_with(endpoints: &Endpoints, query: &str) -> Result<Fetched<Vec<Road>>> {
+    endpoints.overpass_client().fetch_query(query).await
+}
+
+/// Like [`fetch_query`], but a query matching nothing is not an error, for the features that a
+/// place may simply not have.
+async fn fetch_features(endpoints: &Endpoints, query: &str) -> Result<Fetched<Vec<Road>>> {
+    endpoints.overpass_client().fetch_elements(query).await
+}
+
+/// Run an arbitrary Overpass query written by hand, e.g. copied from overpass-turbo, for the
+/// filters that no option can express.
+///
+/// Unlike [`fetch_query`] the query doesn't have to ask for json or for the geometry, see
+/// [`custom_query`].
+pub async fn fetch_custom(query: &str) -> Result<Fetched<Vec<Road>>> {
+    fetch_custom_with(&Endpoints::public(), query).await
+}
+
+/// Like [`fetch_custom`], but query the given Overpass instance.
+pub async fn fetch_custom_with(endpoints: &Endpoints, query: &str) -> Result<Fetched<Vec<Road>>> {
+    fetch_query_with(endpoints, &custom_query(query)).await
+}
+
+/// The given query written by hand made parsable: asking for json, since Overpass defaults to
+/// xml, and outputting the geometry of the elements at the end if it outputs nothing.
+pub fn custom_query(query: &str) -> String {
+    let query = overpass::set_setting(query.trim(), "out", "json");
+
+    let outputs = query
+        .split(';')
+        .skip(1)
+        .any(|s| s.trim_start().starts_with("out"));
+    if outputs {
+        query
+    } else {
+        format!("{}\n{}", query, OutputMode::Geom)
+    }
+}
+
+impl<T> Fetched<T> {
+    /// A result without warnings.
+    pub fn complete(value: T) -> Self {
+        Fetched {
+            value,
+            warnings: vec![],
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.warnings.is_empty()
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Fetched<U> {
+        Fetched {
+            value: f(self.value),
+            warnings: self.warnings,
+        }
+    }
+}
+
+/// The circle with the given center and radius in meters as a closed ring, already projected.
+pub fn circle_ring(lat: f64, lon: f64, radius: f64) -> Vec<(f64, f64)> {
+    const SEGMENTS: usize = 72;
+
+    // destination of each bearing on the sphere, see
+    // https://www.movable-type.co.uk/scripts/latlong.html
+    let d = radius / projection::EARTH_RADIUS;
+    let (phi1, lambda1) = (lat.to_radians(), lon.to_radians());
+
+    (0..=SEGMENTS)
+        .map(|i| {
+            let bearing = (i % SEGMENTS) as f64 / SEGMENTS as f64 * std::f64::consts::TAU;
+            let phi2 = (phi1.sin() * d.cos() + phi1.cos() * d.sin() * bearing.cos()).asin();
+            let lambda2 = lambda1
+                + f64::atan2(
+                    bearing.sin() * d.sin() * phi1.cos(),
+                    d.cos() - phi1.sin() * phi2.sin(),
+                );
 
-    Ok(r.elements
+            LatLon::new(phi2.to_degrees(), lambda2.to_degrees()).to_xy()
+        })
+        .collect()
+}
+
+/// The coordinates in the given text, e.g. `45.07, 7.68` or `45.07 7.68`, if it's nothing but a
+/// latitude and a longitude in degrees.
+pub fn parse_coordinates(s: &str) -> Option<LatLon> {
+    let (lat, lon) = s
+        .trim()
+        .split_once(|c: char| c == ',' || c.is_whitespace())?;
+    let lat = lat.trim().parse::<f64>().ok()?;
+    let lon = lon.trim().parse::<f64>().ok()?;
+
+    ((-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon))
+        .then(|| LatLon::new(lat, lon))
+}
+
+impl SearchOptions {
+    /// The most results Nominatim lists per search.
+    pub const MAX_LIMIT: u32 = 40;
+
+    /// The same search for the next page of results, after the given ones.
+    pub fn after<'a>(&self, found: impl IntoIterator<Item = &'a NominatimEntry>) -> Self {
+        let mut options = self.clone();
+        options
+            .exclude_place_ids
+            .extend(found.into_iter().map(|e| e.place_id));
+        options
+    }
+}
+
+impl SearchQuery {
+    /// Prefix of the searches by address among the free text ones, e.g.
+    /// `address: city=Springfield, state=Illinois`.
+    pub const PREFIX: &'static str = "address:";
+
+    /// The parts of the address by their Nominatim parameter, in the order they're written.
+    fn parts(&self) -> [(&'static str, &Option<String>); 6] {
+        [
+            ("street", &self.street),
+            ("city", &self.city),
+            ("county", &self.county),
+            ("state", &self.state),
+            ("country", &self.country),
+            ("postalcode", &self.postalcode),
+        ]
+    }
+
+    fn part_mut(&mut self, name: &str) -> Option<&mut Option<String>> {
+        Some(match name {
+            "street" => &mut self.street,
+            "city" => &mut self.city,
+            "county" => &mut self.county,
+            "state" => &mut self.state,
+            "country" => &mut self.country,
+            "postalcode" => &mut self.postalcode,
+            _ => return None,
+        })
+    }
+
+    /// The query parameters of the parts that are set.
+    pub fn params(&self) -> Vec<(&'static str, String)> {
+        self.parts()
+            .into_iter()
+            .filter_map(|(k, v)| Some((k, v.clone()?)))
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parts().iter().all(|(_, v)| v.is_none())
+    }
+}
+
+impl FromStr for SearchQuery {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut query = SearchQuery::default();
+        for part in s.split([',', ';']).map(str::trim).filter(|p| !p.is_empty()) {
+            let invalid = || {
+                format!(
+                    "invalid address part '{}', expected e.g. city=Springfield",
+                    part
+                )
+            };
+
+            let (name, value) = part.split_once('=').ok_or_else(invalid)?;
+            let value = value.trim();
+            let slot = query
+                .part_mut(name.trim())
+                .filter(|_| !value.is_empty())
+                .ok_or_else(|| {
+                    format!(
+                        "{}, the parts are street, city, county, state, country and postalcode",
+                        invalid()
+                    )
+                })?;
+            *slot = Some(value.to_string());
+        }
+
+        if query.is_empty() {
+            return Err("empty address, expected e.g. city=Springfield".to_string());
+        }
+        Ok(query)
+    }
+}
+
+impl fmt::Display for SearchQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let params = self
+            .params()
+            .into_iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>();
+        f.write_str(&params.join(", "))
+    }
+}
+
+impl NominatimEntry {
+    /// Type of the entries made by [`NominatimEntry::at`] rather than found by Nominatim.
+    pub const COORDINATES_TYPE: &'static str = "coordinates";
+
+    /// An entry for the given point, as if Nominatim had found it.
+    pub fn at(point: LatLon) -> Self {
+        let (lat, lon) = (point.lat.to_string(), point.lon.to_string());
+        NominatimEntry {
+            place_id: 0,
+            osm_type: Self::COORDINATES_TYPE.to_string(),
+            osm_id: 0,
+            display_name: format!("{:.5}, {:.5}", point.lat, point.lon),
+            importance: 0.0,
+            boundingbox: [lat.clone(), lat, lon.clone(), lon],
+            class: String::new(),
+            r#type: String::new(),
+        }
+    }
+
+    /// The center of the bounding box of this entry.
+    pub fn center(&self) -> LatLon {
+        let c = |i: usize| self.boundingbox[i].parse::<f64>().unwrap_or_default();
+        LatLon::new((c(0) + c(1)) / 2.0, (c(2) + c(3)) / 2.0)
+    }
+
+    /// The bounding box of this entry as south, west, north and east.
+    pub fn bounds(&self) -> overpass::Bounds {
+        let c = |i: usize| self.boundingbox[i].parse::<f64>().unwrap_or_default();
+        (c(0), c(2), c(1), c(3))
+    }
+
+    /// The bounding box of this entry as a closed ring, already projected.
+    pub fn bbox_ring(&self) -> Vec<(f64, f64)> {
+        let c = |i: usize| self.boundingbox[i].parse::<f64>().unwrap_or_default();
+        let (min_lat, max_lat, min_lon, max_lon) = (c(0), c(1), c(2), c(3));
+
+        [
+            (min_lat, min_lon),
+            (min_lat, max_lon),
+            (max_lat, max_lon),
+            (max_lat, min_lon),
+            (min_lat, min_lon),
+        ]
         .into_iter()
-        .map(|e| e.geometry.into_iter().map(|p| p.to_xy()).collect())
-        .collect())
+        .map(|(lat, lon)| LatLon { lat, lon }.to_xy())
+        .collect()
+    }
+
+    /// This entry with its bounding box grown, or shrunk, by the given padding on every side.
+    ///
+    /// A percentage is relative to the size of the box along each axis, a shrunk box never gets
+    /// smaller than its center.
+    pub fn padded(&self, padding: Padding) -> Self {
+        /// Meters per degree of latitude, roughly.
+        const METERS_PER_DEGREE: f64 = 111_320.0;
+
+        if padding.is_none() {
+            return self.clone();
+        }
+
+        let c = |i: usize| self.boundingbox[i].parse::<f64>().unwrap_or_default();
+        let (min_lat, max_lat, min_lon, max_lon) = (c(0), c(1), c(2), c(3));
+
+        let (dlat, dlon) = match padding {
+            Padding::Percent(p) => (
+                (max_lat - min_lat) * p / 100.0,
+                (max_lon - min_lon) * p / 100.0,
+            ),
+            Padding::Distance(d) => {
+                let m = d.to_meters();
+                let lat = ((min_lat + max_lat) / 2.0).to_radians();
+                (
+                    m / METERS_PER_DEGREE,
+                    m / (METERS_PER_DEGREE * lat.cos().max(1e-6)),
+                )
+            }
+        };
+
+        let pad = |min: f64, max: f64, d: f64| {
+            let center = (min + max) / 2.0;
+            ((min - d).min(center), (max + d).max(center))
+        };
+        let (min_lat, max_lat) = pad(min_lat, max_lat, dlat);
+        let (min_lon, max_lon) = pad(min_lon, max_lon, dlon);
+
+        NominatimEntry {
+            boundingbox: [
+                format!("{:.7}", min_lat.max(-90.0)),
+                format!("{:.7}", max_lat.min(90.0)),
+                format!("{:.7}", min_lon.max(-180.0)),
+                format!("{:.7}", max_lon.min(180.0)),
+            ],
+            ..self.clone()
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        let is_boundary = self.osm_type == "relation" && self.class == "boundary";
+        let is_settlement = matches!(
+            self.r#type.as_str(),
+            "city" | "town" | "village" | "administrative"
+        );
+
+        u8::from(is_boundary) * 2 + u8::from(is_settlement)
+    }
+
+    fn bbox_area(&self) -> f64 {
+        let c = |i: usize| self.boundingbox[i].parse::<f64>().unwrap_or(f64::NAN);
+        let area = (c(1) - c(0)).abs() * (c(3) - c(2)).abs();
+
+        if area.is_nan() {
+            f64::INFINITY
+        } else {
+            area
+        }
+    }
+}
+
+impl Road {
+    /// Whether the outline of this road is a closed ring, like the ones of areas.
+    pub fn is_closed(&self) -> bool {
+        self.points.len() > 3 && self.points.first() == self.points.last()
+    }
+
+    /// Whether this is a closed way mapped as an area, like a pedestrian square, rather than a
+    /// loop, like a roundabout.
+    ///
+    /// An explicit `area` tag wins, otherwise only pedestrian highways, buildings and
+    /// multipolygons are considered areas.
+    pub fn is_area(&self) -> bool {
+        if !self.is_closed() {
+            return false;
+        }
+
+        match self.tags.get("area").map(String::as_str) {
+            Some("yes") => true,
+            Some("no") => false,
+            _ => {
+                self.tags.get("highway").is_some_and(|h| h == "pedestrian")
+                    || self.tags.contains_key("building")
+                    || !self.holes.is_empty()
+            }
+        }
+    }
+}
+
+impl From<Road> for RoadSegment {
+    fn from(road: Road) -> Self {
+        let flag = |k| road.tags.get(k).is_some_and(|v| v != "no");
+
+        RoadSegment {
+            id: road.id,
+            highway_class: road
+                .tags
+                .get("highway")
+                .and_then(|h| HighwayClass::from_tag(h)),
+            name: road.tags.get("name").cloned(),
+            oneway: matches!(
+                road.tags.get("oneway").map(String::as_str),
+                Some("yes" | "true" | "1" | "-1")
+            ),
+            bridge: flag("bridge"),
+            tunnel: flag("tunnel"),
+            points: road.points,
+        }
+    }
+}
+
+impl OverpassResponse {
+    /// Fill in the geometry of the ways and relations output as a skeleton, i.e. with the ids of
+    /// their nodes and members rather than their geometry, see
+    /// [`FetchStrategy::Skeleton`](overpass::FetchStrategy::Skeleton).
+    ///
+    /// The untagged nodes and ways output only to resolve the geometry are dropped.
+    fn resolve_skeleton(&mut self) {
+        if !self.elements.iter().any(|e| !e.nodes.is_empty()) {
+            return;
+        }
+
+        let nodes = self
+            .elements
+            .iter()
+            .filter(|e| e.kind == "node")
+            .filter_map(|e| {
+                Some((
+                    e.id,
+                    LatLon {
+                        lat: e.lat?,
+                        lon: e.lon?,
+                    },
+                ))
+            })
+            .collect::<HashMap<_, _>>();
+
+        let mut used_nodes = HashSet::new();
+        for e in self.elements.iter_mut().filter(|e| e.geometry.is_empty()) {
+            e.geometry = e
+                .nodes
+                .iter()
+                .filter_map(|n| nodes.get(n).copied())
+                .collect();
+            used_nodes.extend(e.nodes.iter().copied());
+        }
+
+        let ways = self
+            .elements
+            .iter()
+            .filter(|e| e.kind == "way")
+            .map(|e| (e.id, e.geometry.clone()))
+            .collect::<HashMap<_, _>>();
+
+        let mut used_ways = HashSet::new();
+        for e in &mut self.elements {
+            for m in e.members.iter_mut().filter(|m| m.kind == "way") {
+                if m.geometry.is_empty() {
+                    m.geometry = ways.get(&m.id).cloned().unwrap_or_default();
+                }
+                used_ways.insert(m.id);
+            }
+        }
+
+        self.elements.retain(|e| {
+            let used = match e.kind.as_str() {
+                "node" => used_nodes.contains(&e.id),
+                "way" => used_ways.contains(&e.id),
+                _ => false,
+            };
+            !used || !e.tags.is_empty()
+        });
+    }
+}
+
+impl OverpassElement {
+    /// The roads of this element as they are on the globe. The members of the relations are
+    /// joined on their coordinates as they are, so that they're not altered either.
+    fn into_roads(self) -> Vec<GeoRoad> {
+        let road = |points: Vec<(f64, f64)>, holes: Vec<Vec<(f64, f64)>>| GeoRoad {
+            id: self.id,
+            tags: self.tags.clone(),
+            points: from_lon_lat(points),
+            holes: holes.into_iter().map(from_lon_lat).collect(),
+        };
+
+        if !self.members.is_empty() {
+            let is_area = matches!(
+                self.tags.get("type").map(String::as_str),
+                Some("multipolygon" | "boundary")
+            );
+
+            if !is_area {
+                let ways = self.members.iter().map(OverpassMember::lon_lat).collect();
+                return multipolygon::join_paths(ways)
+                    .into_iter()
+                    .map(|p| road(p, vec![]))
+                    .collect();
+            }
+
+            let (inners, outers): (Vec<_>, Vec<_>) =
+                self.members.iter().partition(|m| m.role == "inner");
+
+            return multipolygon::assemble(
+                outers.into_iter().map(OverpassMember::lon_lat).collect(),
+                inners.into_iter().map(OverpassMember::lon_lat).collect(),
+            )
+            .into_iter()
+            .map(|p| road(p.outer, p.holes))
+            .collect();
+        }
+
+        let points = match (self.lat, self.lon) {
+            (Some(lat), Some(lon)) if self.geometry.is_empty() => vec![LatLon { lat, lon }],
+            _ => self.geometry.clone(),
+        };
+
+        vec![GeoRoad {
+            id: self.id,
+            tags: self.tags,
+            points,
+            holes: vec![],
+        }]
+    }
+}
+
+impl OverpassMember {
+    fn lon_lat(&self) -> Vec<(f64, f64)> {
+        self.geometry.iter().map(|p| (p.lon, p.lat)).collect()
+    }
+}
+
+fn from_lon_lat(path: Vec<(f64, f64)>) -> Vec<LatLon> {
+    path.into_iter()
+        .map(|(lon, lat)| LatLon { lat, lon })
+        .collect()
+}
+
+impl GeoRoad {
+    /// This road in the given projection.
+    pub fn project(self, projection: &(impl Projection + ?Sized)) -> Road {
+        let path = |p: Vec<LatLon>| {
+            p.into_iter()
+                .map(|ll| projection.project(ll.lat, ll.lon))
+                .collect()
+        };
+        Road {
+            id: self.id,
+            tags: self.tags,
+            points: path(self.points),
+            holes: self.holes.into_iter().map(path).collect(),
+        }
+    }
+}
+
+/// In [`WebMercator`], like all the fetched roads.
+impl From<GeoRoad> for Road {
+    fn from(road: GeoRoad) -> Self {
+        road.project(&WebMercator)
+    }
 }
 
 impl LatLon {
-    /// Earth radius in meters.
-    const EARTH_RADIUS: f64 = 6378137.0;
+    pub fn new(lat: f64, lon: f64) -> Self {
+        LatLon { lat, lon }
+    }
 
+    /// The point in [`WebMercator`], the projection of the fetched roads.
     pub fn to_xy(self) -> (f64, f64) {
-        // https://wiki.openstreetmap.org/wiki/Mercator
+        WebMercator.project(self.lat, self.lon)
+    }
+
+    /// Inverse of [`LatLon::to_xy`].
+    pub fn from_xy(p: (f64, f64)) -> Self {
+        let (lat, lon) = WebMercator.unproject(p);
+        LatLon { lat, lon }
+    }
+
+    /// The distance in meters to the given point along the great circle, with the haversine
+    /// formula.
+    pub fn distance(self, other: LatLon) -> f64 {
+        let (phi1, phi2) = (self.lat.to_radians(), other.lat.to_radians());
+        let dphi = phi2 - phi1;
+        let dlambda = (other.lon - self.lon).to_radians();
+
+        let a =
+            (dphi / 2.0).sin().powi(2) + phi1.cos() * phi2.cos() * (dlambda / 2.0).sin().powi(2);
+        2.0 * projection::EARTH_RADIUS * a.sqrt().asin()
+    }
+}
+
+/// Inverse of the projection of the fetched points, returns the `(latitude, longitude)` in
+/// degrees of the given point.
+pub fn to_lat_lon(p: (f64, f64)) -> (f64, f64) {
+    WebMercator.unproject(p)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An entry whose bounding box spans the given latitudes and longitudes.
+    fn entry(lat: (f64, f64), lon: (f64, f64)) -> NominatimEntry {
+        NominatimEntry {
+            boundingbox: [lat.0, lat.1, lon.0, lon.1].map(|c| c.to_string()),
+            ..NominatimEntry::at(LatLon::new(0.0, 0.0))
+        }
+    }
+
+    #[test]
+    fn padded_by_percentage() {
+        let entry = entry((45.0, 46.0), (7.0, 9.0));
+
+        let padded = entry.padded(Padding::Percent(10.0));
+        assert_eq!(
+            padded.boundingbox,
+            ["44.9000000", "46.1000000", "6.8000000", "9.2000000"]
+        );
+
+        let shrunk = entry.padded("-25%".parse().unwrap());
+        assert_eq!(shrunk.bounds(), (45.25, 7.5, 45.75, 8.5));
+
+        assert_eq!(entry.padded(Padding::none()).boundingbox, entry.boundingbox);
+    }
+
+    #[test]
+    fn padded_by_distance() {
+        let padded = entry((0.0, 0.0), (0.0, 0.0)).padded("1113.2m".parse().unwrap());
+        assert_eq!(
+            padded.boundingbox,
+            ["-0.0100000", "0.0100000", "-0.0100000", "0.0100000"]
+        );
 
-        use std::f64::consts::FRAC_PI_4;
+        // a degree of longitude is half as long at 60 degrees of latitude
+        let (s, w, n, e) = entry((60.0, 60.0), (10.0, 10.0))
+            .padded("1.1132km".parse().unwrap())
+            .bounds();
+        assert!((n - s - 0.02).abs() < 1e-6);
+        assert!((e - w - 0.04).abs() < 1e-6);
+    }
+
+    #[test]
+    fn padded_never_past_the_center() {
+        let place = entry((45.0, 46.0), (7.0, 9.0));
+        assert_eq!(
+            place.padded(Padding::Percent(-80.0)).bounds(),
+            (45.5, 8.0, 45.5, 8.0)
+        );
+        assert_eq!(
+            place.padded("-500km".parse().unwrap()).bounds(),
+            (45.5, 8.0, 45.5, 8.0)
+        );
+
+        // nor past the poles and the antimeridian
+        let world = entry((-89.0, 89.0), (-179.0, 179.0)).padded(Padding::Percent(50.0));
+        assert_eq!(world.bounds(), (-90.0, -180.0, 90.0, 180.0));
+    }
+
+    #[test]
+    fn parse_search_query() {
+        let query = "street=10 Downing Street;city=London"
+            .parse::<SearchQuery>()
+            .unwrap();
+        assert_eq!(
+            query.params(),
+            vec![
+                ("street", "10 Downing Street".to_string()),
+                ("city", "London".to_string()),
+            ]
+        );
+
+        let query = " city = Springfield, state=Illinois ,country=USA, "
+            .parse::<SearchQuery>()
+            .unwrap();
+        assert_eq!(
+            query.to_string(),
+            "city=Springfield, state=Illinois, country=USA"
+        );
+        assert_eq!(query.to_string().parse(), Ok(query));
+    }
+
+    #[test]
+    fn parse_invalid_search_query() {
+        // free text is searched as such, without the prefix
+        assert_eq!(
+            "Springfield".parse::<SearchQuery>(),
+            Err("invalid address part 'Springfield', expected e.g. city=Springfield".to_string())
+        );
+        assert_eq!(
+            "city=Springfield, planet=Earth".parse::<SearchQuery>(),
+            Err(
+                "invalid address part 'planet=Earth', expected e.g. city=Springfield, the parts \
+                 are street, city, county, state, country and postalcode"
+                    .to_string()
+            )
+        );
+        assert!("city=".parse::<SearchQuery>().is_err());
+        assert_eq!(
+            " ; ".parse::<SearchQuery>(),
+            Err("empty address, expected e.g. city=Springfield".to_string())
+        );
+    }
 
-        let x = self.lon.to_radians() * Self::EARTH_RADIUS;
-        let y = f64::ln(f64::tan(self.lat.to_radians() / 2.0 + FRAC_PI_4)) * Self::EARTH_RADIUS;
+    #[test]
+    fn coordinates() {
+        assert_eq!(
+            parse_coordinates("45.07, 7.68"),
+            Some(LatLon::new(45.07, 7.68))
+        );
+        assert_eq!(
+            parse_coordinates(" -33.86 151.2 "),
+            Some(LatLon::new(-33.86, 151.2))
+        );
+        assert_eq!(
+            parse_coordinates("45.07,7.68"),
+            Some(LatLon::new(45.07, 7.68))
+        );
 
-        (x, y)
+        assert_eq!(parse_coordinates("Torino"), None);
+        assert_eq!(parse_coordinates("city=Torino, country=Italy"), None);
+        assert_eq!(parse_coordinates("45.07"), None);
+        assert_eq!(parse_coordinates("91, 7.68"), None);
+        assert_eq!(parse_coordinates("45.07, 181"), None);
     }
 }
@@ -0,0 +1,432 @@
+//! Level of detail profiles bundling the settings that make sense for a given output size.
+
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use crate::{
+    filter::{HighwayClass, RoadFilter},
+    pipeline::{PathFilter, Pipeline},
+    simplify::{Algorithm, Simplify},
+};
+
+/// How much detail to keep for a given kind of output.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LodProfile {
+    pub name: String,
+
+    /// The highway classes worth fetching.
+    pub filter: RoadFilter,
+
+    /// Simplification tolerance, in meters on the ground, see [`LodProfile::scaled`].
+    pub tolerance: f64,
+
+    /// Paths shorter than this, in meters on the ground, are dropped.
+    pub min_length: f64,
+
+    /// Whether [`LodProfile::fit`] tunes this profile to each output, see [`LodProfile::auto`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub auto: bool,
+}
+
+/// How much the paths written by an exporter are simplified.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Simplification {
+    /// Use the tolerance of the detail profile.
+    #[default]
+    Profile,
+
+    /// Keep every single point, e.g. for GIS formats.
+    Off,
+
+    /// Use the given tolerance, in meters on the ground.
+    Tolerance(f64),
+}
+
+/// The [`Simplification`] of each export target by name, e.g. `svg=profile,thumbnail=20`.
+/// Targets not listed use the tolerance of the detail profile.
+///
+/// The [`Algorithm`] can be chosen per target too, alone like `gcode=vw` or before the
+/// simplification like `thumbnail=vw:20`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TargetSimplification {
+    targets: BTreeMap<String, Simplification>,
+    algorithms: BTreeMap<String, Algorithm>,
+}
+
+/// [`PathFilter`] that drops the paths shorter than the given length.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MinLength(pub f64);
+
+/// Points per pixel of the output past which [`LodProfile::fit`] simplifies the auto profile by
+/// more than half a pixel, i.e. a point every 10 pixels.
+const AUTO_DENSITY: f64 = 0.1;
+
+impl LodProfile {
+    pub const BUILTIN_NAMES: [&'static str; 5] =
+        ["full", "auto", "web-thumbnail", "a4-plot", "a0-poster"];
+
+    /// Keep everything, the default.
+    pub fn full() -> Self {
+        LodProfile {
+            name: "full".to_string(),
+            filter: RoadFilter::all(),
+            tolerance: Simplify::default().eps,
+            min_length: 0.0,
+            auto: false,
+        }
+    }
+
+    /// All the roads, simplified as much as the resolution of the output allows, see
+    /// [`LodProfile::fit`].
+    pub fn auto() -> Self {
+        LodProfile {
+            name: "auto".to_string(),
+            auto: true,
+            ..Self::full()
+        }
+    }
+
+    /// Only the main roads, heavily simplified, for small images.
+    pub fn web_thumbnail() -> Self {
+        LodProfile {
+            name: "web-thumbnail".to_string(),
+            filter: RoadFilter::only(&[
+                HighwayClass::Motorway,
+                HighwayClass::Trunk,
+                HighwayClass::Primary,
+                HighwayClass::Secondary,
+                HighwayClass::Tertiary,
+            ]),
+            tolerance: 10.0,
+            min_length: 100.0,
+            auto: false,
+        }
+    }
+
+    /// Everything drivable, lightly simplified, for pen plotters on A4 paper.
+    pub fn a4_plot() -> Self {
+        LodProfile {
+            name: "a4-plot".to_string(),
+            filter: RoadFilter::only(&[
+                HighwayClass::Motorway,
+                HighwayClass::Trunk,
+                HighwayClass::Primary,
+                HighwayClass::Secondary,
+                HighwayClass::Tertiary,
+                HighwayClass::Unclassified,
+                HighwayClass::Residential,
+                HighwayClass::LivingStreet,
+            ]),
+            tolerance: 2.0,
+            min_length: 20.0,
+            auto: false,
+        }
+    }
+
+    /// All the roads with barely noticeable simplification, for big posters.
+    pub fn a0_poster() -> Self {
+        LodProfile {
+            name: "a0-poster".to_string(),
+            filter: RoadFilter::all(),
+            tolerance: 0.2,
+            min_length: 0.0,
+            auto: false,
+        }
+    }
+
+    /// The built-in profile with the given name, if any.
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "full" => Some(Self::full()),
+            "auto" => Some(Self::auto()),
+            "web-thumbnail" => Some(Self::web_thumbnail()),
+            "a4-plot" => Some(Self::a4_plot()),
+            "a0-poster" => Some(Self::a0_poster()),
+            _ => None,
+        }
+    }
+
+    /// Whether this is tuned to each output like the [`LodProfile::auto`] profile, whatever its
+    /// name.
+    pub fn is_auto(&self) -> bool {
+        self.auto
+    }
+
+    /// This profile tuned to draw `points` points spanning `world` on an output of `resolution`
+    /// pixels, or dots of the plotter. The world is in the planar units of the paths, like the
+    /// tolerance once [scaled](LodProfile::scaled). Only the auto profiles are tuned: their
+    /// tolerance is half a pixel, growing with the square root of the points per pixel once there
+    /// are more than one every 10 pixels, and the paths shorter than two tolerances are dropped.
+    /// This way villages keep every detail while megacities lose the ones that would only
+    /// clutter.
+    pub fn fit(&self, points: usize, world: (f64, f64), resolution: (f64, f64)) -> Self {
+        let units_per_pixel = f64::max(world.0 / resolution.0, world.1 / resolution.1);
+        let density = points as f64 / (resolution.0 * resolution.1);
+        if !self.is_auto() || !units_per_pixel.is_finite() || !density.is_finite() {
+            return self.clone();
+        }
+
+        let tolerance = units_per_pixel / 2.0 * (density / AUTO_DENSITY).sqrt().max(1.0);
+        LodProfile {
+            tolerance,
+            min_length: tolerance * 2.0,
+            ..self.clone()
+        }
+    }
+
+    /// This profile for paths whose planar units are `scale` times smaller than the meters on the
+    /// ground, see [`Projection::scale`](crate::projection::Projection::scale). The paths in Web
+    /// Mercator are stretched away from the equator, so they'd be simplified less and less.
+    pub fn scaled(&self, scale: f64) -> Self {
+        LodProfile {
+            tolerance: self.tolerance * scale,
+            min_length: self.min_length * scale,
+            ..self.clone()
+        }
+    }
+
+    /// The simplification stage of this profile.
+    pub fn simplify(&self) -> Simplify {
+        Simplify::new(self.tolerance)
+    }
+
+    /// Register the stages of this profile in the given pipeline.
+    pub fn register(&self, pipeline: &mut Pipeline, simplify: Simplify) {
+        if self.min_length > 0.0 {
+            pipeline.register_filter(MinLength(self.min_length));
+        }
+        pipeline.register_transform(simplify);
+    }
+
+    /// Like [`LodProfile::register`], but simplify as much and how requested by an exporter. The
+    /// simplification stage is returned to inspect its stats, unless it's turned off.
+    pub fn register_with(
+        &self,
+        pipeline: &mut Pipeline,
+        simplification: Simplification,
+        algorithm: Algorithm,
+    ) -> Option<Simplify> {
+        if self.min_length > 0.0 {
+            pipeline.register_filter(MinLength(self.min_length));
+        }
+
+        let simplify = Simplify::new(simplification.tolerance(self)?).with_algorithm(algorithm);
+        pipeline.register_transform(simplify.clone());
+        Some(simplify)
+    }
+}
+
+impl Simplification {
+    /// The tolerance to use with the given profile, `None` if the paths are kept as they are.
+    pub fn tolerance(self, profile: &LodProfile) -> Option<f64> {
+        match self {
+            Simplification::Profile => Some(profile.tolerance),
+            Simplification::Off => None,
+            Simplification::Tolerance(t) => Some(t),
+        }
+    }
+
+    /// This simplification for paths whose planar units are `scale` times smaller than the
+    /// meters on the ground, like [`LodProfile::scaled`].
+    pub fn scaled(self, scale: f64) -> Self {
+        match self {
+            Simplification::Tolerance(t) => Simplification::Tolerance(t * scale),
+            s => s,
+        }
+    }
+}
+
+impl TargetSimplification {
+    pub fn with(mut self, target: impl Into<String>, simplification: Simplification) -> Self {
+        self.targets.insert(target.into(), simplification);
+        self
+    }
+
+    pub fn get(&self, target: &str) -> Simplification {
+        self.get_or(target, Simplification::default())
+    }
+
+    /// The simplification of the given target, or the given one if the target is not listed.
+    pub fn get_or(&self, target: &str, default: Simplification) -> Simplification {
+        self.targets.get(target).copied().unwrap_or(default)
+    }
+
+    pub fn with_algorithm(mut self, target: impl Into<String>, algorithm: Algorithm) -> Self {
+        self.algorithms.insert(target.into(), algorithm);
+        self
+    }
+
+    /// The algorithm of the given target, if it has been chosen.
+    pub fn algorithm(&self, target: &str) -> Option<Algorithm> {
+        self.algorithms.get(target).copied()
+    }
+}
+
+impl Default for LodProfile {
+    fn default() -> Self {
+        Self::full()
+    }
+}
+
+impl PathFilter for MinLength {
+    fn keep(&self, path: &[(f64, f64)]) -> bool {
+        // single points are markers, not paths
+        path.len() < 2 || path_length(path) >= self.0
+    }
+}
+
+/// Total length of the given path.
+pub fn path_length(path: &[(f64, f64)]) -> f64 {
+    path.windows(2)
+        .map(|w| (w[1].0 - w[0].0).hypot(w[1].1 - w[0].1))
+        .sum()
+}
+
+impl FromStr for LodProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::builtin(s.trim()).ok_or_else(|| {
+            format!(
+                "unknown detail profile '{}', expected one of {}",
+                s.trim(),
+                Self::BUILTIN_NAMES.join(", ")
+            )
+        })
+    }
+}
+
+impl fmt::Display for LodProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+impl FromStr for Simplification {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "profile" => Ok(Simplification::Profile),
+            "off" => Ok(Simplification::Off),
+            t => t
+                .parse::<f64>()
+                .ok()
+                .filter(|t| *t >= 0.0)
+                .map(Simplification::Tolerance)
+                .ok_or_else(|| {
+                    format!(
+                        "invalid simplification '{}', expected profile, off or a tolerance",
+                        t
+                    )
+                }),
+        }
+    }
+}
+
+impl fmt::Display for Simplification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Simplification::Profile => f.write_str("profile"),
+            Simplification::Off => f.write_str("off"),
+            Simplification::Tolerance(t) => write!(f, "{}", t),
+        }
+    }
+}
+
+impl FromStr for TargetSimplification {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut targets = TargetSimplification::default();
+
+        for t in s.split(',').filter(|t| !t.trim().is_empty()) {
+            let (target, value) = t
+                .split_once('=')
+                .ok_or_else(|| format!("expected target=simplification, got '{}'", t.trim()))?;
+            let target = target.trim();
+
+            let (algorithm, simplification) = match value.split_once(':') {
+                Some((a, s)) => (Some(a.parse()?), Some(s.parse()?)),
+                None => match value.parse::<Algorithm>() {
+                    Ok(a) => (Some(a), None),
+                    Err(_) => (None, Some(value.parse()?)),
+                },
+            };
+            if let Some(a) = algorithm {
+                targets = targets.with_algorithm(target, a);
+            }
+            if let Some(s) = simplification {
+                targets = targets.with(target, s);
+            }
+        }
+
+        Ok(targets)
+    }
+}
+
+impl fmt::Display for TargetSimplification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = self
+            .targets
+            .keys()
+            .chain(self.algorithms.keys())
+            .collect::<std::collections::BTreeSet<_>>();
+
+        for (i, target) in names.into_iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{}=", target)?;
+
+            match (self.algorithms.get(target), self.targets.get(target)) {
+                (Some(a), Some(s)) => write!(f, "{}:{}", a, s)?,
+                (Some(a), None) => write!(f, "{}", a)?,
+                (None, Some(s)) => write!(f, "{}", s)?,
+                (None, None) => unreachable!("targets come from either map"),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serde")]
+crate::util::serde_via_str!(Simplification, TargetSimplification);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fit_village() {
+        // 2 units per pixel and a point every 500 pixels
+        let lod = LodProfile::auto().fit(2_000, (2_000.0, 1_000.0), (1_000.0, 1_000.0));
+        assert!((lod.tolerance - 1.0).abs() < 1e-9);
+        assert!((lod.min_length - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn fit_dense() {
+        // a point per pixel, 10 times the auto density
+        let lod = LodProfile::auto().fit(1_000_000, (2_000.0, 1_000.0), (1_000.0, 1_000.0));
+        assert!((lod.tolerance - 10_f64.sqrt()).abs() < 1e-9);
+
+        let denser = LodProfile::auto().fit(4_000_000, (2_000.0, 1_000.0), (1_000.0, 1_000.0));
+        assert!(denser.tolerance > lod.tolerance);
+    }
+
+    #[test]
+    fn fit_only_auto() {
+        let named_auto = LodProfile {
+            name: "auto".to_string(),
+            ..LodProfile::a4_plot()
+        };
+        for lod in [LodProfile::a4_plot(), named_auto] {
+            assert_eq!(
+                lod.fit(1_000_000, (2_000.0, 1_000.0), (1_000.0, 1_000.0)),
+                lod
+            );
+        }
+    }
+}
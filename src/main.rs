@@ -1,14 +1,19 @@
 use std::{
     any::Any,
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
     fmt::Display,
     fs,
     future::Future,
-    io::{self, Write},
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
     str::FromStr,
     sync::Arc,
     time::Duration,
 };
 
+use anyhow::Context;
+use clap::{Args, Parser, Subcommand};
 use crossterm::event::{Event, EventStream, KeyCode, KeyEvent, KeyModifiers};
 
 use futures::StreamExt;
@@ -22,8 +27,46 @@ use tui::{
 };
 
 use roads::{
-    util::{DotsSpinner, WrappingList},
-    NominatimEntry,
+    analysis::StreetHighlight,
+    cache::Cache,
+    clip::Clip,
+    coastline::{self, COASTLINE_SELECTOR},
+    color::{Color, ColorSpace, SpotColors},
+    config::Config,
+    crop::{self, CanvasPreset, Crop},
+    endpoints::{Auth, ClientConfig, Endpoints},
+    filter::{HighwayClass, NamePattern, RoadFilter, TagExclusions},
+    gazetteer::StreetIndex,
+    georef::WorldFile,
+    lod::{LodProfile, Simplification, TargetSimplification},
+    marks::PrintMarks,
+    neighborhood::{self, Neighborhoods, NEIGHBORHOODS_SELECTOR},
+    optimize::{JoinPaths, OptimizeOrder},
+    overpass::{
+        attic_query, combine_queries, merge_tiles, AtticDate, Bounds, ElementCount, FetchStrategy,
+        OverpassClient, QueryLimits, SearchArea, Tiling, SET_TAG,
+    },
+    pipeline::Pipeline,
+    polite::{PoliteMode, Throttle},
+    progress::{format_duration, Progress, ProgressObserver, Stage as ProgressStage},
+    project::{self, Project},
+    projection::{reproject, Projection, ProjectionKind, WebMercator},
+    render::{
+        dxf::{self, DxfOptions},
+        gcode::{self, GcodeOptions},
+        pdf::{self, PageSize, PdfOptions},
+        svg::{self, LineCap, LineJoin, StrokeOptions, SvgOptions},
+    },
+    retry::RetryPolicy,
+    rules::StyleRules,
+    simplify::{Algorithm, SimplifyStats},
+    split::SplitAtTurns,
+    style::{group_by_style, Style, StyledPaths},
+    text::Caption,
+    units::{Distance, Length, LengthUnit, Padding},
+    util::{write_atomic, write_file_atomic, DotsSpinner, Lru, WrappingList},
+    viewport::{bounds, Canvas, Origin, YAxis},
+    Fetched, NominatimEntry, Road, SearchOptions, SearchQuery, Warning,
 };
 
 trait ParamValue: Display + Send + Sync {
@@ -56,10 +99,122 @@ struct State {
     focus: WidgetId,
     user_city: String,
     places: WrappingList<NominatimEntry>,
-    params: WrappingList<(&'static str, Box<dyn ParamValue>)>,
+    params: WrappingList<ParamGroup>,
     worker_state: WorkerState,
     fetching_spinner: DotsSpinner,
     parm_edit_state: Option<ParmEditState>,
+
+    /// Extra stages run after the ones of the detail profile, before the paths are joined, split
+    /// and ordered for the plotter.
+    pipeline: Pipeline,
+    throttle: Arc<Throttle>,
+
+    /// Summary of the last export, if any.
+    status: Option<String>,
+
+    /// The retry the current fetch is waiting for, if any.
+    retry_status: Arc<std::sync::Mutex<Option<String>>>,
+
+    /// How far along the download, parsing or simplification of the current fetch is, if they
+    /// started. Shared with the worker so that it can be shown while it holds the state.
+    progress: Arc<std::sync::Mutex<Option<Progress>>>,
+
+    /// Problems of the last fetch that didn't stop the export, e.g. partial results.
+    warnings: Vec<Warning>,
+
+    /// The projection of the last laid out drawing, needed to georeference it.
+    projection: Box<dyn Projection>,
+
+    /// The style rules of the opened project along with the path they were read from on the
+    /// machine that saved it, used instead of the file as long as the option is that path.
+    project_rules: Option<(String, String)>,
+
+    /// The drawing waiting to be confirmed before being exported, if any.
+    preview: Option<Preview>,
+
+    /// The query of the highlighted place shown instead of running it, if any.
+    dry_run: Option<String>,
+
+    /// The layers fetched last in this session, by servers, place id and query of the layer, so
+    /// that going back to a place downloads nothing and turning on a layer downloads only that
+    /// one. The boundaries are kept by [`Boundary::cache_key`].
+    fetched: Lru<(Servers, i64, String), Fetched<Vec<Road>>>,
+
+    /// What has been downloaded ahead of time for the highlighted place, if anything.
+    prefetch: Option<Prefetch>,
+    prefetch_task: Option<tokio::task::JoinHandle<()>>,
+
+    /// The running fetch, aborting it drops its requests.
+    fetch_task: Option<tokio::task::JoinHandle<()>>,
+
+    /// The post-export command of the last export, run without holding the state so that the
+    /// TUI stays responsive meanwhile, along with why it failed once it did.
+    hook_task: Option<tokio::task::JoinHandle<()>>,
+    hook_error: Arc<std::sync::Mutex<Option<anyhow::Error>>>,
+
+    /// The road classes of the last place fitted in the Way budget.
+    fitted: Option<FittedFilter>,
+}
+
+/// The road filter of a place trimmed to fit in the Way budget.
+#[derive(Debug, Clone)]
+struct FittedFilter {
+    place_id: i64,
+
+    /// The filter and the budget set by the options, the fitted one is stale once they change.
+    wanted: RoadFilter,
+    max_ways: u64,
+    fitted: RoadFilter,
+}
+
+/// What outlines a place, to draw it or to clip the roads to it.
+#[derive(Debug, Clone)]
+enum Boundary {
+    /// The boundary of the place, or its bounding box if it has none.
+    Place(NominatimEntry),
+
+    /// The ring of the circle searched around the place, already projected.
+    Circle(Vec<(f64, f64)>),
+}
+
+impl Boundary {
+    /// Key of the boundary among the fetched layers of its place, which never clashes with a
+    /// query. The padding changes the bounding box of the places without a boundary.
+    fn cache_key(&self) -> String {
+        match self {
+            Boundary::Place(entry) => format!("boundary {:?}", entry.boundingbox),
+            Boundary::Circle(points) => format!("circle {:?}", points),
+        }
+    }
+}
+
+/// The boundary and the size estimate of a place, downloaded while it's highlighted.
+struct Prefetch {
+    place_id: i64,
+
+    /// The query of the place, the prefetched data is stale once the options change it.
+    query: String,
+
+    boundary: Option<Fetched<Vec<Road>>>,
+    count: Option<ElementCount>,
+}
+
+/// A drawing ready to be written once the user is happy with it.
+struct Preview {
+    name: String,
+    query: String,
+    layers: Vec<StyledPaths>,
+    streets: StreetIndex,
+
+    /// The layers as they're going to be written in the svg.
+    shown: Vec<StyledPaths>,
+
+    /// The last braille rendering along with the size of the area it fills, the drawing is
+    /// rendered again only when the terminal is resized.
+    raster: Option<((u16, u16), Vec<String>)>,
+
+    /// The project to save along with the drawing, if enabled.
+    project: Option<Project>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -70,6 +225,8 @@ enum WidgetId {
     Help,
     Error,
     ParamEdit,
+    Preview,
+    DryRun,
 }
 
 enum WorkerState {
@@ -78,6 +235,150 @@ enum WorkerState {
     Error(anyhow::Error),
 }
 
+/// The servers the data comes from along with the credentials sent to them, the same place id
+/// or query may give something else once they change.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Servers {
+    nominatim: String,
+    overpass: Vec<String>,
+    auth: [Option<Auth>; 2],
+}
+
+/// Everything needed to talk to the servers from a worker task.
+struct Remote {
+    endpoints: Endpoints,
+    search: SearchOptions,
+    polite: PoliteMode,
+    throttle: Arc<Throttle>,
+
+    /// The grid to split the places in, if they're too big to be fetched at once.
+    tiling: Option<Tiling>,
+}
+
+/// Names of the export targets whose simplification can be configured.
+const SVG_TARGET: &str = "svg";
+const PNG_TARGET: &str = "png";
+const PDF_TARGET: &str = "pdf";
+const DXF_TARGET: &str = "dxf";
+const GCODE_TARGET: &str = "gcode";
+const THUMBNAIL_TARGET: &str = "thumbnail";
+
+/// How many fetched layers are kept in memory, enough for a handful of places with all their
+/// layers.
+const FETCHED_CAPACITY: usize = 32;
+
+/// How long a job of the TUI has to take for its end to be notified, searches and cached places
+/// should stay quiet.
+const NOTIFY_AFTER: Duration = Duration::from_secs(10);
+
+/// Radius in meters of the circle searched around coordinates typed in the search, when the Radius
+/// option is empty.
+const DEFAULT_RADIUS: f64 = 1000.0;
+
+/// How long a place has to stay highlighted before it's prefetched.
+const PREFETCH_DELAY: Duration = Duration::from_millis(500);
+
+/// Tag added to the outline of the selected place to tell it apart from the other elements.
+const BOUNDARY_TAG: &str = "roads:boundary";
+
+/// Tag added to the neighborhood areas with the index of their neighborhood.
+const NEIGHBORHOOD_TAG: &str = "roads:neighborhood";
+
+/// Tag added to the rings (fill) or the hatching (hatch) of the sea.
+const SEA_TAG: &str = "roads:sea";
+
+/// Tag added to the ways added or removed since the date compared with.
+const DIFF_TAG: &str = "roads:diff";
+
+/// An option that can be left empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Optional<T>(Option<T>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Profile {
+    Public,
+    SelfHosted,
+}
+
+/// How to write the index of the street names, if at all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IndexFormat {
+    Off,
+    Text,
+    Svg,
+}
+
+/// What to color by neighborhood, if anything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NeighborhoodColoring {
+    Off,
+    Roads,
+    Areas,
+}
+
+/// How to reorder the paths to shorten the pen up travel of plotters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PathOrder {
+    Off,
+    Greedy,
+    TwoOpt,
+
+    /// Plot the paths chapter by chapter, see [`Chapter`], nearest first within each of them.
+    Story,
+}
+
+/// The parts of the map drawn one after the other by [`PathOrder::Story`], in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Chapter {
+    Water,
+    Areas,
+
+    /// Points and ways that are neither roads nor anything else.
+    Other,
+
+    /// The roads from the minor to the major ones.
+    Roads(Reverse<HighwayClass>),
+
+    Railways,
+    Boundary,
+}
+
+/// How to tell the user that a long job finished, since they likely switched to something else.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Notify {
+    Off,
+    Bell,
+
+    /// A desktop notification, falling back to the bell where none can be shown.
+    Desktop,
+}
+
+/// What to bundle in the project file saved along with the drawing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProjectContents {
+    Off,
+
+    /// The place, the query, the options and the style rules.
+    Settings,
+
+    /// The settings and the fetched roads, so that the project renders offline and exactly the
+    /// same even after the map data changed.
+    Geometry,
+}
+
+/// The dash pattern of an outline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LineKind {
+    Solid,
+    Dashed,
+    DashDot,
+}
+
+struct ParamGroup {
+    name: &'static str,
+    params: WrappingList<(&'static str, Box<dyn ParamValue>)>,
+}
+
 struct ParmEditState {
     buffer: String,
     value: Box<dyn ParamValue>,
@@ -86,7 +387,12 @@ struct ParmEditState {
 
 impl ParmEditState {
     fn new(mut value: Box<dyn ParamValue>) -> Self {
-        let buffer = value.to_string();
+        // credentials are shown redacted, so they are typed again from scratch
+        let buffer = if value.as_any().is::<Optional<Auth>>() {
+            String::new()
+        } else {
+            value.to_string()
+        };
         let is_valid = value.parse_from(&buffer);
         ParmEditState {
             buffer,
@@ -96,537 +402,3809 @@ impl ParmEditState {
     }
 }
 
-impl State {
-    const WIDTH_OPTION: &'static str = "Width";
-    const HEIGHT_OPTION: &'static str = "Height";
-    const STROKE_WIDTH_OPTION: &'static str = "Line width";
-    const BACKGROUND_COLOR: &'static str = "Background color";
-    const OPEN_OPTION: &'static str = "Open on save";
-
-    fn new() -> Self {
-        State {
-            focus: WidgetId::Search,
-            user_city: String::new(),
-            places: WrappingList::new(vec![]),
-            params: WrappingList::new(vec![
-                (Self::WIDTH_OPTION, Box::new(1920.0)),
-                (Self::HEIGHT_OPTION, Box::new(1080.0)),
-                (Self::STROKE_WIDTH_OPTION, Box::new(0.3)),
-                (Self::BACKGROUND_COLOR, Box::new("none".to_string())),
-                (Self::OPEN_OPTION, Box::new(true)),
-            ]),
-            worker_state: WorkerState::Idle,
-            fetching_spinner: DotsSpinner::new(),
-            parm_edit_state: None,
+impl Remote {
+    fn servers(&self) -> Servers {
+        let e = &self.endpoints;
+        Servers {
+            nominatim: e.nominatim.clone(),
+            overpass: std::iter::once(&e.overpass)
+                .chain(&e.overpass_mirrors)
+                .cloned()
+                .collect(),
+            auth: [e.nominatim_auth.clone(), e.overpass_auth.clone()],
         }
     }
 
-    fn worker_busy(&self) -> bool {
-        match self.worker_state {
-            WorkerState::Fetching => true,
-            WorkerState::Idle | WorkerState::Error(_) => false,
+    /// Search the given place, coordinates are taken as they are without asking Nominatim while
+    /// the places starting with [`SearchQuery::PREFIX`] are searched by the parts of the address.
+    async fn search(&self, place: &str) -> anyhow::Result<Vec<NominatimEntry>> {
+        self.search_after(place, &[]).await
+    }
+
+    /// The next page of results of the given search, after the places already found.
+    async fn search_after(
+        &self,
+        place: &str,
+        found: &[NominatimEntry],
+    ) -> anyhow::Result<Vec<NominatimEntry>> {
+        if let Some(point) = roads::parse_coordinates(place) {
+            return Ok(if found.is_empty() {
+                vec![NominatimEntry::at(point)]
+            } else {
+                vec![]
+            });
+        }
+
+        let options = self.search.after(found);
+        if let Some(address) = place.trim_start().strip_prefix(SearchQuery::PREFIX) {
+            let query = address.parse::<SearchQuery>().map_err(anyhow::Error::msg)?;
+            return Ok(roads::search_structured(&self.endpoints, &query, &options).await?);
         }
+
+        Ok(roads::search_with_options(&self.endpoints, place, &options).await?)
     }
 
-    fn max_option_key_len(&self) -> usize {
-        self.params
-            .iter()
-            .map(|(k, _)| k.len())
-            .max()
-            .unwrap_or_default()
+    /// The client of the Overpass servers, paced by the polite mode.
+    fn overpass(&self) -> OverpassClient {
+        self.endpoints
+            .overpass_client()
+            .with_throttle(Arc::clone(&self.throttle), self.polite)
     }
 
-    fn param<T: Any>(&self, key: &str) -> &T {
-        for (k, v) in self.params.iter() {
-            if k != &key {
-                continue;
-            }
+    async fn fetch_query(&self, query: &str) -> anyhow::Result<Fetched<Vec<Road>>> {
+        Ok(self.overpass().fetch_query(query).await?)
+    }
 
-            return v.as_any().downcast_ref::<T>().expect("invalid param type");
-        }
+    /// Like [`Remote::fetch_query`], but a query matching nothing is not an error.
+    async fn fetch_elements(&self, query: &str) -> anyhow::Result<Fetched<Vec<Road>>> {
+        Ok(self.overpass().fetch_elements(query).await?)
+    }
 
-        panic!("parameter {} not found", key)
+    async fn count(&self, query: &str) -> anyhow::Result<ElementCount> {
+        Ok(self.overpass().count(query).await?)
     }
 
-    fn set_current_param(&mut self, value: Box<dyn ParamValue>) {
-        if let Some((_, v)) = self.params.selected_mut() {
-            *v = value;
+    /// The filter keeping the most classes among the given ones, sorted by how many they keep,
+    /// whose ways fit in the given budget, see [`OverpassClient::fit_budget`]. The first one is
+    /// the fallback when none fits.
+    async fn fit_budget(
+        &self,
+        max_ways: u64,
+        mut candidates: Vec<(RoadFilter, String)>,
+    ) -> anyhow::Result<RoadFilter> {
+        let queries = candidates
+            .iter()
+            .map(|(_, q)| q.clone())
+            .collect::<Vec<_>>();
+        let i = self.overpass().fit_budget(max_ways, &queries).await?;
+        if candidates.is_empty() {
+            return Ok(RoadFilter::all());
         }
+        Ok(candidates.swap_remove(i).0)
     }
 
-    fn fetch<T: Send + 'static>(
-        &mut self,
-        state: Arc<Mutex<Self>>,
-        fut: impl Future<Output = anyhow::Result<T>> + Send + 'static,
-        mut on_success: impl FnMut(&mut Self, T) -> anyhow::Result<()> + Send + 'static,
-    ) {
-        self.worker_state = WorkerState::Fetching;
-        self.fetching_spinner = DotsSpinner::new();
+    /// The given boundary, places that don't have one, like nodes, get their bounding box.
+    async fn fetch_boundary(&self, boundary: &Boundary) -> anyhow::Result<Fetched<Vec<Road>>> {
+        let ring = |id, points| {
+            Fetched::complete(vec![Road {
+                id,
+                tags: BTreeMap::new(),
+                points,
+                holes: vec![],
+            }])
+        };
 
-        let _complete = tokio::task::spawn(async move {
-            let err = |st: &mut State, e| {
-                st.worker_state = WorkerState::Error(e);
-                st.focus = WidgetId::Error;
-                st.fetching_spinner = DotsSpinner::new();
+        Ok(match boundary {
+            Boundary::Circle(points) => ring(0, points.clone()),
+            Boundary::Place(entry) => match roads::boundary_query(entry) {
+                Some(q) => self.fetch_elements(&q).await?,
+                None => ring(entry.osm_id, entry.bbox_ring()),
+            },
+        })
+    }
+
+    /// Run the given query in each tile of the given bounding box, pacing every request like the
+    /// single ones. It's `None` if no tile matched anything.
+    async fn fetch_tiled(
+        &self,
+        query: &str,
+        bounds: Bounds,
+        tiling: Tiling,
+    ) -> anyhow::Result<Option<Fetched<Vec<Road>>>> {
+        let tiled = self.overpass().fetch_tiled(query, bounds, tiling).await?;
+        Ok(Some(tiled).filter(|t| !t.value.is_empty()))
+    }
+
+    /// Fetch the given layers of a place, in tiles of the given bounding box if it's set. The
+    /// layers sharing their settings come with a single request, see [`combine_queries`]. The
+    /// layers matching nothing are `None`.
+    async fn fetch_layers(
+        &self,
+        queries: &[String],
+        bounds: Option<Bounds>,
+    ) -> anyhow::Result<Vec<Option<Fetched<Vec<Road>>>>> {
+        let mut layers = vec![None; queries.len()];
+        for (query, indices) in combine_queries(queries) {
+            let fetched = match self.tiling.zip(bounds) {
+                Some((tiling, bounds)) => self.fetch_tiled(&query, bounds, tiling).await?,
+                None => Some(self.fetch_elements(&query).await?).filter(|l| !l.value.is_empty()),
+            };
+            let Some(Fetched { value, warnings }) = fetched else {
+                continue;
             };
+            if let [i] = indices[..] {
+                layers[i] = Some(Fetched { value, warnings });
+                continue;
+            }
 
-            match fut.await {
-                Ok(d) => {
-                    let mut state = state.lock().await;
-                    state.worker_state = WorkerState::Idle;
-                    if let Err(e) = on_success(&mut state, d) {
-                        err(&mut state, e);
-                    }
+            // the output sets are named after the layers, whose problems are the request's
+            for mut r in value {
+                let set = r.tags.remove(SET_TAG).and_then(|s| s.parse::<usize>().ok());
+                if let Some(i) = set.filter(|i| indices.contains(i)) {
+                    let layer = layers[i].get_or_insert_with(|| Fetched {
+                        value: vec![],
+                        warnings: warnings.clone(),
+                    });
+                    layer.value.push(r);
                 }
-                Err(e) => {
-                    let mut state = state.lock().await;
-                    err(&mut state, e);
+            }
+        }
+
+        Ok(layers)
+    }
+
+    /// Fetch the elements of a place along with the boundary of the given entry, if any, unless
+    /// it has been prefetched already. The boundary comes with the same request when possible.
+    ///
+    /// The elements are fetched in tiles of the given bounding box if it's set.
+    async fn fetch_place(
+        &self,
+        query: &str,
+        bounds: Option<Bounds>,
+        boundary: Option<Boundary>,
+        prefetched: Option<Fetched<Vec<Road>>>,
+    ) -> anyhow::Result<Fetched<Vec<Road>>> {
+        let tiled = self.tiling.zip(bounds);
+        let combined = match (&boundary, &prefetched) {
+            (Some(Boundary::Place(entry)), None) if tiled.is_none() => {
+                roads::with_boundary(query, entry)
+            }
+            _ => None,
+        };
+        if let Some(combined) = combined {
+            let mut roads = self.fetch_query(&combined).await?;
+            for r in &mut roads.value {
+                if r.tags.remove(SET_TAG).as_deref() == Some(roads::BOUNDARY_SET) {
+                    r.tags.insert(BOUNDARY_TAG.to_string(), "yes".to_string());
                 }
             }
-        });
+            return Ok(roads);
+        }
+
+        let roads = match tiled {
+            Some((tiling, bounds)) => match self.fetch_tiled(query, bounds, tiling).await? {
+                Some(roads) => roads,
+                None => return Err(roads::Error::empty(query).into()),
+            },
+            None => self.fetch_query(query).await?,
+        };
+
+        let boundary = match (boundary, prefetched) {
+            (None, _) => None,
+            (Some(_), Some(b)) => Some(b),
+            (Some(b), None) => Some(self.fetch_boundary(&b).await?),
+        };
+
+        Ok(add_boundary(roads, boundary))
     }
 }
 
-async fn main_loop(terminal: &mut Terminal<impl Backend>) -> anyhow::Result<()> {
-    let mut reader = EventStream::new();
-    let state = Arc::new(Mutex::new(State::new()));
+/// The roads of a place from its layers, flagged when they're of the snapshot compared with,
+/// along with its boundary.
+fn assemble(
+    layers: Vec<(bool, Fetched<Vec<Road>>)>,
+    boundary: Option<Fetched<Vec<Road>>>,
+) -> Fetched<Vec<Road>> {
+    let (before, now): (Vec<_>, Vec<_>) = layers.into_iter().partition(|(b, _)| *b);
 
-    loop {
-        {
-            let mut st = state.lock().await;
-            terminal.draw(|f| draw(f, &mut st))?;
+    // an element matched by more than one layer is kept once, like in a single query
+    let mut roads = merge_tiles(now.into_iter().map(|(_, l)| l));
+    if !before.is_empty() {
+        roads = compare(merge_tiles(before.into_iter().map(|(_, l)| l)), roads);
+    }
+
+    add_boundary(roads, boundary)
+}
+
+/// The roads of the `now` snapshot along with the ones removed since the `before` one, tagged
+/// with [`DIFF_TAG`] when they've been added or removed. The boundary is never compared.
+fn compare(before: Fetched<Vec<Road>>, now: Fetched<Vec<Road>>) -> Fetched<Vec<Road>> {
+    let (boundary, roads): (Vec<_>, Vec<_>) = now
+        .value
+        .into_iter()
+        .partition(|r| r.tags.contains_key(BOUNDARY_TAG));
+    let before_roads = before
+        .value
+        .into_iter()
+        .filter(|r| !r.tags.contains_key(BOUNDARY_TAG))
+        .collect();
+    let diff = roads::diff::diff(before_roads, roads);
+
+    let tag = |kind: &'static str| {
+        move |mut r: Road| {
+            r.tags.insert(DIFF_TAG.to_string(), kind.to_string());
+            r
         }
+    };
+    let mut roads = diff.unchanged;
+    roads.extend(diff.removed.into_iter().map(tag("removed")));
+    roads.extend(diff.added.into_iter().map(tag("added")));
+    roads.extend(boundary);
 
-        let ev = match tokio::time::timeout(Duration::from_millis(50), reader.next()).await {
-            Err(_) => {
-                // timeout expired
-                continue;
-            }
-            Ok(ev) => ev,
-        };
+    let mut warnings = now.warnings;
+    for w in before.warnings {
+        if !warnings.contains(&w) {
+            warnings.push(w);
+        }
+    }
 
-        let mut st = state.lock().await;
-        match ev {
-            Some(Ok(event)) => {
-                let KeyEvent {
-                    code, modifiers, ..
-                } = match event {
-                    Event::Key(k) => k,
-                    _ => continue,
-                };
+    Fetched {
+        value: roads,
+        warnings,
+    }
+}
 
-                if st.focus != WidgetId::ParamEdit {
-                    if code == KeyCode::Esc
-                        || (code, modifiers) == (KeyCode::Char('c'), KeyModifiers::CONTROL)
-                    {
-                        break;
-                    }
+/// The given elements along with the given boundary, if any, marked as such.
+fn add_boundary(
+    mut roads: Fetched<Vec<Road>>,
+    boundary: Option<Fetched<Vec<Road>>>,
+) -> Fetched<Vec<Road>> {
+    if let Some(boundary) = boundary {
+        roads.warnings.extend(boundary.warnings);
+        roads.value.extend(boundary.value.into_iter().map(|mut r| {
+            r.tags.insert(BOUNDARY_TAG.to_string(), "yes".to_string());
+            r
+        }));
+    }
 
-                    if code == KeyCode::Tab || code == KeyCode::BackTab {
-                        let tab_order = [
-                            WidgetId::Search,
-                            WidgetId::Places,
-                            WidgetId::Options,
-                            WidgetId::Help,
-                        ];
-                        let current = tab_order.iter().position(|w| w == &st.focus).unwrap();
-                        let next = current
-                            + if code == KeyCode::Tab {
-                                1
-                            } else {
-                                tab_order.len() - 1
-                            };
+    roads
+}
 
-                        st.focus = tab_order[next % tab_order.len()];
-                        continue;
-                    }
-                }
+impl<T: FromStr> FromStr for Optional<T> {
+    type Err = T::Err;
 
-                handle_key_event(code, &mut st, &state).await?;
-            }
-            Some(Err(_)) | None => break,
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Ok(Optional(None));
         }
+
+        s.parse().map(|a| Optional(Some(a)))
     }
+}
 
-    Ok(())
+impl<T: Display> Display for Optional<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.0 {
+            None => Ok(()),
+            Some(a) => a.fmt(f),
+        }
+    }
 }
 
-fn main() -> anyhow::Result<()> {
-    crossterm::terminal::enable_raw_mode()?;
+impl FromStr for Profile {
+    type Err = String;
 
-    let stdout = io::stdout();
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "public" => Ok(Profile::Public),
+            "self-hosted" => Ok(Profile::SelfHosted),
+            _ => Err(format!("invalid profile '{}'", s)),
+        }
+    }
+}
 
-    terminal.clear()?;
+impl Display for Profile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Profile::Public => "public",
+            Profile::SelfHosted => "self-hosted",
+        })
+    }
+}
 
-    let runtime = Runtime::new().unwrap();
-    let _ = runtime.block_on(main_loop(&mut terminal));
+impl FromStr for IndexFormat {
+    type Err = String;
 
-    terminal.clear()?;
-    crossterm::terminal::disable_raw_mode()?;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "off" => Ok(IndexFormat::Off),
+            "txt" => Ok(IndexFormat::Text),
+            "svg" => Ok(IndexFormat::Svg),
+            _ => Err(format!("invalid index format '{}'", s)),
+        }
+    }
+}
 
-    Ok(())
+impl Display for IndexFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            IndexFormat::Off => "off",
+            IndexFormat::Text => "txt",
+            IndexFormat::Svg => "svg",
+        })
+    }
 }
 
-fn draw(f: &mut Frame<impl Backend>, state: &mut State) {
-    use tui::{
-        layout::{Constraint, Direction, Layout},
+impl FromStr for NeighborhoodColoring {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "off" => Ok(NeighborhoodColoring::Off),
+            "roads" => Ok(NeighborhoodColoring::Roads),
+            "areas" => Ok(NeighborhoodColoring::Areas),
+            _ => Err(format!("invalid neighborhood coloring '{}'", s)),
+        }
+    }
+}
+
+impl Display for NeighborhoodColoring {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            NeighborhoodColoring::Off => "off",
+            NeighborhoodColoring::Roads => "roads",
+            NeighborhoodColoring::Areas => "areas",
+        })
+    }
+}
+
+impl FromStr for PathOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "off" => Ok(PathOrder::Off),
+            "greedy" => Ok(PathOrder::Greedy),
+            "2-opt" => Ok(PathOrder::TwoOpt),
+            "story" => Ok(PathOrder::Story),
+            _ => Err(format!("invalid path order '{}'", s)),
+        }
+    }
+}
+
+impl Display for PathOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            PathOrder::Off => "off",
+            PathOrder::Greedy => "greedy",
+            PathOrder::TwoOpt => "2-opt",
+            PathOrder::Story => "story",
+        })
+    }
+}
+
+impl Notify {
+    /// Notify the given summary of a finished job.
+    fn send(self, summary: &str) {
+        let shown = match self {
+            Notify::Off => return,
+            Notify::Bell => false,
+            Notify::Desktop => desktop_notification(summary).is_ok(),
+        };
+
+        if !shown {
+            eprint!("\x07");
+        }
+    }
+}
+
+/// Show a desktop notification with the given text through the tool of the platform.
+fn desktop_notification(summary: &str) -> io::Result<()> {
+    use std::process::{Command, Stdio};
+
+    let mut cmd = if cfg!(target_os = "macos") {
+        let text = summary.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut cmd = Command::new("osascript");
+        cmd.arg("-e").arg(format!(
+            "display notification \"{}\" with title \"roads\"",
+            text
+        ));
+        cmd
+    } else if cfg!(windows) {
+        return Err(io::ErrorKind::Unsupported.into());
+    } else {
+        let mut cmd = Command::new("notify-send");
+        cmd.arg("roads").arg(summary);
+        cmd
+    };
+
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+impl FromStr for Notify {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "off" => Ok(Notify::Off),
+            "bell" => Ok(Notify::Bell),
+            "desktop" => Ok(Notify::Desktop),
+            _ => Err(format!("invalid notification '{}'", s)),
+        }
+    }
+}
+
+impl Display for Notify {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Notify::Off => "off",
+            Notify::Bell => "bell",
+            Notify::Desktop => "desktop",
+        })
+    }
+}
+
+impl FromStr for ProjectContents {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "off" => Ok(ProjectContents::Off),
+            "settings" => Ok(ProjectContents::Settings),
+            "geometry" => Ok(ProjectContents::Geometry),
+            _ => Err(format!("invalid project contents '{}'", s)),
+        }
+    }
+}
+
+impl Display for ProjectContents {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ProjectContents::Off => "off",
+            ProjectContents::Settings => "settings",
+            ProjectContents::Geometry => "geometry",
+        })
+    }
+}
+
+impl LineKind {
+    /// The dash pattern of a line as wide as the given one.
+    fn dash(self, width: f64) -> Vec<f64> {
+        match self {
+            LineKind::Solid => vec![],
+            LineKind::Dashed => vec![width * 4.0, width * 2.0],
+            LineKind::DashDot => vec![width * 4.0, width * 1.5, width * 0.5, width * 1.5],
+        }
+    }
+}
+
+impl FromStr for LineKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "solid" => Ok(LineKind::Solid),
+            "dashed" => Ok(LineKind::Dashed),
+            "dash-dot" => Ok(LineKind::DashDot),
+            _ => Err(format!("invalid line kind '{}'", s)),
+        }
+    }
+}
+
+impl Display for LineKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            LineKind::Solid => "solid",
+            LineKind::Dashed => "dashed",
+            LineKind::DashDot => "dash-dot",
+        })
+    }
+}
+
+impl ParamGroup {
+    fn new(name: &'static str, params: Vec<(&'static str, Box<dyn ParamValue>)>) -> Self {
+        ParamGroup {
+            name,
+            params: WrappingList::new(params),
+        }
+    }
+}
+
+impl State {
+    const WIDTH_OPTION: &'static str = "Width";
+    const HEIGHT_OPTION: &'static str = "Height";
+    const CANVAS_OPTION: &'static str = "Canvas";
+    const CROP_OPTION: &'static str = "Crop";
+    const STROKE_WIDTH_OPTION: &'static str = "Line width";
+    const STROKE_COLOR_OPTION: &'static str = "Stroke color";
+    const BACKGROUND_COLOR: &'static str = "Background color";
+    const OPEN_OPTION: &'static str = "Open on save";
+    const NOTIFY_OPTION: &'static str = "Notify";
+    const POST_EXPORT_OPTION: &'static str = "Post-export command";
+    const PREVIEW_OPTION: &'static str = "Preview";
+
+    /// The options about the machine rather than the drawing, which are never saved in or loaded
+    /// from projects. The whole network group is too, since it may hold credentials.
+    const LOCAL_OPTIONS: [&'static str; 5] = [
+        Self::OUTPUT_DIR_OPTION,
+        Self::OPEN_OPTION,
+        Self::NOTIFY_OPTION,
+        Self::POST_EXPORT_OPTION,
+        Self::SAVE_PROJECT_OPTION,
+    ];
+    /// The options holding credentials, which are never written to disk.
+    const SECRET_OPTIONS: [&'static str; 2] =
+        [Self::NOMINATIM_AUTH_OPTION, Self::OVERPASS_AUTH_OPTION];
+    const PRINT_MARKS_OPTION: &'static str = "Print marks";
+    const BLEED_OPTION: &'static str = "Bleed";
+    const COLOR_SPACE_OPTION: &'static str = "Color space";
+    const SPOT_COLORS_OPTION: &'static str = "Spot colors";
+    const ICC_PROFILE_OPTION: &'static str = "ICC profile";
+    const CAPTION_OPTION: &'static str = "Caption";
+    const STREET_INDEX_OPTION: &'static str = "Street index";
+    const WORLD_FILE_OPTION: &'static str = "World file";
+    const THUMBNAIL_OPTION: &'static str = "Thumbnail size";
+    const PNG_OPTION: &'static str = "PNG dpi";
+    const PDF_PAGE_OPTION: &'static str = "PDF page";
+    const PDF_MARGIN_OPTION: &'static str = "PDF margin";
+    const DXF_UNITS_OPTION: &'static str = "DXF units";
+    const GCODE_AREA_OPTION: &'static str = "G-code area";
+    const FEED_RATE_OPTION: &'static str = "Feed rate";
+    const PEN_UP_OPTION: &'static str = "Pen up";
+    const PEN_DOWN_OPTION: &'static str = "Pen down";
+    const PEN_DELAY_OPTION: &'static str = "Pen delay";
+    const SPLIT_ANGLE_OPTION: &'static str = "Split at turns";
+    const JOIN_PATHS_OPTION: &'static str = "Join paths";
+    const PATH_ORDER_OPTION: &'static str = "Path order";
+    const REVERSE_PATHS_OPTION: &'static str = "Reverse paths";
+    const STYLE_RULES_OPTION: &'static str = "Style rules";
+    const ROAD_STYLE_OPTION: &'static str = "Road style";
+    const LINE_CAP_OPTION: &'static str = "Line cap";
+    const LINE_JOIN_OPTION: &'static str = "Line join";
+    const SAVE_QUERY_OPTION: &'static str = "Save query";
+    const SAVE_PROJECT_OPTION: &'static str = "Save project";
+    const OUTPUT_DIR_OPTION: &'static str = "Output directory";
+    const ORIGIN_OPTION: &'static str = "Origin";
+    const Y_AXIS_OPTION: &'static str = "Y axis";
+    const ROAD_CLASSES_OPTION: &'static str = "Road classes";
+    const WAY_BUDGET_OPTION: &'static str = "Way budget";
+    const EXCLUDE_TAGS_OPTION: &'static str = "Exclude tags";
+    const PADDING_OPTION: &'static str = "Bbox padding";
+    const RADIUS_OPTION: &'static str = "Radius";
+    const PROFILE_OPTION: &'static str = "Profile";
+    const NOMINATIM_URL_OPTION: &'static str = "Nominatim url";
+    const OVERPASS_URL_OPTION: &'static str = "Overpass url";
+    const SEARCH_LIMIT_OPTION: &'static str = "Search results";
+    const LANGUAGE_OPTION: &'static str = "Language";
+    const OVERPASS_MIRRORS_OPTION: &'static str = "Overpass mirrors";
+    const NOMINATIM_AUTH_OPTION: &'static str = "Nominatim auth";
+    const OVERPASS_AUTH_OPTION: &'static str = "Overpass auth";
+    const POLITE_OPTION: &'static str = "Polite mode";
+    const PREFETCH_OPTION: &'static str = "Prefetch";
+    const FETCH_STRATEGY_OPTION: &'static str = "Fetch strategy";
+    const TILES_OPTION: &'static str = "Tiles";
+    const OVERPASS_TIMEOUT_OPTION: &'static str = "Overpass timeout";
+    const OVERPASS_MAXSIZE_OPTION: &'static str = "Overpass maxsize";
+    const CACHE_OPTION: &'static str = "Cache";
+    const MAX_REQUESTS_OPTION: &'static str = "Max requests";
+    const REQUESTS_PER_MINUTE_OPTION: &'static str = "Requests per minute";
+    const DETAIL_OPTION: &'static str = "Detail";
+    const TOLERANCE_OPTION: &'static str = "Tolerance";
+    const SIMPLIFIER_OPTION: &'static str = "Simplifier";
+    const SIMPLIFICATION_OPTION: &'static str = "Simplification";
+    const PROJECTION_OPTION: &'static str = "Projection";
+    const WATERWAYS_OPTION: &'static str = "Waterways";
+    const WATERWAY_COLOR_OPTION: &'static str = "Waterway color";
+    const SEA_OPTION: &'static str = "Sea";
+    const SEA_COLOR_OPTION: &'static str = "Sea color";
+    const SEA_HATCH_OPTION: &'static str = "Sea hatch";
+    const RAILWAYS_OPTION: &'static str = "Railways";
+    const RAILWAY_COLOR_OPTION: &'static str = "Railway color";
+    const CLIP_OPTION: &'static str = "Clip to boundary";
+    const BOUNDARY_OPTION: &'static str = "Boundary outline";
+    const BOUNDARY_COLOR_OPTION: &'static str = "Boundary color";
+    const BOUNDARY_LINE_OPTION: &'static str = "Boundary line";
+    const NEIGHBORHOODS_OPTION: &'static str = "Neighborhoods";
+    const BUILDINGS_OPTION: &'static str = "Buildings";
+    const BUILDING_COLOR_OPTION: &'static str = "Building color";
+    const AREA_COLOR_OPTION: &'static str = "Area color";
+    const STREET_NAMES_OPTION: &'static str = "Street names";
+    const HIGHLIGHT_OPTION: &'static str = "Highlight streets";
+    const HIGHLIGHT_COLOR_OPTION: &'static str = "Highlight color";
+    const POINTS_OPTION: &'static str = "Points";
+    const POINT_RADIUS_OPTION: &'static str = "Point radius";
+    const ADVANCED_QUERY_OPTION: &'static str = "Advanced query";
+    const COMPARE_DATE_OPTION: &'static str = "Compare date";
+    const ADDED_COLOR_OPTION: &'static str = "Added color";
+    const REMOVED_COLOR_OPTION: &'static str = "Removed color";
+    #[cfg(feature = "scripting")]
+    const STYLE_SCRIPT_OPTION: &'static str = "Style script";
+
+    fn new() -> Self {
+        let output: Vec<(&'static str, Box<dyn ParamValue>)> = vec![
+            (Self::WIDTH_OPTION, Box::new(Length::px(1920.0))),
+            (Self::HEIGHT_OPTION, Box::new(Length::px(1080.0))),
+            (Self::CANVAS_OPTION, Box::new(CanvasPreset::Custom)),
+            (Self::CROP_OPTION, Box::new(Crop::Off)),
+            (Self::OUTPUT_DIR_OPTION, Box::new(String::new())),
+            (Self::OPEN_OPTION, Box::new(true)),
+            (Self::NOTIFY_OPTION, Box::new(Notify::Off)),
+            (Self::POST_EXPORT_OPTION, Box::new(String::new())),
+            (Self::PREVIEW_OPTION, Box::new(false)),
+            (Self::PRINT_MARKS_OPTION, Box::new(false)),
+            (Self::BLEED_OPTION, Box::new(Length::mm(3.0))),
+            (Self::COLOR_SPACE_OPTION, Box::new(ColorSpace::Srgb)),
+            (Self::SPOT_COLORS_OPTION, Box::new(SpotColors::default())),
+            (
+                Self::ICC_PROFILE_OPTION,
+                Box::new(SvgOptions::DEFAULT_ICC_PROFILE.to_string()),
+            ),
+            (Self::CAPTION_OPTION, Box::new(false)),
+            (Self::STREET_INDEX_OPTION, Box::new(IndexFormat::Off)),
+            (Self::SAVE_QUERY_OPTION, Box::new(false)),
+            (Self::SAVE_PROJECT_OPTION, Box::new(ProjectContents::Off)),
+            (Self::WORLD_FILE_OPTION, Box::new(false)),
+            (Self::PNG_OPTION, Box::new(Optional::<f64>(None))),
+            (Self::THUMBNAIL_OPTION, Box::new(Optional::<u32>(None))),
+            (Self::PDF_PAGE_OPTION, Box::new(Optional::<PageSize>(None))),
+            (Self::PDF_MARGIN_OPTION, Box::new(Length::mm(10.0))),
+            (
+                Self::DXF_UNITS_OPTION,
+                Box::new(Optional::<LengthUnit>(None)),
+            ),
+            (
+                Self::GCODE_AREA_OPTION,
+                Box::new(Optional::<PageSize>(None)),
+            ),
+            (Self::FEED_RATE_OPTION, Box::new(3000.0_f64)),
+            (Self::PEN_UP_OPTION, Box::new("M5".to_string())),
+            (Self::PEN_DOWN_OPTION, Box::new("M3 S1000".to_string())),
+            (Self::PEN_DELAY_OPTION, Box::new(0.2_f64)),
+            (Self::ORIGIN_OPTION, Box::new(Origin::TopLeft)),
+            (Self::Y_AXIS_OPTION, Box::new(YAxis::Down)),
+            (Self::SPLIT_ANGLE_OPTION, Box::new(Optional::<f64>(None))),
+            (Self::JOIN_PATHS_OPTION, Box::new(false)),
+            (Self::PATH_ORDER_OPTION, Box::new(PathOrder::Off)),
+            (Self::REVERSE_PATHS_OPTION, Box::new(true)),
+        ];
+
+        #[allow(unused_mut)]
+        let mut style: Vec<(&'static str, Box<dyn ParamValue>)> = vec![
+            (Self::STROKE_WIDTH_OPTION, Box::new(Length::px(0.3))),
+            (Self::STROKE_COLOR_OPTION, Box::new(Color::BLACK)),
+            (Self::BACKGROUND_COLOR, Box::new(Color::None)),
+            (
+                Self::ROAD_STYLE_OPTION,
+                Box::new(roads::render::Style::uniform()),
+            ),
+            (Self::LINE_CAP_OPTION, Box::new(LineCap::Butt)),
+            (Self::LINE_JOIN_OPTION, Box::new(LineJoin::Miter)),
+            (Self::STYLE_RULES_OPTION, Box::new(String::new())),
+        ];
+
+        let layers: Vec<(&'static str, Box<dyn ParamValue>)> = vec![
+            (Self::DETAIL_OPTION, Box::new(LodProfile::full())),
+            (Self::TOLERANCE_OPTION, Box::new(Simplification::Profile)),
+            (
+                Self::SIMPLIFIER_OPTION,
+                Box::new(Algorithm::RamerDouglasPeucker),
+            ),
+            (
+                Self::SIMPLIFICATION_OPTION,
+                Box::new(TargetSimplification::default()),
+            ),
+            (Self::PROJECTION_OPTION, Box::new(ProjectionKind::Mercator)),
+            (Self::ROAD_CLASSES_OPTION, Box::new(RoadFilter::all())),
+            (Self::WAY_BUDGET_OPTION, Box::new(Optional::<u64>(None))),
+            (Self::EXCLUDE_TAGS_OPTION, Box::new(TagExclusions::none())),
+            (Self::PADDING_OPTION, Box::new(Padding::none())),
+            (Self::RADIUS_OPTION, Box::new(Optional::<Distance>(None))),
+            (Self::WATERWAYS_OPTION, Box::new(false)),
+            (
+                Self::WATERWAY_COLOR_OPTION,
+                Box::new(Color::Rgb(74, 128, 192)),
+            ),
+            (Self::SEA_OPTION, Box::new(false)),
+            (Self::SEA_COLOR_OPTION, Box::new(Color::Rgb(200, 220, 240))),
+            (Self::SEA_HATCH_OPTION, Box::new(Optional::<Length>(None))),
+            (Self::RAILWAYS_OPTION, Box::new(false)),
+            (
+                Self::RAILWAY_COLOR_OPTION,
+                Box::new(Color::Rgb(120, 120, 120)),
+            ),
+            (Self::CLIP_OPTION, Box::new(false)),
+            (Self::BOUNDARY_OPTION, Box::new(false)),
+            (
+                Self::BOUNDARY_COLOR_OPTION,
+                Box::new(Color::Rgb(220, 50, 50)),
+            ),
+            (Self::BOUNDARY_LINE_OPTION, Box::new(LineKind::Solid)),
+            (
+                Self::NEIGHBORHOODS_OPTION,
+                Box::new(NeighborhoodColoring::Off),
+            ),
+            (Self::BUILDINGS_OPTION, Box::new(false)),
+            (
+                Self::BUILDING_COLOR_OPTION,
+                Box::new(Color::Rgb(200, 200, 200)),
+            ),
+            (Self::AREA_COLOR_OPTION, Box::new(Color::Rgb(230, 230, 230))),
+            (
+                Self::STREET_NAMES_OPTION,
+                Box::new(Optional::<NamePattern>(None)),
+            ),
+            (Self::HIGHLIGHT_OPTION, Box::new(StreetHighlight::none())),
+            (
+                Self::HIGHLIGHT_COLOR_OPTION,
+                Box::new(Color::Rgb(230, 80, 20)),
+            ),
+            (Self::POINTS_OPTION, Box::new(String::new())),
+            (Self::POINT_RADIUS_OPTION, Box::new(Length::px(1.0))),
+            (Self::ADVANCED_QUERY_OPTION, Box::new(String::new())),
+            (
+                Self::COMPARE_DATE_OPTION,
+                Box::new(Optional::<AtticDate>(None)),
+            ),
+            (Self::ADDED_COLOR_OPTION, Box::new(Color::Rgb(40, 160, 60))),
+            (
+                Self::REMOVED_COLOR_OPTION,
+                Box::new(Color::Rgb(220, 40, 40)),
+            ),
+        ];
+
+        let polite = PoliteMode::public();
+        let network: Vec<(&'static str, Box<dyn ParamValue>)> = vec![
+            (Self::PROFILE_OPTION, Box::new(Profile::Public)),
+            (Self::SEARCH_LIMIT_OPTION, Box::new(10_u32)),
+            (Self::LANGUAGE_OPTION, Box::new(String::new())),
+            (
+                Self::NOMINATIM_URL_OPTION,
+                Box::new(Endpoints::PUBLIC_NOMINATIM.to_string()),
+            ),
+            (
+                Self::OVERPASS_URL_OPTION,
+                Box::new(Endpoints::PUBLIC_OVERPASS.to_string()),
+            ),
+            (Self::OVERPASS_MIRRORS_OPTION, Box::new(String::new())),
+            (
+                Self::NOMINATIM_AUTH_OPTION,
+                Box::new(Optional::<Auth>(None)),
+            ),
+            (Self::OVERPASS_AUTH_OPTION, Box::new(Optional::<Auth>(None))),
+            (Self::CACHE_OPTION, Box::new(true)),
+            (Self::POLITE_OPTION, Box::new(true)),
+            (Self::PREFETCH_OPTION, Box::new(false)),
+            (Self::FETCH_STRATEGY_OPTION, Box::new(FetchStrategy::Geom)),
+            (Self::TILES_OPTION, Box::new(Optional::<Tiling>(None))),
+            (
+                Self::OVERPASS_TIMEOUT_OPTION,
+                Box::new(Optional::<u32>(None)),
+            ),
+            (
+                Self::OVERPASS_MAXSIZE_OPTION,
+                Box::new(Optional::<u64>(None)),
+            ),
+            (
+                Self::MAX_REQUESTS_OPTION,
+                Box::new(polite.max_requests.unwrap_or_default()),
+            ),
+            (
+                Self::REQUESTS_PER_MINUTE_OPTION,
+                Box::new(polite.per_minute.unwrap_or_default()),
+            ),
+        ];
+
+        #[cfg(feature = "scripting")]
+        style.push((Self::STYLE_SCRIPT_OPTION, Box::new(String::new())));
+
+        State {
+            focus: WidgetId::Search,
+            user_city: String::new(),
+            places: WrappingList::new(vec![]),
+            params: WrappingList::new(vec![
+                ParamGroup::new("Output", output),
+                ParamGroup::new("Style", style),
+                ParamGroup::new("Layers", layers),
+                ParamGroup::new("Network", network),
+            ]),
+            worker_state: WorkerState::Idle,
+            fetching_spinner: DotsSpinner::new(),
+            parm_edit_state: None,
+            pipeline: Pipeline::new(),
+            throttle: Arc::new(Throttle::new()),
+            status: None,
+            retry_status: Arc::default(),
+            progress: Arc::default(),
+            warnings: vec![],
+            projection: Box::new(WebMercator),
+            project_rules: None,
+            preview: None,
+            dry_run: None,
+            prefetch: None,
+            prefetch_task: None,
+            fetch_task: None,
+            hook_task: None,
+            hook_error: Arc::default(),
+            fitted: None,
+            fetched: Lru::new(FETCHED_CAPACITY),
+        }
+    }
+
+    /// The server side limits of every Overpass query.
+    fn query_limits(&self) -> QueryLimits {
+        QueryLimits {
+            timeout: self.param::<Optional<u32>>(Self::OVERPASS_TIMEOUT_OPTION).0,
+            maxsize: self
+                .param::<Optional<u64>>(Self::OVERPASS_MAXSIZE_OPTION)
+                .0
+                .map(|mb| mb.saturating_mul(1024 * 1024)),
+        }
+    }
+
+    fn remote(&self) -> anyhow::Result<Remote> {
+        let endpoints = match self.param::<Profile>(Self::PROFILE_OPTION) {
+            Profile::Public => Endpoints::public(),
+            Profile::SelfHosted => {
+                let mirrors = self.param::<String>(Self::OVERPASS_MIRRORS_OPTION);
+                let mut endpoints = Endpoints::self_hosted(
+                    self.param::<String>(Self::NOMINATIM_URL_OPTION),
+                    self.param::<String>(Self::OVERPASS_URL_OPTION),
+                )
+                .with_overpass_mirrors(mirrors.split(',').map(str::trim).filter(|m| !m.is_empty()));
+                endpoints.nominatim_auth = self
+                    .param::<Optional<Auth>>(Self::NOMINATIM_AUTH_OPTION)
+                    .0
+                    .clone();
+                endpoints.overpass_auth = self
+                    .param::<Optional<Auth>>(Self::OVERPASS_AUTH_OPTION)
+                    .0
+                    .clone();
+                endpoints
+            }
+        };
+        let cache = if *self.param::<bool>(Self::CACHE_OPTION) {
+            Cache::user()
+        } else {
+            None
+        };
+
+        let retry_status = Arc::clone(&self.retry_status);
+        let endpoints = endpoints
+            .with_cache(cache)
+            .with_env_auth()
+            .map_err(anyhow::Error::msg)?
+            .with_client(ClientConfig::default().with_env())
+            .with_retry(RetryPolicy::default().with_observer(move |attempt| {
+                *retry_status.lock().unwrap() = Some(attempt.to_string());
+            }))
+            .with_progress(self.progress_observer())
+            .with_strategy(*self.param::<FetchStrategy>(Self::FETCH_STRATEGY_OPTION))
+            .with_limits(self.query_limits());
+
+        let polite = if *self.param::<bool>(Self::POLITE_OPTION) {
+            let max_requests = *self.param::<usize>(Self::MAX_REQUESTS_OPTION);
+            let per_minute = *self.param::<u32>(Self::REQUESTS_PER_MINUTE_OPTION);
+
+            PoliteMode {
+                max_requests: Some(max_requests).filter(|n| *n > 0),
+                per_minute: Some(per_minute).filter(|n| *n > 0),
+            }
+        } else {
+            PoliteMode::off()
+        };
+
+        Ok(Remote {
+            search: SearchOptions {
+                limit: Some(
+                    (*self.param::<u32>(Self::SEARCH_LIMIT_OPTION)).min(SearchOptions::MAX_LIMIT),
+                ),
+                language: self.language(),
+                ..SearchOptions::default()
+            },
+            polite: endpoints.polite_mode(polite),
+            endpoints,
+            throttle: Arc::clone(&self.throttle),
+            tiling: self.param::<Optional<Tiling>>(Self::TILES_OPTION).0,
+        })
+    }
+
+    fn worker_busy(&self) -> bool {
+        match self.worker_state {
+            WorkerState::Fetching => true,
+            WorkerState::Idle | WorkerState::Error(_) => false,
+        }
+    }
+
+    fn max_option_key_len(&self) -> usize {
+        self.params
+            .selected()
+            .into_iter()
+            .flat_map(|g| g.params.iter())
+            .map(|(k, _)| k.len())
+            .max()
+            .unwrap_or_default()
+    }
+
+    fn param<T: Any>(&self, key: &str) -> &T {
+        for (k, v) in self.params.iter().flat_map(|g| g.params.iter()) {
+            if k != &key {
+                continue;
+            }
+
+            return v.as_any().downcast_ref::<T>().expect("invalid param type");
+        }
+
+        panic!("parameter {} not found", key)
+    }
+
+    fn set_param<T: ParamValue + 'static>(&mut self, key: &str, value: T) {
+        for (k, v) in self.params.iter_mut().flat_map(|g| g.params.iter_mut()) {
+            if k == &key {
+                *v = Box::new(value);
+                return;
+            }
+        }
+
+        panic!("parameter {} not found", key)
+    }
+
+    /// Set the option with the given name from its textual representation, like the option
+    /// editor does.
+    fn parse_param(&mut self, key: &str, value: &str) -> anyhow::Result<()> {
+        let param = self
+            .params
+            .iter_mut()
+            .flat_map(|g| g.params.iter_mut())
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| v)
+            .ok_or_else(|| anyhow::anyhow!("unknown option '{}'", key))?;
+
+        if !param.parse_from(value) {
+            anyhow::bail!("invalid value '{}' for option '{}'", value, key);
+        }
+
+        Ok(())
+    }
+
+    /// The state with the defaults of the user config file applied, if there's one.
+    fn with_config() -> anyhow::Result<Self> {
+        let mut state = State::new();
+        if let Some(path) = Config::default_path() {
+            let config = Config::load(&path)?;
+            state.apply_config(&config, &path)?;
+        }
+
+        Ok(state)
+    }
+
+    fn canvas_size(&self) -> (f64, f64) {
+        let (w, h) = self.param::<CanvasPreset>(Self::CANVAS_OPTION).size(
+            *self.param::<Length>(Self::WIDTH_OPTION),
+            *self.param::<Length>(Self::HEIGHT_OPTION),
+        );
+        (w.to_px(), h.to_px())
+    }
+
+    fn canvas(&self) -> Canvas {
+        let (w, h) = self.canvas_size();
+        Canvas {
+            origin: *self.param::<Origin>(Self::ORIGIN_OPTION),
+            y_axis: *self.param::<YAxis>(Self::Y_AXIS_OPTION),
+            ..Canvas::new(w, h)
+        }
+    }
+
+    /// Where to save the files of the export with the given name, without extension.
+    fn output_path(&self, name: &str) -> String {
+        let dir = self.param::<String>(Self::OUTPUT_DIR_OPTION);
+        if dir.is_empty() {
+            return name.to_string();
+        }
+
+        Path::new(dir).join(name).to_string_lossy().into_owned()
+    }
+
+    /// Set the options saved in the config file at the given path.
+    fn apply_config(&mut self, config: &Config, path: &Path) -> anyhow::Result<()> {
+        for (k, v) in &config.options {
+            self.parse_param(k, v)
+                .with_context(|| format!("invalid config {}", path.display()))?;
+        }
+
+        Ok(())
+    }
+
+    /// The options that differ from the builtin defaults, but the credentials.
+    fn changed_options(&self) -> Config {
+        let defaults = State::new()
+            .params
+            .iter()
+            .flat_map(|g| g.params.iter())
+            .map(|(k, v)| (*k, v.to_string()))
+            .collect::<HashMap<_, _>>();
+
+        let options = self
+            .params
+            .iter()
+            .flat_map(|g| g.params.iter())
+            .filter(|(k, _)| !Self::SECRET_OPTIONS.contains(k))
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .filter(|(k, v)| defaults.get(k.as_str()) != Some(v))
+            .collect();
+
+        Config { options }
+    }
+
+    /// The project to save along with the drawing of the given roads, if enabled.
+    fn project(
+        &self,
+        name: &str,
+        query: &str,
+        place: Option<&NominatimEntry>,
+        roads: &[Road],
+    ) -> anyhow::Result<Option<Project>> {
+        let contents = *self.param::<ProjectContents>(Self::SAVE_PROJECT_OPTION);
+        if contents == ProjectContents::Off {
+            return Ok(None);
+        }
+
+        // the name is also where to save the export, which is local to this machine
+        let name = Path::new(name)
+            .file_name()
+            .map_or_else(|| name.into(), |n| n.to_string_lossy());
+        let mut project = Project::new(name, query);
+        project.place = place.cloned();
+        project.options = self.changed_options().options;
+        project.options.retain(|k, _| !self.is_local_option(k));
+        project.style_rules = self.style_rules()?;
+        if contents == ProjectContents::Geometry {
+            project = project.with_geometry(roads);
+        }
+
+        Ok(Some(project))
+    }
+
+    fn is_local_option(&self, key: &str) -> bool {
+        Self::LOCAL_OPTIONS.contains(&key)
+            || self
+                .params
+                .iter()
+                .any(|g| g.name == "Network" && g.params.iter().any(|(k, _)| *k == key))
+    }
+
+    /// The contents of the style rules, if any.
+    fn style_rules(&self) -> anyhow::Result<Option<String>> {
+        let path = self.param::<String>(Self::STYLE_RULES_OPTION);
+        if path.is_empty() {
+            return Ok(None);
+        }
+
+        match &self.project_rules {
+            Some((p, rules)) if p == path => Ok(Some(rules.clone())),
+            _ => fs::read_to_string(path)
+                .map(Some)
+                .with_context(|| format!("cannot read the style rules {}", path)),
+        }
+    }
+
+    /// Replace all the options with the ones of the given project, the options it doesn't set
+    /// get their builtin defaults so that it renders the same as where it was saved.
+    fn apply_project(&mut self, project: &Project) -> anyhow::Result<()> {
+        let defaults = State::new()
+            .params
+            .iter()
+            .flat_map(|g| g.params.iter())
+            .map(|(k, v)| (*k, v.to_string()))
+            .filter(|(k, _)| !self.is_local_option(k))
+            .collect::<Vec<_>>();
+        for (k, v) in defaults {
+            self.parse_param(k, &v)?;
+        }
+
+        for (k, v) in &project.options {
+            if self.is_local_option(k) {
+                continue;
+            }
+            self.parse_param(k, v)
+                .with_context(|| format!("invalid project option '{}'", k))?;
+        }
+
+        self.project_rules = project.style_rules.clone().map(|rules| {
+            let path = self.param::<String>(Self::STYLE_RULES_OPTION).clone();
+            (path, rules)
+        });
+        Ok(())
+    }
+
+    /// Save the changed options as the defaults of the next runs.
+    fn save_defaults(&mut self) -> anyhow::Result<()> {
+        let path = Config::default_path()
+            .ok_or_else(|| anyhow::anyhow!("cannot find the user config directory"))?;
+        self.changed_options().save(&path)?;
+
+        self.status = Some(format!("saved defaults to {}", path.display()));
+        Ok(())
+    }
+
+    /// The Overpass query fetching all the enabled layers of the given place, unless it's
+    /// overridden by the advanced query.
+    fn place_query(&self, place: &NominatimEntry) -> String {
+        let advanced = self.param::<String>(Self::ADVANCED_QUERY_OPTION);
+        if !advanced.trim().is_empty() {
+            return roads::custom_query(advanced);
+        }
+
+        self.generated_query(place)
+    }
+
+    /// The Overpass query built from the options for the given place.
+    fn generated_query(&self, place: &NominatimEntry) -> String {
+        let selectors = self
+            .layer_selectors(place)
+            .into_iter()
+            .flat_map(|(_, s)| s)
+            .collect::<Vec<_>>();
+        self.selectors_query(place, &selectors)
+    }
+
+    /// The queries of each of the enabled layers of the given place, to fetch them apart.
+    fn layer_queries(&self, place: &NominatimEntry) -> Vec<(&'static str, String)> {
+        let advanced = self.param::<String>(Self::ADVANCED_QUERY_OPTION);
+        if !advanced.trim().is_empty() {
+            return vec![("query", roads::custom_query(advanced))];
+        }
+
+        self.layer_selectors(place)
+            .into_iter()
+            .map(|(layer, selectors)| (layer, self.selectors_query(place, &selectors)))
+            .collect()
+    }
+
+    /// The queries of the layers of the given place, followed by the ones of the snapshot to
+    /// compare with, if any, which are flagged.
+    fn snapshot_layers(&self, place: &NominatimEntry) -> Vec<(String, bool)> {
+        let layers = self.layer_queries(place);
+        let before = match &self
+            .param::<Optional<AtticDate>>(Self::COMPARE_DATE_OPTION)
+            .0
+        {
+            None => vec![],
+            Some(date) => layers
+                .iter()
+                .map(|(_, q)| (attic_query(q, date), true))
+                .collect(),
+        };
+
+        layers
+            .into_iter()
+            .map(|(_, q)| (q, false))
+            .chain(before)
+            .collect()
+    }
+
+    /// The roads to fetch according to the options.
+    fn road_filter(&self) -> RoadFilter {
+        // explicitly chosen classes win over the ones of the detail profile
+        let filter = self.param::<RoadFilter>(Self::ROAD_CLASSES_OPTION);
+        let mut filter = if filter.is_all() {
+            self.param::<LodProfile>(Self::DETAIL_OPTION).filter.clone()
+        } else {
+            filter.clone()
+        };
+        if let Some(name) = self.street_names() {
+            filter = filter.with_name(name.clone());
+        }
+        filter.without(self.exclusions())
+    }
+
+    /// The roads to leave out according to the options.
+    fn exclusions(&self) -> TagExclusions {
+        self.param::<TagExclusions>(Self::EXCLUDE_TAGS_OPTION)
+            .clone()
+    }
+
+    /// The road filter of the given place trimmed to fit in the Way budget, if it has been.
+    fn fitted_filter(&self, place: &NominatimEntry) -> Option<&FittedFilter> {
+        let max_ways = self.param::<Optional<u64>>(Self::WAY_BUDGET_OPTION).0?;
+        self.fitted.as_ref().filter(|f| {
+            f.place_id == place.place_id && f.max_ways == max_ways && f.wanted == self.road_filter()
+        })
+    }
+
+    /// The Way budget along with the road filters to choose from to fit in it, from the one
+    /// keeping only the most important class to the one keeping them all, and their queries.
+    /// Nothing if there's no budget, or if it has already been fitted for the given place.
+    fn budget_candidates(
+        &self,
+        place: &NominatimEntry,
+    ) -> Option<(u64, Vec<(RoadFilter, String)>)> {
+        let max_ways = self.param::<Optional<u64>>(Self::WAY_BUDGET_OPTION).0?;
+        let advanced = self.param::<String>(Self::ADVANCED_QUERY_OPTION);
+        if !advanced.trim().is_empty() || self.fitted_filter(place).is_some() {
+            return None;
+        }
+
+        let filter = self.road_filter();
+        let candidates = (1..=filter.ranked_classes().len())
+            .map(|n| {
+                let f = filter.most_important(n);
+                let query = self.selectors_query(place, &[f.selector()]);
+                (f, query)
+            })
+            .collect();
+        Some((max_ways, candidates))
+    }
+
+    /// The given fetched roads of the given place warning about the road classes left out to
+    /// fit in the Way budget, if any.
+    fn warn_trimmed(
+        &self,
+        place: Option<&NominatimEntry>,
+        mut roads: Fetched<Vec<Road>>,
+    ) -> Fetched<Vec<Road>> {
+        let fitted = match place.and_then(|p| self.fitted_filter(p)) {
+            None => return roads,
+            Some(f) => f,
+        };
+        if fitted.fitted != fitted.wanted {
+            roads.warnings.push(Warning::Trimmed {
+                kept: fitted.fitted.classes().to_vec(),
+                max_ways: fitted.max_ways,
+            });
+        }
+        roads
+    }
+
+    /// The selectors of each of the enabled layers of the given place, by layer name.
+    fn layer_selectors(&self, place: &NominatimEntry) -> Vec<(&'static str, Vec<String>)> {
+        let filter = match self.fitted_filter(place) {
+            Some(f) => f.fitted.clone(),
+            None => self.road_filter(),
+        };
+        let points = self.param::<String>(Self::POINTS_OPTION);
+
+        let mut layers = vec![("roads", vec![filter.selector()])];
+        if *self.param::<NeighborhoodColoring>(Self::NEIGHBORHOODS_OPTION)
+            != NeighborhoodColoring::Off
+        {
+            layers.push(("neighborhoods", vec![NEIGHBORHOODS_SELECTOR.to_string()]));
+        }
+        if *self.param::<bool>(Self::WATERWAYS_OPTION) {
+            layers.push(("waterways", vec![roads::WATERWAYS_SELECTOR.to_string()]));
+        }
+        if *self.param::<bool>(Self::RAILWAYS_OPTION) {
+            layers.push(("railways", vec![roads::RAILWAYS_SELECTOR.to_string()]));
+        }
+        if *self.param::<bool>(Self::SEA_OPTION) {
+            layers.push(("sea", vec![COASTLINE_SELECTOR.to_string()]));
+        }
+        if *self.param::<bool>(Self::BUILDINGS_OPTION) {
+            let selectors = roads::BUILDINGS_SELECTORS.map(String::from).to_vec();
+            layers.push(("buildings", selectors));
+        }
+        if !points.is_empty() {
+            layers.push(("points", vec![roads::node_selector(points)]));
+        }
+
+        layers
+    }
+
+    /// The query fetching the elements matching any of the given selectors in the given place.
+    fn selectors_query(&self, place: &NominatimEntry, selectors: &[String]) -> String {
+        let selectors = selectors.iter().map(String::as_str).collect::<Vec<_>>();
+        roads::elements_query_in(self.search_area(place), &selectors)
+    }
+
+    /// The preferred languages of the names of the places, if set.
+    fn language(&self) -> Option<String> {
+        Some(
+            self.param::<String>(Self::LANGUAGE_OPTION)
+                .trim()
+                .to_string(),
+        )
+        .filter(|l| !l.is_empty())
+    }
+
+    /// The name of the exports of the given place: what was searched, or the name of the place
+    /// when it was searched by address or in the chosen Language when set.
+    fn export_name(&self, searched: &str, place: &NominatimEntry) -> String {
+        let name = place
+            .display_name
+            .split(',')
+            .next()
+            .unwrap_or_default()
+            .trim();
+        let by_address = searched.trim_start().starts_with(SearchQuery::PREFIX);
+        if (self.language().is_none() && !by_address) || place.osm_id == 0 || name.is_empty() {
+            return searched.to_string();
+        }
+
+        name.replace(['/', '\\'], "-")
+    }
+
+    /// Where the elements of the given place are searched.
+    fn search_area(&self, place: &NominatimEntry) -> SearchArea {
+        if let Some(radius) = self.radius_of(place) {
+            let center = place.center();
+            return SearchArea::Around {
+                lat: center.lat,
+                lon: center.lon,
+                radius,
+            };
+        }
+
+        // areas cannot be padded, fall back to their padded bounding box
+        let padding = *self.param::<Padding>(Self::PADDING_OPTION);
+        if padding.is_none() {
+            roads::search_area(place)
+        } else {
+            let (s, w, n, e) = place.padded(padding).bounds();
+            SearchArea::Bbox(s, w, n, e)
+        }
+    }
+
+    /// The requests that would be sent for the given place, each preceded by comments telling
+    /// what it fetches, ready to be pasted in overpass-turbo. They're the ones of
+    /// [`open_place`], but for the layers already in memory.
+    fn dry_run(&self, place: &NominatimEntry) -> String {
+        let mut notes = vec![place.display_name.clone()];
+
+        if self
+            .param::<String>(Self::ADVANCED_QUERY_OPTION)
+            .trim()
+            .is_empty()
+        {
+            let search = match self.search_area(place) {
+                SearchArea::Area(id) => {
+                    format!(
+                        "searching area {} of {} {}",
+                        id, place.osm_type, place.osm_id
+                    )
+                }
+                SearchArea::Bbox(s, w, n, e) => {
+                    format!("searching the bounding box {},{},{},{}", s, w, n, e)
+                }
+                SearchArea::Around { lat, lon, radius } => {
+                    format!("searching {} m around {},{}", radius, lat, lon)
+                }
+                _ => "searching everywhere".to_string(),
+            };
+            notes.push(search);
+        } else {
+            notes.push("advanced query".to_string());
+        }
+
+        // the budget is fitted by counting the ways, which is sending requests already
+        if let Some((max_ways, _)) = self.budget_candidates(place) {
+            notes.push(format!(
+                "the road classes are trimmed first to fit in {} ways",
+                max_ways
+            ));
+        }
+        if let Some(tiling) = self.param::<Optional<Tiling>>(Self::TILES_OPTION).0 {
+            let (s, w, n, e) = self.bounds_of(place);
+            notes.push(format!(
+                "each sent once per tile of a {} grid of {},{},{},{}, setting its bbox",
+                tiling, s, w, n, e
+            ));
+        }
+
+        let queries = self
+            .snapshot_layers(place)
+            .into_iter()
+            .map(|(q, _)| q)
+            .collect::<Vec<_>>();
+        let mut requests = combine_queries(&queries)
+            .into_iter()
+            .map(|(q, _)| self.sent_query(&q))
+            .collect::<Vec<_>>();
+        match self.boundary_of(place) {
+            Some(Boundary::Place(entry)) => {
+                if let Some(q) = roads::boundary_query(&entry) {
+                    requests.push(format!("// boundary\n{}", self.sent_query(&q)));
+                }
+            }
+            Some(Boundary::Circle(_)) => notes.push("the boundary is a circle".to_string()),
+            None => {}
+        }
+
+        let notes = notes
+            .iter()
+            .map(|n| format!("// {}\n", n))
+            .collect::<String>();
+        format!("{}{}", notes, requests.join("\n\n"))
+    }
+
+    /// The given query as it's sent to Overpass, according to the Fetch strategy and the limits.
+    fn sent_query(&self, query: &str) -> String {
+        let strategy = self.param::<FetchStrategy>(Self::FETCH_STRATEGY_OPTION);
+        self.query_limits().apply(&strategy.query(query))
+    }
+
+    /// The boundary to fetch along with the place, if it's either drawn or used for clipping.
+    fn boundary_of(&self, place: &NominatimEntry) -> Option<Boundary> {
+        if !*self.param::<bool>(Self::BOUNDARY_OPTION) && !*self.param::<bool>(Self::CLIP_OPTION) {
+            return None;
+        }
+
+        Some(match self.radius_of(place) {
+            Some(radius) => {
+                let center = place.center();
+                Boundary::Circle(roads::circle_ring(center.lat, center.lon, radius))
+            }
+            None => Boundary::Place(place.padded(*self.param::<Padding>(Self::PADDING_OPTION))),
+        })
+    }
+
+    /// The bounding box the elements of the given place are searched in.
+    fn bounds_of(&self, place: &NominatimEntry) -> Bounds {
+        match self.radius_of(place) {
+            Some(radius) => NominatimEntry::at(place.center())
+                .padded(Padding::Distance(Distance::meters(radius)))
+                .bounds(),
+            None => place
+                .padded(*self.param::<Padding>(Self::PADDING_OPTION))
+                .bounds(),
+        }
+    }
+
+    /// The radius in meters of the circle to search around the given place instead of its area,
+    /// if any.
+    fn radius_of(&self, place: &NominatimEntry) -> Option<f64> {
+        let radius = self.param::<Optional<Distance>>(Self::RADIUS_OPTION).0;
+        match radius {
+            Some(r) => Some(r.to_meters()),
+            None if place.osm_type == NominatimEntry::COORDINATES_TYPE => Some(DEFAULT_RADIUS),
+            None => None,
+        }
+    }
+
+    /// The pattern of the names of the streets to isolate, if any.
+    fn street_names(&self) -> Option<&NamePattern> {
+        self.param::<Optional<NamePattern>>(Self::STREET_NAMES_OPTION)
+            .0
+            .as_ref()
+    }
+
+    fn current_params(&mut self) -> Option<&mut WrappingList<(&'static str, Box<dyn ParamValue>)>> {
+        self.params.selected_mut().map(|g| &mut g.params)
+    }
+
+    fn set_current_param(&mut self, value: Box<dyn ParamValue>) {
+        if let Some((_, v)) = self.current_params().and_then(|p| p.selected_mut()) {
+            *v = value;
+        }
+    }
+
+    /// Observer keeping [`State::progress`] up to date.
+    fn progress_observer(&self) -> ProgressObserver {
+        let progress = Arc::clone(&self.progress);
+        ProgressObserver::new(move |p| *progress.lock().unwrap() = Some(*p))
+    }
+
+    fn fetch<T: Send + 'static>(
+        &mut self,
+        state: Arc<Mutex<Self>>,
+        fut: impl Future<Output = anyhow::Result<T>> + Send + 'static,
+        mut on_success: impl FnMut(&mut Self, T) -> anyhow::Result<()> + Send + 'static,
+    ) {
+        self.worker_state = WorkerState::Fetching;
+        self.fetching_spinner = DotsSpinner::new();
+        *self.retry_status.lock().unwrap() = None;
+        *self.progress.lock().unwrap() = None;
+        self.warnings.clear();
+
+        let started = std::time::Instant::now();
+        let notify = *self.param::<Notify>(Self::NOTIFY_OPTION);
+
+        self.fetch_task = Some(tokio::task::spawn(async move {
+            let err = |st: &mut State, e| {
+                st.worker_state = WorkerState::Error(e);
+                st.focus = WidgetId::Error;
+                st.fetching_spinner = DotsSpinner::new();
+            };
+
+            let st = match fut.await {
+                Ok(d) => {
+                    let mut state = state.lock().await;
+                    state.worker_state = WorkerState::Idle;
+                    if let Err(e) = on_success(&mut state, d) {
+                        err(&mut state, e);
+                    }
+                    state
+                }
+                Err(e) => {
+                    let mut state = state.lock().await;
+                    err(&mut state, e);
+                    state
+                }
+            };
+
+            if started.elapsed() >= NOTIFY_AFTER {
+                let summary = match &st.worker_state {
+                    WorkerState::Error(e) => format!("Failed: {}", e),
+                    _ => st.status.clone().unwrap_or_else(|| "Done".to_string()),
+                };
+                notify.send(&summary);
+            }
+        }));
+    }
+
+    /// Abort the running fetch, if any, dropping its requests and whatever it fetched so far.
+    fn cancel_fetch(&mut self) {
+        if let Some(task) = self.fetch_task.take() {
+            task.abort();
+        }
+
+        self.worker_state = WorkerState::Idle;
+        self.fetching_spinner = DotsSpinner::new();
+        *self.retry_status.lock().unwrap() = None;
+        *self.progress.lock().unwrap() = None;
+        self.status = Some("cancelled".to_string());
+    }
+
+    /// Wait for the post-export command to finish, failing if it did.
+    async fn wait_hook(&mut self) -> anyhow::Result<()> {
+        if let Some(task) = self.hook_task.take() {
+            task.await?;
+        }
+        match self.hook_error.lock().unwrap().take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+async fn main_loop(terminal: &mut Terminal<impl Backend>, state: State) -> anyhow::Result<()> {
+    let mut reader = EventStream::new();
+    let progress = Arc::clone(&state.progress);
+    let state = Arc::new(Mutex::new(state));
+
+    loop {
+        match state.try_lock() {
+            Ok(mut st) => {
+                let hook_error = st.hook_error.lock().unwrap().take();
+                if let Some(e) = hook_error {
+                    st.worker_state = WorkerState::Error(e);
+                    st.focus = WidgetId::Error;
+                }
+                terminal.draw(|f| draw(f, &mut st))?;
+            }
+            Err(_) => {
+                // a worker is busy processing what it fetched while holding the state, show how
+                // far along it is rather than freezing
+                let p = *progress.lock().unwrap();
+                if let Some(p) = p.filter(|p| p.stage == ProgressStage::Process) {
+                    terminal.draw(|f| draw_progress(f, &p))?;
+                }
+            }
+        }
+
+        let ev = match tokio::time::timeout(Duration::from_millis(50), reader.next()).await {
+            Err(_) => {
+                // timeout expired
+                continue;
+            }
+            Ok(ev) => ev,
+        };
+
+        let mut st = state.lock().await;
+        match ev {
+            Some(Ok(event)) => {
+                let KeyEvent {
+                    code, modifiers, ..
+                } = match event {
+                    Event::Key(k) => k,
+                    _ => continue,
+                };
+
+                if code == KeyCode::Esc && st.worker_busy() {
+                    st.cancel_fetch();
+                    continue;
+                }
+
+                if !matches!(
+                    st.focus,
+                    WidgetId::ParamEdit | WidgetId::Preview | WidgetId::DryRun
+                ) {
+                    if code == KeyCode::Esc
+                        || (code, modifiers) == (KeyCode::Char('c'), KeyModifiers::CONTROL)
+                    {
+                        break;
+                    }
+
+                    if code == KeyCode::Tab || code == KeyCode::BackTab {
+                        let tab_order = [
+                            WidgetId::Search,
+                            WidgetId::Places,
+                            WidgetId::Options,
+                            WidgetId::Help,
+                        ];
+                        let current = tab_order.iter().position(|w| w == &st.focus).unwrap();
+                        let next = current
+                            + if code == KeyCode::Tab {
+                                1
+                            } else {
+                                tab_order.len() - 1
+                            };
+
+                        st.focus = tab_order[next % tab_order.len()];
+                        continue;
+                    }
+                }
+
+                handle_key_event(code, &mut st, &state).await?;
+            }
+            Some(Err(_)) | None => break,
+        }
+    }
+
+    Ok(())
+}
+
+/// Fetch and render roads to svg using OpenStreetMap. Without a command the interactive TUI is
+/// started.
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Render the best match for the given place without the TUI.
+    Render(RenderArgs),
+
+    /// Render a project saved by the TUI, e.g. `roads export turin.roads`.
+    Export(ExportArgs),
+
+    /// Render a project along with what changed since an older project of the same place, e.g.
+    /// `roads diff turin-2020.roads turin.roads`.
+    Diff(DiffArgs),
+}
+
+#[derive(Args)]
+struct DiffArgs {
+    /// The `.roads` project to compare with.
+    before: PathBuf,
+
+    #[command(flatten)]
+    after: ExportArgs,
+}
+
+#[derive(Args)]
+struct ExportArgs {
+    /// The `.roads` project file.
+    project: PathBuf,
+
+    /// Where to save the svg, defaults to `<project name>.svg`.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Override any of the options of the project, e.g. `--set "Stroke color=#333"`.
+    #[arg(long = "set", value_name = "OPTION=VALUE")]
+    options: Vec<String>,
+}
+
+#[derive(Args)]
+struct RenderArgs {
+    /// The place to search, e.g. "Berlin" or "address: city=Springfield, state=Illinois", or the
+    /// path of a saved `.ql` query to run again.
+    place: String,
+
+    /// Width of the drawing, e.g. 1920 or 297mm.
+    #[arg(long)]
+    width: Option<Length>,
+
+    /// Height of the drawing, e.g. 1080 or 210mm.
+    #[arg(long)]
+    height: Option<Length>,
+
+    /// Width of the roads.
+    #[arg(long)]
+    stroke: Option<Length>,
+
+    /// Where to save the svg, defaults to `<place>.svg`.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Set any of the options of the TUI, e.g. `--set "Stroke color=#333"`.
+    #[arg(long = "set", value_name = "OPTION=VALUE")]
+    options: Vec<String>,
+
+    /// Print all the candidates for the place, not only when the choice is ambiguous.
+    #[arg(short, long)]
+    alternatives: bool,
+
+    /// Always download fresh data.
+    #[arg(long)]
+    no_cache: bool,
+
+    /// Also render the map to `<output>.png` at the given resolution, e.g. 300.
+    #[arg(long, value_name = "DPI")]
+    png: Option<f64>,
+
+    /// Also write `<output>.thumb.png`, whose longest side is this many pixels.
+    #[arg(long, value_name = "PX")]
+    thumbnail: Option<u32>,
+
+    /// Never download the roads with any of these tags, e.g. access=private,service=driveway or
+    /// a bare key like area:highway.
+    #[arg(long, value_name = "TAGS")]
+    exclude: Option<TagExclusions>,
+
+    /// Also write `<output>.pdf` on a page of this size, e.g. a4, a3-landscape or 500x700mm.
+    #[arg(long, value_name = "PAGE")]
+    pdf: Option<PageSize>,
+
+    /// Also write `<output>.dxf` with coordinates in this unit, e.g. mm.
+    #[arg(long, value_name = "UNIT")]
+    dxf: Option<LengthUnit>,
+
+    /// Also write `<output>.gcode` for a pen plotter with this work area, e.g. a4 or 300x200mm.
+    #[arg(long, value_name = "AREA")]
+    gcode: Option<PageSize>,
+
+    /// Simplify the roads with this tolerance in meters, e.g. 2, or keep every point with off.
+    #[arg(long, value_name = "METERS")]
+    tolerance: Option<Simplification>,
+
+    /// Draw the roads within this distance of the center of the place, e.g. 1.5km.
+    #[arg(long, value_name = "DISTANCE")]
+    radius: Option<Distance>,
+
+    /// Print the Overpass query of the place, along with where it searches, without running it.
+    #[arg(long)]
+    dry_run: bool,
+}
+
+/// Non interactive mode: search the given place, pick the best match and export it with the
+/// default options.
+async fn run_batch(args: RenderArgs) -> anyhow::Result<()> {
+    let place = args.place;
+
+    let mut state = State::with_config()?;
+    state.set_param(State::OPEN_OPTION, false);
+    for o in &args.options {
+        let (k, v) = o
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("options must be set as OPTION=VALUE, got '{}'", o))?;
+        state.parse_param(k.trim(), v.trim())?;
+    }
+    if let Some(w) = args.width {
+        state.set_param(State::WIDTH_OPTION, w);
+    }
+    if let Some(h) = args.height {
+        state.set_param(State::HEIGHT_OPTION, h);
+    }
+    if let Some(sw) = args.stroke {
+        state.set_param(State::STROKE_WIDTH_OPTION, sw);
+    }
+    if args.no_cache {
+        state.set_param(State::CACHE_OPTION, false);
+    }
+    if let Some(dpi) = args.png {
+        state.set_param(State::PNG_OPTION, Optional(Some(dpi)));
+    }
+    if let Some(size) = args.thumbnail {
+        state.set_param(State::THUMBNAIL_OPTION, Optional(Some(size)));
+    }
+    if let Some(exclusions) = args.exclude {
+        state.set_param(State::EXCLUDE_TAGS_OPTION, exclusions);
+    }
+    if let Some(page) = args.pdf {
+        state.set_param(State::PDF_PAGE_OPTION, Optional(Some(page)));
+    }
+    if let Some(units) = args.dxf {
+        state.set_param(State::DXF_UNITS_OPTION, Optional(Some(units)));
+    }
+    if let Some(area) = args.gcode {
+        state.set_param(State::GCODE_AREA_OPTION, Optional(Some(area)));
+    }
+    if let Some(tolerance) = args.tolerance {
+        state.set_param(State::TOLERANCE_OPTION, tolerance);
+    }
+    if let Some(radius) = args.radius {
+        state.set_param(State::RADIUS_OPTION, Optional(Some(radius)));
+    }
+    // an explicit output path is not relative to the default output directory
+    if args.output.is_some() {
+        state.set_param(State::OUTPUT_DIR_OPTION, String::new());
+    }
+
+    let remote = state.remote()?;
+
+    // saved queries are run as they are, like in the Search of the TUI
+    if place.ends_with(".ql") {
+        let path = PathBuf::from(&place);
+        let query = std::fs::read_to_string(&path)?;
+        if args.dry_run {
+            println!("{}", state.sent_query(&query));
+            return Ok(());
+        }
+
+        let name = match args.output {
+            None => path.with_extension("").to_string_lossy().into_owned(),
+            Some(o) => output_name(o),
+        };
+        let bar = io::stderr()
+            .is_terminal()
+            .then(|| tokio::spawn(progress_bar(Arc::clone(&state.progress))));
+        let exported = async {
+            let roads = remote.fetch_query(&query).await?;
+            export(&mut state, &name, &query, None, roads)
+        }
+        .await;
+        return finish_batch(&mut state, &place, &name, bar, exported).await;
+    }
+
+    let entries = remote.search(&place).await?;
+    let best = roads::best_entry(&entries).ok_or_else(|| roads::Error::NotFound {
+        place: place.clone(),
+    })?;
+
+    // the sidecar files are named after the svg
+    let name = match args.output {
+        None => state.export_name(&place, best),
+        Some(o) => output_name(o),
+    };
+
+    if args.alternatives || roads::is_ambiguous(&entries) {
+        for e in &entries {
+            let marker = if e.place_id == best.place_id {
+                '*'
+            } else {
+                ' '
+            };
+            eprintln!(
+                "{} {} ({} {})",
+                marker, e.display_name, e.osm_type, e.osm_id
+            );
+        }
+    }
+
+    if args.dry_run {
+        println!("{}", state.dry_run(best));
+        return Ok(());
+    }
+
+    if let Some((max_ways, candidates)) = state.budget_candidates(best) {
+        state.fitted = Some(FittedFilter {
+            place_id: best.place_id,
+            wanted: state.road_filter(),
+            max_ways,
+            fitted: remote.fit_budget(max_ways, candidates).await?,
+        });
+    }
+
+    let query = state.place_query(best);
+    let bar = io::stderr()
+        .is_terminal()
+        .then(|| tokio::spawn(progress_bar(Arc::clone(&state.progress))));
+    let exported = async {
+        let roads = remote
+            .fetch_place(
+                &query,
+                Some(state.bounds_of(best)),
+                state.boundary_of(best),
+                None,
+            )
+            .await?;
+        let roads = match &state
+            .param::<Optional<AtticDate>>(State::COMPARE_DATE_OPTION)
+            .0
+        {
+            None => roads,
+            Some(date) => {
+                let before = remote
+                    .fetch_layers(&[attic_query(&query, date)], Some(state.bounds_of(best)))
+                    .await?;
+                let before = before.into_iter().flatten().next();
+                compare(before.unwrap_or_else(|| Fetched::complete(vec![])), roads)
+            }
+        };
+        export(&mut state, &name, &query, Some(best), roads)
+    }
+    .await;
+    finish_batch(&mut state, &place, &name, bar, exported).await
+}
+
+/// The name of the export saved to the given path, the sidecar files are named after the svg.
+fn output_name(output: PathBuf) -> String {
+    if output.extension().is_some_and(|e| e == "svg") {
+        output.with_extension("").to_string_lossy().into_owned()
+    } else {
+        output.to_string_lossy().into_owned()
+    }
+}
+
+/// Stop the progress bar, wait for the post-export command, notify the outcome of the export of
+/// the given place and print where it was saved along with its warnings.
+async fn finish_batch(
+    state: &mut State,
+    place: &str,
+    name: &str,
+    bar: Option<tokio::task::JoinHandle<()>>,
+    exported: anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    if let Some(bar) = bar {
+        bar.abort();
+        eprint!("\r\x1b[2K");
+    }
+    let exported = match exported {
+        Ok(()) => state.wait_hook().await,
+        e => e,
+    };
+    let notify = *state.param::<Notify>(State::NOTIFY_OPTION);
+    match &exported {
+        Ok(()) => notify.send(&format!("Exported {}", place)),
+        Err(e) => notify.send(&format!("Failed to export {}: {}", place, e)),
+    }
+    exported?;
+
+    println!("{}.svg", state.output_path(name));
+    if let Some(status) = &state.status {
+        eprintln!("{}", status);
+    }
+    for w in &state.warnings {
+        eprintln!("warning: {}", w);
+    }
+    Ok(())
+}
+
+async fn run_export(args: ExportArgs, before: Option<PathBuf>) -> anyhow::Result<()> {
+    let project = Project::load(&args.project)?;
+    let before = before
+        .map(|p| Project::load(&p).map(|b| (p, b)))
+        .transpose()?;
+
+    // without geometry the current data of both sides would be compared with itself
+    if let Some((path, before)) = &before {
+        for (path, p) in [(path, before), (&args.project, &project)] {
+            if !p.has_geometry() {
+                anyhow::bail!(
+                    "{} has no geometry to compare, save it with Save project set to geometry",
+                    path.display()
+                );
+            }
+        }
+    }
+
+    let mut state = State::with_config()?;
+    state.apply_project(&project)?;
+    state.set_param(State::OPEN_OPTION, false);
+    for o in &args.options {
+        let (k, v) = o
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("options must be set as OPTION=VALUE, got '{}'", o))?;
+        state.parse_param(k.trim(), v.trim())?;
+    }
+    if args.output.is_some() {
+        state.set_param(State::OUTPUT_DIR_OPTION, String::new());
+    }
+
+    let name = match args.output {
+        None => project.name.clone(),
+        Some(o) => output_name(o),
+    };
+
+    let place = project.place.clone();
+    let (query, mut roads) = load_project(project, state.remote(), state.exclusions()).await?;
+    if let Some((_, before)) = before {
+        let (_, before) = load_project(before, state.remote(), state.exclusions()).await?;
+        roads = compare(before, roads);
+    }
+    export(&mut state, &name, &query, place.as_ref(), roads)?;
+    state.wait_hook().await?;
+
+    println!("{}.svg", state.output_path(&name));
+    if let Some(status) = &state.status {
+        eprintln!("{}", status);
+    }
+    for w in &state.warnings {
+        eprintln!("warning: {}", w);
+    }
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    match Cli::parse().command {
+        Some(Command::Render(args)) => return Runtime::new()?.block_on(run_batch(args)),
+        Some(Command::Export(args)) => return Runtime::new()?.block_on(run_export(args, None)),
+        Some(Command::Diff(args)) => {
+            return Runtime::new()?.block_on(run_export(args.after, Some(args.before)))
+        }
+        None => {}
+    }
+
+    let state = State::with_config()?;
+
+    crossterm::terminal::enable_raw_mode()?;
+
+    let stdout = io::stdout();
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+
+    terminal.clear()?;
+
+    let runtime = Runtime::new().unwrap();
+    let _ = runtime.block_on(main_loop(&mut terminal, state));
+
+    terminal.clear()?;
+    crossterm::terminal::disable_raw_mode()?;
+
+    Ok(())
+}
+
+/// The given progress followed by the time left estimated from the count of the elements of the
+/// place when the server doesn't say how big the response is.
+fn progress_status(progress: &Progress, count: Option<ElementCount>) -> String {
+    let estimate = count
+        .filter(|_| progress.stage == ProgressStage::Download && progress.total.is_none())
+        .and_then(|c| progress.eta_for(c.estimated_bytes()));
+
+    match estimate {
+        Some(eta) => format!("{}, ~{} left", progress, format_duration(eta)),
+        None => progress.to_string(),
+    }
+}
+
+/// Print the progress of the fetch on a single line of stderr until the task is aborted.
+async fn progress_bar(progress: Arc<std::sync::Mutex<Option<Progress>>>) {
+    const WIDTH: usize = 30;
+
+    let mut interval = tokio::time::interval(Duration::from_millis(200));
+    loop {
+        interval.tick().await;
+
+        let Some(p) = *progress.lock().unwrap() else {
+            continue;
+        };
+        let bar = match p.ratio() {
+            Some(r) => {
+                let filled = (r * WIDTH as f64).round() as usize;
+                format!(
+                    "[{}{}] {:>3.0}%",
+                    "#".repeat(filled),
+                    "-".repeat(WIDTH - filled),
+                    r * 100.0
+                )
+            }
+            None => format!("[{}]     ", "?".repeat(WIDTH)),
+        };
+        eprint!("\r\x1b[2K{} {}", bar, p);
+    }
+}
+
+/// Draw just the progress of the worker, for when the rest of the state is not available.
+fn draw_progress(f: &mut Frame<impl Backend>, progress: &Progress) {
+    use tui::{
+        layout::{Constraint, Direction, Layout},
+        style::{Color, Style},
+        widgets::{Block, Borders, Gauge},
+    };
+
+    let vcentered = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage(45),
+            Constraint::Length(3),
+            Constraint::Percentage(45),
+        ])
+        .split(f.size());
+
+    let gauge = Gauge::default()
+        .block(Block::default().title("Processing").borders(Borders::ALL))
+        .gauge_style(Style::default().fg(Color::LightYellow))
+        .ratio(progress.ratio().unwrap_or(0.0))
+        .label(progress.to_string());
+    f.render_widget(gauge, vcentered[1]);
+}
+
+fn draw(f: &mut Frame<impl Backend>, state: &mut State) {
+    use tui::{
+        layout::{Constraint, Direction, Layout},
         style::{Color, Modifier, Style},
-        widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Wrap},
+        widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Tabs, Wrap},
+    };
+
+    let focus = state.focus;
+    let block = |widget, title| {
+        Block::default()
+            .title(title)
+            .borders(Borders::ALL)
+            .border_style(if focus == widget {
+                Style::default().fg(Color::LightYellow)
+            } else {
+                Style::default()
+            })
+    };
+
+    let list = |widget, title, symbol, items| {
+        List::new(items)
+            .block(block(widget, title))
+            .highlight_symbol(symbol)
+            .highlight_style(
+                Style::default()
+                    .fg(Color::LightYellow)
+                    .add_modifier(Modifier::ITALIC | Modifier::DIM),
+            )
+    };
+
+    let worker_busy = {
+        match state.worker_state {
+            WorkerState::Idle => false,
+            WorkerState::Fetching => {
+                state.fetching_spinner.tick();
+                true
+            }
+            WorkerState::Error(ref e) => {
+                let error = Paragraph::new(e.to_string())
+                    .block(block(WidgetId::Error, "Error"))
+                    .wrap(Wrap { trim: false });
+                f.render_widget(error, f.size());
+                return;
+            }
+        }
+    };
+
+    let hchunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(70), Constraint::Percentage(30)])
+        .split(f.size());
+
+    let left_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(10), Constraint::Percentage(90)].as_ref())
+        .split(hchunks[0]);
+
+    let right_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)].as_ref())
+        .split(hchunks[1]);
+
+    let city_input = Paragraph::new(state.user_city.as_ref())
+        .block(block(WidgetId::Search, "Search"))
+        .wrap(Wrap { trim: true });
+
+    let symbol = if worker_busy {
+        state.fetching_spinner.pattern().to_string() + " "
+    } else {
+        "> ".to_string()
+    };
+
+    let retry_status = state.retry_status.lock().unwrap().clone();
+    let count = state
+        .prefetch
+        .as_ref()
+        .filter(|p| state.places.selected().map(|s| s.place_id) == Some(p.place_id))
+        .and_then(|p| p.count);
+    let progress = state
+        .progress
+        .lock()
+        .unwrap()
+        .map(|p| progress_status(&p, count));
+    let places_title = match (worker_busy, retry_status.or(progress), count, &state.status) {
+        (true, Some(retry), _, _) => format!("Places ({}, Esc to cancel)", retry),
+        (true, None, _, _) => "Places (Esc to cancel)".to_string(),
+        (false, _, Some(count), _) => format!("Places (~{} elements)", count.total()),
+        (_, _, _, Some(s)) => format!("Places ({})", s),
+        _ => "Places".to_string(),
+    };
+    let found_entries = list(
+        WidgetId::Places,
+        places_title.as_str(),
+        &symbol,
+        state
+            .places
+            .iter()
+            .map(|e| {
+                ListItem::new(Spans::from(vec![
+                    Span::raw(e.display_name.clone()),
+                    Span::raw(" "),
+                    Span::styled(
+                        format!("({} - {})", e.osm_id, e.osm_type),
+                        Style::default().add_modifier(Modifier::ITALIC),
+                    ),
+                ]))
+            })
+            .collect::<Vec<_>>(),
+    );
+
+    let max_option_key_len = state.max_option_key_len();
+    let options = List::new(
+        state
+            .params
+            .selected()
+            .map(|g| g.params.iter())
+            .into_iter()
+            .flatten()
+            .map(|(k, v)| {
+                let mut s = k.to_string();
+                s += ": ";
+                for _ in 0..max_option_key_len - k.len() {
+                    s.push(' ');
+                }
+                s += &v.to_string();
+
+                ListItem::new(s)
+            })
+            .collect::<Vec<_>>(),
+    )
+    .highlight_symbol("* ")
+    .highlight_style(
+        Style::default()
+            .fg(Color::LightYellow)
+            .add_modifier(Modifier::ITALIC | Modifier::DIM),
+    );
+
+    let options_tabs = Tabs::new(state.params.iter().map(|g| Spans::from(g.name)).collect())
+        .select(state.params.selected_ix().unwrap_or_default())
+        .highlight_style(Style::default().fg(Color::LightYellow))
+        .block(Block::default().borders(Borders::BOTTOM));
+
+    let help = Paragraph::new(
+        r#"Simple TUI to render the roads of a given place into an svg file.
+
+To start off, search a place by editing the Search line edit, hit enter and select the desired place to render.
+
+To tell apart places with the same name search by the parts of the address after address:, e.g. address: city=Springfield, state=Illinois, country=USA, and the export is named after the place found. The parts are street, city, county, state, country and postalcode.
+
+Language sets the languages of the names of the places, e.g. de or fr,en, and names the exports after the place in that language rather than after the search.
+
+Use the arrow keys or jk to move up and down and <TAB> to switch section.
+
+Hit <Enter> on an option to edit it and use the left and right arrow keys or hl to switch between the groups of options. Sizes accept units, e.g. 210mm or 0.5pt, and default to pixels.
+
+Canvas sets the shape of the drawing: custom uses Width and Height, a ratio like 1:1, 4:5 or 16:9 keeps the Width, and a paper size like a4 or a3-landscape sets both. Crop fills the canvas with the part of the map around the centroid of the boundary (centroid) or around its densest area (densest) rather than shrinking the whole map to fit.
+
+Turn on the Preview option to check the crop and the density of the map in the terminal before saving it.
+
+Post-export command runs after each export with {file} replaced by the path of the svg and {name} by the path without extension, e.g. vpype read {file} linemerge write {file}.
+
+Notify rings the terminal bell (bell) or shows a desktop notification (desktop) when a fetch or an export longer than 10 seconds is over, and at the end of the render command.
+
+A non zero Bbox padding switches to the bbox mode, searching the bounding box of the place rather than its area: try it when a place comes back empty.
+
+Radius searches the circle of the given size around the center of the place instead, e.g. 1.5km around an address for a print of your neighborhood. Searching coordinates like 45.07, 7.68 picks that very point, with a radius of 1km unless set. Turn on Clip to boundary to crop the roads to the circle.
+
+Turn on Sea to fetch the coastline and fill the sea with Sea color, or to shade it with lines of that color spaced by Sea hatch, e.g. 2mm for pen plotters. It works best with a Bbox padding, since the coastline is often outside the area of the place.
+
+Compare date, e.g. 2015-01-01, also fetches the place as it was then and draws the ways added since in Added color and the removed ones dashed in Removed color. Overpass areas have no history, so it works best with a Bbox padding. The diff command compares two projects instead, e.g. roads diff turin-2015.roads turin.roads.
+
+Boundary outline draws the limits of the place on top of the map, as a solid, dashed or dash-dot line set by Boundary line.
+
+Neighborhoods fetches the admin level 9 and 10 boundaries inside the place and colors the roads (roads) or the background (areas) by the neighborhood they fall in.
+
+Exclude tags drops the roads with any of the given tags before downloading them, e.g. access=private,service=driveway,area:highway.
+
+Way budget counts the roads of a place before downloading them and leaves out the least important road classes, steps and paths first, until there are at most that many ways, e.g. 50000, so that huge metros render something rather than timing out. A warning tells which classes were kept.
+
+The Road style option draws the roads by highway class: uniform, hierarchy or atlas.
+
+The Detail option trades accuracy for size: full, auto, web-thumbnail, a4-plot or a0-poster. auto picks the tolerance and the shortest path kept from how many points there are for the pixels of each export target, or for the dots a pen draws on paper, so that the same setting fits a village and a megacity. Tolerance overrides its simplification tolerance in meters, e.g. 2, or turns it off to keep every point. Simplifier picks how: rdp (Ramer-Douglas-Peucker) is faithful, while vw (Visvalingam-Whyatt) keeps the character of curvy roads at big tolerances. Simplification overrides both per export target, e.g. svg=off,thumbnail=vw:20,gcode=vw.
+
+Projection flattens the map: mercator matches web maps but stretches places far from the equator, equirectangular, utm and azimuthal (equidistant) are centered on the place and keep its shape, e.g. use utm for Reykjavík.
+
+PDF page also writes a vector pdf for print, e.g. a4, a3-landscape or 500x700mm, styled like the svg.
+
+For print shops, Color space cmyk writes the pdf in CMYK and adds a CMYK approximation to every color of the svg, referring to the ICC profile file or url, while Spot colors names the inks of some colors, e.g. #222=PANTONE-Black-C, which the pdf separates and the svg refers to the profile named after the ink, e.g. PANTONE-Black-C.icc. The PNGs are always tagged as sRGB.
+
+DXF units also writes the polylines of the map to a dxf for laser cutters and CAD tools, in mm, cm, in, pt or px.
+
+G-code area also writes the moves of a grbl pen plotter with that work area, e.g. a4 or 300x200mm, lifting and lowering the pen with the Pen up and Pen down commands and waiting Pen delay seconds after each of them, e.g. 0 for a solenoid.
+
+Join paths chains the roads sharing an endpoint into single long lines, so that plotters don't lift the pen at every way.
+
+Path order draws the paths of each layer nearest first to shorten the travel of plotters, greedy is fast while 2-opt also flips runs of paths and needs Reverse paths. story plots the water first, then the areas, the roads from the minor to the major ones, the railways and the boundary, nearest first within each of them. Turn off Reverse paths to keep one-way lines drawn in their direction.
+
+Street index writes the alphabetical list of the street names next to the svg, as plain text (txt) or as an engraved page (svg).
+
+The last places fetched are kept in memory layer by layer while Cache is on, going back to one of them with the same options downloads nothing and turning on a layer like Buildings downloads only that layer.
+
+Turn on Prefetch to download the boundary of the highlighted place and count its elements before it's selected, the count also estimates how long the download takes when the server doesn't tell its size.
+
+Set Fetch strategy to skeleton when Overpass runs out of memory on a big place, the nodes of the roads are downloaded apart from them which takes much less memory on the server.
+
+Overpass timeout (in seconds) and Overpass maxsize (in MiB) raise the time and the memory Overpass may spend on each query, big cities need several minutes and more than the default 512 MiB; the public instances may refuse too high values.
+
+For whole countries or metro areas set Tiles to split the bounding box of the place in a grid, e.g. 4x4, whose tiles are fetched two at a time, or as many as set after a slash like 4x4/1, and merged.
+
+Hit s in the Options to save the changed options as the defaults of the next runs.
+
+Advanced query replaces the query built from the options with any Overpass QL, for the filters no option can express. Editing it while a place is highlighted starts from the query of that place; empty it to go back to the options.
+
+Search results sets how many places a search lists, up to 40, hit m on the places to list the next ones, e.g. for the many San José.
+
+Hit d on a place for a dry run: it shows the Overpass query that would be sent and the area it searches without running it, to debug the filters or to run it by hand on overpass-turbo.eu.
+
+Enter the path of a saved .ql query in Search to run it again with the current options.
+
+Save project also writes a .roads project with the place, the query, the options and the style rules (settings), plus the fetched roads (geometry) to render the same map offline. Enter its path in Search to open it with its own options.
+
+Hit Esc while fetching to cancel the requests and get the TUI back.
+
+Esc or Ctrl-C to quit.
+"#,
+    )
+    .block(block(WidgetId::Help, "Help"))
+    .wrap(Wrap { trim: true });
+
+    f.render_widget(city_input, left_chunks[0]);
+
+    // the maps saved with partial results are incomplete, make it hard to miss
+    let places_area = if state.warnings.is_empty() {
+        left_chunks[1]
+    } else {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Min(0),
+                Constraint::Length(state.warnings.len() as u16 + 2),
+            ])
+            .split(left_chunks[1]);
+
+        let warnings = state
+            .warnings
+            .iter()
+            .map(|w| Spans::from(w.to_string()))
+            .collect::<Vec<_>>();
+        let banner = Paragraph::new(warnings)
+            .style(Style::default().fg(Color::Yellow))
+            .block(Block::default().borders(Borders::ALL).title("Warning"))
+            .wrap(Wrap { trim: true });
+        f.render_widget(banner, chunks[1]);
+
+        chunks[0]
+    };
+    f.render_stateful_widget(found_entries, places_area, state.places.state());
+
+    let options_block = block(WidgetId::Options, "Options");
+    let options_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(2), Constraint::Min(0)])
+        .split(options_block.inner(right_chunks[0]));
+    f.render_widget(options_block, right_chunks[0]);
+    f.render_widget(options_tabs, options_chunks[0]);
+
+    match state.current_params() {
+        Some(params) if focus == WidgetId::Options => {
+            f.render_stateful_widget(options, options_chunks[1], params.state());
+        }
+        _ => f.render_widget(options, options_chunks[1]),
+    }
+    f.render_widget(help, right_chunks[1]);
+
+    if state.focus == WidgetId::ParamEdit {
+        let edit_state = state.parm_edit_state.as_ref().unwrap();
+
+        if let Some((param, _)) = state.params.selected().and_then(|g| g.params.selected()) {
+            let parm_edit = Paragraph::new(edit_state.buffer.as_ref())
+                .block(block(WidgetId::ParamEdit, param))
+                .wrap(Wrap { trim: true })
+                .style(if edit_state.is_valid {
+                    Style::default()
+                } else {
+                    Style::default().bg(Color::LightRed)
+                });
+
+            let hcentered = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([
+                    Constraint::Percentage(30),
+                    Constraint::Percentage(40),
+                    Constraint::Percentage(30),
+                ])
+                .split(f.size());
+            let vcentered = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Percentage(30),
+                    Constraint::Max(3),
+                    Constraint::Percentage(30),
+                ])
+                .split(hcentered[1]);
+
+            f.render_widget(Clear, vcentered[1]);
+            f.render_widget(parm_edit, vcentered[1]);
+        }
+    }
+
+    if let (WidgetId::DryRun, Some(query)) = (state.focus, &state.dry_run) {
+        let dry_run = Paragraph::new(query.as_str())
+            .block(block(WidgetId::DryRun, "Dry run (Enter or Esc to close)"))
+            .wrap(Wrap { trim: false });
+        f.render_widget(Clear, f.size());
+        f.render_widget(dry_run, f.size());
+    }
+
+    if state.focus == WidgetId::Preview {
+        let canvas = state.canvas();
+        let (w, h) = state.canvas_size();
+        if let Some(preview) = &mut state.preview {
+            let paths = preview.shown.iter().map(|l| l.paths.len()).sum::<usize>();
+            let title = format!(
+                "Preview {}x{} px, {} paths (Enter to save, Esc to discard)",
+                w, h, paths
+            );
+            let preview_block = block(WidgetId::Preview, &title);
+
+            let area = preview_block.inner(f.size());
+            let size = (area.width, area.height);
+            if preview.raster.as_ref().map(|(s, _)| *s) != Some(size) {
+                let braille = roads::preview::render(
+                    &canvas,
+                    &preview.shown,
+                    area.width.into(),
+                    area.height.into(),
+                );
+                preview.raster = Some((size, braille.lines()));
+            }
+
+            let lines = preview.raster.as_ref().map_or(vec![], |(_, lines)| {
+                lines.iter().map(|l| Spans::from(l.as_str())).collect()
+            });
+
+            f.render_widget(Clear, f.size());
+            f.render_widget(Paragraph::new(lines).block(preview_block), f.size());
+        }
+    }
+}
+
+async fn handle_key_event(
+    code: KeyCode,
+    state: &mut State,
+    state_m: &Arc<Mutex<State>>,
+) -> anyhow::Result<()> {
+    if state.worker_busy() {
+        return Ok(());
+    }
+
+    match state.focus {
+        WidgetId::Search => match code {
+            KeyCode::Enter => {
+                if state
+                    .user_city
+                    .ends_with(&format!(".{}", project::EXTENSION))
+                {
+                    let project = Project::load(&state.user_city)?;
+                    state.apply_project(&project)?;
+
+                    let name = project.name.clone();
+                    let place = project.place.clone();
+                    let remote = state.remote();
+                    let exclusions = state.exclusions();
+
+                    state.fetch(
+                        Arc::clone(state_m),
+                        async move { load_project(project, remote, exclusions).await },
+                        move |state, (query, roads)| {
+                            show_or_export(state, &name, &query, place.as_ref(), roads)
+                        },
+                    );
+                } else if state.user_city.ends_with(".ql") {
+                    let path = std::path::PathBuf::from(&state.user_city);
+                    let name = path.with_extension("").to_string_lossy().into_owned();
+
+                    let remote = state.remote();
+
+                    state.fetch(
+                        Arc::clone(state_m),
+                        async move {
+                            let query = tokio::fs::read_to_string(&path).await?;
+                            let roads = remote?.fetch_query(&query).await?;
+                            Ok((query, roads))
+                        },
+                        move |state, (query, roads)| {
+                            show_or_export(state, &name, &query, None, roads)
+                        },
+                    );
+                } else if !state.user_city.is_empty() {
+                    let user_city = state.user_city.clone();
+                    let remote = state.remote();
+
+                    state.fetch(
+                        Arc::clone(state_m),
+                        async move {
+                            let cities = remote?.search(&user_city).await?;
+                            if cities.is_empty() {
+                                return Err(roads::Error::NotFound { place: user_city }.into());
+                            }
+                            Ok(cities)
+                        },
+                        {
+                            let state_m = Arc::clone(state_m);
+                            move |state, cities| {
+                                state.places = WrappingList::new(cities);
+                                state.focus = WidgetId::Places;
+                                prefetch(state, &state_m);
+                                Ok(())
+                            }
+                        },
+                    );
+                }
+            }
+            code => {
+                if edit_string(&mut state.user_city, code) {
+                    state.places = WrappingList::new(vec![]);
+                }
+            }
+        },
+        WidgetId::Places => match code {
+            KeyCode::Up | KeyCode::Char('k') => {
+                state.places.up();
+                prefetch(state, state_m);
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                state.places.down();
+                prefetch(state, state_m);
+            }
+            KeyCode::Char('d') => {
+                if let Some(place) = state.places.selected() {
+                    state.dry_run = Some(state.dry_run(place));
+                    state.focus = WidgetId::DryRun;
+                }
+            }
+            KeyCode::Char('m') => {
+                let user_city = state.user_city.clone();
+                let found = state.places.iter().cloned().collect::<Vec<_>>();
+                let remote = state.remote();
+
+                state.fetch(
+                    Arc::clone(state_m),
+                    async move { remote?.search_after(&user_city, &found).await },
+                    move |state, more| {
+                        // Nominatim may send back places already listed when it runs out
+                        let listed = state.places.iter().map(|p| p.place_id).collect::<Vec<_>>();
+                        let more = more
+                            .into_iter()
+                            .filter(|p| !listed.contains(&p.place_id))
+                            .collect::<Vec<_>>();
+                        if more.is_empty() {
+                            state.status = Some("no more places found".to_string());
+                        }
+                        state.places.extend(more);
+                        Ok(())
+                    },
+                );
+            }
+            KeyCode::Enter => {
+                if let Some(task) = state.prefetch_task.take() {
+                    task.abort();
+                }
+
+                if let Some(place) = state.places.selected().cloned() {
+                    open_place(state, state_m, place);
+                }
+            }
+            _ => {}
+        },
+        WidgetId::Options => match code {
+            KeyCode::Left | KeyCode::Char('h') => {
+                state.params.up();
+            }
+            KeyCode::Right | KeyCode::Char('l') => {
+                state.params.down();
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                if let Some(params) = state.current_params() {
+                    params.up();
+                }
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                if let Some(params) = state.current_params() {
+                    params.down();
+                }
+            }
+            KeyCode::Enter => {
+                if let Some((param, value)) = state.current_params().and_then(|p| p.selected()) {
+                    let param = *param;
+                    let mut edit = ParmEditState::new(value.cloned());
+
+                    // start from the query of the highlighted place rather than from scratch
+                    if param == State::ADVANCED_QUERY_OPTION && edit.buffer.is_empty() {
+                        if let Some(place) = state.places.selected() {
+                            edit.buffer = state.generated_query(place).replace('\n', " ");
+                            edit.is_valid = edit.value.parse_from(&edit.buffer);
+                        }
+                    }
+
+                    state.parm_edit_state = Some(edit);
+                    state.focus = WidgetId::ParamEdit;
+                }
+            }
+            KeyCode::Char('s') => {
+                if let Err(e) = state.save_defaults() {
+                    state.worker_state = WorkerState::Error(e);
+                    state.focus = WidgetId::Error;
+                }
+            }
+            _ => {}
+        },
+        WidgetId::ParamEdit => match code {
+            KeyCode::Enter => {
+                if state.parm_edit_state.as_ref().unwrap().is_valid {
+                    let mut edit_state = None;
+                    std::mem::swap(&mut edit_state, &mut state.parm_edit_state);
+
+                    state.set_current_param(edit_state.unwrap().value);
+                    state.focus = WidgetId::Options;
+                }
+            }
+            KeyCode::Esc => {
+                state.parm_edit_state = None;
+                state.focus = WidgetId::Options;
+            }
+            _ => {
+                let edit_state = state.parm_edit_state.as_mut().unwrap();
+                edit_string(&mut edit_state.buffer, code);
+
+                edit_state.is_valid = edit_state.value.parse_from(&edit_state.buffer);
+            }
+        },
+        WidgetId::Preview => match code {
+            KeyCode::Enter => {
+                if let Some(p) = state.preview.take() {
+                    state.focus = WidgetId::Places;
+                    let written = write_export(
+                        state,
+                        &p.name,
+                        &p.query,
+                        p.layers,
+                        &p.streets,
+                        p.project.as_ref(),
+                    );
+                    if let Err(e) = written {
+                        state.worker_state = WorkerState::Error(e);
+                        state.focus = WidgetId::Error;
+                    }
+                }
+            }
+            KeyCode::Esc => {
+                state.preview = None;
+                state.focus = WidgetId::Places;
+            }
+            _ => {}
+        },
+        WidgetId::DryRun => {
+            if matches!(code, KeyCode::Enter | KeyCode::Esc) {
+                state.dry_run = None;
+                state.focus = WidgetId::Places;
+            }
+        }
+        WidgetId::Help => {}
+        WidgetId::Error => {
+            if code == KeyCode::Enter {
+                state.worker_state = WorkerState::Idle;
+                state.focus = WidgetId::Search;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn edit_string(s: &mut String, code: KeyCode) -> bool {
+    match code {
+        KeyCode::Backspace => {
+            s.pop();
+            true
+        }
+        KeyCode::Char(c) => {
+            s.push(c);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// The query of the given project along with its roads, which are downloaded only if the project
+/// doesn't bundle them.
+///
+/// The bundled roads are fetched already, so the given exclusions are applied to them here in
+/// case they changed since.
+async fn load_project(
+    project: Project,
+    remote: anyhow::Result<Remote>,
+    exclusions: TagExclusions,
+) -> anyhow::Result<(String, Fetched<Vec<Road>>)> {
+    let roads = match project.geometry() {
+        Some(roads) => Fetched {
+            value: roads
+                .into_iter()
+                .filter(|r| !r.tags.contains_key("highway") || !exclusions.excludes(&r.tags))
+                .collect(),
+            warnings: vec![],
+        },
+        None => remote?.fetch_query(&project.query).await?,
     };
 
-    let focus = state.focus;
-    let block = |widget, title| {
-        Block::default()
-            .title(title)
-            .borders(Borders::ALL)
-            .border_style(if focus == widget {
-                Style::default().fg(Color::LightYellow)
-            } else {
-                Style::default()
+    Ok((project.query, roads))
+}
+
+/// Export the fetched roads right away or show them in the preview first, depending on the
+/// options.
+fn show_or_export(
+    state: &mut State,
+    name: &str,
+    query: &str,
+    place: Option<&NominatimEntry>,
+    roads: Fetched<Vec<Road>>,
+) -> anyhow::Result<()> {
+    if !*state.param::<bool>(State::PREVIEW_OPTION) {
+        return export(state, name, query, place, roads);
+    }
+
+    let roads = state.warn_trimmed(place, roads);
+
+    let project = state.project(name, query, place, &roads.value)?;
+    let (layers, streets) = layout(state, roads)?;
+    let (shown, _) = process(state, layers.clone(), SVG_TARGET);
+    state.preview = Some(Preview {
+        name: name.to_string(),
+        query: query.to_string(),
+        layers,
+        streets,
+        shown,
+        raster: None,
+        project,
+    });
+    state.focus = WidgetId::Preview;
+
+    Ok(())
+}
+
+fn export(
+    state: &mut State,
+    name: &str,
+    query: &str,
+    place: Option<&NominatimEntry>,
+    roads: Fetched<Vec<Road>>,
+) -> anyhow::Result<()> {
+    let roads = state.warn_trimmed(place, roads);
+    let project = state.project(name, query, place, &roads.value)?;
+    let (layers, streets) = layout(state, roads)?;
+    write_export(state, name, query, layers, &streets, project.as_ref())
+}
+
+/// Fetch the given place, or take it from the cache, and show or export it. With a Way budget
+/// the road classes that fit in it are found first.
+fn open_place(state: &mut State, state_m: &Arc<Mutex<State>>, place: NominatimEntry) {
+    if let Some((max_ways, candidates)) = state.budget_candidates(&place) {
+        let remote = state.remote();
+        let wanted = state.road_filter();
+        let state_m2 = Arc::clone(state_m);
+        state.fetch(
+            Arc::clone(state_m),
+            async move { remote?.fit_budget(max_ways, candidates).await },
+            move |state, fitted| {
+                state.fitted = Some(FittedFilter {
+                    place_id: place.place_id,
+                    wanted: wanted.clone(),
+                    max_ways,
+                    fitted,
+                });
+                open_place(state, &state_m2, place.clone());
+                Ok(())
+            },
+        );
+        return;
+    }
+
+    let place = &place;
+    let name = state.export_name(&state.user_city, place);
+    let query = state.place_query(place);
+    let boundary = state.boundary_of(place);
+    let bounds = state.bounds_of(place);
+
+    // only the layers that aren't in memory yet are downloaded, so turning on
+    // a layer after the fact doesn't download the others again
+    let use_cache = *state.param::<bool>(State::CACHE_OPTION);
+    let remote = state.remote();
+    let servers = remote.as_ref().map(Remote::servers).unwrap_or_default();
+    let mut cached = vec![];
+    let mut missing = vec![];
+    for (layer, before) in state.snapshot_layers(place) {
+        let key = (servers.clone(), place.place_id, layer);
+        match state.fetched.get(&key).filter(|_| use_cache) {
+            Some(roads) => cached.push((before, roads.clone())),
+            None => missing.push((key.2, before)),
+        }
+    }
+
+    let boundary_key = boundary
+        .as_ref()
+        .map(|b| (servers.clone(), place.place_id, b.cache_key()));
+    let prefetched = match &boundary_key {
+        Some(key) => state.fetched.get(key).filter(|_| use_cache).cloned(),
+        None => None,
+    }
+    .or_else(|| {
+        state
+            .prefetch
+            .as_ref()
+            .filter(|p| p.place_id == place.place_id && p.query == query)
+            .and_then(|p| p.boundary.clone())
+    });
+
+    if missing.is_empty() && (boundary.is_none() || prefetched.is_some()) {
+        let roads = assemble(cached, prefetched);
+        if let Err(e) = show_or_export(state, &name, &query, Some(place), roads) {
+            state.worker_state = WorkerState::Error(e);
+            state.focus = WidgetId::Error;
+        }
+        return;
+    }
+
+    let place = place.clone();
+    let nothing_cached = cached.iter().all(|(_, l)| l.value.is_empty());
+
+    state.fetch(
+        Arc::clone(state_m),
+        {
+            let query = query.clone();
+            async move {
+                let remote = remote?;
+                let queries = missing.iter().map(|(q, _)| q.clone()).collect::<Vec<_>>();
+                let layers = remote.fetch_layers(&queries, Some(bounds)).await?;
+                if nothing_cached && layers.iter().all(Option::is_none) {
+                    return Err(roads::Error::empty(&query).into());
+                }
+
+                let boundary = match (boundary, prefetched) {
+                    (None, _) => None,
+                    (Some(_), Some(b)) => Some(b),
+                    (Some(b), None) => Some(remote.fetch_boundary(&b).await?),
+                };
+
+                let layers = missing
+                    .into_iter()
+                    .zip(layers)
+                    .map(|(q, l)| (q, l.unwrap_or_else(|| Fetched::complete(vec![]))));
+                anyhow::Ok((layers.collect::<Vec<_>>(), boundary))
+            }
+        },
+        move |state, (layers, boundary)| {
+            for ((layer, _), roads) in &layers {
+                let key = (servers.clone(), place.place_id, layer.clone());
+                state.fetched.put(key, roads.clone());
+            }
+            if let (Some(key), Some(b)) = (boundary_key.clone(), &boundary) {
+                state.fetched.put(key, b.clone());
+            }
+
+            let layers = cached
+                .iter()
+                .cloned()
+                .chain(layers.into_iter().map(|((_, before), l)| (before, l)));
+            let roads = assemble(layers.collect(), boundary);
+            show_or_export(state, &name, &query, Some(&place), roads)
+        },
+    );
+}
+
+/// Download the boundary of the highlighted place and count the elements it's going to fetch in
+/// the background, so that selecting it starts the heavy download right away.
+fn prefetch(state: &mut State, state_m: &Arc<Mutex<State>>) {
+    if let Some(task) = state.prefetch_task.take() {
+        task.abort();
+    }
+    if !*state.param::<bool>(State::PREFETCH_OPTION) {
+        return;
+    }
+
+    let place = match state.places.selected() {
+        None => return,
+        Some(p) => p.clone(),
+    };
+    let query = state.place_query(&place);
+    let is_fresh = |p: &Prefetch| p.place_id == place.place_id && p.query == query;
+    if state.prefetch.as_ref().is_some_and(is_fresh) {
+        return;
+    }
+    let boundary = state.boundary_of(&place);
+    let remote = match state.remote() {
+        Err(_) => return,
+        Ok(r) => r,
+    };
+
+    let state_m = Arc::clone(state_m);
+    state.prefetch_task = Some(tokio::task::spawn(async move {
+        // nothing is sent while scrolling through the places
+        tokio::time::sleep(PREFETCH_DELAY).await;
+
+        // failures are not shown, the place is fetched as usual once selected
+        let boundary = match boundary {
+            None => None,
+            Some(b) => remote.fetch_boundary(&b).await.ok(),
+        };
+        let count = remote.count(&query).await.ok();
+
+        state_m.lock().await.prefetch = Some(Prefetch {
+            place_id: place.place_id,
+            query,
+            boundary,
+            count,
+        });
+    }));
+}
+
+/// Style the fetched roads into the layers of the drawing, before they're processed for the
+/// export targets, and index the names of the drawn streets.
+fn layout(
+    state: &mut State,
+    roads: Fetched<Vec<Road>>,
+) -> anyhow::Result<(Vec<StyledPaths>, StreetIndex)> {
+    state.warnings = roads.warnings;
+    let (projection, roads) = project_roads(
+        *state.param::<ProjectionKind>(State::PROJECTION_OPTION),
+        roads.value,
+    );
+    state.projection = projection;
+    let window = crop_window(state, &roads);
+
+    let sea = *state.param::<bool>(State::SEA_OPTION);
+    let (coastline, roads): (Vec<_>, Vec<_>) = roads
+        .into_iter()
+        .partition(|r| sea && coastline::is_coastline(r));
+
+    let sw = state.param::<Length>(State::STROKE_WIDTH_OPTION).to_px();
+    let stroke = *state.param::<Color>(State::STROKE_COLOR_OPTION);
+
+    let road = Style {
+        radius: state.param::<Length>(State::POINT_RADIUS_OPTION).to_px(),
+        ..Style::new(stroke, sw)
+    };
+
+    // the neighborhoods are kept whole, clipping would open their rings
+    let coloring = *state.param::<NeighborhoodColoring>(State::NEIGHBORHOODS_OPTION);
+    let (areas, roads): (Vec<_>, Vec<_>) = roads.into_iter().partition(|r| {
+        coloring != NeighborhoodColoring::Off
+            && !r.tags.contains_key(BOUNDARY_TAG)
+            && neighborhood::admin_level(r).is_some()
+    });
+    let neighborhoods = Neighborhoods::new(&areas);
+
+    let mut roads = clip_roads(state, roads);
+    let streets = StreetIndex::new(&roads);
+    let styles = LayerStyles {
+        waterway: Style {
+            color: *state.param::<Color>(State::WATERWAY_COLOR_OPTION),
+            layer: -1,
+            ..road.clone()
+        },
+        railway: Style {
+            color: *state.param::<Color>(State::RAILWAY_COLOR_OPTION),
+            width: sw * 1.5,
+            dash: vec![sw * 6.0, sw * 3.0],
+            layer: 1,
+            ..road.clone()
+        },
+        boundary: Style {
+            color: *state.param::<Color>(State::BOUNDARY_COLOR_OPTION),
+            width: sw * 3.0,
+            dash: state
+                .param::<LineKind>(State::BOUNDARY_LINE_OPTION)
+                .dash(sw * 3.0),
+            layer: 10,
+            ..road.clone()
+        },
+        building: Style {
+            fill: *state.param::<Color>(State::BUILDING_COLOR_OPTION),
+            layer: -2,
+            ..road.clone()
+        },
+        sea: Style {
+            color: Color::None,
+            fill: *state.param::<Color>(State::SEA_COLOR_OPTION),
+            layer: -5,
+            ..road.clone()
+        },
+        sea_hatch: Style {
+            color: *state.param::<Color>(State::SEA_COLOR_OPTION),
+            layer: -5,
+            ..road.clone()
+        },
+        area: Style {
+            fill: *state.param::<Color>(State::AREA_COLOR_OPTION),
+            layer: -1,
+            ..road.clone()
+        },
+        added: Style {
+            color: *state.param::<Color>(State::ADDED_COLOR_OPTION),
+            width: sw * 1.5,
+            layer: 3,
+            ..road.clone()
+        },
+        removed: Style {
+            color: *state.param::<Color>(State::REMOVED_COLOR_OPTION),
+            width: sw * 1.5,
+            dash: vec![sw * 3.0, sw * 2.0],
+            layer: 3,
+            ..road.clone()
+        },
+        highlights: street_highlights(state, &roads, &road),
+        neighborhood_roads: match coloring {
+            NeighborhoodColoring::Roads => neighborhood_roads(state, &roads, &neighborhoods, &road),
+            _ => HashMap::new(),
+        },
+        // the quarters are painted over the suburbs they're nested in, like the roads get the
+        // color of the smallest neighborhood they're in
+        neighborhood_areas: (0..neighborhoods.len())
+            .map(|i| Style {
+                color: Color::None,
+                fill: Neighborhoods::tint(i),
+                layer: match neighborhoods.get(i).map(|n| n.admin_level) {
+                    Some(10) => -3,
+                    _ => -4,
+                },
+                ..road.clone()
             })
+            .collect(),
+        named: state.street_names().map(|pattern| {
+            let style = Style {
+                color: *state.param::<Color>(State::HIGHLIGHT_COLOR_OPTION),
+                width: sw * 2.0,
+                layer: 4,
+                label: Some(pattern.to_string()),
+                ..road.clone()
+            };
+            (pattern.clone(), style)
+        }),
+        classes: {
+            let table = state.param::<roads::render::Style>(State::ROAD_STYLE_OPTION);
+            table
+                .classes
+                .keys()
+                .map(|&c| (c, table.class_style(c, &road)))
+                .collect()
+        },
+        road,
     };
 
-    let list = |widget, title, symbol, items| {
-        List::new(items)
-            .block(block(widget, title))
-            .highlight_symbol(symbol)
-            .highlight_style(
-                Style::default()
-                    .fg(Color::LightYellow)
-                    .add_modifier(Modifier::ITALIC | Modifier::DIM),
-            )
+    if sea {
+        let sea = sea_roads(state, &coastline, &roads);
+        roads.extend(sea);
+    }
+
+    if coloring == NeighborhoodColoring::Areas {
+        roads.extend(areas.into_iter().filter_map(|mut r| {
+            let i = neighborhoods.position(r.id)?;
+            r.tags.insert(NEIGHBORHOOD_TAG.to_string(), i.to_string());
+            Some(r)
+        }));
+    }
+
+    if let Some(window) = window {
+        roads = roads
+            .into_iter()
+            .flat_map(|r| {
+                let filled = styles.base(&r).fill != Color::None;
+                crop::crop(r, window, filled)
+            })
+            .collect();
+    }
+
+    if *state.param::<PathOrder>(State::PATH_ORDER_OPTION) == PathOrder::Story {
+        // every chapter gets its own layers, so that it's plotted and ordered on its own
+        let mut chapters = BTreeMap::<Chapter, Vec<Road>>::new();
+        for r in roads {
+            chapters.entry(Chapter::of(&r)).or_default().push(r);
+        }
+
+        let mut layers = vec![];
+        for roads in chapters.into_values() {
+            layers.extend(style_roads(state, roads, &styles)?);
+        }
+        return Ok((layers, streets));
+    }
+
+    Ok((style_roads(state, roads, &styles)?, streets))
+}
+
+/// The part of the projected roads to crop the map to so that it fills the canvas, if any.
+fn crop_window(state: &State, roads: &[Road]) -> Option<((f64, f64), (f64, f64))> {
+    let crop = *state.param::<Crop>(State::CROP_OPTION);
+    if crop == Crop::Off {
+        return None;
+    }
+
+    let (w, h) = state.canvas_size();
+    let points = roads
+        .iter()
+        .flat_map(|r| r.points.iter().copied())
+        .collect::<Vec<_>>();
+    let boundary = roads
+        .iter()
+        .filter(|r| r.tags.contains_key(BOUNDARY_TAG))
+        .map(|r| r.points.clone())
+        .collect::<Vec<_>>();
+    crop::window(crop, w / h, &points, &boundary)
+}
+
+/// The sea around the given roads, closing the given coastline against their bounding box, as
+/// filled rings or as hatching.
+fn sea_roads(state: &State, coastline: &[Road], roads: &[Road]) -> Vec<Road> {
+    let (min, max) = match bounds(roads.iter().flat_map(|r| &r.points)) {
+        None => return vec![],
+        Some(b) => b,
     };
 
-    let worker_busy = {
-        match state.worker_state {
-            WorkerState::Idle => false,
-            WorkerState::Fetching => {
-                state.fetching_spinner.tick();
-                true
-            }
-            WorkerState::Error(ref e) => {
-                let error = Paragraph::new(e.to_string()).block(block(WidgetId::Error, "Error"));
-                f.render_widget(error, f.size());
-                return;
-            }
+    let paths = coastline
+        .iter()
+        .map(|r| r.points.clone())
+        .collect::<Vec<_>>();
+    let rings = coastline::sea(&paths, min, max);
+    let sea = |kind: &str, points| Road {
+        id: 0,
+        tags: BTreeMap::from([(SEA_TAG.to_string(), kind.to_string())]),
+        points,
+        holes: vec![],
+    };
+
+    match state.param::<Optional<Length>>(State::SEA_HATCH_OPTION).0 {
+        None => rings.into_iter().map(|r| sea("fill", r)).collect(),
+        Some(spacing) => {
+            // the spacing is on the canvas, roughly, since the map is scaled to fit it
+            let (w, h) = state.canvas_size();
+            let scale = ((max.0 - min.0) / w).max((max.1 - min.1) / h);
+            Clip::new(rings)
+                .hatch(spacing.to_px() * scale, 45.0)
+                .into_iter()
+                .map(|l| sea("hatch", l))
+                .collect()
+        }
+    }
+}
+
+/// The detail profile to process the given layers with for the given export target, tuning the
+/// auto profile to the resolution of the target. Its lengths are scaled to the units of the
+/// layers, which are `scale` times the meters on the ground.
+fn auto_detail(state: &State, layers: &[StyledPaths], target: &str, scale: f64) -> LodProfile {
+    let lod = state
+        .param::<LodProfile>(State::DETAIL_OPTION)
+        .scaled(scale);
+    let paths = || layers.iter().flat_map(|l| &l.paths);
+    let world = match bounds(paths().flatten()) {
+        Some((min, max)) if lod.is_auto() => (max.0 - min.0, max.1 - min.1),
+        _ => return lod,
+    };
+
+    // the canvas is in css pixels, while the plots are drawn by a pen resolving a quarter of a
+    // millimeter, roughly
+    let (w, h) = state.canvas_size();
+    let dots = |(pw, ph): (f64, f64)| (pw * 4.0, ph * 4.0);
+    let resolution = match target {
+        PNG_TARGET => {
+            let dpi = state.param::<Optional<f64>>(State::PNG_OPTION).0;
+            let scale = dpi.map_or(1.0, |dpi| dpi / 96.0);
+            (w * scale, h * scale)
+        }
+        THUMBNAIL_TARGET => {
+            let size = state.param::<Optional<u32>>(State::THUMBNAIL_OPTION).0;
+            let scale = size.map_or(1.0, |s| f64::from(s) / w.max(h));
+            (w * scale, h * scale)
+        }
+        PDF_TARGET => state
+            .param::<Optional<PageSize>>(State::PDF_PAGE_OPTION)
+            .0
+            .map_or((w, h), |p| dots((p.width, p.height))),
+        GCODE_TARGET => state
+            .param::<Optional<PageSize>>(State::GCODE_AREA_OPTION)
+            .0
+            .map_or((w, h), |p| dots((p.width, p.height))),
+        DXF_TARGET => dots((Length::px(w).to_mm(), Length::px(h).to_mm())),
+        _ => (w, h),
+    };
+
+    lod.fit(paths().map(Vec::len).sum(), world, resolution)
+}
+
+/// Run the processing stages on the given layers, simplifying them as configured for the given
+/// export target. The stats of the simplification are returned too, with the error in meters on
+/// the ground, unless it's turned off.
+fn process(
+    state: &State,
+    layers: Vec<StyledPaths>,
+    target: &str,
+) -> (Vec<StyledPaths>, Option<SimplifyStats>) {
+    // the tolerances are in meters on the ground, which the projection stretches or shrinks
+    let scale = bounds(layers.iter().flat_map(|l| &l.paths).flatten())
+        .map(|(min, max)| {
+            let center = ((min.0 + max.0) / 2.0, (min.1 + max.1) / 2.0);
+            state.projection.scale(center)
+        })
+        .filter(|s| s.is_finite() && *s > 0.0)
+        .unwrap_or(1.0);
+
+    let lod = auto_detail(state, &layers, target, scale);
+    let targets = state.param::<TargetSimplification>(State::SIMPLIFICATION_OPTION);
+    let simplification = targets
+        .get_or(
+            target,
+            *state.param::<Simplification>(State::TOLERANCE_OPTION),
+        )
+        .scaled(scale);
+    let algorithm = targets
+        .algorithm(target)
+        .unwrap_or(*state.param::<Algorithm>(State::SIMPLIFIER_OPTION));
+
+    let mut simplifying = Pipeline::new().with_progress(state.progress_observer());
+    let simplify = lod.register_with(&mut simplifying, simplification, algorithm);
+
+    // the extra stages get the roads whole, before they're joined, split and ordered for the
+    // plotter
+    let mut ordering = Pipeline::new().with_progress(state.progress_observer());
+    let reverse = *state.param::<bool>(State::REVERSE_PATHS_OPTION);
+    if *state.param::<bool>(State::JOIN_PATHS_OPTION) {
+        ordering.register_transform(JoinPaths::new(reverse));
+    }
+    if let Some(max_turn) = state.param::<Optional<f64>>(State::SPLIT_ANGLE_OPTION).0 {
+        ordering.register_transform(SplitAtTurns::new(max_turn));
+    }
+    let order = *state.param::<PathOrder>(State::PATH_ORDER_OPTION);
+    if order != PathOrder::Off {
+        ordering.register_transform(OptimizeOrder::new(reverse, order == PathOrder::TwoOpt));
+    }
+
+    let layers = simplifying.run_layers(layers);
+    let layers = state.pipeline.run_layers(layers);
+    let layers = ordering.run_layers(layers);
+
+    // only once every stage is done, so that the bar doesn't vanish while the extra ones run
+    *state.progress.lock().unwrap() = None;
+
+    let stats = simplify.map(|s| SimplifyStats {
+        max_error: s.stats().max_error / scale,
+        ..s.stats()
+    });
+    (layers, stats)
+}
+
+/// Write the svg of the given layers along with the enabled sidecar files.
+fn write_export(
+    state: &mut State,
+    name: &str,
+    query: &str,
+    styled: Vec<StyledPaths>,
+    streets: &StreetIndex,
+    project: Option<&Project>,
+) -> anyhow::Result<()> {
+    let title = name;
+    let name = &state.output_path(name);
+    if let Some(dir) = Path::new(name).parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let (w, h) = state.canvas_size();
+    let stroke = *state.param::<Color>(State::STROKE_COLOR_OPTION);
+    let background = *state.param::<Color>(State::BACKGROUND_COLOR);
+
+    let canvas = state.canvas();
+    let mut options = SvgOptions {
+        background,
+        stroke: StrokeOptions {
+            cap: *state.param::<LineCap>(State::LINE_CAP_OPTION),
+            join: *state.param::<LineJoin>(State::LINE_JOIN_OPTION),
+        },
+        marks: state
+            .param::<bool>(State::PRINT_MARKS_OPTION)
+            .then(|| PrintMarks::new(state.param::<Length>(State::BLEED_OPTION).to_px())),
+        color_space: *state.param::<ColorSpace>(State::COLOR_SPACE_OPTION),
+        spots: state.param::<SpotColors>(State::SPOT_COLORS_OPTION).clone(),
+        icc_profile: state.param::<String>(State::ICC_PROFILE_OPTION).clone(),
+        ..SvgOptions::with_canvas(&canvas)
+    };
+
+    if *state.param::<bool>(State::SAVE_QUERY_OPTION) {
+        write_file_atomic(format!("{}.ql", name), query)?;
+        options.metadata.push((
+            "query-hash".to_string(),
+            format!("{:016x}", roads::util::fnv1a(query.as_bytes())),
+        ));
+    }
+
+    if let Some(project) = project {
+        project.save(format!("{}.{}", name, project::EXTENSION))?;
+    }
+
+    let path = format!("{}.svg", name);
+    let (layers, stats) = process(state, styled.clone(), SVG_TARGET);
+    state.status = Some(stats.map_or_else(|| "simplification off".to_string(), |s| s.to_string()));
+
+    // canvas units are css pixels, i.e. 96 per inch
+    let png_scale = state
+        .param::<Optional<f64>>(State::PNG_OPTION)
+        .0
+        .map(|dpi| dpi / 96.0);
+
+    // georeference the png if there's one, otherwise the raster renderings of the svg, which have
+    // one pixel per canvas unit
+    if *state.param::<bool>(State::WORLD_FILE_OPTION) {
+        let viewport = svg::viewport(&canvas, &layers);
+        let world_file = match png_scale {
+            Some(scale) => WorldFile::for_raster(&viewport, scale),
+            None => WorldFile::for_drawing(&viewport, options.total_margin()),
+        };
+        write_file_atomic(format!("{}.pgw", name), world_file.to_string())?;
+        write_file_atomic(format!("{}.prj", name), state.projection.wkt())?;
+
+        if let Some(epsg) = state.projection.epsg() {
+            options.metadata.push(("crs".to_string(), epsg));
+        }
+        options.metadata.push((
+            "extent".to_string(),
+            format!(
+                "{} {} {} {}",
+                viewport.min.0, viewport.min.1, viewport.max.0, viewport.max.1
+            ),
+        ));
+    }
+
+    if let Some(scale) = png_scale {
+        let (layers, _) = process(state, styled.clone(), PNG_TARGET);
+        if let Some(png) = roads::raster::render(&canvas, background, &layers, scale, 0.0) {
+            write_file_atomic(format!("{}.png", name), roads::raster::encode_png(&png)?)?;
+        }
+    }
+
+    if let Some(size) = state.param::<Optional<u32>>(State::THUMBNAIL_OPTION).0 {
+        let (layers, _) = process(state, styled.clone(), THUMBNAIL_TARGET);
+        if let Some(thumbnail) = roads::raster::thumbnail(&canvas, background, &layers, size) {
+            let png = roads::raster::encode_png(&thumbnail)?;
+            write_file_atomic(format!("{}.thumb.png", name), png)?;
         }
-    };
+    }
 
-    let hchunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(70), Constraint::Percentage(30)])
-        .split(f.size());
+    match state.param::<IndexFormat>(State::STREET_INDEX_OPTION) {
+        IndexFormat::Off => {}
+        IndexFormat::Text => write_file_atomic(format!("{}.index.txt", name), streets.to_string())?,
+        IndexFormat::Svg => {
+            let size = w.min(h) / 60.0;
+            // the page is laid out with the y axis growing downwards, unlike the world
+            let paths = streets
+                .page((w, h), size)
+                .into_iter()
+                .map(|p| p.into_iter().map(|(x, y)| (x, -y)).collect())
+                .collect();
+            let page = StyledPaths {
+                style: Style::new(stroke, size / 10.0),
+                paths,
+            };
+            let page_options = SvgOptions {
+                background,
+                ..SvgOptions::new(w, h)
+            };
+            write_atomic(format!("{}.index.svg", name), |f| {
+                svg::write(vec![page], &page_options, f)
+            })?;
+        }
+    }
 
-    let left_chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([Constraint::Percentage(10), Constraint::Percentage(90)].as_ref())
-        .split(hchunks[0]);
+    options.caption = state.param::<bool>(State::CAPTION_OPTION).then(|| {
+        let projection = state.projection.as_ref();
+        map_caption(title, &layers, projection, w.min(h) / 60.0, stroke)
+    });
 
-    let right_chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)].as_ref())
-        .split(hchunks[1]);
+    if let Some(page) = state.param::<Optional<PageSize>>(State::PDF_PAGE_OPTION).0 {
+        let (layers, _) = process(state, styled.clone(), PDF_TARGET);
+        let pdf_options = PdfOptions {
+            margin: state.param::<Length>(State::PDF_MARGIN_OPTION).to_mm(),
+            title: Some(title.to_string()),
+            ..PdfOptions::new(page, options.clone())
+        };
+        write_atomic(format!("{}.pdf", name), |f| {
+            pdf::write(layers, &pdf_options, f)
+        })?;
+    }
 
-    let city_input = Paragraph::new(state.user_city.as_ref())
-        .block(block(WidgetId::Search, "Search"))
-        .wrap(Wrap { trim: true });
+    if let Some(units) = state
+        .param::<Optional<LengthUnit>>(State::DXF_UNITS_OPTION)
+        .0
+    {
+        let (layers, _) = process(state, styled.clone(), DXF_TARGET);
+        let dxf_options = DxfOptions::new(units, options.clone());
+        write_atomic(format!("{}.dxf", name), |f| {
+            dxf::write(layers, &dxf_options, f)
+        })?;
+    }
 
-    let symbol = if worker_busy {
-        state.fetching_spinner.pattern().to_string() + " "
-    } else {
-        "> ".to_string()
-    };
+    if let Some(area) = state
+        .param::<Optional<PageSize>>(State::GCODE_AREA_OPTION)
+        .0
+    {
+        let (layers, _) = process(state, styled, GCODE_TARGET);
+        let gcode_options = GcodeOptions {
+            feed_rate: *state.param::<f64>(State::FEED_RATE_OPTION),
+            pen_up: state.param::<String>(State::PEN_UP_OPTION).clone(),
+            pen_down: state.param::<String>(State::PEN_DOWN_OPTION).clone(),
+            pen_delay: *state.param::<f64>(State::PEN_DELAY_OPTION),
+            ..GcodeOptions::new(area, options.clone())
+        };
+        write_atomic(format!("{}.gcode", name), |f| {
+            gcode::write(layers, &gcode_options, f)
+        })?;
+    }
 
-    let found_entries = list(
-        WidgetId::Places,
-        "Places",
-        &symbol,
-        state
-            .places
-            .iter()
-            .map(|e| {
-                ListItem::new(Spans::from(vec![
-                    Span::raw(e.display_name.clone()),
-                    Span::raw(" "),
-                    Span::styled(
-                        format!("({} - {})", e.osm_id, e.osm_type),
-                        Style::default().add_modifier(Modifier::ITALIC),
-                    ),
-                ]))
-            })
-            .collect::<Vec<_>>(),
-    );
+    write_atomic(&path, |f| svg::write(layers, &options, f))?;
 
-    let max_option_key_len = state.max_option_key_len();
-    let options = list(
-        WidgetId::Options,
-        "Options",
-        "* ",
-        state
-            .params
-            .iter()
-            .map(|(k, v)| {
-                let mut s = k.to_string();
-                s += ": ";
-                for _ in 0..max_option_key_len - k.len() {
-                    s.push(' ');
-                }
-                s += &v.to_string();
+    let hook = state.param::<String>(State::POST_EXPORT_OPTION);
+    if !hook.trim().is_empty() {
+        let command = hook_command(hook, &path, name);
+        let hook_error = Arc::clone(&state.hook_error);
+        state.hook_task = Some(tokio::task::spawn(async move {
+            if let Err(e) = run_hook(&command).await {
+                *hook_error.lock().unwrap() = Some(e);
+            }
+        }));
+    }
 
-                ListItem::new(s)
-            })
-            .collect(),
-    );
+    let open_on_save = *state.param::<bool>(State::OPEN_OPTION);
+    if open_on_save {
+        opener::open(&path)?;
+    }
 
-    let help = Paragraph::new(
-        r#"Simple TUI to render the roads of a given place into an svg file.
+    Ok(())
+}
 
-To start off, search a place by editing the Search line edit, hit enter and select the desired place to render.
+/// The given post-export command with `{file}` replaced by the path of the svg and `{name}` by
+/// the path without extension, which the sidecar files share. The paths are never searched for
+/// placeholders themselves.
+fn hook_command(template: &str, file: &str, name: &str) -> String {
+    let mut command = String::new();
+    let mut rest = template;
+    while let Some(i) = rest.find('{') {
+        command += &rest[..i];
+        rest = &rest[i..];
+        if let Some(r) = rest.strip_prefix("{file}") {
+            command += &shell_quote(file);
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix("{name}") {
+            command += &shell_quote(name);
+            rest = r;
+        } else {
+            command.push('{');
+            rest = &rest[1..];
+        }
+    }
+    command + rest
+}
 
-Use the arrow keys or jk to move up and down and <TAB> to switch section.
+/// Run the given shell command. Its output is captured so that it doesn't mess the TUI up and
+/// only shown if it fails.
+async fn run_hook(command: &str) -> anyhow::Result<()> {
+    use std::process::Stdio;
+    use tokio::process::Command;
 
-Hit <Enter> on an option to edit it.
+    let mut shell = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C");
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c");
+        cmd
+    };
+    let output = shell
+        .arg(command)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .with_context(|| format!("cannot run the post-export command '{}'", command))?;
 
-Esc or Ctrl-C to quit.
-"#,
-    )
-    .block(block(WidgetId::Help, "Help"))
-    .wrap(Wrap { trim: true });
+    if !output.status.success() {
+        anyhow::bail!(
+            "the post-export command '{}' failed with {}: {}",
+            command,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
 
-    f.render_widget(city_input, left_chunks[0]);
-    f.render_stateful_widget(found_entries, left_chunks[1], state.places.state());
+    Ok(())
+}
 
-    if state.focus == WidgetId::Options {
-        f.render_stateful_widget(options, right_chunks[0], state.params.state());
+/// The given argument quoted so that the shell passes it as is.
+fn shell_quote(arg: &str) -> String {
+    if cfg!(windows) {
+        format!("\"{}\"", arg)
     } else {
-        f.render_widget(options, right_chunks[0]);
+        format!("'{}'", arg.replace('\'', "'\\''"))
     }
-    f.render_widget(help, right_chunks[1]);
-
-    if state.focus == WidgetId::ParamEdit {
-        let edit_state = state.parm_edit_state.as_ref().unwrap();
+}
 
-        if let Some((param, _)) = state.params.selected() {
-            let parm_edit = Paragraph::new(edit_state.buffer.as_ref())
-                .block(block(WidgetId::ParamEdit, param))
-                .wrap(Wrap { trim: true })
-                .style(if edit_state.is_valid {
-                    Style::default()
-                } else {
-                    Style::default().bg(Color::LightRed)
-                });
+/// The title of the map followed by the coordinates of its center.
+fn map_caption(
+    title: &str,
+    layers: &[StyledPaths],
+    projection: &dyn Projection,
+    size: f64,
+    color: Color,
+) -> Caption {
+    let mut lines = vec![title.to_string()];
 
-            let hcentered = Layout::default()
-                .direction(Direction::Horizontal)
-                .constraints([
-                    Constraint::Percentage(30),
-                    Constraint::Percentage(40),
-                    Constraint::Percentage(30),
-                ])
-                .split(f.size());
-            let vcentered = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints([
-                    Constraint::Percentage(30),
-                    Constraint::Max(3),
-                    Constraint::Percentage(30),
-                ])
-                .split(hcentered[1]);
+    if let Some((min, max)) =
+        roads::viewport::bounds(layers.iter().flat_map(|l| &l.paths).flatten())
+    {
+        let (lat, lon) = projection.unproject(((min.0 + max.0) / 2.0, (min.1 + max.1) / 2.0));
+        lines.push(format!(
+            "{:.4}° {}, {:.4}° {}",
+            lat.abs(),
+            if lat < 0.0 { 'S' } else { 'N' },
+            lon.abs(),
+            if lon < 0.0 { 'W' } else { 'E' },
+        ));
+    }
 
-            f.render_widget(Clear, vcentered[1]);
-            f.render_widget(parm_edit, vcentered[1]);
-        }
+    Caption {
+        color,
+        ..Caption::new(lines, size)
     }
 }
 
-async fn handle_key_event(
-    code: KeyCode,
-    state: &mut State,
-    state_m: &Arc<Mutex<State>>,
-) -> anyhow::Result<()> {
-    if state.worker_busy() {
-        return Ok(());
+/// Move the fetched roads, which are in Web Mercator, to the given projection centered on them.
+fn project_roads(kind: ProjectionKind, mut roads: Vec<Road>) -> (Box<dyn Projection>, Vec<Road>) {
+    let center = roads::viewport::bounds(roads.iter().flat_map(|r| &r.points))
+        .map(|(min, max)| roads::to_lat_lon(((min.0 + max.0) / 2.0, (min.1 + max.1) / 2.0)));
+
+    let projection = match (kind, center) {
+        (ProjectionKind::Mercator, _) | (_, None) => return (Box::new(WebMercator), roads),
+        (kind, Some((lat, lon))) => kind.centered(lat, lon),
+    };
+
+    for r in &mut roads {
+        for p in r.points.iter_mut().chain(r.holes.iter_mut().flatten()) {
+            *p = reproject(*p, &WebMercator, projection.as_ref());
+        }
     }
 
-    match state.focus {
-        WidgetId::Search => match code {
-            KeyCode::Enter => {
-                if !state.user_city.is_empty() {
-                    let user_city = state.user_city.clone();
+    (projection, roads)
+}
 
-                    state.fetch(
-                        Arc::clone(state_m),
-                        async move { roads::search(&user_city).await.map_err(anyhow::Error::msg) },
-                        |state, cities| {
-                            state.places = WrappingList::new(cities);
-                            state.focus = WidgetId::Places;
-                            Ok(())
-                        },
-                    );
-                }
-            }
-            code => {
-                if edit_string(&mut state.user_city, code) {
-                    state.places = WrappingList::new(vec![]);
-                }
-            }
-        },
-        WidgetId::Places => match code {
-            KeyCode::Up | KeyCode::Char('k') => {
-                state.places.up();
-            }
-            KeyCode::Down | KeyCode::Char('j') => {
-                state.places.down();
-            }
-            KeyCode::Enter => {
-                if let Some(place) = state.places.selected() {
-                    let place: NominatimEntry = place.clone();
+/// Clip the roads to the boundary of the place if requested, dropping the boundary itself unless
+/// it has to be drawn.
+fn clip_roads(state: &State, roads: Vec<Road>) -> Vec<Road> {
+    let (boundary, mut roads): (Vec<_>, Vec<_>) = roads
+        .into_iter()
+        .partition(|r| r.tags.contains_key(BOUNDARY_TAG));
 
-                    state.fetch(
-                        Arc::clone(state_m),
-                        async move { roads::fetch_roads(&place).await.map_err(anyhow::Error::msg) },
-                        move |state, paths| {
-                            let w = *state.param::<f64>(State::WIDTH_OPTION);
-                            let h = *state.param::<f64>(State::HEIGHT_OPTION);
-                            let sw = *state.param::<f64>(State::STROKE_WIDTH_OPTION);
-                            let background = state.param::<String>(State::BACKGROUND_COLOR);
-
-                            let path = format!("{}.svg", &state.user_city);
-                            dump_svg(&path, (w, h), sw, background, paths)?;
-
-                            let open_on_save = *state.param::<bool>(State::OPEN_OPTION);
-                            if open_on_save {
-                                opener::open(&path)?;
-                            }
+    if *state.param::<bool>(State::CLIP_OPTION) && !boundary.is_empty() {
+        let clip = Clip::new(
+            boundary
+                .iter()
+                .flat_map(|b| std::iter::once(&b.points).chain(&b.holes))
+                .cloned()
+                .collect(),
+        );
 
-                            Ok(())
-                        },
-                    );
-                }
-            }
-            _ => {}
-        },
-        WidgetId::Options => match code {
-            KeyCode::Up | KeyCode::Char('k') => {
-                state.params.up();
-            }
-            KeyCode::Down | KeyCode::Char('j') => {
-                state.params.down();
-            }
-            KeyCode::Enter => {
-                if let Some((_param, value)) = state.params.selected() {
-                    state.parm_edit_state = Some(ParmEditState::new(value.cloned()));
-                    state.focus = WidgetId::ParamEdit;
+        roads = roads
+            .into_iter()
+            .flat_map(|r| {
+                // areas fully inside are kept as they are, the others become open paths
+                if r.points.iter().all(|p| clip.contains(*p)) {
+                    return vec![r];
                 }
-            }
-            _ => {}
-        },
-        WidgetId::ParamEdit => match code {
-            KeyCode::Enter => {
-                if state.parm_edit_state.as_ref().unwrap().is_valid {
-                    let mut edit_state = None;
-                    std::mem::swap(&mut edit_state, &mut state.parm_edit_state);
 
-                    state.set_current_param(edit_state.unwrap().value);
-                    state.focus = WidgetId::Options;
-                }
-            }
-            KeyCode::Esc => {
-                state.parm_edit_state = None;
-                state.focus = WidgetId::Options;
-            }
-            _ => {
-                let edit_state = state.parm_edit_state.as_mut().unwrap();
-                edit_string(&mut edit_state.buffer, code);
+                clip.clip_path(&r.points)
+                    .into_iter()
+                    .map(|points| Road {
+                        points,
+                        holes: vec![],
+                        ..r.clone()
+                    })
+                    .collect()
+            })
+            .collect();
+    }
 
-                edit_state.is_valid = edit_state.value.parse_from(&edit_state.buffer);
-            }
-        },
-        WidgetId::Help => {}
-        WidgetId::Error => {
-            if code == KeyCode::Enter {
-                state.worker_state = WorkerState::Idle;
-                state.focus = WidgetId::Search;
-            }
-        }
+    if *state.param::<bool>(State::BOUNDARY_OPTION) {
+        roads.extend(boundary);
     }
 
-    Ok(())
+    roads
 }
 
-fn edit_string(s: &mut String, code: KeyCode) -> bool {
-    match code {
-        KeyCode::Backspace => {
-            s.pop();
-            true
-        }
-        KeyCode::Char(c) => {
-            s.push(c);
-            true
+/// The styles of the ways of the streets to highlight, each street labeled with its rank.
+fn street_highlights(state: &State, roads: &[Road], road: &Style) -> HashMap<i64, Style> {
+    let highlight = *state.param::<StreetHighlight>(State::HIGHLIGHT_OPTION);
+    if highlight.is_none() {
+        return HashMap::new();
+    }
+
+    let streets = roads::analysis::rank(roads::analysis::streets(roads), highlight);
+
+    let mut styles = HashMap::new();
+    for (i, street) in streets.iter().enumerate() {
+        let style = Style {
+            color: *state.param::<Color>(State::HIGHLIGHT_COLOR_OPTION),
+            width: road.width * 3.0,
+            layer: 5,
+            label: Some(format!("{}. {}", i + 1, street)),
+            ..road.clone()
+        };
+
+        for id in &street.ways {
+            styles.insert(*id, style.clone());
         }
-        _ => false,
     }
+
+    styles
+}
+
+/// The styles of the roads colored by the neighborhood they fall in, by way id.
+fn neighborhood_roads(
+    state: &State,
+    roads: &[Road],
+    neighborhoods: &Neighborhoods,
+    road: &Style,
+) -> HashMap<i64, Style> {
+    let table = state.param::<roads::render::Style>(State::ROAD_STYLE_OPTION);
+
+    roads
+        .iter()
+        .filter(|r| r.tags.contains_key("highway"))
+        .filter_map(|r| {
+            let i = neighborhoods.locate_path(&r.points)?;
+            let base = match highway_class(r) {
+                Some(c) if table.classes.contains_key(&c) => table.class_style(c, road),
+                _ => road.clone(),
+            };
+            let style = Style {
+                color: Neighborhoods::color(i),
+                ..base
+            };
+            Some((r.id, style))
+        })
+        .collect()
 }
 
-fn dump_svg(
-    path: &str,
-    (w, h): (f64, f64),
-    stroke_width: f64,
-    background_color: &str,
-    mut paths: Vec<Vec<(f64, f64)>>,
-) -> io::Result<()> {
-    use std::f64::{INFINITY, NEG_INFINITY};
+/// The styles of the different kinds of features before the user rules are applied.
+struct LayerStyles {
+    road: Style,
+    waterway: Style,
+    railway: Style,
+    building: Style,
+    boundary: Style,
+
+    /// Styles of the filled and of the hatched sea.
+    sea: Style,
+    sea_hatch: Style,
+
+    /// Style of the closed ways mapped as areas, like squares.
+    area: Style,
 
-    let mut min_x = INFINITY;
-    let mut min_y = INFINITY;
-    let mut max_x = NEG_INFINITY;
-    let mut max_y = NEG_INFINITY;
+    /// Styles of the ways added and removed since the snapshot compared with.
+    added: Style,
+    removed: Style,
 
-    for p in &mut paths {
-        *p = roads::simplify::simplify(p);
+    /// Styles of the roads by highway class, the ones not listed use the road style.
+    classes: BTreeMap<HighwayClass, Style>,
 
-        for (x, y) in p {
-            *y *= -1.0;
+    /// Styles of the ways of the highlighted streets, by way id.
+    highlights: HashMap<i64, Style>,
 
-            min_x = x.min(min_x);
-            min_y = y.min(min_y);
-            max_x = x.max(max_x);
-            max_y = y.max(max_y);
+    /// Styles of the roads colored by neighborhood, by way id.
+    neighborhood_roads: HashMap<i64, Style>,
+
+    /// Styles of the neighborhood areas, by neighborhood index.
+    neighborhood_areas: Vec<Style>,
+
+    /// Style of the streets isolated by name, if any.
+    named: Option<(NamePattern, Style)>,
+}
+
+impl LayerStyles {
+    /// The sea, waterways, buildings and areas are drawn below the roads, railways and the
+    /// boundary above them.
+    fn base(&self, r: &Road) -> &Style {
+        let is_named = |(pattern, _): &&(NamePattern, Style)| {
+            r.tags.contains_key("highway")
+                && r.tags.get("name").is_some_and(|n| pattern.is_match(n))
+        };
+
+        if let Some(kind) = r.tags.get(DIFF_TAG) {
+            if kind == "added" {
+                &self.added
+            } else {
+                &self.removed
+            }
+        } else if let Some(s) = self.highlights.get(&r.id) {
+            s
+        } else if let Some((_, s)) = self.named.as_ref().filter(is_named) {
+            s
+        } else if let Some(s) = r
+            .tags
+            .get(NEIGHBORHOOD_TAG)
+            .and_then(|i| self.neighborhood_areas.get(i.parse::<usize>().ok()?))
+        {
+            s
+        } else if let Some(s) = self.neighborhood_roads.get(&r.id) {
+            s
+        } else if let Some(kind) = r.tags.get(SEA_TAG) {
+            if kind == "hatch" {
+                &self.sea_hatch
+            } else {
+                &self.sea
+            }
+        } else if r.tags.contains_key(BOUNDARY_TAG) {
+            &self.boundary
+        } else if r.tags.contains_key("waterway") {
+            &self.waterway
+        } else if r.tags.contains_key("railway") {
+            &self.railway
+        } else if r.tags.contains_key("building") && r.is_closed() {
+            &self.building
+        } else if r.is_area() {
+            &self.area
+        } else if let Some(s) = highway_class(r).and_then(|c| self.classes.get(&c)) {
+            s
+        } else {
+            &self.road
         }
     }
+}
 
-    if min_x > max_x || min_y > max_y {
-        return Ok(());
-    }
+fn highway_class(r: &Road) -> Option<HighwayClass> {
+    HighwayClass::from_tag(r.tags.get("highway")?)
+}
+
+impl Chapter {
+    fn of(r: &Road) -> Self {
+        let is_water = r.tags.contains_key(SEA_TAG)
+            || r.tags.contains_key("waterway")
+            || r.tags.get("natural").is_some_and(|v| v == "water");
 
-    let sf = f64::min(w / (max_x - min_x), h / (max_y - min_y));
+        if is_water {
+            Chapter::Water
+        } else if r.tags.contains_key(BOUNDARY_TAG) {
+            Chapter::Boundary
+        } else if r.tags.contains_key("railway") {
+            Chapter::Railways
+        } else if r.tags.contains_key(NEIGHBORHOOD_TAG)
+            || r.is_area()
+            || r.is_closed() && r.tags.contains_key("building")
+        {
+            Chapter::Areas
+        } else if let Some(c) = highway_class(r) {
+            Chapter::Roads(Reverse(c))
+        } else {
+            Chapter::Other
+        }
+    }
+}
 
-    let f = fs::File::create(path)?;
-    let mut f = io::BufWriter::new(f);
+fn style_roads(
+    state: &State,
+    roads: Vec<Road>,
+    styles: &LayerStyles,
+) -> anyhow::Result<Vec<StyledPaths>> {
+    let base = |r: &Road| styles.base(r);
 
-    writeln!(
-        f,
-        r#"<?xml version="1.0" encoding="UTF-8"?>
-<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {w:.2} {h:.2}">
-<rect x="0" y="0" width="{w:.2}" height="{h:.2}" fill="{background}" stroke="none"/>
-<g stroke="black" stroke-width="{}" fill="none" >"#,
-        stroke_width,
-        w = (max_x - min_x) * sf,
-        h = (max_y - min_y) * sf,
-        background = background_color,
-    )?;
+    let rules = match state.style_rules()? {
+        None => StyleRules::default(),
+        Some(rules) => rules.parse()?,
+    };
 
-    for p in paths {
-        write!(f, r#"<polyline points=""#)?;
-        for (x, y) in p {
-            write!(f, "{:.2},{:.2} ", (x - min_x) * sf, (y - min_y) * sf)?;
+    #[cfg(feature = "scripting")]
+    {
+        let script = state.param::<String>(State::STYLE_SCRIPT_OPTION);
+        if !script.is_empty() {
+            let script = roads::script::StyleScript::from_file(script)?;
+            return Ok(roads::style::try_group_by_style(roads, |r| {
+                match rules.style(r, base(r)) {
+                    None => Ok(None),
+                    Some(s) => script.style(r, &s),
+                }
+            })?);
         }
-        writeln!(f, r#"" />"#)?;
     }
 
-    writeln!(f, "</g>\n</svg>")?;
-
-    Ok(())
+    Ok(group_by_style(roads, |r| rules.style(r, base(r))))
 }
@@ -0,0 +1,81 @@
+//! Crop marks and registration crosses drawn outside the map frame, so that prints can be trimmed
+//! and multi-pass plots can be aligned.
+
+/// Where and how big the marks are, in canvas units.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PrintMarks {
+    /// Gap between the frame and the marks.
+    pub bleed: f64,
+
+    /// Length of the crop marks and size of the registration crosses.
+    pub length: f64,
+}
+
+impl PrintMarks {
+    pub fn new(bleed: f64) -> Self {
+        PrintMarks {
+            bleed,
+            length: 20.0,
+        }
+    }
+
+    /// Space needed around the frame to fit the marks.
+    pub fn margin(&self) -> f64 {
+        self.bleed + self.length
+    }
+
+    /// The crop marks at the corners of the frame with the given min corner and size, as pairs
+    /// of segments continuing the edges of the frame.
+    pub fn crop_marks(&self, (x, y): (f64, f64), (w, h): (f64, f64)) -> Vec<Vec<(f64, f64)>> {
+        let (b, l) = (self.bleed, self.length);
+        let mut marks = vec![];
+
+        for cx in [x, x + w] {
+            for cy in [y, y + h] {
+                // which way is outside the frame
+                let sx = if cx == x { -1.0 } else { 1.0 };
+                let sy = if cy == y { -1.0 } else { 1.0 };
+
+                marks.push(vec![(cx + sx * b, cy), (cx + sx * (b + l), cy)]);
+                marks.push(vec![(cx, cy + sy * b), (cx, cy + sy * (b + l))]);
+            }
+        }
+
+        marks
+    }
+
+    /// Registration crosses in the middle of each side of the frame with the given min corner and
+    /// size, each made of a cross and a circle.
+    pub fn registration_marks(
+        &self,
+        (x, y): (f64, f64),
+        (w, h): (f64, f64),
+    ) -> Vec<Vec<(f64, f64)>> {
+        let d = self.bleed + self.length / 2.0;
+        let centers = [
+            (x + w / 2.0, y - d),
+            (x + w / 2.0, y + h + d),
+            (x - d, y + h / 2.0),
+            (x + w + d, y + h / 2.0),
+        ];
+
+        let r = self.length / 2.0;
+        let mut marks = vec![];
+        for (cx, cy) in centers {
+            marks.push(vec![(cx - r, cy), (cx + r, cy)]);
+            marks.push(vec![(cx, cy - r), (cx, cy + r)]);
+            marks.push(circle((cx, cy), r * 0.6, 32));
+        }
+
+        marks
+    }
+}
+
+fn circle((cx, cy): (f64, f64), r: f64, segments: usize) -> Vec<(f64, f64)> {
+    (0..=segments)
+        .map(|i| {
+            let a = std::f64::consts::TAU * i as f64 / segments as f64;
+            (cx + r * a.cos(), cy + r * a.sin())
+        })
+        .collect()
+}
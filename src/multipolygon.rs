@@ -0,0 +1,172 @@
+//! Assembly of [multipolygon relations] from their member ways.
+//!
+//! [multipolygon relations]: https://wiki.openstreetmap.org/wiki/Relation:multipolygon
+
+/// A closed ring with zero or more holes.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Polygon {
+    pub outer: Vec<(f64, f64)>,
+    pub holes: Vec<Vec<(f64, f64)>>,
+}
+
+/// Join the given paths into as few paths as possible by chaining together the ones that share
+/// an endpoint, reversing them if needed.
+pub fn join_paths(mut paths: Vec<Vec<(f64, f64)>>) -> Vec<Vec<(f64, f64)>> {
+    let mut joined = vec![];
+
+    paths.retain(|p| !p.is_empty());
+    paths.reverse();
+
+    while let Some(mut path) = paths.pop() {
+        while path.len() < 2 || path[0] != path[path.len() - 1] {
+            let last = path[path.len() - 1];
+
+            let next = paths
+                .iter()
+                .position(|p| p[0] == last || p[p.len() - 1] == last);
+
+            let mut next = match next {
+                None => break,
+                Some(i) => paths.remove(i),
+            };
+
+            if next[0] != last {
+                next.reverse();
+            }
+            path.extend_from_slice(&next[1..]);
+        }
+
+        joined.push(path);
+    }
+
+    joined
+}
+
+/// Assemble the outer and inner ways of a multipolygon into polygons.
+///
+/// Rings that cannot be closed are kept open, inner rings that are not inside any outer ring are
+/// dropped.
+pub fn assemble(outers: Vec<Vec<(f64, f64)>>, inners: Vec<Vec<(f64, f64)>>) -> Vec<Polygon> {
+    let mut polygons = join_paths(outers)
+        .into_iter()
+        .map(|outer| Polygon {
+            outer,
+            holes: vec![],
+        })
+        .collect::<Vec<_>>();
+
+    for inner in join_paths(inners) {
+        let polygon = polygons.iter_mut().find(|p| contains(&p.outer, inner[0]));
+
+        if let Some(p) = polygon {
+            p.holes.push(inner);
+        }
+    }
+
+    polygons
+}
+
+/// Whether the given point is inside the given ring using the even-odd rule.
+pub fn contains(ring: &[(f64, f64)], (x, y): (f64, f64)) -> bool {
+    let mut inside = false;
+
+    for (i, &(x0, y0)) in ring.iter().enumerate() {
+        let (x1, y1) = ring[(i + 1) % ring.len()];
+
+        if (y0 > y) != (y1 > y) && x < (x1 - x0) * (y - y0) / (y1 - y0) + x0 {
+            inside = !inside;
+        }
+    }
+
+    inside
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outers_split_across_ways() {
+        let polygons = assemble(
+            vec![
+                vec![(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)],
+                vec![(0.0, 10.0), (0.0, 0.0)],
+                vec![(10.0, 10.0), (0.0, 10.0)],
+            ],
+            vec![],
+        );
+
+        assert_eq!(
+            polygons,
+            vec![Polygon {
+                outer: vec![
+                    (0.0, 0.0),
+                    (10.0, 0.0),
+                    (10.0, 10.0),
+                    (0.0, 10.0),
+                    (0.0, 0.0)
+                ],
+                holes: vec![],
+            }]
+        );
+    }
+
+    #[test]
+    fn reversed_segments() {
+        let polygons = assemble(
+            vec![
+                vec![(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)],
+                vec![(0.0, 0.0), (0.0, 10.0), (10.0, 10.0)],
+            ],
+            vec![],
+        );
+
+        assert_eq!(polygons.len(), 1);
+        assert_eq!(
+            polygons[0].outer,
+            vec![
+                (0.0, 0.0),
+                (10.0, 0.0),
+                (10.0, 10.0),
+                (0.0, 10.0),
+                (0.0, 0.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn inners_go_in_their_outer() {
+        let square = |x: f64, y: f64, size: f64| {
+            vec![
+                (x, y),
+                (x + size, y),
+                (x + size, y + size),
+                (x, y + size),
+                (x, y),
+            ]
+        };
+
+        let polygons = assemble(
+            vec![square(0.0, 0.0, 10.0), square(20.0, 0.0, 10.0)],
+            vec![
+                square(22.0, 2.0, 2.0),
+                // split across two ways like the outers can be
+                vec![(2.0, 2.0), (4.0, 2.0), (4.0, 4.0)],
+                vec![(4.0, 4.0), (2.0, 4.0), (2.0, 2.0)],
+                // outside of every outer
+                square(50.0, 50.0, 1.0),
+            ],
+        );
+
+        assert_eq!(polygons.len(), 2);
+        assert_eq!(polygons[0].holes, vec![square(2.0, 2.0, 2.0)]);
+        assert_eq!(polygons[1].holes, vec![square(22.0, 2.0, 2.0)]);
+    }
+
+    #[test]
+    fn unclosed_rings_stay_open() {
+        let polygons = assemble(vec![vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)]], vec![]);
+        assert_eq!(polygons[0].outer, vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)]);
+    }
+}
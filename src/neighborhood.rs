@@ -0,0 +1,137 @@
+//! Assignment of the roads to the neighborhoods of a place, so that each neighborhood can be told
+//! apart by color.
+
+use crate::{clip::Clip, color::Color, Road};
+
+/// Overpass selector of the administrative boundaries below the city level, i.e. the
+/// neighborhoods, quarters and suburbs mapped with `admin_level` 9 or 10.
+pub const NEIGHBORHOODS_SELECTOR: &str =
+    r#"relation[boundary=administrative][admin_level~"^(9|10)$"]"#;
+
+/// Distinct colors given to the neighborhoods in turn.
+pub const PALETTE: [Color; 8] = [
+    Color::Rgb(31, 119, 180),
+    Color::Rgb(255, 127, 14),
+    Color::Rgb(44, 160, 44),
+    Color::Rgb(214, 39, 40),
+    Color::Rgb(148, 103, 189),
+    Color::Rgb(140, 86, 75),
+    Color::Rgb(227, 119, 194),
+    Color::Rgb(23, 190, 207),
+];
+
+/// The neighborhoods of a place, sorted by name.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Neighborhoods {
+    areas: Vec<Neighborhood>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Neighborhood {
+    /// Id of the boundary relation.
+    pub id: i64,
+    pub name: Option<String>,
+
+    /// 9 or 10, the higher the smaller.
+    pub admin_level: u8,
+
+    area: Clip,
+}
+
+impl Neighborhoods {
+    /// Collect the neighborhoods among the given elements, merging the polygons of the same
+    /// boundary relation.
+    pub fn new(boundaries: &[Road]) -> Self {
+        let mut areas: Vec<Neighborhood> = vec![];
+        let mut rings: Vec<Vec<Vec<(f64, f64)>>> = vec![];
+
+        for b in boundaries {
+            let admin_level = match admin_level(b) {
+                None => continue,
+                Some(l) => l,
+            };
+
+            let polygon = std::iter::once(&b.points).chain(&b.holes).cloned();
+            match areas.iter().position(|a| a.id == b.id) {
+                Some(i) => rings[i].extend(polygon),
+                None => {
+                    areas.push(Neighborhood {
+                        id: b.id,
+                        name: b.tags.get("name").cloned(),
+                        admin_level,
+                        area: Clip::new(vec![]),
+                    });
+                    rings.push(polygon.collect());
+                }
+            }
+        }
+
+        for (a, rings) in areas.iter_mut().zip(rings) {
+            a.area = Clip::new(rings);
+        }
+        areas.sort_by(|a, b| (&a.name, a.id).cmp(&(&b.name, b.id)));
+
+        Neighborhoods { areas }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.areas.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.areas.len()
+    }
+
+    pub fn get(&self, i: usize) -> Option<&Neighborhood> {
+        self.areas.get(i)
+    }
+
+    /// The index of the neighborhood of the given boundary relation.
+    pub fn position(&self, id: i64) -> Option<usize> {
+        self.areas.iter().position(|a| a.id == id)
+    }
+
+    /// The index of the neighborhood containing the given point, the smallest one if they're
+    /// nested.
+    pub fn locate(&self, p: (f64, f64)) -> Option<usize> {
+        self.areas
+            .iter()
+            .enumerate()
+            .filter(|(_, a)| a.area.contains(p))
+            .max_by_key(|(_, a)| a.admin_level)
+            .map(|(i, _)| i)
+    }
+
+    /// The index of the neighborhood the given path falls in, judging by its middle point.
+    pub fn locate_path(&self, path: &[(f64, f64)]) -> Option<usize> {
+        self.locate(*path.get(path.len() / 2)?)
+    }
+
+    /// The color of the neighborhood with the given index.
+    pub fn color(i: usize) -> Color {
+        PALETTE[i % PALETTE.len()]
+    }
+
+    /// A light tint of the color of the neighborhood with the given index, to fill its area
+    /// without hiding the roads on top.
+    pub fn tint(i: usize) -> Color {
+        let (r, g, b) = Self::color(i)
+            .rgb()
+            .expect("the palette has no transparent colors");
+        let tint = |c: u8| 255 - (255 - c) / 4;
+        Color::Rgb(tint(r), tint(g), tint(b))
+    }
+}
+
+/// The admin level of the given element if it's a neighborhood boundary.
+pub fn admin_level(r: &Road) -> Option<u8> {
+    if r.tags.get("boundary").map(String::as_str) != Some("administrative") {
+        return None;
+    }
+
+    r.tags
+        .get("admin_level")?
+        .parse()
+        .ok()
+        .filter(|l| (9..=10).contains(l))
+}
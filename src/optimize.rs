@@ -0,0 +1,377 @@
+//! Joining and ordering of the paths for pen plotters, where the time spent moving with the pen
+//! up between paths often dominates the time spent drawing.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::pipeline::PathTransform;
+
+/// How many following paths 2-opt considers for each path, it keeps the passes roughly linear.
+const TWO_OPT_WINDOW: usize = 64;
+
+/// Maximum number of 2-opt passes, each one improving the whole order.
+const TWO_OPT_PASSES: usize = 8;
+
+/// [`PathTransform`] that reorders the paths to shorten the travel between the end of a path and
+/// the start of the next one.
+///
+/// The order is built greedily by always moving to the nearest path left and then optionally
+/// refined with 2-opt, which needs paths to be reversible.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OptimizeOrder {
+    /// Whether paths can be drawn backwards, from their last point to their first one.
+    pub reverse: bool,
+
+    /// Whether to refine the greedy order, it's only done if paths can be reversed.
+    pub two_opt: bool,
+}
+
+impl OptimizeOrder {
+    pub fn new(reverse: bool, two_opt: bool) -> Self {
+        OptimizeOrder { reverse, two_opt }
+    }
+
+    /// The given paths reordered, and possibly reversed, starting from the first one.
+    pub fn order(&self, paths: Vec<Vec<(f64, f64)>>) -> Vec<Vec<(f64, f64)>> {
+        let mut paths = nearest_neighbor(paths, self.reverse);
+        if self.two_opt && self.reverse {
+            two_opt(&mut paths);
+        }
+        paths
+    }
+}
+
+impl PathTransform for OptimizeOrder {
+    fn apply(&self, paths: Vec<Vec<(f64, f64)>>) -> Vec<Vec<(f64, f64)>> {
+        self.order(paths)
+    }
+}
+
+/// [`PathTransform`] that chains the paths sharing an endpoint into a single longer path, like
+/// the many ways a road is split into by OpenStreetMap.
+///
+/// Endpoints must match exactly, which they do for the ways sharing a node. At junctions paths
+/// are chained in the order they come. Closed rings are kept whole, and so are single points.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct JoinPaths {
+    /// Whether paths can be reversed to be joined, e.g. two ways both starting at the same node.
+    pub reverse: bool,
+}
+
+impl JoinPaths {
+    pub fn new(reverse: bool) -> Self {
+        JoinPaths { reverse }
+    }
+
+    /// The given paths joined, in the order of the first path of each chain.
+    pub fn join(&self, paths: Vec<Vec<(f64, f64)>>) -> Vec<Vec<(f64, f64)>> {
+        let is_open = |p: &Vec<(f64, f64)>| p.len() > 1 && p.first() != p.last();
+
+        let mut starts: HashMap<_, Vec<usize>> = HashMap::new();
+        let mut ends: HashMap<_, Vec<usize>> = HashMap::new();
+        for (i, p) in paths.iter().enumerate().filter(|(_, p)| is_open(p)) {
+            starts.entry(key(p[0])).or_default().push(i);
+            ends.entry(key(p[p.len() - 1])).or_default().push(i);
+        }
+
+        let mut used = vec![false; paths.len()];
+
+        let mut joined = Vec::with_capacity(paths.len());
+        for i in 0..paths.len() {
+            if !is_open(&paths[i]) {
+                joined.push(paths[i].clone());
+                continue;
+            }
+            if used[i] {
+                continue;
+            }
+            used[i] = true;
+
+            let mut chain = paths[i].iter().copied().collect::<VecDeque<_>>();
+            let is_closed = |c: &VecDeque<(f64, f64)>| c.front() == c.back();
+
+            // forwards from the end of the chain...
+            while !is_closed(&chain) {
+                let tail = key(*chain.back().unwrap());
+                if let Some(j) = take(&mut used, starts.get(&tail)) {
+                    chain.extend(&paths[j][1..]);
+                } else if let Some(j) = take(&mut used, ends.get(&tail).filter(|_| self.reverse)) {
+                    chain.extend(paths[j].iter().rev().skip(1));
+                } else {
+                    break;
+                }
+            }
+
+            // ...and backwards from its start
+            while !is_closed(&chain) {
+                let head = key(*chain.front().unwrap());
+                if let Some(j) = take(&mut used, ends.get(&head)) {
+                    for &p in paths[j].iter().rev().skip(1) {
+                        chain.push_front(p);
+                    }
+                } else if let Some(j) = take(&mut used, starts.get(&head).filter(|_| self.reverse))
+                {
+                    for &p in &paths[j][1..] {
+                        chain.push_front(p);
+                    }
+                } else {
+                    break;
+                }
+            }
+
+            joined.push(chain.into());
+        }
+
+        joined
+    }
+}
+
+impl PathTransform for JoinPaths {
+    fn apply(&self, paths: Vec<Vec<(f64, f64)>>) -> Vec<Vec<(f64, f64)>> {
+        self.join(paths)
+    }
+}
+
+/// Total distance travelled with the pen up to draw the given paths in order.
+pub fn travel(paths: &[Vec<(f64, f64)>]) -> f64 {
+    paths
+        .windows(2)
+        .filter_map(|w| Some(dist(*w[0].last()?, *w[1].first()?)))
+        .sum()
+}
+
+fn nearest_neighbor(paths: Vec<Vec<(f64, f64)>>, reverse: bool) -> Vec<Vec<(f64, f64)>> {
+    let (mut empty, paths): (Vec<_>, Vec<_>) = paths.into_iter().partition(|p| p.is_empty());
+    if paths.len() < 3 {
+        empty.extend(paths);
+        return empty;
+    }
+
+    let grid = Grid::new(&paths, reverse);
+    let mut used = vec![false; paths.len()];
+    let mut paths = paths.into_iter().map(Some).collect::<Vec<_>>();
+
+    let mut ordered = Vec::with_capacity(paths.len());
+    let mut next = Some((0, false));
+    while let Some((i, reversed)) = next {
+        used[i] = true;
+        let mut p = paths[i].take().expect("every path is visited once");
+        if reversed {
+            p.reverse();
+        }
+
+        next = grid.nearest(*p.last().unwrap(), &used);
+        ordered.push(p);
+    }
+
+    ordered.extend(empty);
+    ordered
+}
+
+/// Reverse runs of paths whenever that shortens the travel between them, considering only the
+/// runs up to [`TWO_OPT_WINDOW`] paths long.
+fn two_opt(paths: &mut [Vec<(f64, f64)>]) {
+    let start = |p: &Vec<(f64, f64)>| *p.first().unwrap();
+    let end = |p: &Vec<(f64, f64)>| *p.last().unwrap();
+    if paths.iter().any(|p| p.is_empty()) {
+        return;
+    }
+
+    for _ in 0..TWO_OPT_PASSES {
+        let mut improved = false;
+
+        for i in 1..paths.len() {
+            for j in i + 1..paths.len().min(i + TWO_OPT_WINDOW) {
+                let before = end(&paths[i - 1]);
+                let after = paths.get(j + 1).map(start);
+
+                // drawing the run i..=j backwards swaps the ends it's connected by
+                let old =
+                    dist(before, start(&paths[i])) + after.map_or(0.0, |a| dist(end(&paths[j]), a));
+                let new =
+                    dist(before, end(&paths[j])) + after.map_or(0.0, |a| dist(start(&paths[i]), a));
+
+                if new + 1e-9 < old {
+                    paths[i..=j].reverse();
+                    for p in &mut paths[i..=j] {
+                        p.reverse();
+                    }
+                    improved = true;
+                }
+            }
+        }
+
+        if !improved {
+            break;
+        }
+    }
+}
+
+/// Uniform grid of the endpoints of the paths, to find the nearest one without looking at all of
+/// them.
+struct Grid {
+    cell: f64,
+    min: (f64, f64),
+    size: (i64, i64),
+
+    cells: HashMap<(i64, i64), Vec<Endpoint>>,
+}
+
+/// The index of a path, whether it has to be reversed to start from the endpoint and the endpoint.
+type Endpoint = (usize, bool, (f64, f64));
+
+impl Grid {
+    fn new(paths: &[Vec<(f64, f64)>], reverse: bool) -> Self {
+        let endpoints = paths
+            .iter()
+            .enumerate()
+            .flat_map(|(i, p)| {
+                let first = Some((i, false, p[0]));
+                let last = (reverse && p.len() > 1).then(|| (i, true, p[p.len() - 1]));
+                first.into_iter().chain(last)
+            })
+            .collect::<Vec<_>>();
+
+        let (mut min, mut max) = (
+            (f64::INFINITY, f64::INFINITY),
+            (f64::NEG_INFINITY, f64::NEG_INFINITY),
+        );
+        for (_, _, (x, y)) in &endpoints {
+            min = (min.0.min(*x), min.1.min(*y));
+            max = (max.0.max(*x), max.1.max(*y));
+        }
+
+        // about one endpoint per cell along the longest side, so that the cells don't shrink to
+        // nothing when the endpoints are lined up
+        let extent = (max.0 - min.0).max(max.1 - min.1);
+        let cell = (extent / (endpoints.len() as f64).sqrt()).max(1e-9);
+
+        let mut grid = Grid {
+            cell,
+            min,
+            size: (
+                ((max.0 - min.0) / cell) as i64 + 1,
+                ((max.1 - min.1) / cell) as i64 + 1,
+            ),
+            cells: HashMap::new(),
+        };
+        for e in endpoints {
+            grid.cells.entry(grid.cell_of(e.2)).or_default().push(e);
+        }
+
+        grid
+    }
+
+    fn cell_of(&self, (x, y): (f64, f64)) -> (i64, i64) {
+        (
+            ((x - self.min.0) / self.cell).floor() as i64,
+            ((y - self.min.1) / self.cell).floor() as i64,
+        )
+    }
+
+    /// The unused path with the endpoint nearest to the given point, searching the rings of cells
+    /// around it until no nearer endpoint can be found.
+    ///
+    /// Points outside of the grid, like the last points of the paths when they can't be reversed,
+    /// are searched from the nearest cell, since no ring beyond the grid holds anything.
+    fn nearest(&self, p: (f64, f64), used: &[bool]) -> Option<(usize, bool)> {
+        let (cx, cy) = self.cell_of(p);
+        let center = (cx.clamp(0, self.size.0 - 1), cy.clamp(0, self.size.1 - 1));
+        let max_ring = self.size.0.max(self.size.1) - 1;
+
+        let mut best: Option<(f64, usize, bool)> = None;
+        let visit = |best: &mut Option<(f64, usize, bool)>, &(i, reversed, e): &Endpoint| {
+            if used[i] {
+                return;
+            }
+
+            let d = dist(p, e);
+            if best.is_none_or(|(b, _, _)| d < b) {
+                *best = Some((d, i, reversed));
+            }
+        };
+
+        for r in 0..=max_ring {
+            if let Some((d, _, _)) = best {
+                // every endpoint in this ring or farther is at least this far, moving the center
+                // inside the grid only brings the cells closer
+                if d <= (r - 1) as f64 * self.cell {
+                    break;
+                }
+            }
+
+            for c in self.ring(center, r) {
+                for e in self.cells.get(&c).into_iter().flatten() {
+                    visit(&mut best, e);
+                }
+            }
+        }
+
+        // the rings cover the whole grid, but the cells of non finite coordinates can't be told
+        if best.is_none() {
+            for e in self.cells.values().flatten() {
+                visit(&mut best, e);
+            }
+        }
+
+        best.map(|(_, i, reversed)| (i, reversed))
+    }
+
+    /// The cells of the grid at the given Chebyshev distance from the center.
+    fn ring(&self, (cx, cy): (i64, i64), r: i64) -> impl Iterator<Item = (i64, i64)> {
+        let (w, h) = self.size;
+        ((cx - r).max(0)..=(cx + r).min(w - 1)).flat_map(move |x| {
+            ((cy - r).max(0)..=(cy + r).min(h - 1))
+                .filter(move |y| (x - cx).abs() == r || (y - cy).abs() == r)
+                .map(move |y| (x, y))
+        })
+    }
+}
+
+/// The first of the given paths that's not used yet, marking it as used.
+fn take(used: &mut [bool], candidates: Option<&Vec<usize>>) -> Option<usize> {
+    let i = *candidates?.iter().find(|&&i| !used[i])?;
+    used[i] = true;
+    Some(i)
+}
+
+/// Hashable key of a point, `0.0` and `-0.0` are the same point.
+fn key((x, y): (f64, f64)) -> (u64, u64) {
+    ((x + 0.0).to_bits(), (y + 0.0).to_bits())
+}
+
+fn dist(a: (f64, f64), b: (f64, f64)) -> f64 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn order_keeps_every_path() {
+        // the last points are far from every first point, so they fall outside of the grid
+        let paths = (0..20)
+            .map(|i| vec![(i as f64, 0.0), (i as f64 * 100.0, 1000.0)])
+            .collect::<Vec<_>>();
+
+        let ordered = OptimizeOrder::new(false, false).order(paths.clone());
+        assert_eq!(ordered.len(), paths.len());
+        for p in &paths {
+            assert!(ordered.contains(p));
+        }
+    }
+
+    #[test]
+    fn order_collinear_endpoints() {
+        let paths = (0..1000)
+            .map(|i| vec![(i as f64, 0.0), (i as f64 + 0.5, 0.0)])
+            .rev()
+            .collect::<Vec<_>>();
+
+        let ordered = OptimizeOrder::new(true, false).order(paths);
+        assert_eq!(ordered.len(), 1000);
+        assert!(travel(&ordered) < 1000.0);
+    }
+}
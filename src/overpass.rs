@@ -0,0 +1,466 @@
+//! Client for one or more [Overpass](https://wiki.openstreetmap.org/wiki/Overpass_API) instances.
+
+use std::{fmt, str::FromStr, sync::Arc, time::Duration};
+
+use futures::{StreamExt, TryStreamExt};
+
+mod query;
+mod tiles;
+
+pub use query::{
+    area_id, attic_query, combine_queries, set_marker, AtticDate, OutputMode, QueryBuilder,
+    QueryLimits, SearchArea, Selector, SET_TAG,
+};
+pub use tiles::{merge_tiles, tile_query, Bounds, Tiling};
+
+pub(crate) use query::set_setting;
+
+/// The fetches of the elements of a place and the queries behind them.
+pub use crate::{
+    around_roads_query, bbox_roads_query, boundary_query, buildings_query, count_query,
+    custom_query, elements_query, elements_query_around, elements_query_in_bbox, fetch_boundary,
+    fetch_boundary_with, fetch_buildings, fetch_buildings_with, fetch_custom, fetch_custom_with,
+    fetch_points, fetch_points_with, fetch_query, fetch_query_with, fetch_railways,
+    fetch_railways_with, fetch_roads, fetch_roads_around, fetch_roads_around_with,
+    fetch_roads_detailed, fetch_roads_for_osm_id, fetch_roads_for_osm_id_with, fetch_roads_in_bbox,
+    fetch_roads_in_bbox_with, fetch_roads_mercator, fetch_roads_with, fetch_tagged_roads,
+    fetch_tagged_roads_with, fetch_waterways, fetch_waterways_with, osm_id_roads_query,
+    railways_query, roads_query, skeleton_query, waterways_query, with_boundary, Fetched,
+};
+
+use crate::{
+    cache::Cache,
+    endpoints::{Auth, ClientConfig},
+    error::{check_status, decode, Error, Result, Service, Warning},
+    polite::{PoliteMode, Throttle},
+    progress::{Progress, ProgressObserver, Stage, TransferRate},
+    retry::{self, RetryPolicy},
+    GeoRoad, OverpassForm, OverpassResponse, Road,
+};
+
+/// How many elements a query matches, as counted by Overpass without sending their geometry.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ElementCount {
+    pub nodes: u64,
+    pub ways: u64,
+    pub relations: u64,
+}
+
+/// How the geometry of the elements is downloaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum FetchStrategy {
+    /// Along with each element, the simplest and the fastest.
+    #[default]
+    Geom,
+
+    /// As the ids of the nodes of the ways followed by the nodes, which Overpass can answer for
+    /// areas way bigger than with the geometry inline before running out of memory. The response
+    /// is bigger though.
+    Skeleton,
+}
+
+/// Runs Overpass queries against a list of instances, moving on to the next one when an instance
+/// is overloaded, i.e. it answers with `429 Too Many Requests`, `503 Service Unavailable` or
+/// `504 Gateway Timeout`, it cannot be reached at all or it drops the connection midway.
+///
+/// When all of them are overloaded the whole list is tried again according to the
+/// [`RetryPolicy`].
+#[derive(Debug, Clone)]
+pub struct OverpassClient {
+    urls: Vec<String>,
+    auth: Option<Auth>,
+    retry: RetryPolicy,
+    progress: ProgressObserver,
+    strategy: FetchStrategy,
+    limits: QueryLimits,
+    config: ClientConfig,
+    cache: Option<Cache>,
+    throttle: Option<(Arc<Throttle>, PoliteMode)>,
+    client: reqwest::Client,
+}
+
+impl OverpassClient {
+    /// A client trying the given interpreter urls in order.
+    pub fn new(urls: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        OverpassClient {
+            urls: urls.into_iter().map(Into::into).collect(),
+            auth: None,
+            retry: RetryPolicy::default(),
+            progress: ProgressObserver::none(),
+            strategy: FetchStrategy::Geom,
+            limits: QueryLimits::default(),
+            config: ClientConfig::default(),
+            cache: None,
+            throttle: None,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// The main public instance along with its well known mirrors.
+    pub fn public() -> Self {
+        crate::Endpoints::public().overpass_client()
+    }
+
+    /// Credentials of the first instance, they are never sent to the others since those are
+    /// usually public mirrors.
+    pub fn with_auth(mut self, auth: Auth) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    pub fn with_client(mut self, config: ClientConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Use the given cache, `None` disables caching. Only complete results are cached.
+    pub fn with_cache(mut self, cache: Option<Cache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Report how the downloads and the parsing of their responses are going to the given
+    /// observer.
+    pub fn with_progress(mut self, progress: ProgressObserver) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Download the geometry of the queries ending in `out geom;` as configured by the given
+    /// strategy.
+    pub fn with_strategy(mut self, strategy: FetchStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Run every query with the given server side limits, whatever the query itself asks for.
+    pub fn with_limits(mut self, limits: QueryLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Pace the queries sent to the servers as the given mode asks, sharing the given throttle
+    /// with the other clients. The answers taken from the cache are not paced.
+    pub fn with_throttle(mut self, throttle: Arc<Throttle>, mode: PoliteMode) -> Self {
+        self.throttle = Some((throttle, mode));
+        self
+    }
+
+    pub fn urls(&self) -> &[String] {
+        &self.urls
+    }
+
+    /// Run the given query on the first instance that is able to answer it.
+    ///
+    /// The error of the last instance is returned if none of them succeeds, e.g.
+    /// [`Error::RateLimited`] if they're all still overloaded after the retries, and
+    /// [`Error::Empty`] is returned if the query matched nothing at all.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the client has no urls.
+    pub async fn fetch_query(&self, query: &str) -> Result<Fetched<Vec<Road>>> {
+        Ok(project(self.fetch_geo_query(query).await?))
+    }
+
+    /// Like [`OverpassClient::fetch_query`], but a query matching nothing is not an error, as
+    /// for the layers a place may simply not have, like railways.
+    pub async fn fetch_elements(&self, query: &str) -> Result<Fetched<Vec<Road>>> {
+        Ok(project(self.fetch_geometry(query).await?))
+    }
+
+    /// Like [`OverpassClient::fetch_query`], but keep the coordinates as Overpass sent them.
+    pub async fn fetch_geo_query(&self, query: &str) -> Result<Fetched<Vec<GeoRoad>>> {
+        let roads = self.fetch_geometry(query).await?;
+        // elements of the named sets, like the boundary, come along with those of the query
+        if is_empty(&roads) {
+            return Err(Error::empty(query));
+        }
+
+        Ok(roads)
+    }
+
+    /// Like [`OverpassClient::fetch_elements`], but split the given bounding box in the tiles of
+    /// the given grid and run the query in each of them, for areas too big to fetch at once.
+    ///
+    /// The elements crossing more than one tile are returned only once, see [`merge_tiles`]. The
+    /// query fails as soon as any of its tiles does.
+    pub async fn fetch_tiled(
+        &self,
+        query: &str,
+        bounds: Bounds,
+        tiling: Tiling,
+    ) -> Result<Fetched<Vec<Road>>> {
+        let tiles = futures::stream::iter(tiling.tiles(bounds))
+            .map(|tile| async move { self.fetch_elements(&tile_query(query, tile)).await })
+            .buffered(tiling.concurrency)
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        Ok(merge_tiles(tiles))
+    }
+
+    /// The index of the last of the given queries, sorted by how many ways they match, whose
+    /// ways fit in the given budget, bisecting them by counting their ways. It's 0, the
+    /// fallback, when none fits or there are no queries.
+    pub async fn fit_budget(&self, max_ways: u64, queries: &[String]) -> Result<usize> {
+        let fits = |i: usize| async move {
+            let count = self.count(&queries[i]).await;
+            count.map(|c| c.ways <= max_ways)
+        };
+
+        let mut hi = match queries.len().checked_sub(1) {
+            None => return Ok(0),
+            Some(last) => last,
+        };
+        if fits(hi).await? {
+            return Ok(hi);
+        }
+
+        // the query at lo fits, or it's the fallback, while the one at hi doesn't
+        let mut lo = 0;
+        while hi - lo > 1 {
+            let mid = (lo + hi) / 2;
+            if fits(mid).await? {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        Ok(lo)
+    }
+
+    /// Count the elements the given query would fetch, running it with `out count` instead of
+    /// `out geom`. Much cheaper than fetching them, handy to estimate the size of a download.
+    pub async fn count(&self, query: &str) -> Result<ElementCount> {
+        let counted = self.run(&crate::count_query(query)).await?;
+
+        // every output set, like the boundary, is counted on its own
+        let mut count = ElementCount::default();
+        for c in &counted.value {
+            let get = |k: &str| c.tags.get(k).and_then(|n| n.parse().ok()).unwrap_or(0);
+            count.nodes += get("nodes");
+            count.ways += get("ways");
+            count.relations += get("relations");
+        }
+        Ok(count)
+    }
+
+    async fn fetch_geometry(&self, query: &str) -> Result<Fetched<Vec<GeoRoad>>> {
+        self.run(&self.strategy.query(query)).await
+    }
+
+    async fn run(&self, query: &str) -> Result<Fetched<Vec<GeoRoad>>> {
+        // the limits don't change the result, unless it fails, while each server has its own data
+        let key = format!("overpass {}\n{}", self.urls.join(" "), query);
+        let cached = self.cache.as_ref().and_then(|c| c.get(&key));
+        if let Some(Ok(roads)) = cached.map(|body| parse(&body, &self.progress)) {
+            return Ok(roads);
+        }
+
+        let query = &self.limits.apply(query);
+
+        let mut attempt = 0;
+
+        loop {
+            let mut last_err = None;
+            let mut retry_after: Option<Duration> = None;
+
+            for (i, url) in self.urls.iter().enumerate() {
+                // failovers and retries are requests too, paced and counted like the first one
+                if let Some((throttle, mode)) = &self.throttle {
+                    throttle.acquire(mode).await?;
+                }
+
+                let auth = self.auth.as_ref().filter(|_| i == 0);
+                let e = match self.send(url, auth, query).await {
+                    Ok(r) if retry::is_overloaded(r.status()) => {
+                        let after = retry::retry_after(&r);
+                        retry_after = retry_after.max(after);
+                        Error::RateLimited {
+                            service: Service::Overpass,
+                            retry_after: after,
+                        }
+                    }
+                    // a dropped connection is likely to be a hiccup of that instance
+                    Ok(r) => match self.receive(&key, r).await {
+                        Err(e @ Error::Truncated { .. }) => e,
+                        res => return res,
+                    },
+                    Err(e) if retry::is_unreachable(&e) => {
+                        Error::from_reqwest(Service::Overpass, e)
+                    }
+                    Err(e) => return Err(Error::from_reqwest(Service::Overpass, e)),
+                };
+
+                last_err = Some(e);
+            }
+
+            let last_err = last_err.expect("no Overpass instance to query");
+            if attempt >= self.retry.max_retries {
+                return Err(last_err);
+            }
+
+            attempt += 1;
+            self.retry
+                .wait(attempt, retry_after, last_err.to_string())
+                .await;
+        }
+    }
+
+    /// Parse the response, caching it if it's complete.
+    async fn receive(&self, key: &str, r: reqwest::Response) -> Result<Fetched<Vec<GeoRoad>>> {
+        let mut r = check_status(Service::Overpass, r).await?;
+
+        let total = r.content_length();
+        let mut body = Vec::with_capacity(total.unwrap_or(0) as usize);
+        self.progress.report(Stage::Download, 0, total);
+        let truncated = |received: usize| Error::Truncated {
+            service: Service::Overpass,
+            received: received as u64,
+            expected: total,
+        };
+        let mut rate = TransferRate::new();
+        while let Some(chunk) = r.chunk().await.map_err(|_| truncated(body.len()))? {
+            body.extend_from_slice(&chunk);
+            let done = body.len() as u64;
+            self.progress.report_progress(Progress {
+                stage: Stage::Download,
+                done,
+                total,
+                rate: rate.update(done),
+            });
+        }
+        if total.is_some_and(|t| (body.len() as u64) < t) {
+            return Err(truncated(body.len()));
+        }
+
+        let roads = parse(&body, &self.progress)?;
+        if let (Some(cache), true) = (&self.cache, roads.is_complete()) {
+            let _ = cache.put(key, &body);
+        }
+
+        Ok(roads)
+    }
+
+    async fn send(
+        &self,
+        url: &str,
+        auth: Option<&Auth>,
+        query: &str,
+    ) -> reqwest::Result<reqwest::Response> {
+        crate::endpoints::request(self.client.post(url), &self.config, auth)
+            .form(&OverpassForm {
+                data: query.to_string(),
+            })
+            .header(reqwest::header::CONTENT_TYPE, "application/osm3s+xml")
+            .send()
+            .await
+    }
+}
+
+/// Whether the given result has no elements of the query, the elements of the named sets, like
+/// the boundary, come along with them.
+fn is_empty(roads: &Fetched<Vec<GeoRoad>>) -> bool {
+    roads.value.iter().all(|r| r.tags.contains_key(SET_TAG))
+}
+
+/// The given roads in [`WebMercator`](crate::projection::WebMercator), like all the fetched ones.
+fn project(roads: Fetched<Vec<GeoRoad>>) -> Fetched<Vec<Road>> {
+    roads.map(|roads| roads.into_iter().map(Road::from).collect())
+}
+
+/// Decode the body of a response, failing if the query failed on the server without returning anything and
+/// warning if only part of the elements were returned.
+fn parse(body: &[u8], progress: &ProgressObserver) -> Result<Fetched<Vec<GeoRoad>>> {
+    let mut r: OverpassResponse = decode(Service::Overpass, body)?;
+    r.resolve_skeleton();
+    let total = r.elements.len() as u64;
+
+    let mut warnings = vec![];
+    if let Some(remark) = r.remark {
+        if r.elements.is_empty() && remark.contains("runtime error") {
+            return Err(Error::from_remark(remark));
+        }
+
+        warnings.push(Warning::Partial { remark });
+    }
+
+    let mut set = None;
+    let mut roads = vec![];
+    for (e, done) in r.elements.into_iter().zip(1..) {
+        progress.report_every(Stage::Parse, done, total);
+
+        if e.kind == query::SET_MARKER {
+            set = e.tags.get("name").cloned();
+            continue;
+        }
+
+        let start = roads.len();
+        roads.extend(e.into_roads());
+        if let Some(set) = &set {
+            for r in &mut roads[start..] {
+                r.tags.insert(SET_TAG.to_string(), set.clone());
+            }
+        }
+    }
+
+    Ok(Fetched {
+        value: roads,
+        warnings,
+    })
+}
+
+impl FetchStrategy {
+    /// The given query as it's sent with this strategy.
+    pub fn query(&self, query: &str) -> String {
+        match self {
+            FetchStrategy::Geom => query.to_string(),
+            FetchStrategy::Skeleton => crate::skeleton_query(query),
+        }
+    }
+}
+
+impl FromStr for FetchStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim() {
+            "geom" => Ok(FetchStrategy::Geom),
+            "skeleton" => Ok(FetchStrategy::Skeleton),
+            _ => Err(format!(
+                "invalid fetch strategy '{}', expected geom or skeleton",
+                s.trim()
+            )),
+        }
+    }
+}
+
+impl fmt::Display for FetchStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FetchStrategy::Geom => "geom",
+            FetchStrategy::Skeleton => "skeleton",
+        })
+    }
+}
+
+impl ElementCount {
+    pub fn total(&self) -> u64 {
+        self.nodes + self.ways + self.relations
+    }
+
+    /// Rough size in bytes of the `out geom` json of the counted elements, for when the server
+    /// doesn't say how big the response is. Ways carry the coordinates of a dozen nodes on
+    /// average, relations those of all their members.
+    pub fn estimated_bytes(&self) -> u64 {
+        self.nodes * 150 + self.ways * 1_000 + self.relations * 20_000
+    }
+}
@@ -0,0 +1,653 @@
+//! Construction of [Overpass QL](https://wiki.openstreetmap.org/wiki/Overpass_API/Overpass_QL)
+//! queries.
+
+use std::{fmt, str::FromStr};
+
+use crate::filter::quote;
+
+/// Builder of the queries outputting the json of the matched elements.
+///
+/// Statements are searched in the area or the bounding box of the query, if any, and they're put
+/// in a union when there's more than one.
+///
+/// The statements after [`QueryBuilder::output_set`] are output apart, after a marker element
+/// naming their set, so that a single request can fetch elements meant for different uses. The
+/// [parser](super::OverpassClient::fetch_query) tags them with [`SET_TAG`].
+///
+/// ```
+/// use roads::overpass::{QueryBuilder, Selector};
+///
+/// let query = QueryBuilder::new()
+///     .bbox(45.4, 9.1, 45.5, 9.2)
+///     .select(Selector::way().has("highway").not_eq("access", "private"))
+///     .build();
+///
+/// assert_eq!(
+///     query,
+///     r#"[out:json][timeout:60][bbox:45.4,9.1,45.5,9.2];
+/// way["highway"]["access"!="private"];
+/// out geom;"#
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryBuilder {
+    timeout: u32,
+    maxsize: Option<u64>,
+    search: SearchArea,
+    sets: Vec<OutputSet>,
+    output: OutputMode,
+}
+
+/// Tag added to the elements of a named output set, whose value is the name of the set.
+pub const SET_TAG: &str = "roads:set";
+
+/// Type of the elements marking the start of an output set.
+pub(crate) const SET_MARKER: &str = "set";
+
+#[derive(Debug, Clone, PartialEq)]
+struct OutputSet {
+    /// `None` for the main set.
+    name: Option<String>,
+    statements: Vec<Statement>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Statement {
+    text: String,
+
+    /// Whether the statement is searched in the area of the query.
+    in_area: bool,
+}
+
+/// Where the statements of a query look for elements.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum SearchArea {
+    /// The whole planet, i.e. the statements select the elements by id or are bounded on their
+    /// own.
+    Everywhere,
+
+    /// The bounding box given by south, west, north and east.
+    Bbox(f64, f64, f64, f64),
+
+    /// The Overpass area with the given id, see [`area_id`].
+    Area(i64),
+
+    /// The circle with the given center and radius in meters.
+    Around { lat: f64, lon: f64, radius: f64 },
+}
+
+/// How much time and memory Overpass may spend on the queries, overriding the settings of the
+/// queries themselves. Big cities need several minutes and more than the default memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct QueryLimits {
+    /// Seconds, Overpass defaults to 180 while the queries built by this crate ask for 60.
+    pub timeout: Option<u32>,
+
+    /// Bytes, Overpass defaults to 512 MiB.
+    pub maxsize: Option<u64>,
+}
+
+/// A moment in the past to run the queries at, with the attic data of Overpass, written as
+/// `2015-01-01` or `2015-01-01T12:00:00Z`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AtticDate(String);
+
+/// What the query outputs for each element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum OutputMode {
+    /// The tags and the full geometry, inline.
+    #[default]
+    Geom,
+
+    /// Only how many elements matched, by type.
+    Count,
+
+    /// The tags and the ids of the nodes or members, followed by the coordinates of the nodes
+    /// without their tags. It takes much less memory on the server than [`OutputMode::Geom`].
+    Skeleton,
+}
+
+/// Tag filters on an element type, e.g. `way["highway"]["access"!="private"]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selector {
+    element: String,
+    filters: String,
+}
+
+impl QueryBuilder {
+    pub fn new() -> Self {
+        QueryBuilder {
+            timeout: 60,
+            maxsize: None,
+            search: SearchArea::Everywhere,
+            sets: vec![OutputSet {
+                name: None,
+                statements: vec![],
+            }],
+            output: OutputMode::Geom,
+        }
+    }
+
+    /// Give up after the given number of seconds on the server.
+    pub fn timeout(mut self, seconds: u32) -> Self {
+        self.timeout = seconds;
+        self
+    }
+
+    /// Give up once the query needs more than the given number of bytes of memory on the server,
+    /// which defaults to 512 MiB.
+    pub fn maxsize(mut self, bytes: u64) -> Self {
+        self.maxsize = Some(bytes);
+        self
+    }
+
+    pub fn search(mut self, search: SearchArea) -> Self {
+        self.search = search;
+        self
+    }
+
+    pub fn bbox(self, south: f64, west: f64, north: f64, east: f64) -> Self {
+        self.search(SearchArea::Bbox(south, west, north, east))
+    }
+
+    pub fn area(self, id: i64) -> Self {
+        self.search(SearchArea::Area(id))
+    }
+
+    pub fn around(self, lat: f64, lon: f64, radius: f64) -> Self {
+        self.search(SearchArea::Around { lat, lon, radius })
+    }
+
+    /// Also output the elements matching the given selector, e.g. `way[highway]` or a
+    /// [`Selector`].
+    pub fn select(self, selector: impl fmt::Display) -> Self {
+        self.statement(selector.to_string(), true)
+    }
+
+    /// Output the element with the given type and id, e.g. the relation of a city, wherever it
+    /// is.
+    pub fn element(self, osm_type: &str, id: i64) -> Self {
+        self.statement(format!("{}({})", osm_type, id), false)
+    }
+
+    /// Output the elements of the following statements apart, as the set with the given name.
+    pub fn output_set(mut self, name: impl Into<String>) -> Self {
+        self.sets.push(OutputSet {
+            name: Some(name.into()),
+            statements: vec![],
+        });
+        self
+    }
+
+    fn statement(mut self, text: String, in_area: bool) -> Self {
+        let set = self.sets.last_mut().expect("there's always the main set");
+        set.statements.push(Statement { text, in_area });
+        self
+    }
+
+    pub fn output(mut self, output: OutputMode) -> Self {
+        self.output = output;
+        self
+    }
+
+    pub fn build(&self) -> String {
+        let mut settings = format!("[out:json][timeout:{}]", self.timeout);
+        if let Some(maxsize) = self.maxsize {
+            settings += &format!("[maxsize:{}]", maxsize);
+        }
+        if let SearchArea::Bbox(s, w, n, e) = self.search {
+            settings += &format!("[bbox:{},{},{},{}]", s, w, n, e);
+        }
+
+        let mut query = settings + ";\n";
+        if let SearchArea::Area(id) = self.search {
+            query += &format!("area({})->.a;\n", id);
+        }
+
+        let sets = self
+            .sets
+            .iter()
+            .filter(|s| s.name.is_none() || !s.statements.is_empty())
+            .map(|s| {
+                let mut block = s.name.as_deref().map(set_marker).unwrap_or_default();
+                block += &self.statements(&s.statements);
+                block + self.output.to_string().as_str()
+            })
+            .collect::<Vec<_>>();
+
+        query + sets.join("\n").as_str()
+    }
+
+    /// The given statements, in a union if there's more than one.
+    fn statements(&self, statements: &[Statement]) -> String {
+        let scope = match self.search {
+            SearchArea::Area(_) => "(area.a)".to_string(),
+            SearchArea::Around { lat, lon, radius } => {
+                format!("(around:{},{},{})", radius, lat, lon)
+            }
+            SearchArea::Everywhere | SearchArea::Bbox(..) => String::new(),
+        };
+        let lines = statements
+            .iter()
+            .map(|s| {
+                if s.in_area {
+                    format!("{}{};", s.text, scope)
+                } else {
+                    format!("{};", s.text)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        if statements.len() > 1 {
+            format!("(\n{}\n);\n", lines)
+        } else {
+            lines + "\n"
+        }
+    }
+}
+
+impl QueryLimits {
+    /// The given query with these limits.
+    pub fn apply(&self, query: &str) -> String {
+        let mut query = query.to_string();
+        if let Some(timeout) = self.timeout {
+            query = set_setting(&query, "timeout", &timeout.to_string());
+        }
+        if let Some(maxsize) = self.maxsize {
+            query = set_setting(&query, "maxsize", &maxsize.to_string());
+        }
+        query
+    }
+}
+
+/// The given query run on the map as it was at the given date.
+pub fn attic_query(query: &str, date: &AtticDate) -> String {
+    set_setting(query, "date", &format!("\"{}\"", date.timestamp()))
+}
+
+impl AtticDate {
+    /// The date in the format Overpass wants, e.g. `2015-01-01T00:00:00Z`.
+    pub fn timestamp(&self) -> String {
+        if self.0.len() == 10 {
+            format!("{}T00:00:00Z", self.0)
+        } else {
+            self.0.clone()
+        }
+    }
+}
+
+impl FromStr for AtticDate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let invalid = || format!("invalid date '{}', expected e.g. 2015-01-01", s);
+
+        // digits everywhere but at the separators of YYYY-MM-DDThh:mm:ssZ
+        let pattern = "dddd-dd-ddTdd:dd:ddZ";
+        if s.len() != 10 && s.len() != pattern.len() {
+            return Err(invalid());
+        }
+        let matches = s.chars().zip(pattern.chars()).all(|(c, p)| match p {
+            'd' => c.is_ascii_digit(),
+            _ => c == p,
+        });
+        if !matches {
+            return Err(invalid());
+        }
+
+        Ok(AtticDate(s.to_string()))
+    }
+}
+
+impl fmt::Display for AtticDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// The given query with the given global setting, e.g. `[timeout:60]`, replacing its value if
+/// the query has it already.
+pub(crate) fn set_setting(query: &str, name: &str, value: &str) -> String {
+    let setting = format!("[{}:{}]", name, value);
+
+    let (settings, rest) = match query.split_once(';') {
+        Some((settings, rest)) if settings.trim_start().starts_with('[') => (settings, rest),
+        _ => return format!("{};\n{}", setting, query),
+    };
+
+    let settings = match settings.find(&format!("[{}:", name)) {
+        Some(start) => {
+            let end = settings[start..]
+                .find(']')
+                .map_or(settings.len(), |i| start + i + 1);
+            format!("{}{}{}", &settings[..start], setting, &settings[end..])
+        }
+        None => format!("{}{}", settings, setting),
+    };
+
+    format!("{};{}", settings, rest)
+}
+
+/// The given queries merged into as few requests as possible, e.g. so that the layers of a place
+/// come with a single one.
+///
+/// The queries sharing their global settings, i.e. not the ones at another date, are run one
+/// after the other in the same request, each outputting its elements as the output set named
+/// after its index among the given queries, see [`SET_TAG`]. Every request is returned along
+/// with the indices of its queries, a query alone is left as it is.
+pub fn combine_queries(queries: &[String]) -> Vec<(String, Vec<usize>)> {
+    /// The global settings of the given query and its statements.
+    fn split(q: &str) -> (&str, &str) {
+        match q.split_once(';') {
+            Some((settings, body)) if settings.trim_start().starts_with('[') => {
+                (settings.trim(), body.trim_start())
+            }
+            _ => ("", q),
+        }
+    }
+    let marker = format!("make {} ", SET_MARKER);
+
+    // the sets of a query of its own would be mixed up with the ones of the others
+    let mut groups: Vec<(Option<&str>, Vec<usize>)> = vec![];
+    for (i, q) in queries.iter().enumerate() {
+        let (settings, body) = split(q);
+        let key = (!body.contains(&marker)).then_some(settings);
+        match groups.iter_mut().find(|(k, _)| key.is_some() && *k == key) {
+            Some((_, group)) => group.push(i),
+            None => groups.push((key, vec![i])),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(key, group)| {
+            let query = match key.filter(|_| group.len() > 1) {
+                None => queries[group[0]].clone(),
+                Some(settings) => {
+                    let bodies = group
+                        .iter()
+                        .map(|&i| set_marker(&i.to_string()) + split(&queries[i]).1)
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    if settings.is_empty() {
+                        bodies
+                    } else {
+                        format!("{};\n{}", settings, bodies)
+                    }
+                }
+            };
+            (query, group)
+        })
+        .collect()
+}
+
+/// The statements outputting the marker of the output set with the given name.
+pub fn set_marker(name: &str) -> String {
+    format!(
+        "make {} name={};\nout;\n",
+        SET_MARKER,
+        crate::filter::quote(name)
+    )
+}
+
+impl fmt::Display for OutputMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OutputMode::Geom => "out geom;",
+            OutputMode::Count => "out count;",
+            OutputMode::Skeleton => "out body qt;\n>;\nout skel qt;",
+        })
+    }
+}
+
+impl Default for QueryBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Selector {
+    /// Select the elements of the given type, i.e. `node`, `way`, `relation` or `nwr`.
+    pub fn new(element: impl Into<String>) -> Self {
+        Selector {
+            element: element.into(),
+            filters: String::new(),
+        }
+    }
+
+    pub fn node() -> Self {
+        Self::new("node")
+    }
+
+    pub fn way() -> Self {
+        Self::new("way")
+    }
+
+    pub fn relation() -> Self {
+        Self::new("relation")
+    }
+
+    /// Only the elements with the given tag, whatever its value.
+    pub fn has(self, key: &str) -> Self {
+        self.filter(format!("[{}]", quote(key)))
+    }
+
+    /// Only the elements without the given tag.
+    pub fn not_has(self, key: &str) -> Self {
+        self.filter(format!("[!{}]", quote(key)))
+    }
+
+    pub fn eq(self, key: &str, value: &str) -> Self {
+        self.filter(format!("[{}={}]", quote(key), quote(value)))
+    }
+
+    /// Only the elements whose tag doesn't have the given value, including those without it.
+    pub fn not_eq(self, key: &str, value: &str) -> Self {
+        self.filter(format!("[{}!={}]", quote(key), quote(value)))
+    }
+
+    /// Only the elements whose tag matches the given regular expression.
+    pub fn matches(self, key: &str, regex: &str) -> Self {
+        self.filter(format!("[{}~{}]", quote(key), quote(regex)))
+    }
+
+    /// Append the given raw filter, e.g. `[name~"^via",i]`.
+    pub fn filter(mut self, filter: impl AsRef<str>) -> Self {
+        self.filters += filter.as_ref();
+        self
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.element, self.filters)
+    }
+}
+
+/// The id of the Overpass area derived from the given relation or way, `None` for other types.
+pub fn area_id(osm_type: &str, osm_id: i64) -> Option<i64> {
+    match osm_type {
+        "relation" => Some(3_600_000_000 + osm_id),
+        "way" => Some(2_400_000_000 + osm_id),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn combined_layers() {
+        let layer = |selector: &str| QueryBuilder::new().area(42).select(selector).build();
+        let date = "2015-01-01".parse::<AtticDate>().unwrap();
+        let queries = [
+            layer("way[highway]"),
+            layer("way[railway]"),
+            attic_query(&layer("way[highway]"), &date),
+        ];
+
+        let combined = combine_queries(&queries);
+        assert_eq!(combined.len(), 2);
+        assert_eq!(combined[0].1, vec![0, 1]);
+        assert_eq!(
+            combined[0].0,
+            "[out:json][timeout:60];\nmake set name=\"0\";\nout;\narea(42)->.a;\nway[highway](area.a);\nout geom;\nmake set name=\"1\";\nout;\narea(42)->.a;\nway[railway](area.a);\nout geom;"
+        );
+        assert_eq!(combined[1], (queries[2].clone(), vec![2]));
+    }
+
+    #[test]
+    fn single_statement_in_area() {
+        let query = QueryBuilder::new().area(3600044915).select("way[highway]");
+
+        assert_eq!(
+            query.build(),
+            "[out:json][timeout:60];\narea(3600044915)->.a;\nway[highway](area.a);\nout geom;"
+        );
+    }
+
+    #[test]
+    fn union_in_bbox() {
+        let query = QueryBuilder::new()
+            .bbox(1.0, 2.0, 3.5, 4.0)
+            .select("way[building]")
+            .select("relation[building][type=multipolygon]");
+
+        assert_eq!(
+            query.build(),
+            "[out:json][timeout:60][bbox:1,2,3.5,4];\n(\nway[building];\nrelation[building][type=multipolygon];\n);\nout geom;"
+        );
+    }
+
+    #[test]
+    fn union_in_area() {
+        let query = QueryBuilder::new()
+            .area(42)
+            .select("way[waterway]")
+            .select("way[railway]");
+
+        assert_eq!(
+            query.build(),
+            "[out:json][timeout:60];\narea(42)->.a;\n(\nway[waterway](area.a);\nway[railway](area.a);\n);\nout geom;"
+        );
+    }
+
+    #[test]
+    fn around_point() {
+        let query = QueryBuilder::new()
+            .around(45.07, 7.68, 1500.0)
+            .select("way[highway]")
+            .output_set("boundary")
+            .element("relation", 7);
+
+        assert_eq!(
+            query.build(),
+            "[out:json][timeout:60];\nway[highway](around:1500,45.07,7.68);\nout geom;\nmake set name=\"boundary\";\nout;\nrelation(7);\nout geom;"
+        );
+    }
+
+    #[test]
+    fn element_by_id() {
+        let query = QueryBuilder::new().element("relation", 44915);
+
+        assert_eq!(
+            query.build(),
+            "[out:json][timeout:60];\nrelation(44915);\nout geom;"
+        );
+    }
+
+    #[test]
+    fn settings_and_output() {
+        let query = QueryBuilder::new()
+            .timeout(180)
+            .select("node[amenity]")
+            .output(OutputMode::Count);
+
+        assert_eq!(
+            query.build(),
+            "[out:json][timeout:180];\nnode[amenity];\nout count;"
+        );
+    }
+
+    #[test]
+    fn limits_override_settings() {
+        let limits = QueryLimits {
+            timeout: Some(600),
+            maxsize: Some(1 << 30),
+        };
+
+        assert_eq!(
+            limits.apply("[out:json][timeout:60];\nway[highway];\nout geom;"),
+            "[out:json][timeout:600][maxsize:1073741824];\nway[highway];\nout geom;"
+        );
+        assert_eq!(
+            QueryLimits::default().apply("way[highway];\nout geom;"),
+            "way[highway];\nout geom;"
+        );
+    }
+
+    #[test]
+    fn query_in_the_past() {
+        let date = "2015-01-01".parse().unwrap();
+        assert_eq!(
+            attic_query("[out:json][timeout:60];\nway[highway];\nout geom;", &date),
+            "[out:json][timeout:60][date:\"2015-01-01T00:00:00Z\"];\nway[highway];\nout geom;"
+        );
+        assert!("2015-1-1".parse::<AtticDate>().is_err());
+        assert!("2015-01-01T12:00:00Z".parse::<AtticDate>().is_ok());
+    }
+
+    #[test]
+    fn skeleton_output() {
+        let query = QueryBuilder::new()
+            .area(42)
+            .select("way[highway]")
+            .output(OutputMode::Skeleton);
+
+        assert_eq!(
+            query.build(),
+            "[out:json][timeout:60];\narea(42)->.a;\nway[highway](area.a);\nout body qt;\n>;\nout skel qt;"
+        );
+    }
+
+    #[test]
+    fn output_sets() {
+        let query = QueryBuilder::new()
+            .area(42)
+            .select("way[highway]")
+            .output_set("boundary")
+            .element("relation", 7);
+
+        assert_eq!(
+            query.build(),
+            "[out:json][timeout:60];\narea(42)->.a;\nway[highway](area.a);\nout geom;\nmake set name=\"boundary\";\nout;\nrelation(7);\nout geom;"
+        );
+    }
+
+    #[test]
+    fn selector_filters() {
+        let selector = Selector::way()
+            .has("highway")
+            .not_has("area:highway")
+            .eq("oneway", "yes")
+            .not_eq("access", "private")
+            .matches("name", "^Via \"Roma\"$");
+
+        assert_eq!(
+            selector.to_string(),
+            r#"way["highway"][!"area:highway"]["oneway"="yes"]["access"!="private"]["name"~"^Via \"Roma\"$"]"#
+        );
+    }
+
+    #[test]
+    fn area_ids() {
+        assert_eq!(area_id("relation", 1), Some(3_600_000_001));
+        assert_eq!(area_id("way", 1), Some(2_400_000_001));
+        assert_eq!(area_id("node", 1), None);
+    }
+}
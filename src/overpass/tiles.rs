@@ -0,0 +1,215 @@
+//! Splitting of the queries of areas too big for a single Overpass request, like whole countries
+//! or metro areas, into a grid of smaller ones.
+
+use std::{collections::HashSet, fmt, str::FromStr};
+
+use super::query::set_setting;
+use crate::{Fetched, Road};
+
+/// A bounding box as south, west, north and east, like in the queries.
+pub type Bounds = (f64, f64, f64, f64);
+
+/// The grid splitting the bounding box of a query, along with how many of its tiles are fetched
+/// at the same time.
+///
+/// Written as `<rows>x<cols>`, optionally followed by `/<concurrency>`, e.g. `4x4/2`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tiling {
+    pub rows: u32,
+    pub cols: u32,
+
+    /// Maximum number of requests running at once, the public instances allow a couple per user.
+    pub concurrency: usize,
+}
+
+impl Tiling {
+    pub const DEFAULT_CONCURRENCY: usize = 2;
+
+    pub fn new(rows: u32, cols: u32) -> Self {
+        Tiling {
+            rows: rows.max(1),
+            cols: cols.max(1),
+            concurrency: Self::DEFAULT_CONCURRENCY,
+        }
+    }
+
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// The number of tiles, never zero.
+    pub fn tile_count(&self) -> usize {
+        self.rows as usize * self.cols as usize
+    }
+
+    /// The tiles covering the given bounding box, row by row from the south west one.
+    pub fn tiles(&self, (s, w, n, e): Bounds) -> Vec<Bounds> {
+        let dlat = (n - s) / f64::from(self.rows);
+        let dlon = (e - w) / f64::from(self.cols);
+
+        // the last row and column end exactly on the box, leaving no gap to rounding
+        let lat = |i: u32| {
+            if i == self.rows {
+                n
+            } else {
+                s + dlat * f64::from(i)
+            }
+        };
+        let lon = |j: u32| {
+            if j == self.cols {
+                e
+            } else {
+                w + dlon * f64::from(j)
+            }
+        };
+
+        (0..self.rows)
+            .flat_map(|i| (0..self.cols).map(move |j| (lat(i), lon(j), lat(i + 1), lon(j + 1))))
+            .collect()
+    }
+}
+
+/// The given query restricted to the given tile, by setting its global bounding box.
+///
+/// The bounding box applies to all the statements of the query, including the ones searching
+/// an area, and it replaces the one of the query if any.
+pub fn tile_query(query: &str, (s, w, n, e): Bounds) -> String {
+    set_setting(query, "bbox", &format!("{},{},{},{}", s, w, n, e))
+}
+
+/// Merge the results of the tiles of a query, keeping only once the elements crossing more than
+/// one tile. The pieces of a relation found in the same tile are all kept.
+pub fn merge_tiles(tiles: impl IntoIterator<Item = Fetched<Vec<Road>>>) -> Fetched<Vec<Road>> {
+    let mut seen = HashSet::new();
+    let mut merged = Fetched::complete(vec![]);
+
+    for tile in tiles {
+        let keys = tile
+            .value
+            .iter()
+            .map(|r| (r.id, r.tags.clone()))
+            .collect::<Vec<_>>();
+
+        merged.value.extend(
+            tile.value
+                .into_iter()
+                .zip(&keys)
+                .filter(|(_, k)| !seen.contains(*k))
+                .map(|(r, _)| r),
+        );
+        for w in tile.warnings {
+            if !merged.warnings.contains(&w) {
+                merged.warnings.push(w);
+            }
+        }
+
+        seen.extend(keys);
+    }
+
+    merged
+}
+
+impl FromStr for Tiling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid tiling '{}', expected e.g. 4x4 or 4x4/2", s);
+
+        let (grid, concurrency) = match s.trim().split_once('/') {
+            Some((grid, c)) => (grid, Some(c.trim().parse().map_err(|_| invalid())?)),
+            None => (s.trim(), None),
+        };
+        let (rows, cols) = grid.split_once(['x', 'X']).ok_or_else(invalid)?;
+        let rows: u32 = rows.trim().parse().map_err(|_| invalid())?;
+        let cols: u32 = cols.trim().parse().map_err(|_| invalid())?;
+        if rows == 0 || cols == 0 || concurrency == Some(0) {
+            return Err(invalid());
+        }
+
+        let tiling = Tiling::new(rows, cols);
+        Ok(match concurrency {
+            Some(c) => tiling.with_concurrency(c),
+            None => tiling,
+        })
+    }
+}
+
+impl fmt::Display for Tiling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.rows, self.cols)?;
+        if self.concurrency != Self::DEFAULT_CONCURRENCY {
+            write!(f, "/{}", self.concurrency)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Warning;
+
+    #[test]
+    fn grid_covers_the_box() {
+        let tiles = Tiling::new(2, 3).tiles((0.0, 0.0, 1.0, 3.0));
+        assert_eq!(tiles.len(), 6);
+        assert_eq!(tiles[0], (0.0, 0.0, 0.5, 1.0));
+        assert_eq!(tiles[5], (0.5, 2.0, 1.0, 3.0));
+    }
+
+    #[test]
+    fn query_restricted_to_tile() {
+        assert_eq!(
+            tile_query(
+                "[out:json][timeout:60];\narea(42)->.a;\nway[highway](area.a);\nout geom;",
+                (1.0, 2.0, 3.0, 4.0)
+            ),
+            "[out:json][timeout:60][bbox:1,2,3,4];\narea(42)->.a;\nway[highway](area.a);\nout geom;"
+        );
+        assert_eq!(
+            tile_query(
+                "[out:json][bbox:0,0,9,9][timeout:60];\nway[highway];\nout geom;",
+                (1.0, 2.0, 3.0, 4.0)
+            ),
+            "[out:json][bbox:1,2,3,4][timeout:60];\nway[highway];\nout geom;"
+        );
+    }
+
+    #[test]
+    fn merged_once() {
+        let road = |id: i64, highway: &str| Road {
+            id,
+            tags: [("highway".to_string(), highway.to_string())].into(),
+            points: vec![(id as f64, 0.0)],
+            holes: vec![],
+        };
+        let partial = Warning::Partial {
+            remark: "runtime error".to_string(),
+        };
+
+        let merged = merge_tiles([
+            Fetched::complete(vec![road(1, "primary"), road(2, "residential")]),
+            Fetched {
+                value: vec![road(2, "residential"), road(3, "primary")],
+                warnings: vec![partial.clone()],
+            },
+            Fetched {
+                value: vec![road(1, "primary"), road(2, "service")],
+                warnings: vec![partial.clone()],
+            },
+        ]);
+
+        let ids = merged.value.iter().map(|r| r.id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![1, 2, 3, 2]);
+        assert_eq!(merged.warnings, vec![partial]);
+    }
+
+    #[test]
+    fn parse_tiling() {
+        assert_eq!("4x4".parse(), Ok(Tiling::new(4, 4)));
+        assert_eq!("2x3/1".parse(), Ok(Tiling::new(2, 3).with_concurrency(1)));
+        assert_eq!(Tiling::new(2, 3).with_concurrency(4).to_string(), "2x3/4");
+        assert!("0x4".parse::<Tiling>().is_err());
+    }
+}
@@ -0,0 +1,103 @@
+//! Geometry post-processing that happens between fetching the roads and exporting them.
+//!
+//! A [`Pipeline`] is an ordered list of stages, each one being either a [`PathFilter`] that
+//! decides whether a path survives or a [`PathTransform`] that rewrites the whole set of paths.
+
+use crate::{
+    progress::{ProgressObserver, Stage as ProgressStage},
+    style::StyledPaths,
+};
+
+/// A stage that drops the paths it doesn't want to keep.
+pub trait PathFilter: Send + Sync {
+    fn keep(&self, path: &[(f64, f64)]) -> bool;
+}
+
+/// A stage that rewrites the set of paths, possibly changing their number.
+pub trait PathTransform: Send + Sync {
+    fn apply(&self, paths: Vec<Vec<(f64, f64)>>) -> Vec<Vec<(f64, f64)>>;
+}
+
+impl<F: Fn(&[(f64, f64)]) -> bool + Send + Sync> PathFilter for F {
+    fn keep(&self, path: &[(f64, f64)]) -> bool {
+        self(path)
+    }
+}
+
+impl<F: Fn(Vec<Vec<(f64, f64)>>) -> Vec<Vec<(f64, f64)>> + Send + Sync> PathTransform for F {
+    fn apply(&self, paths: Vec<Vec<(f64, f64)>>) -> Vec<Vec<(f64, f64)>> {
+        self(paths)
+    }
+}
+
+enum Stage {
+    Filter(Box<dyn PathFilter>),
+    Transform(Box<dyn PathTransform>),
+}
+
+#[derive(Default)]
+pub struct Pipeline {
+    stages: Vec<Stage>,
+    progress: ProgressObserver,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn filter(mut self, f: impl PathFilter + 'static) -> Self {
+        self.register_filter(f);
+        self
+    }
+
+    pub fn transform(mut self, t: impl PathTransform + 'static) -> Self {
+        self.register_transform(t);
+        self
+    }
+
+    /// Report the paths processed by [`Pipeline::run_layers`] to the given observer.
+    pub fn with_progress(mut self, progress: ProgressObserver) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    pub fn register_filter(&mut self, f: impl PathFilter + 'static) {
+        self.stages.push(Stage::Filter(Box::new(f)));
+    }
+
+    pub fn register_transform(&mut self, t: impl PathTransform + 'static) {
+        self.stages.push(Stage::Transform(Box::new(t)));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    /// Run all the stages in registration order.
+    pub fn run(&self, mut paths: Vec<Vec<(f64, f64)>>) -> Vec<Vec<(f64, f64)>> {
+        for stage in &self.stages {
+            paths = match stage {
+                Stage::Filter(f) => paths.into_iter().filter(|p| f.keep(p)).collect(),
+                Stage::Transform(t) => t.apply(paths),
+            };
+        }
+
+        paths
+    }
+
+    /// Run the pipeline on each layer independently, so that paths never move across styles.
+    pub fn run_layers(&self, mut layers: Vec<StyledPaths>) -> Vec<StyledPaths> {
+        let total = layers.iter().map(|l| l.paths.len() as u64).sum();
+        let mut done = 0;
+
+        for l in &mut layers {
+            done += l.paths.len() as u64;
+            l.paths = self.run(std::mem::take(&mut l.paths));
+            self.progress
+                .report(ProgressStage::Process, done, Some(total));
+        }
+
+        layers
+    }
+}
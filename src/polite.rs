@@ -0,0 +1,106 @@
+//! Client side limits to avoid abusing the public OpenStreetMap servers, especially when running
+//! many requests unattended.
+
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::Mutex;
+
+/// The limits to enforce on the requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PoliteMode {
+    /// Maximum number of requests for the whole run, if any.
+    pub max_requests: Option<usize>,
+
+    /// Maximum number of requests per minute, if any.
+    pub per_minute: Option<u32>,
+}
+
+/// Keeps track of the requests issued so far and enforces the [`PoliteMode`] limits.
+#[derive(Debug, Default)]
+pub struct Throttle {
+    state: Mutex<ThrottleState>,
+}
+
+#[derive(Debug, Default)]
+struct ThrottleState {
+    issued: usize,
+    last: Option<Instant>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BudgetExceeded {
+    pub max_requests: usize,
+}
+
+impl PoliteMode {
+    /// No limits at all, only suitable for private servers.
+    pub fn off() -> Self {
+        Self::default()
+    }
+
+    /// Reasonable limits for the public Overpass instances.
+    pub fn public() -> Self {
+        PoliteMode {
+            max_requests: Some(100),
+            per_minute: Some(6),
+        }
+    }
+
+    fn interval(&self) -> Option<Duration> {
+        self.per_minute
+            .filter(|n| *n > 0)
+            .map(|n| Duration::from_secs(60) / n)
+    }
+}
+
+impl Throttle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wait until a new request can be issued under the given limits, failing if the budget has
+    /// been exhausted.
+    ///
+    /// Concurrent callers are served one at a time, so that pacing holds across tasks too.
+    pub async fn acquire(&self, mode: &PoliteMode) -> Result<(), BudgetExceeded> {
+        let mut state = self.state.lock().await;
+
+        if let Some(max_requests) = mode.max_requests {
+            if state.issued >= max_requests {
+                return Err(BudgetExceeded { max_requests });
+            }
+        }
+
+        if let (Some(interval), Some(last)) = (mode.interval(), state.last) {
+            let elapsed = last.elapsed();
+            if elapsed < interval {
+                tokio::time::sleep(interval - elapsed).await;
+            }
+        }
+
+        state.issued += 1;
+        state.last = Some(Instant::now());
+
+        Ok(())
+    }
+
+    /// Number of requests issued so far.
+    pub async fn issued(&self) -> usize {
+        self.state.lock().await.issued
+    }
+}
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the budget of {} requests for this run has been exhausted",
+            self.max_requests
+        )
+    }
+}
+
+impl std::error::Error for BudgetExceeded {}
@@ -0,0 +1,30 @@
+//! The types and functions needed by most programs drawing a place, to glob import them all at
+//! once. [`Result`](crate::Result) is left out, so that it doesn't shadow the one of the
+//! standard library.
+//!
+//! ```
+//! use roads::prelude::*;
+//!
+//! async fn roads_of(place: &str) -> roads::Result<Vec<Vec<(f64, f64)>>> {
+//!     let entries = search(place).await?;
+//!     let entry = best_entry(&entries).ok_or_else(|| Error::NotFound {
+//!         place: place.to_string(),
+//!     })?;
+//!
+//!     let roads = fetch_roads(entry, &RoadFilter::all()).await?;
+//!     Ok(project(&roads.value, &WebMercator))
+//! }
+//! ```
+
+pub use crate::{
+    best_entry,
+    endpoints::Endpoints,
+    fetch_roads, fetch_roads_around, fetch_roads_around_with, fetch_roads_for_osm_id,
+    fetch_roads_for_osm_id_with, fetch_roads_in_bbox, fetch_roads_in_bbox_with, fetch_roads_with,
+    filter::{HighwayClass, RoadFilter},
+    project,
+    projection::{Projection, ProjectionKind, WebMercator},
+    search,
+    style::{Style, StyledPaths},
+    Error, Fetched, GeoRoad, LatLon, NominatimEntry, Road, Warning,
+};
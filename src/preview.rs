@@ -0,0 +1,118 @@
+//! Rough rendering of the drawings with braille characters, good enough to check the crop,
+//! density and aspect ratio of a map in a terminal before exporting it.
+
+use crate::{render::svg, style::StyledPaths, viewport::Canvas};
+
+/// The first braille character, the one without dots.
+const BLANK: u32 = 0x2800;
+
+/// A grid of braille characters, each one made of 2x4 dots that can be set individually.
+///
+/// Terminal cells are about twice as tall as wide, so the dots are roughly square.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Braille {
+    cols: usize,
+    rows: usize,
+    cells: Vec<u8>,
+}
+
+impl Braille {
+    pub fn new(cols: usize, rows: usize) -> Self {
+        Braille {
+            cols,
+            rows,
+            cells: vec![0; cols * rows],
+        }
+    }
+
+    /// Size in dots.
+    pub fn size(&self) -> (usize, usize) {
+        (self.cols * 2, self.rows * 4)
+    }
+
+    /// Set the dot containing the given point, points outside the grid are ignored.
+    pub fn set(&mut self, (x, y): (f64, f64)) {
+        let (w, h) = self.size();
+        if !(x >= 0.0 && y >= 0.0 && x < w as f64 && y < h as f64) {
+            return;
+        }
+
+        let (x, y) = (x as usize, y as usize);
+        let bit = match (x % 2, y % 4) {
+            (0, 3) => 0x40,
+            (1, 3) => 0x80,
+            (dx, dy) => 1 << (dx * 3 + dy),
+        };
+        self.cells[y / 4 * self.cols + x / 2] |= bit;
+    }
+
+    pub fn line(&mut self, a: (f64, f64), b: (f64, f64)) {
+        let steps = f64::max((b.0 - a.0).abs(), (b.1 - a.1).abs())
+            .ceil()
+            .max(1.0);
+        for i in 0..=steps as usize {
+            let t = i as f64 / steps;
+            let p = (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t);
+            self.set((p.0.round(), p.1.round()));
+        }
+    }
+
+    pub fn polyline(&mut self, path: &[(f64, f64)]) {
+        match path {
+            [] => {}
+            [p] => self.set(*p),
+            _ => {
+                for w in path.windows(2) {
+                    self.line(w[0], w[1]);
+                }
+            }
+        }
+    }
+
+    /// The rows of characters.
+    pub fn lines(&self) -> Vec<String> {
+        self.cells
+            .chunks(self.cols.max(1))
+            .take(self.rows)
+            .map(|row| {
+                row.iter()
+                    .map(|&c| char::from_u32(BLANK + u32::from(c)).unwrap_or(' '))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// Draw the given layers as [`svg::write`] lays them out, scaled to fit the given number of
+/// characters and surrounded by the frame of the drawing.
+///
+/// Styles are ignored, every path is drawn as a thin line.
+pub fn render(canvas: &Canvas, layers: &[StyledPaths], cols: usize, rows: usize) -> Braille {
+    let mut braille = Braille::new(cols, rows);
+
+    let viewport = svg::viewport(canvas, layers);
+    let (w, h) = viewport.size();
+    let (mx, my) = viewport.min_corner();
+
+    let (dw, dh) = braille.size();
+    let scale = f64::min((dw as f64 - 1.0) / w, (dh as f64 - 1.0) / h);
+    let scale = if scale.is_finite() {
+        scale.max(0.0)
+    } else {
+        0.0
+    };
+    let dot = |p| {
+        let (x, y) = viewport.to_canvas(p);
+        ((x - mx) * scale, (y - my) * scale)
+    };
+
+    let (fw, fh) = (w * scale, h * scale);
+    braille.polyline(&[(0.0, 0.0), (fw, 0.0), (fw, fh), (0.0, fh), (0.0, 0.0)]);
+
+    for path in layers.iter().flat_map(|l| &l.paths) {
+        let path = path.iter().map(|&p| dot(p)).collect::<Vec<_>>();
+        braille.polyline(&path);
+    }
+
+    braille
+}
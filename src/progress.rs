@@ -0,0 +1,219 @@
+//! Reporting of how far along the slow steps between asking for a place and having its drawing
+//! are, so that a huge city doesn't look like a hang.
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// How often the elements and paths processed are reported, reporting each one of them would
+/// cost more than processing it.
+pub const REPORT_EVERY: u64 = 1000;
+
+/// How far back [`TransferRate`] looks, long enough to smooth the bursts of a chunked response
+/// and short enough to follow a connection slowing down.
+pub const RATE_WINDOW: Duration = Duration::from_secs(3);
+
+/// How much of a step is done.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Progress {
+    pub stage: Stage,
+    pub done: u64,
+
+    /// How much there's to do, if known. Servers don't always say how big a response is.
+    pub total: Option<u64>,
+
+    /// Bytes per second of the download, once it's been going long enough to tell.
+    pub rate: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Stage {
+    /// Bytes of the response received.
+    Download,
+
+    /// Elements of the response turned into roads.
+    Parse,
+
+    /// Paths run through the post-processing pipeline, simplified, joined, reordered and the
+    /// like.
+    Process,
+}
+
+/// Function called as a step makes progress, e.g. to show it to the user.
+///
+/// The default observer does nothing.
+#[derive(Clone, Default)]
+pub struct ProgressObserver(Option<ObserverFn>);
+
+type ObserverFn = Arc<dyn Fn(&Progress) + Send + Sync>;
+
+/// Speed of a transfer, averaged over the last [`RATE_WINDOW`].
+#[derive(Debug, Clone)]
+pub struct TransferRate {
+    /// When each amount of bytes had been received, oldest first.
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl Progress {
+    /// How much is done, between 0 and 1, if the total is known.
+    pub fn ratio(&self) -> Option<f64> {
+        let total = self.total.filter(|t| *t > 0)?;
+        Some((self.done as f64 / total as f64).min(1.0))
+    }
+
+    /// How long until the total is reached at the current rate, if both are known.
+    pub fn eta(&self) -> Option<Duration> {
+        self.eta_for(self.total?)
+    }
+
+    /// How long until the given total is reached at the current rate, e.g. an estimate of the
+    /// size of a response whose server didn't tell it.
+    pub fn eta_for(&self, total: u64) -> Option<Duration> {
+        let rate = self.rate.filter(|r| *r > 0)?;
+        let left = total.saturating_sub(self.done);
+        Some(Duration::from_secs(left.div_ceil(rate)))
+    }
+}
+
+impl TransferRate {
+    pub fn new() -> Self {
+        TransferRate {
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Record that `received` bytes have been received so far, returning the bytes per second.
+    pub fn update(&mut self, received: u64) -> Option<u64> {
+        let now = Instant::now();
+        self.samples.push_back((now, received));
+
+        // keep one sample older than the window so that it's always fully covered
+        while self.samples.len() > 2 && now.duration_since(self.samples[1].0) >= RATE_WINDOW {
+            self.samples.pop_front();
+        }
+
+        let (start, from) = *self.samples.front()?;
+        let elapsed = now.duration_since(start).as_secs_f64();
+        if elapsed < 0.5 {
+            return None;
+        }
+
+        Some((received.saturating_sub(from) as f64 / elapsed) as u64)
+    }
+}
+
+impl Default for TransferRate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProgressObserver {
+    pub fn new(f: impl Fn(&Progress) + Send + Sync + 'static) -> Self {
+        ProgressObserver(Some(Arc::new(f)))
+    }
+
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    pub fn report(&self, stage: Stage, done: u64, total: Option<u64>) {
+        self.report_progress(Progress {
+            stage,
+            done,
+            total,
+            rate: None,
+        });
+    }
+
+    pub fn report_progress(&self, progress: Progress) {
+        if let Some(f) = &self.0 {
+            f(&progress);
+        }
+    }
+
+    /// Like [`ProgressObserver::report`], but only every [`REPORT_EVERY`] items and for the last
+    /// one.
+    pub fn report_every(&self, stage: Stage, done: u64, total: u64) {
+        if done.is_multiple_of(REPORT_EVERY) || done == total {
+            self.report(stage, done, Some(total));
+        }
+    }
+}
+
+/// Short human friendly duration like `45s` or `3m 20s`.
+pub fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m {:02}s", secs / 60, secs % 60),
+        _ => format!("{}h {:02}m", secs / 3600, secs / 60 % 60),
+    }
+}
+
+fn format_bytes(n: u64) -> String {
+    match n {
+        0..=999_999 => format!("{:.0} kB", n as f64 / 1e3),
+        _ => format!("{:.1} MB", n as f64 / 1e6),
+    }
+}
+
+/// Human friendly summary like `parsing 12k/80k elements` or
+/// `downloading 3.4 MB of 9.0 MB at 1.2 MB/s, 5s left`.
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn count(n: u64) -> String {
+            match n {
+                0..=999 => n.to_string(),
+                1_000..=999_999 => format!("{:.0}k", n as f64 / 1e3),
+                _ => format!("{:.1}M", n as f64 / 1e6),
+            }
+        }
+
+        let (verb, unit) = match self.stage {
+            Stage::Download => {
+                write!(f, "downloading {}", format_bytes(self.done))?;
+                if let Some(total) = self.total {
+                    write!(f, " of {}", format_bytes(total))?;
+                }
+                if let Some(rate) = self.rate {
+                    write!(f, " at {}/s", format_bytes(rate))?;
+                }
+                if let Some(eta) = self.eta() {
+                    write!(f, ", {} left", format_duration(eta))?;
+                }
+                return Ok(());
+            }
+            Stage::Parse => ("parsing", "elements"),
+            Stage::Process => ("processing", "paths"),
+        };
+
+        write!(f, "{} {}", verb, count(self.done))?;
+        if let Some(total) = self.total {
+            write!(f, "/{}", count(total))?;
+        }
+        write!(f, " {}", unit)
+    }
+}
+
+impl fmt::Debug for ProgressObserver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ProgressObserver")
+            .field(&self.0.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
+
+/// Observers are not compared.
+impl PartialEq for ProgressObserver {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for ProgressObserver {}
@@ -0,0 +1,160 @@
+//! Project files bundling everything needed to render a map again, possibly on another machine:
+//! the place, the Overpass query, the options, the style rules and optionally the fetched
+//! geometry, so that the render doesn't even depend on the data still being the same.
+//!
+//! They're json documents that name their format and version, e.g.
+//!
+//! ```json
+//! {
+//!   "format": "roads-project",
+//!   "version": 1,
+//!   "name": "Turin",
+//!   "query": "[out:json][timeout:60];\narea(3600044880)->.a;\nway[highway](area.a);\nout geom;",
+//!   "options": { "Stroke color": "#333" }
+//! }
+//! ```
+
+use std::{collections::BTreeMap, fs, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{util::write_file_atomic, NominatimEntry, Road};
+
+/// Value of the `format` field of every project file.
+pub const FORMAT: &str = "roads-project";
+
+/// Version of the project files written by this version of the crate. Older ones can always be
+/// read, newer ones are rejected rather than rendered differently.
+pub const VERSION: u32 = 1;
+
+/// Extension of the project files.
+pub const EXTENSION: &str = "roads";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Project {
+    format: String,
+    pub version: u32,
+
+    /// Name of the export, the files are named after it.
+    pub name: String,
+
+    /// The place that was searched, if the map comes from a search rather than a raw query.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub place: Option<NominatimEntry>,
+
+    pub query: String,
+
+    /// Values of the options by name, in the format accepted by the TUI, like in the
+    /// [config](crate::config::Config).
+    #[serde(default)]
+    pub options: BTreeMap<String, String>,
+
+    /// The style rules, which are otherwise a path local to the machine that made the project.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub style_rules: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    geometry: Option<Vec<StoredRoad>>,
+}
+
+/// A [`Road`] as stored in the project files.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredRoad {
+    id: i64,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    tags: BTreeMap<String, String>,
+
+    points: Vec<(f64, f64)>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    holes: Vec<Vec<(f64, f64)>>,
+}
+
+impl Project {
+    pub fn new(name: impl Into<String>, query: impl Into<String>) -> Self {
+        Project {
+            format: FORMAT.to_string(),
+            version: VERSION,
+            name: name.into(),
+            place: None,
+            query: query.into(),
+            options: BTreeMap::new(),
+            style_rules: None,
+            geometry: None,
+        }
+    }
+
+    /// Bundle the given fetched roads, so that the project renders without downloading anything.
+    pub fn with_geometry(mut self, roads: &[Road]) -> Self {
+        self.geometry = Some(roads.iter().map(StoredRoad::from).collect());
+        self
+    }
+
+    pub fn has_geometry(&self) -> bool {
+        self.geometry.is_some()
+    }
+
+    /// The roads bundled in the project, if any.
+    pub fn geometry(&self) -> Option<Vec<Road>> {
+        let roads = self.geometry.as_ref()?;
+        Some(roads.iter().cloned().map(Road::from).collect())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let invalid = |msg: String| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid project {}: {}", path.display(), msg),
+            )
+        };
+
+        let project: Project =
+            serde_json::from_slice(&fs::read(path)?).map_err(|e| invalid(e.to_string()))?;
+        if project.format != FORMAT {
+            return Err(invalid(format!("unknown format '{}'", project.format)));
+        }
+        if project.version > VERSION {
+            return Err(invalid(format!(
+                "version {} is newer than the supported {}, update roads",
+                project.version, VERSION
+            )));
+        }
+
+        Ok(project)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        // the geometry would take a line per coordinate
+        let data = if self.geometry.is_some() {
+            serde_json::to_vec(self)
+        } else {
+            serde_json::to_vec_pretty(self)
+        }
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        write_file_atomic(path, data)
+    }
+}
+
+impl From<&Road> for StoredRoad {
+    fn from(r: &Road) -> Self {
+        StoredRoad {
+            id: r.id,
+            tags: r.tags.clone(),
+            points: r.points.clone(),
+            holes: r.holes.clone(),
+        }
+    }
+}
+
+impl From<StoredRoad> for Road {
+    fn from(r: StoredRoad) -> Self {
+        Road {
+            id: r.id,
+            tags: r.tags,
+            points: r.points,
+            holes: r.holes,
+        }
+    }
+}
@@ -0,0 +1,372 @@
+//! Map projections turning latitudes and longitudes into planar coordinates in meters.
+//!
+//! The points are fetched in Web Mercator, which is fine for most cities but it stretches the
+//! ones far from the equator, e.g. Reykjavík comes out twice as tall as it should. The other
+//! projections are centered on the place instead, so that it keeps its shape.
+
+use std::{f64::consts::FRAC_PI_2, f64::consts::FRAC_PI_4, fmt, str::FromStr};
+
+use crate::georef;
+
+/// Radius of the sphere of the spherical projections, in meters.
+pub const EARTH_RADIUS: f64 = 6378137.0;
+
+/// ESRI WKT of the sphere the spherical projections are based on.
+const SPHERE_GEOGCS: &str = r#"GEOGCS["GCS_Sphere",DATUM["D_Sphere",SPHEROID["Sphere",6378137.0,0.0]],PRIMEM["Greenwich",0.0],UNIT["Degree",0.0174532925199433]]"#;
+
+/// A way to flatten the Earth, the y axis grows northwards.
+pub trait Projection: Send + Sync {
+    /// The planar coordinates, in meters, of the given latitude and longitude in degrees.
+    fn project(&self, lat: f64, lon: f64) -> (f64, f64);
+
+    /// The `(latitude, longitude)` in degrees of the given planar coordinates.
+    fn unproject(&self, p: (f64, f64)) -> (f64, f64);
+
+    /// The coordinate system as the ESRI WKT expected in `.prj` files.
+    fn wkt(&self) -> String;
+
+    /// The EPSG code of the coordinate system, if it has one, e.g. `EPSG:3857`.
+    fn epsg(&self) -> Option<String> {
+        None
+    }
+
+    /// How many planar units a meter on the ground spans around the given planar point, e.g.
+    /// about `1 / cos(latitude)` in Web Mercator. It's measured along the meridian.
+    fn scale(&self, p: (f64, f64)) -> f64 {
+        let (lat, lon) = self.unproject(p);
+        let (d, s) = (self.project(lat, lon), self.project(lat + 0.01, lon));
+        (s.0 - d.0).hypot(s.1 - d.1) / (0.01_f64.to_radians() * EARTH_RADIUS)
+    }
+}
+
+/// The built-in projections, by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum ProjectionKind {
+    #[default]
+    Mercator,
+    Equirectangular,
+    Utm,
+    AzimuthalEquidistant,
+}
+
+/// Spherical [Web Mercator](https://en.wikipedia.org/wiki/Web_Mercator_projection), the
+/// projection of the fetched points and of most web maps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WebMercator;
+
+/// [Equirectangular](https://en.wikipedia.org/wiki/Equirectangular_projection) projection whose
+/// longitudes are scaled by the cosine of the given latitude, true to scale around it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Equirectangular {
+    pub lat0: f64,
+}
+
+/// [Universal Transverse Mercator](https://en.wikipedia.org/wiki/Universal_Transverse_Mercator_coordinate_system)
+/// on the WGS 84 ellipsoid, the most accurate within its 6° wide zone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Utm {
+    /// From 1 to 60.
+    pub zone: u8,
+    pub north: bool,
+}
+
+/// Spherical [azimuthal equidistant](https://en.wikipedia.org/wiki/Azimuthal_equidistant_projection)
+/// projection, which keeps distances and directions from its center.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AzimuthalEquidistant {
+    pub lat0: f64,
+    pub lon0: f64,
+}
+
+impl ProjectionKind {
+    /// The projection of this kind best suited to a place centered at the given latitude and
+    /// longitude.
+    pub fn centered(self, lat: f64, lon: f64) -> Box<dyn Projection> {
+        match self {
+            ProjectionKind::Mercator => Box::new(WebMercator),
+            ProjectionKind::Equirectangular => Box::new(Equirectangular { lat0: lat }),
+            ProjectionKind::Utm => Box::new(Utm::containing(lat, lon)),
+            ProjectionKind::AzimuthalEquidistant => Box::new(AzimuthalEquidistant {
+                lat0: lat,
+                lon0: lon,
+            }),
+        }
+    }
+}
+
+impl Projection for WebMercator {
+    fn project(&self, lat: f64, lon: f64) -> (f64, f64) {
+        // https://wiki.openstreetmap.org/wiki/Mercator
+        let x = lon.to_radians() * EARTH_RADIUS;
+        let y = f64::ln(f64::tan(lat.to_radians() / 2.0 + FRAC_PI_4)) * EARTH_RADIUS;
+
+        (x, y)
+    }
+
+    fn unproject(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let lon = (x / EARTH_RADIUS).to_degrees();
+        let lat = (2.0 * f64::atan(f64::exp(y / EARTH_RADIUS)) - FRAC_PI_2).to_degrees();
+
+        (lat, lon)
+    }
+
+    fn wkt(&self) -> String {
+        georef::WEB_MERCATOR_WKT.to_string()
+    }
+
+    fn epsg(&self) -> Option<String> {
+        Some(georef::WEB_MERCATOR_EPSG.to_string())
+    }
+}
+
+impl Projection for Equirectangular {
+    fn project(&self, lat: f64, lon: f64) -> (f64, f64) {
+        let x = lon.to_radians() * self.lat0.to_radians().cos() * EARTH_RADIUS;
+        let y = lat.to_radians() * EARTH_RADIUS;
+
+        (x, y)
+    }
+
+    fn unproject(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let lon = (x / EARTH_RADIUS / self.lat0.to_radians().cos()).to_degrees();
+        let lat = (y / EARTH_RADIUS).to_degrees();
+
+        (lat, lon)
+    }
+
+    fn wkt(&self) -> String {
+        format!(
+            r#"PROJCS["Equidistant_Cylindrical",{},PROJECTION["Equidistant_Cylindrical"],PARAMETER["False_Easting",0.0],PARAMETER["False_Northing",0.0],PARAMETER["Central_Meridian",0.0],PARAMETER["Standard_Parallel_1",{}],UNIT["Meter",1.0]]"#,
+            SPHERE_GEOGCS, self.lat0
+        )
+    }
+}
+
+impl Utm {
+    /// Semi-major axis of the WGS 84 ellipsoid, in meters.
+    const A: f64 = 6378137.0;
+
+    /// Flattening of the WGS 84 ellipsoid.
+    const F: f64 = 1.0 / 298.257223563;
+
+    /// Scale along the central meridian.
+    const K0: f64 = 0.9996;
+
+    const FALSE_EASTING: f64 = 500_000.0;
+    const FALSE_NORTHING_SOUTH: f64 = 10_000_000.0;
+
+    /// The zone containing the given latitude and longitude, ignoring the exceptions around
+    /// Norway and Svalbard.
+    pub fn containing(lat: f64, lon: f64) -> Self {
+        let zone = (((lon + 180.0) / 6.0).floor() as i64).rem_euclid(60) + 1;
+        Utm {
+            zone: zone as u8,
+            north: lat >= 0.0,
+        }
+    }
+
+    /// Longitude of the central meridian of the zone, in degrees.
+    pub fn central_meridian(&self) -> f64 {
+        f64::from(self.zone) * 6.0 - 183.0
+    }
+
+    fn false_northing(&self) -> f64 {
+        if self.north {
+            0.0
+        } else {
+            Self::FALSE_NORTHING_SOUTH
+        }
+    }
+}
+
+/// The formulas are the series of Snyder, "Map Projections: A Working Manual", pages 61-64,
+/// accurate to less than a millimeter within a zone.
+impl Projection for Utm {
+    fn project(&self, lat: f64, lon: f64) -> (f64, f64) {
+        let e2 = Self::F * (2.0 - Self::F);
+        let ep2 = e2 / (1.0 - e2);
+        let (e4, e6) = (e2 * e2, e2 * e2 * e2);
+
+        let phi = lat.to_radians();
+        let (sin, cos, tan) = (phi.sin(), phi.cos(), phi.tan());
+
+        let n = Self::A / (1.0 - e2 * sin * sin).sqrt();
+        let t = tan * tan;
+        let c = ep2 * cos * cos;
+        let a = (lon - self.central_meridian()).to_radians() * cos;
+        let m = Self::A
+            * ((1.0 - e2 / 4.0 - 3.0 * e4 / 64.0 - 5.0 * e6 / 256.0) * phi
+                - (3.0 * e2 / 8.0 + 3.0 * e4 / 32.0 + 45.0 * e6 / 1024.0) * (2.0 * phi).sin()
+                + (15.0 * e4 / 256.0 + 45.0 * e6 / 1024.0) * (4.0 * phi).sin()
+                - (35.0 * e6 / 3072.0) * (6.0 * phi).sin());
+
+        let x = Self::K0
+            * n
+            * (a + (1.0 - t + c) * a.powi(3) / 6.0
+                + (5.0 - 18.0 * t + t * t + 72.0 * c - 58.0 * ep2) * a.powi(5) / 120.0);
+        let y = Self::K0
+            * (m + n
+                * tan
+                * (a * a / 2.0
+                    + (5.0 - t + 9.0 * c + 4.0 * c * c) * a.powi(4) / 24.0
+                    + (61.0 - 58.0 * t + t * t + 600.0 * c - 330.0 * ep2) * a.powi(6) / 720.0));
+
+        (x + Self::FALSE_EASTING, y + self.false_northing())
+    }
+
+    fn unproject(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let e2 = Self::F * (2.0 - Self::F);
+        let ep2 = e2 / (1.0 - e2);
+        let (e4, e6) = (e2 * e2, e2 * e2 * e2);
+        let e1 = (1.0 - (1.0 - e2).sqrt()) / (1.0 + (1.0 - e2).sqrt());
+
+        let m = (y - self.false_northing()) / Self::K0;
+        let mu = m / (Self::A * (1.0 - e2 / 4.0 - 3.0 * e4 / 64.0 - 5.0 * e6 / 256.0));
+        let phi1 = mu
+            + (3.0 * e1 / 2.0 - 27.0 * e1.powi(3) / 32.0) * (2.0 * mu).sin()
+            + (21.0 * e1 * e1 / 16.0 - 55.0 * e1.powi(4) / 32.0) * (4.0 * mu).sin()
+            + (151.0 * e1.powi(3) / 96.0) * (6.0 * mu).sin()
+            + (1097.0 * e1.powi(4) / 512.0) * (8.0 * mu).sin();
+
+        let (sin, cos, tan) = (phi1.sin(), phi1.cos(), phi1.tan());
+        let c1 = ep2 * cos * cos;
+        let t1 = tan * tan;
+        let n1 = Self::A / (1.0 - e2 * sin * sin).sqrt();
+        let r1 = Self::A * (1.0 - e2) / (1.0 - e2 * sin * sin).powf(1.5);
+        let d = (x - Self::FALSE_EASTING) / (n1 * Self::K0);
+
+        let phi = phi1
+            - (n1 * tan / r1)
+                * (d * d / 2.0
+                    - (5.0 + 3.0 * t1 + 10.0 * c1 - 4.0 * c1 * c1 - 9.0 * ep2) * d.powi(4) / 24.0
+                    + (61.0 + 90.0 * t1 + 298.0 * c1 + 45.0 * t1 * t1
+                        - 252.0 * ep2
+                        - 3.0 * c1 * c1)
+                        * d.powi(6)
+                        / 720.0);
+        let lambda = (d - (1.0 + 2.0 * t1 + c1) * d.powi(3) / 6.0
+            + (5.0 - 2.0 * c1 + 28.0 * t1 - 3.0 * c1 * c1 + 8.0 * ep2 + 24.0 * t1 * t1)
+                * d.powi(5)
+                / 120.0)
+            / cos;
+
+        (
+            phi.to_degrees(),
+            self.central_meridian() + lambda.to_degrees(),
+        )
+    }
+
+    fn wkt(&self) -> String {
+        format!(
+            r#"PROJCS["WGS_1984_UTM_Zone_{}{}",GEOGCS["GCS_WGS_1984",DATUM["D_WGS_1984",SPHEROID["WGS_1984",6378137.0,298.257223563]],PRIMEM["Greenwich",0.0],UNIT["Degree",0.0174532925199433]],PROJECTION["Transverse_Mercator"],PARAMETER["False_Easting",500000.0],PARAMETER["False_Northing",{:.1}],PARAMETER["Central_Meridian",{:.1}],PARAMETER["Scale_Factor",0.9996],PARAMETER["Latitude_Of_Origin",0.0],UNIT["Meter",1.0]]"#,
+            self.zone,
+            if self.north { 'N' } else { 'S' },
+            self.false_northing(),
+            self.central_meridian(),
+        )
+    }
+
+    fn epsg(&self) -> Option<String> {
+        let base = if self.north { 32600 } else { 32700 };
+        Some(format!("EPSG:{}", base + u32::from(self.zone)))
+    }
+}
+
+impl Projection for AzimuthalEquidistant {
+    fn project(&self, lat: f64, lon: f64) -> (f64, f64) {
+        let (phi0, phi) = (self.lat0.to_radians(), lat.to_radians());
+        let dl = (lon - self.lon0).to_radians();
+
+        let cos_c = phi0.sin() * phi.sin() + phi0.cos() * phi.cos() * dl.cos();
+        let c = cos_c.clamp(-1.0, 1.0).acos();
+        let k = if c.abs() < 1e-12 { 1.0 } else { c / c.sin() };
+
+        let x = EARTH_RADIUS * k * phi.cos() * dl.sin();
+        let y = EARTH_RADIUS * k * (phi0.cos() * phi.sin() - phi0.sin() * phi.cos() * dl.cos());
+
+        (x, y)
+    }
+
+    fn unproject(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let rho = x.hypot(y);
+        if rho < 1e-9 {
+            return (self.lat0, self.lon0);
+        }
+
+        let phi0 = self.lat0.to_radians();
+        let c = rho / EARTH_RADIUS;
+
+        let phi = (c.cos() * phi0.sin() + y * c.sin() * phi0.cos() / rho).asin();
+        let dl = f64::atan2(
+            x * c.sin(),
+            rho * phi0.cos() * c.cos() - y * phi0.sin() * c.sin(),
+        );
+
+        (phi.to_degrees(), self.lon0 + dl.to_degrees())
+    }
+
+    fn wkt(&self) -> String {
+        format!(
+            r#"PROJCS["Azimuthal_Equidistant",{},PROJECTION["Azimuthal_Equidistant"],PARAMETER["False_Easting",0.0],PARAMETER["False_Northing",0.0],PARAMETER["Central_Meridian",{}],PARAMETER["Latitude_Of_Origin",{}],UNIT["Meter",1.0]]"#,
+            SPHERE_GEOGCS, self.lon0, self.lat0
+        )
+    }
+}
+
+/// The given point projected with `from` projected again with `to`.
+pub fn reproject(p: (f64, f64), from: &dyn Projection, to: &dyn Projection) -> (f64, f64) {
+    let (lat, lon) = from.unproject(p);
+    to.project(lat, lon)
+}
+
+impl FromStr for ProjectionKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "mercator" => Ok(ProjectionKind::Mercator),
+            "equirectangular" => Ok(ProjectionKind::Equirectangular),
+            "utm" => Ok(ProjectionKind::Utm),
+            "azimuthal" => Ok(ProjectionKind::AzimuthalEquidistant),
+            _ => Err(format!(
+                "unknown projection '{}', expected mercator, equirectangular, utm or azimuthal",
+                s.trim()
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ProjectionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ProjectionKind::Mercator => "mercator",
+            ProjectionKind::Equirectangular => "equirectangular",
+            ProjectionKind::Utm => "utm",
+            ProjectionKind::AzimuthalEquidistant => "azimuthal",
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+crate::util::serde_via_str!(ProjectionKind);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ground_scale() {
+        let mercator = WebMercator;
+        assert!((mercator.scale(mercator.project(0.0, 0.0)) - 1.0).abs() < 1e-3);
+        assert!((mercator.scale(mercator.project(60.0, 10.0)) - 2.0).abs() < 1e-3);
+
+        // on the ellipsoid rather than the sphere of the ground meters, close enough
+        let utm = Utm::containing(64.1, -21.9);
+        assert!((utm.scale(utm.project(64.1, -21.9)) - 1.0).abs() < 1e-2);
+    }
+}
@@ -0,0 +1,215 @@
+//! Rasterization of the drawings, e.g. for the thumbnails of a gallery of maps.
+
+use std::io;
+
+use tiny_skia::{FillRule, Paint, Path, PathBuilder, Pixmap, Stroke, StrokeDash, Transform};
+
+use crate::{
+    color::Color,
+    render::svg::{self, is_ring},
+    style::{Marker, StyledPaths},
+    viewport::Canvas,
+};
+
+/// Render the given layers laid out like [`svg::write`] does, with `scale` pixels per canvas unit.
+///
+/// Lines thinner than `min_width` pixels are drawn `min_width` wide so that they don't fade away
+/// in small images. Legends, captions and print marks are not drawn. `None` if the image would
+/// be empty.
+pub fn render(
+    canvas: &Canvas,
+    background_color: Color,
+    layers: &[StyledPaths],
+    scale: f64,
+    min_width: f64,
+) -> Option<Pixmap> {
+    let viewport = svg::viewport(canvas, layers);
+    let (w, h) = viewport.size();
+    let (vx, vy) = viewport.min_corner();
+
+    let mut pixmap = Pixmap::new(
+        (w * scale).round().max(1.0) as u32,
+        (h * scale).round().max(1.0) as u32,
+    )?;
+    if let Some(c) = paint_color(background_color) {
+        pixmap.fill(c);
+    }
+
+    let to_pixels = Transform::from_row(
+        scale as f32,
+        0.0,
+        0.0,
+        scale as f32,
+        (-vx * scale) as f32,
+        (-vy * scale) as f32,
+    );
+
+    for l in layers {
+        let style = &l.style;
+        let t = &style.transform;
+        let transform = to_pixels.pre_concat(Transform::from_row(
+            t.a as f32, t.b as f32, t.c as f32, t.d as f32, t.e as f32, t.f as f32,
+        ));
+        let tr = |p| viewport.to_canvas(p);
+
+        // same as in the svg, only rings are filled and open paths are never closed
+        let filled = paint(style.fill);
+        if let Some(fill) = &filled {
+            let mut pb = PathBuilder::new();
+            for p in l.paths.iter().filter(|p| is_ring(p)) {
+                push_polyline(&mut pb, p.iter().map(|&p| tr(p)));
+                pb.close();
+            }
+            if let Some(path) = pb.finish() {
+                pixmap.fill_path(&path, fill, FillRule::EvenOdd, transform, None);
+            }
+        }
+
+        let color = match paint(style.color) {
+            None => continue,
+            Some(c) => c,
+        };
+
+        let mut pb = PathBuilder::new();
+        for p in &l.paths {
+            if let [p] = p[..] {
+                if let Some(marker) = marker(style.marker, tr(p), style.radius) {
+                    pixmap.fill_path(&marker, &color, FillRule::Winding, transform, None);
+                }
+                continue;
+            }
+
+            // the outline of the filled rings is stroked too, like in the svg
+            push_polyline(&mut pb, p.iter().map(|&p| tr(p)));
+            if filled.is_some() && is_ring(p) {
+                pb.close();
+            }
+        }
+
+        let width = style.width.max(min_width / scale);
+        let dash = style.dash.iter().map(|&d| d as f32).collect::<Vec<_>>();
+        let stroke = Stroke {
+            width: width as f32,
+            dash: StrokeDash::new(dash, 0.0),
+            ..Stroke::default()
+        };
+        if let Some(path) = pb.finish() {
+            pixmap.stroke_path(&path, &color, &stroke, transform, None);
+        }
+    }
+
+    Some(pixmap)
+}
+
+/// Render the given layers in an image whose longest side is `size` pixels, see [`render`].
+pub fn thumbnail(
+    canvas: &Canvas,
+    background_color: Color,
+    layers: &[StyledPaths],
+    size: u32,
+) -> Option<Pixmap> {
+    let (w, h) = svg::viewport(canvas, layers).size();
+    let scale = f64::from(size) / w.max(h);
+    if !scale.is_finite() {
+        return None;
+    }
+
+    render(canvas, background_color, layers, scale, 1.0)
+}
+
+/// Encode the given image as a PNG tagged as sRGB, so that print workflows don't have to guess
+/// its color space.
+pub fn encode_png(pixmap: &Pixmap) -> io::Result<Vec<u8>> {
+    let png = pixmap.encode_png().map_err(io::Error::other)?;
+    Ok(with_srgb_chunk(png))
+}
+
+/// Add an `sRGB` chunk with the perceptual rendering intent right after the header of the given
+/// PNG, where the specification wants it.
+fn with_srgb_chunk(mut png: Vec<u8>) -> Vec<u8> {
+    // signature, then length, type, 13 bytes of data and crc of the header
+    const AFTER_IHDR: usize = 8 + 4 + 4 + 13 + 4;
+    if png.len() < AFTER_IHDR || &png[12..16] != b"IHDR" {
+        return png;
+    }
+
+    let data = b"sRGB\x00";
+    let mut chunk = 1_u32.to_be_bytes().to_vec();
+    chunk.extend_from_slice(data);
+    chunk.extend_from_slice(&crc32(data).to_be_bytes());
+
+    png.splice(AFTER_IHDR..AFTER_IHDR, chunk);
+    png
+}
+
+/// The CRC-32 of the PNG chunks, see the annex D of the PNG specification.
+fn crc32(bytes: &[u8]) -> u32 {
+    let crc = bytes.iter().fold(0xffff_ffff_u32, |crc, &b| {
+        (0..8).fold(crc ^ u32::from(b), |c, _| {
+            if c & 1 == 1 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            }
+        })
+    });
+    !crc
+}
+
+fn paint_color(c: Color) -> Option<tiny_skia::Color> {
+    let (r, g, b) = c.rgb()?;
+    Some(tiny_skia::Color::from_rgba8(r, g, b, 255))
+}
+
+fn paint(c: Color) -> Option<Paint<'static>> {
+    let mut paint = Paint::default();
+    paint.set_color(paint_color(c)?);
+    Some(paint)
+}
+
+fn push_polyline(pb: &mut PathBuilder, points: impl IntoIterator<Item = (f64, f64)>) {
+    for (i, (x, y)) in points.into_iter().enumerate() {
+        if i == 0 {
+            pb.move_to(x as f32, y as f32);
+        } else {
+            pb.line_to(x as f32, y as f32);
+        }
+    }
+}
+
+/// The shape of the marker centered at the given point, like the svg symbols.
+fn marker(marker: Marker, (x, y): (f64, f64), r: f64) -> Option<Path> {
+    let (x, y, r) = (x as f32, y as f32, r as f32);
+    match marker {
+        Marker::Circle => PathBuilder::from_circle(x, y, r),
+        Marker::Square => {
+            tiny_skia::Rect::from_xywh(x - r, y - r, 2.0 * r, 2.0 * r).map(PathBuilder::from_rect)
+        }
+        Marker::Triangle => {
+            let mut pb = PathBuilder::new();
+            pb.move_to(x, y - r);
+            pb.line_to(x + r * 0.866, y + r / 2.0);
+            pb.line_to(x - r * 0.866, y + r / 2.0);
+            pb.close();
+            pb.finish()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srgb_chunk() {
+        let pixmap = Pixmap::new(2, 2).unwrap();
+        let png = encode_png(&pixmap).unwrap();
+
+        // the well known bytes of the chunk, crc included, right after the header
+        assert_eq!(
+            &png[33..46],
+            b"\x00\x00\x00\x01sRGB\x00\xae\xce\x1c\xe9".as_slice()
+        );
+        assert_eq!(Pixmap::decode_png(&png).unwrap().width(), 2);
+    }
+}
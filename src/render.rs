@@ -0,0 +1,139 @@
+//! Built-in looks of the maps, i.e. how the roads are drawn depending on their highway class.
+
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use crate::{color::Color, filter::HighwayClass, style};
+
+pub mod dxf;
+pub mod gcode;
+pub mod pdf;
+pub mod svg;
+
+/// A table mapping each highway class to its stroke, relative to the base style of the roads so
+/// that changing the line width scales the whole map.
+///
+/// Roads whose class is not in the table, or that have no class at all, keep the base style.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Style {
+    pub name: String,
+    pub classes: BTreeMap<HighwayClass, Stroke>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stroke {
+    /// Multiplier of the base line width.
+    pub width: f64,
+
+    /// Color of the stroke, the base one if `None`.
+    pub color: Option<Color>,
+}
+
+impl Style {
+    pub const BUILTIN_NAMES: [&'static str; 3] = ["uniform", "hierarchy", "atlas"];
+
+    /// Every road drawn the same, the default.
+    pub fn uniform() -> Self {
+        Style {
+            name: "uniform".to_string(),
+            classes: BTreeMap::new(),
+        }
+    }
+
+    /// Motorways thick and footways hairline, all in the base color.
+    pub fn hierarchy() -> Self {
+        let widths = [
+            (HighwayClass::Motorway, 4.0),
+            (HighwayClass::Trunk, 3.5),
+            (HighwayClass::Primary, 3.0),
+            (HighwayClass::Secondary, 2.5),
+            (HighwayClass::Tertiary, 2.0),
+            (HighwayClass::Unclassified, 1.5),
+            (HighwayClass::Residential, 1.5),
+            (HighwayClass::LivingStreet, 1.2),
+            (HighwayClass::Service, 1.0),
+            (HighwayClass::Pedestrian, 1.0),
+            (HighwayClass::Track, 0.6),
+            (HighwayClass::Footway, 0.3),
+            (HighwayClass::Cycleway, 0.3),
+            (HighwayClass::Path, 0.3),
+            (HighwayClass::Steps, 0.3),
+        ];
+
+        Style {
+            name: "hierarchy".to_string(),
+            classes: widths
+                .into_iter()
+                .map(|(c, width)| (c, Stroke { width, color: None }))
+                .collect(),
+        }
+    }
+
+    /// Like [`Style::hierarchy`], with the main roads colored like on a road atlas.
+    pub fn atlas() -> Self {
+        let colors = [
+            (HighwayClass::Motorway, Color::Rgb(226, 122, 143)),
+            (HighwayClass::Trunk, Color::Rgb(249, 178, 156)),
+            (HighwayClass::Primary, Color::Rgb(252, 214, 164)),
+            (HighwayClass::Secondary, Color::Rgb(246, 225, 98)),
+        ];
+
+        let mut style = Self::hierarchy();
+        style.name = "atlas".to_string();
+        for (c, color) in colors {
+            if let Some(s) = style.classes.get_mut(&c) {
+                s.color = Some(color);
+            }
+        }
+        style
+    }
+
+    /// The built-in style with the given name, if any.
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "uniform" => Some(Self::uniform()),
+            "hierarchy" => Some(Self::hierarchy()),
+            "atlas" => Some(Self::atlas()),
+            _ => None,
+        }
+    }
+
+    /// The style of the given highway class, starting from the base style of the roads.
+    pub fn class_style(&self, class: HighwayClass, base: &style::Style) -> style::Style {
+        let stroke = match self.classes.get(&class) {
+            None => return base.clone(),
+            Some(s) => s,
+        };
+
+        style::Style {
+            width: base.width * stroke.width,
+            color: stroke.color.unwrap_or(base.color),
+            ..base.clone()
+        }
+    }
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self::uniform()
+    }
+}
+
+impl FromStr for Style {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::builtin(s.trim()).ok_or_else(|| {
+            format!(
+                "unknown road style '{}', expected one of {}",
+                s.trim(),
+                Self::BUILTIN_NAMES.join(", ")
+            )
+        })
+    }
+}
+
+impl fmt::Display for Style {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)
+    }
+}
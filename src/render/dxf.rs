@@ -0,0 +1,334 @@
+//! Export roads to [DXF](https://help.autodesk.com/view/OARX/2024/ENU/?guid=GUID-235B22E0-A567-4CF6-92D3-38A2306D73F3),
+//! the format of CAD tools, laser cutters and CNC routers.
+
+use std::io::{self, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    color::Color,
+    render::svg::{self, is_ring, SvgOptions},
+    style::StyledPaths,
+    units::LengthUnit,
+};
+
+/// How [`write`] lays out the drawing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DxfOptions {
+    /// Unit of the coordinates, canvas units are converted to it.
+    pub units: LengthUnit,
+
+    /// The same layout of the svg, only the geometry is written though.
+    pub svg: SvgOptions,
+}
+
+impl DxfOptions {
+    pub fn new(units: LengthUnit, svg: SvgOptions) -> Self {
+        DxfOptions { units, svg }
+    }
+}
+
+impl Default for DxfOptions {
+    fn default() -> Self {
+        Self::new(LengthUnit::Mm, SvgOptions::default())
+    }
+}
+
+/// Write the given layers as R12 `POLYLINE` entities in model space, laid out like [`svg::write`]
+/// does with the y axis flipped so that the drawing is upright in CAD tools.
+///
+/// Each layer is written on its own DXF layer colored with the AutoCAD Color Index closest to its
+/// stroke, closed rings are flagged as closed and single points become circles as big as their
+/// markers. Layer transforms and the caption are applied, while fills, dashes, line widths and
+/// the legend are left to the CAM tool. R12 is the oldest version that every tool still reads,
+/// and it doesn't need the handles and the object tables of the later ones.
+pub fn write(layers: Vec<StyledPaths>, options: &DxfOptions, mut f: impl Write) -> io::Result<()> {
+    let svg = &options.svg;
+    let n = svg.precision;
+
+    let viewport = svg::viewport(&svg.canvas(), &layers);
+    let (_, h) = viewport.size();
+    let (vx, vy) = viewport.min_corner();
+
+    // canvas units grow downwards from the top left corner of the drawing
+    let k = 1.0 / options.units.px_per_unit();
+    let to_dxf = |(x, y): (f64, f64)| ((x - vx) * k, (vy + h - y) * k);
+
+    let mut names = layers
+        .iter()
+        .enumerate()
+        .map(|(i, l)| (format!("roads-{}", i), l.style.color))
+        .collect::<Vec<_>>();
+    if let Some(caption) = &svg.caption {
+        names.push(("caption".to_string(), caption.color));
+    }
+
+    write_pair(&mut f, 0, "SECTION")?;
+    write_pair(&mut f, 2, "HEADER")?;
+    write_pair(&mut f, 9, "$ACADVER")?;
+    write_pair(&mut f, 1, "AC1009")?;
+    write_pair(&mut f, 9, "$INSUNITS")?;
+    write_pair(&mut f, 70, insunits(options.units))?;
+    write_pair(&mut f, 0, "ENDSEC")?;
+
+    write_tables(&mut f, &names)?;
+
+    write_pair(&mut f, 0, "SECTION")?;
+    write_pair(&mut f, 2, "ENTITIES")?;
+
+    for (l, (layer, _)) in layers.iter().zip(&names) {
+        let t = &l.style.transform;
+        let tr = |p| to_dxf(t.apply(viewport.to_canvas(p)));
+
+        for p in l.paths.iter().filter(|p| !p.is_empty()) {
+            if let [p] = p[..] {
+                let (x, y) = tr(p);
+                write_pair(&mut f, 0, "CIRCLE")?;
+                write_pair(&mut f, 8, layer)?;
+                write_point(&mut f, 0, (x, y), n)?;
+                write_pair(&mut f, 40, format!("{:.n$}", l.style.radius * k))?;
+                continue;
+            }
+
+            // the closing point is implied by the closed flag
+            let (points, closed) = if is_ring(p) {
+                (&p[..p.len() - 1], true)
+            } else {
+                (&p[..], false)
+            };
+            write_polyline(&mut f, layer, points.iter().map(|&p| tr(p)), closed, n)?;
+        }
+    }
+
+    if let Some(caption) = &svg.caption {
+        for p in caption.paths((vx, vy + h)) {
+            let points = p.into_iter().map(to_dxf);
+            write_polyline(&mut f, "caption", points, false, n)?;
+        }
+    }
+
+    write_pair(&mut f, 0, "ENDSEC")?;
+    write_pair(&mut f, 0, "EOF")
+}
+
+/// Write the line types and the layers with the given names and colors.
+fn write_tables(mut f: impl Write, layers: &[(String, Color)]) -> io::Result<()> {
+    write_pair(&mut f, 0, "SECTION")?;
+    write_pair(&mut f, 2, "TABLES")?;
+
+    write_pair(&mut f, 0, "TABLE")?;
+    write_pair(&mut f, 2, "LTYPE")?;
+    write_pair(&mut f, 70, 1)?;
+    write_pair(&mut f, 0, "LTYPE")?;
+    write_pair(&mut f, 2, "CONTINUOUS")?;
+    write_pair(&mut f, 70, 0)?;
+    write_pair(&mut f, 3, "Solid line")?;
+    write_pair(&mut f, 72, 65)?;
+    write_pair(&mut f, 73, 0)?;
+    write_pair(&mut f, 40, "0.0")?;
+    write_pair(&mut f, 0, "ENDTAB")?;
+
+    write_pair(&mut f, 0, "TABLE")?;
+    write_pair(&mut f, 2, "LAYER")?;
+    write_pair(&mut f, 70, layers.len())?;
+    for (name, color) in layers {
+        write_pair(&mut f, 0, "LAYER")?;
+        write_pair(&mut f, 2, name)?;
+        write_pair(&mut f, 70, 0)?;
+        write_pair(&mut f, 62, aci(*color))?;
+        write_pair(&mut f, 6, "CONTINUOUS")?;
+    }
+    write_pair(&mut f, 0, "ENDTAB")?;
+
+    write_pair(&mut f, 0, "ENDSEC")
+}
+
+/// Write a `POLYLINE` followed by its vertices, its color is the one of its layer.
+fn write_polyline(
+    mut f: impl Write,
+    layer: &str,
+    points: impl Iterator<Item = (f64, f64)>,
+    closed: bool,
+    n: usize,
+) -> io::Result<()> {
+    write_pair(&mut f, 0, "POLYLINE")?;
+    write_pair(&mut f, 8, layer)?;
+    write_pair(&mut f, 66, 1)?;
+    write_point(&mut f, 0, (0.0, 0.0), n)?;
+    write_pair(&mut f, 70, u8::from(closed))?;
+    for p in points {
+        write_pair(&mut f, 0, "VERTEX")?;
+        write_pair(&mut f, 8, layer)?;
+        write_point(&mut f, 0, p, n)?;
+    }
+    write_pair(&mut f, 0, "SEQEND")?;
+    write_pair(&mut f, 8, layer)
+}
+
+/// Write the coordinates of a point, `i` picks which point of the entity it is.
+fn write_point(mut f: impl Write, i: u16, (x, y): (f64, f64), n: usize) -> io::Result<()> {
+    write_pair(&mut f, 10 + i, format!("{:.n$}", x))?;
+    write_pair(&mut f, 20 + i, format!("{:.n$}", y))?;
+    write_pair(&mut f, 30 + i, format!("{:.n$}", 0.0))
+}
+
+/// The AutoCAD Color Index closest to the given color. Black and white are both 7, which tools
+/// show in the color opposite to their background, as is no color at all.
+fn aci(color: Color) -> u8 {
+    let (r, g, b) = match color.rgb() {
+        None | Some((0, 0, 0)) | Some((255, 255, 255)) => return 7,
+        Some(rgb) => rgb,
+    };
+
+    let distance = |(r2, g2, b2): (u8, u8, u8)| {
+        let d = |a: u8, b: u8| (f64::from(a) - f64::from(b)).powi(2);
+        d(r, r2) + d(g, g2) + d(b, b2)
+    };
+    (1..=255)
+        .filter(|&i| i != 7)
+        .min_by(|&a, &b| distance(aci_rgb(a)).total_cmp(&distance(aci_rgb(b))))
+        .unwrap_or(7)
+}
+
+/// The color of the given index of the standard AutoCAD palette: the 9 named colors, then 24 hues
+/// each in 5 shades of full and half saturation, then 6 grays.
+fn aci_rgb(i: u8) -> (u8, u8, u8) {
+    const NAMED: [(u8, u8, u8); 9] = [
+        (255, 0, 0),
+        (255, 255, 0),
+        (0, 255, 0),
+        (0, 255, 255),
+        (0, 0, 255),
+        (255, 0, 255),
+        (255, 255, 255),
+        (128, 128, 128),
+        (192, 192, 192),
+    ];
+    const SHADES: [f64; 5] = [1.0, 0.65, 0.5, 0.3, 0.15];
+    const GRAYS: [u8; 6] = [51, 91, 132, 173, 214, 255];
+
+    match i {
+        0 => (0, 0, 0),
+        1..=9 => NAMED[usize::from(i) - 1],
+        10..=249 => {
+            let hue = f64::from((i - 10) / 10) * 15.0;
+            let shade = SHADES[usize::from((i - 10) % 10 / 2)];
+            let saturation = if (i - 10).is_multiple_of(2) { 1.0 } else { 0.5 };
+            hsv(hue, saturation, shade)
+        }
+        _ => {
+            let v = GRAYS[usize::from(i - 250)];
+            (v, v, v)
+        }
+    }
+}
+
+fn hsv(hue: f64, saturation: f64, value: f64) -> (u8, u8, u8) {
+    let c = value * saturation;
+    let x = c * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let (r, g, b) = match (hue / 60.0) as u8 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let m = value - c;
+    let to_u8 = |v: f64| ((v + m) * 255.0).round() as u8;
+    (to_u8(r), to_u8(g), to_u8(b))
+}
+
+/// Write a group code followed by its value, each on its own line.
+fn write_pair(mut f: impl Write, code: u16, value: impl std::fmt::Display) -> io::Result<()> {
+    write!(f, "{:>3}\r\n{}\r\n", code, value)
+}
+
+/// The `$INSUNITS` code of the given unit, pixels and points have none and are unitless.
+fn insunits(unit: LengthUnit) -> u8 {
+    match unit {
+        LengthUnit::In => 1,
+        LengthUnit::Mm => 4,
+        LengthUnit::Cm => 5,
+        LengthUnit::Px | LengthUnit::Pt => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::style::Style;
+
+    /// The group codes and values of a DXF file, checking that they come in pairs.
+    fn parse(dxf: &str) -> Vec<(u16, &str)> {
+        let lines = dxf.split("\r\n").collect::<Vec<_>>();
+        assert_eq!(lines.last(), Some(&""));
+        lines[..lines.len() - 1]
+            .chunks(2)
+            .map(|pair| (pair[0].trim().parse().unwrap(), pair[1]))
+            .collect()
+    }
+
+    #[test]
+    fn r12_polylines() {
+        let ring = vec![(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 0.0)];
+        let layers = vec![
+            StyledPaths {
+                style: Style::new(Color::Rgb(255, 0, 0), 1.0),
+                paths: vec![ring, vec![(0.0, 5.0), (10.0, 5.0)], vec![]],
+            },
+            StyledPaths {
+                style: Style::new(Color::BLACK, 1.0),
+                paths: vec![vec![(5.0, 5.0)]],
+            },
+        ];
+        let options = DxfOptions::new(LengthUnit::Mm, SvgOptions::new(100.0, 100.0));
+
+        let mut out = vec![];
+        write(layers, &options, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let pairs = parse(&out);
+
+        let sections = pairs
+            .windows(2)
+            .filter(|w| w[0] == (0, "SECTION"))
+            .map(|w| w[1])
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sections,
+            vec![(2, "HEADER"), (2, "TABLES"), (2, "ENTITIES")]
+        );
+        assert!(pairs.contains(&(1, "AC1009")));
+        assert_eq!(pairs.last(), Some(&(0, "EOF")));
+
+        let layer_colors = pairs
+            .windows(5)
+            .filter(|w| w[0] == (0, "LAYER"))
+            .map(|w| (w[1].1, w[3].1))
+            .collect::<Vec<_>>();
+        assert_eq!(layer_colors, vec![("roads-0", "1"), ("roads-1", "7")]);
+
+        // every polyline is made of its vertices and closed by a SEQEND
+        let entities = pairs
+            .iter()
+            .filter(|(code, _)| *code == 0)
+            .map(|(_, v)| *v)
+            .skip_while(|v| *v != "ENDTAB")
+            .collect::<Vec<_>>()
+            .join(" ");
+        assert!(entities.contains(
+            "POLYLINE VERTEX VERTEX VERTEX SEQEND POLYLINE VERTEX VERTEX SEQEND CIRCLE ENDSEC EOF"
+        ));
+        assert!(!out.contains("\r\n420\r\n") && !out.contains("LWPOLYLINE"));
+    }
+
+    #[test]
+    fn closest_aci() {
+        assert_eq!(aci(Color::Rgb(255, 0, 0)), 1);
+        assert_eq!(aci(Color::Rgb(0, 0, 250)), 5);
+        assert_eq!(aci(Color::Rgb(130, 130, 130)), 8);
+        assert_eq!(aci(Color::WHITE), 7);
+        assert_eq!(aci(Color::None), 7);
+    }
+}
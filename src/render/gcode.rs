@@ -0,0 +1,157 @@
+//! Export roads to [G-code](https://en.wikipedia.org/wiki/G-code) for grbl based pen plotters.
+
+use std::io::{self, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    render::{
+        pdf::PageSize,
+        svg::{self, SvgOptions},
+    },
+    style::StyledPaths,
+};
+
+/// How [`write`] lays out the drawing and drives the plotter.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GcodeOptions {
+    /// The area the pen can reach, the origin is its bottom left corner.
+    pub work_area: PageSize,
+
+    /// Speed of the drawing moves, in mm per minute. Travel moves go as fast as the machine can.
+    pub feed_rate: f64,
+
+    /// Commands lifting and lowering the pen, e.g. for a servo `M3 S30` and `M3 S90`. Multiple
+    /// commands are separated by new lines.
+    pub pen_up: String,
+    pub pen_down: String,
+
+    /// Seconds to wait after the pen is lifted or lowered, so that a slow servo is done moving
+    /// before the next move starts. Zero doesn't wait at all.
+    pub pen_delay: f64,
+
+    /// The same layout of the svg, only the geometry is plotted though.
+    pub svg: SvgOptions,
+}
+
+impl GcodeOptions {
+    pub fn new(work_area: PageSize, svg: SvgOptions) -> Self {
+        GcodeOptions {
+            work_area,
+            feed_rate: 3000.0,
+            pen_up: "M5".to_string(),
+            pen_down: "M3 S1000".to_string(),
+            pen_delay: 0.2,
+            svg,
+        }
+    }
+}
+
+impl Default for GcodeOptions {
+    fn default() -> Self {
+        Self::new(PageSize::A4, SvgOptions::default())
+    }
+}
+
+/// Write the given layers as the moves of a pen plotter, laid out like [`svg::write`] does and
+/// scaled to fit in the work area while preserving the aspect ratio.
+///
+/// Every path is drawn with the pen down, one after the other in the order of the layers, while
+/// paths made of a single point become dots. Layer transforms and the caption are applied, while
+/// colors, widths, dashes and fills are not since there's a single pen. Coordinates are absolute
+/// and in millimeters, with the y axis growing upwards like on most machines.
+pub fn write(
+    layers: Vec<StyledPaths>,
+    options: &GcodeOptions,
+    mut f: impl Write,
+) -> io::Result<()> {
+    let svg = &options.svg;
+    let n = svg.precision;
+
+    let viewport = svg::viewport(&svg.canvas(), &layers);
+    let size = viewport.size();
+    let (vx, vy) = viewport.min_corner();
+
+    // fit the drawing and its margin in the work area, centered
+    let (aw, ah) = (options.work_area.width, options.work_area.height);
+    let margin = svg.total_margin();
+    let (dw, dh) = (size.0 + 2.0 * margin, size.1 + 2.0 * margin);
+    let k = f64::min(aw / dw, ah / dh);
+    let k = if k.is_finite() && k > 0.0 { k } else { 1.0 };
+    let (ox, oy) = ((aw - dw * k) / 2.0, (ah - dh * k) / 2.0);
+    let to_machine =
+        |(x, y): (f64, f64)| (ox + (x - vx + margin) * k, ah - oy - (y - vy + margin) * k);
+
+    let mut paths = vec![];
+    for l in &layers {
+        let t = &l.style.transform;
+        paths.extend(l.paths.iter().map(|p| {
+            p.iter()
+                .map(|&p| to_machine(t.apply(viewport.to_canvas(p))))
+                .collect::<Vec<_>>()
+        }));
+    }
+    if let Some(caption) = &svg.caption {
+        paths.extend(
+            caption
+                .paths((vx, vy + size.1))
+                .into_iter()
+                .map(|p| p.into_iter().map(to_machine).collect()),
+        );
+    }
+
+    paths.retain(|p: &Vec<_>| !p.is_empty());
+
+    writeln!(f, "; roads, {} paths", paths.len())?;
+    writeln!(f, "G21 ; millimeters")?;
+    writeln!(f, "G90 ; absolute coordinates")?;
+    let pen = |f: &mut dyn Write, command: &str| -> io::Result<()> {
+        writeln!(f, "{}", command)?;
+        if options.pen_delay > 0.0 {
+            writeln!(f, "G4 P{:.3}", options.pen_delay)?;
+        }
+        Ok(())
+    };
+    pen(&mut f, &options.pen_up)?;
+
+    for p in paths {
+        let (x, y) = p[0];
+        writeln!(f, "G0 X{:.n$} Y{:.n$}", x, y)?;
+        pen(&mut f, &options.pen_down)?;
+        for (i, (x, y)) in p.into_iter().enumerate().skip(1) {
+            if i == 1 {
+                writeln!(f, "G1 X{:.n$} Y{:.n$} F{}", x, y, options.feed_rate)?;
+            } else {
+                writeln!(f, "G1 X{:.n$} Y{:.n$}", x, y)?;
+            }
+        }
+        pen(&mut f, &options.pen_up)?;
+    }
+
+    writeln!(f, "G0 X0 Y0")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{color::Color, style::Style};
+
+    #[test]
+    fn waits_for_the_pen() {
+        let layers = vec![StyledPaths {
+            style: Style::new(Color::BLACK, 1.0),
+            paths: vec![vec![], vec![(0.0, 0.0), (10.0, 10.0)], vec![]],
+        }];
+        let options = GcodeOptions::new(PageSize::A4, SvgOptions::new(100.0, 100.0));
+
+        let mut out = vec![];
+        write(layers, &options, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.starts_with("; roads, 1 paths\n"));
+        assert_eq!(out.matches("G0 X").count(), 2);
+        assert_eq!(out.matches("M3 S1000\nG4 P0.200\nG1 ").count(), 1);
+        assert_eq!(out.matches("M5\nG4 P0.200\n").count(), 2);
+    }
+}
@@ -0,0 +1,674 @@
+//! Export roads to [PDF](https://www.iso.org/standard/75839.html) pages of a physical size, ready
+//! to be sent to a print shop.
+
+use std::{
+    fmt,
+    io::{self, Write},
+    str::FromStr,
+};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    color::{Color, ColorSpace},
+    render::svg::{self, is_ring, LineCap, LineJoin, SvgOptions},
+    style::{Marker, StyledPaths},
+    transform::Affine,
+};
+
+/// Points per millimeter, PDF user space units are 1/72 of an inch.
+const PT_PER_MM: f64 = 72.0 / 25.4;
+
+/// How [`write`] lays out the drawing on the page.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PdfOptions {
+    pub page: PageSize,
+
+    /// Blank space on every side of the page, in millimeters.
+    pub margin: f64,
+
+    /// The same layout and styling of the svg, the drawing is laid out in its canvas first and
+    /// then scaled to fit the page so that line widths keep their proportions.
+    pub svg: SvgOptions,
+
+    /// Title of the document shown by PDF viewers.
+    #[serde(skip)]
+    pub title: Option<String>,
+}
+
+/// Size of a page in millimeters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PageSize {
+    pub width: f64,
+    pub height: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsePageSizeError(String);
+
+impl PdfOptions {
+    pub fn new(page: PageSize, svg: SvgOptions) -> Self {
+        PdfOptions {
+            page,
+            margin: 10.0,
+            svg,
+            title: None,
+        }
+    }
+}
+
+impl Default for PdfOptions {
+    fn default() -> Self {
+        Self::new(PageSize::A4, SvgOptions::default())
+    }
+}
+
+impl PageSize {
+    pub const A0: PageSize = PageSize::mm(841.0, 1189.0);
+    pub const A1: PageSize = PageSize::mm(594.0, 841.0);
+    pub const A2: PageSize = PageSize::mm(420.0, 594.0);
+    pub const A3: PageSize = PageSize::mm(297.0, 420.0);
+    pub const A4: PageSize = PageSize::mm(210.0, 297.0);
+    pub const A5: PageSize = PageSize::mm(148.0, 210.0);
+
+    const NAMED: [(&'static str, PageSize); 6] = [
+        ("a0", PageSize::A0),
+        ("a1", PageSize::A1),
+        ("a2", PageSize::A2),
+        ("a3", PageSize::A3),
+        ("a4", PageSize::A4),
+        ("a5", PageSize::A5),
+    ];
+
+    pub const fn mm(width: f64, height: f64) -> Self {
+        PageSize { width, height }
+    }
+
+    /// The same page turned sideways.
+    pub fn landscape(self) -> Self {
+        PageSize::mm(self.height, self.width)
+    }
+
+    /// Width and height in points.
+    pub fn to_points(self) -> (f64, f64) {
+        (self.width * PT_PER_MM, self.height * PT_PER_MM)
+    }
+}
+
+/// Write the given layers as a single page pdf, laid out like [`svg::write`] does and scaled to
+/// fit in the page while preserving the aspect ratio.
+///
+/// All the geometry is written as vector paths: markers, fills, dashes, layer transforms, the
+/// caption and the print marks are drawn like in the svg. The legend is not drawn since it needs
+/// fonts. The metadata of the svg options is stored in the document information dictionary.
+/// With the CMYK color space the colors use the DeviceCMYK operators instead, while the spot
+/// colors are painted in their own Separation color space, falling back to their CMYK or sRGB
+/// color on the devices without the ink.
+pub fn write(layers: Vec<StyledPaths>, options: &PdfOptions, mut f: impl Write) -> io::Result<()> {
+    let mut doc = Document::default();
+
+    let content = page_content(&layers, options)?;
+    let (pw, ph) = options.page.to_points();
+
+    doc.object("<< /Type /Catalog /Pages 2 0 R >>".as_bytes())?;
+    doc.object("<< /Type /Pages /Kids [3 0 R] /Count 1 >>".as_bytes())?;
+    doc.object(
+        format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.2} {:.2}] /Contents 4 0 R /Resources << {}>> >>",
+            pw,
+            ph,
+            separations(&options.svg)
+        )
+        .as_bytes(),
+    )?;
+
+    let mut stream = format!("<< /Length {} >>\nstream\n", content.len()).into_bytes();
+    stream.extend(content);
+    stream.extend(b"\nendstream");
+    doc.object(&stream)?;
+
+    let mut info = "<< /Producer (roads)".to_string();
+    if let Some(title) = &options.title {
+        info += &format!(" /Title {}", text_string(title));
+    }
+    for (k, v) in &options.svg.metadata {
+        info += &format!(" /{} {}", name(k), text_string(v));
+    }
+    info += " >>";
+    doc.object(info.as_bytes())?;
+
+    doc.finish(&mut f)
+}
+
+/// The drawing operators of the page.
+fn page_content(layers: &[StyledPaths], options: &PdfOptions) -> io::Result<Vec<u8>> {
+    let svg = &options.svg;
+    let n = svg.precision;
+
+    let viewport = svg::viewport(&svg.canvas(), layers);
+    let size = viewport.size();
+    let (vx, vy) = viewport.min_corner();
+    let tr = |p| viewport.to_canvas(p);
+
+    // fit the drawing and its margin in the printable area, centered in the page
+    let (pw, ph) = options.page.to_points();
+    let page_margin = options.margin * PT_PER_MM;
+    let margin = svg.total_margin();
+    let (dw, dh) = (size.0 + 2.0 * margin, size.1 + 2.0 * margin);
+    let k = f64::min((pw - 2.0 * page_margin) / dw, (ph - 2.0 * page_margin) / dh);
+    let k = if k.is_finite() && k > 0.0 { k } else { 1.0 };
+    let (ox, oy) = ((pw - dw * k) / 2.0, (ph - dh * k) / 2.0);
+
+    let mut f = vec![];
+
+    // the canvas y axis grows downwards like in the svg, pdf pages grow upwards instead
+    writeln!(
+        f,
+        "{:.4} 0 0 {:.4} {:.4} {:.4} cm",
+        k,
+        -k,
+        ox - (vx - margin) * k,
+        ph - oy + (vy - margin) * k
+    )?;
+    writeln!(
+        f,
+        "{} J {} j",
+        line_cap(svg.stroke.cap),
+        line_join(svg.stroke.join)
+    )?;
+
+    if let Some(bg) = fill_color(svg.background, svg) {
+        writeln!(
+            f,
+            "{} {vx:.n$} {vy:.n$} {w:.n$} {h:.n$} re f",
+            bg,
+            w = size.0,
+            h = size.1
+        )?;
+    }
+
+    for l in layers {
+        let style = &l.style;
+        writeln!(f, "q")?;
+        if !style.transform.is_identity() {
+            write_transform(&mut f, &style.transform)?;
+        }
+
+        // same as in the svg, only rings are filled and open paths are never closed, the fill
+        // is skipped without rings since painting operators need a path
+        let filled = fill_color(style.fill, svg);
+        let mut rings = l.paths.iter().filter(|p| is_ring(p)).peekable();
+        if let Some(fill) = filled.as_ref().filter(|_| rings.peek().is_some()) {
+            writeln!(f, "{}", fill)?;
+            for p in rings {
+                write_polyline(&mut f, p.iter().map(|&p| tr(p)), n)?;
+                writeln!(f, "h")?;
+            }
+            writeln!(f, "f*")?;
+        }
+
+        // markers are filled with the stroke color
+        if let (Some(stroke), Some(fill)) =
+            (stroke_color(style.color, svg), fill_color(style.color, svg))
+        {
+            writeln!(f, "{} {} {} w", stroke, fill, style.width)?;
+            if !style.dash.is_empty() {
+                let dash = style.dash.iter().map(f64::to_string).collect::<Vec<_>>();
+                writeln!(f, "[{}] 0 d", dash.join(" "))?;
+            }
+
+            for p in l.paths.iter().filter(|p| !p.is_empty()) {
+                if let [p] = p[..] {
+                    write_marker(&mut f, style.marker, tr(p), style.radius, n)?;
+                    continue;
+                }
+
+                write_polyline(&mut f, p.iter().map(|&p| tr(p)), n)?;
+                if filled.is_some() && is_ring(p) {
+                    writeln!(f, "h")?;
+                }
+                writeln!(f, "S")?;
+            }
+        }
+
+        writeln!(f, "Q")?;
+    }
+
+    if let Some(caption) = &svg.caption {
+        writeln!(f, "q 1 J 1 j {} w", caption.stroke_width())?;
+        if let Some(stroke) = stroke_color(caption.color, svg) {
+            writeln!(f, "{}", stroke)?;
+            for p in caption.paths((vx, vy + size.1)) {
+                write_polyline(&mut f, p, n)?;
+                writeln!(f, "S")?;
+            }
+        }
+        writeln!(f, "Q")?;
+    }
+
+    if let Some(marks) = &svg.marks {
+        // in CMYK the marks are in registration color, so that they show on every plate
+        let registration = match svg.color_space {
+            ColorSpace::Srgb => "0 0 0 RG",
+            ColorSpace::Cmyk => "1 1 1 1 K",
+        };
+        writeln!(f, "q {} 0.5 w [] 0 d", registration)?;
+        let paths = marks
+            .crop_marks((vx, vy), size)
+            .into_iter()
+            .chain(marks.registration_marks((vx, vy), size));
+        for p in paths {
+            write_polyline(&mut f, p, n)?;
+            writeln!(f, "S")?;
+        }
+        writeln!(f, "Q")?;
+    }
+
+    Ok(f)
+}
+
+fn write_transform(mut f: impl Write, t: &Affine) -> io::Result<()> {
+    writeln!(f, "{} {} {} {} {} {} cm", t.a, t.b, t.c, t.d, t.e, t.f)
+}
+
+/// Append the subpath of the given points, already in canvas coordinates.
+fn write_polyline(
+    mut f: impl Write,
+    points: impl IntoIterator<Item = (f64, f64)>,
+    n: usize,
+) -> io::Result<()> {
+    for (i, (x, y)) in points.into_iter().enumerate() {
+        let op = if i == 0 { 'm' } else { 'l' };
+        write!(f, "{:.n$} {:.n$} {} ", x, y, op)?;
+    }
+    writeln!(f)
+}
+
+/// Fill the marker centered at the given point, like the svg symbols.
+fn write_marker(
+    mut f: impl Write,
+    marker: Marker,
+    (x, y): (f64, f64),
+    r: f64,
+    n: usize,
+) -> io::Result<()> {
+    match marker {
+        Marker::Circle => {
+            // four cubic béziers are close enough to a circle
+            let c = r * 0.5523;
+            writeln!(
+                f,
+                "{:.n$} {:.n$} m \
+                 {:.n$} {:.n$} {:.n$} {:.n$} {:.n$} {:.n$} c \
+                 {:.n$} {:.n$} {:.n$} {:.n$} {:.n$} {:.n$} c \
+                 {:.n$} {:.n$} {:.n$} {:.n$} {:.n$} {:.n$} c \
+                 {:.n$} {:.n$} {:.n$} {:.n$} {:.n$} {:.n$} c f",
+                x + r,
+                y,
+                x + r,
+                y + c,
+                x + c,
+                y + r,
+                x,
+                y + r,
+                x - c,
+                y + r,
+                x - r,
+                y + c,
+                x - r,
+                y,
+                x - r,
+                y - c,
+                x - c,
+                y - r,
+                x,
+                y - r,
+                x + c,
+                y - r,
+                x + r,
+                y - c,
+                x + r,
+                y,
+            )
+        }
+        Marker::Square => writeln!(
+            f,
+            "{:.n$} {:.n$} {:.n$} {:.n$} re f",
+            x - r,
+            y - r,
+            2.0 * r,
+            2.0 * r
+        ),
+        Marker::Triangle => writeln!(
+            f,
+            "{:.n$} {:.n$} m {:.n$} {:.n$} l {:.n$} {:.n$} l h f",
+            x,
+            y - r,
+            x + r * 0.866,
+            y + r / 2.0,
+            x - r * 0.866,
+            y + r / 2.0
+        ),
+    }
+}
+
+/// The operands setting the given color in the given space, along with whether it's CMYK.
+fn color_operands(c: Color, space: ColorSpace) -> Option<(String, bool)> {
+    if space == ColorSpace::Cmyk {
+        let (c, m, y, k) = c.cmyk()?;
+        return Some((format!("{:.3} {:.3} {:.3} {:.3}", c, m, y, k), true));
+    }
+
+    let (r, g, b) = c.rgb()?;
+    let c = |c: u8| f64::from(c) / 255.0;
+    Some((format!("{:.3} {:.3} {:.3}", c(r), c(g), c(b)), false))
+}
+
+fn fill_color(c: Color, svg: &SvgOptions) -> Option<String> {
+    if let Some(i) = spot_index(c, svg) {
+        return Some(format!("/Spot{} cs 1 scn", i));
+    }
+
+    let (operands, cmyk) = color_operands(c, svg.color_space)?;
+    Some(format!("{} {}", operands, if cmyk { "k" } else { "rg" }))
+}
+
+fn stroke_color(c: Color, svg: &SvgOptions) -> Option<String> {
+    if let Some(i) = spot_index(c, svg) {
+        return Some(format!("/Spot{} CS 1 SCN", i));
+    }
+
+    let (operands, cmyk) = color_operands(c, svg.color_space)?;
+    Some(format!("{} {}", operands, if cmyk { "K" } else { "RG" }))
+}
+
+/// The index of the Separation color space of the given color among the resources of the page,
+/// if it's printed with a spot ink.
+fn spot_index(c: Color, svg: &SvgOptions) -> Option<usize> {
+    svg.spots.iter().position(|(spot, _)| spot == c)
+}
+
+/// The color space resources of the page with a Separation color space for each spot ink, whose
+/// tint transform goes from paper white to the color in the color space of the document.
+fn separations(svg: &SvgOptions) -> String {
+    if svg.spots.is_empty() {
+        return String::new();
+    }
+
+    let (alternate, white) = match svg.color_space {
+        ColorSpace::Srgb => ("/DeviceRGB", "1 1 1"),
+        ColorSpace::Cmyk => ("/DeviceCMYK", "0 0 0 0"),
+    };
+    let mut resources = "/ColorSpace <<".to_string();
+    for (i, (c, ink)) in svg.spots.iter().enumerate() {
+        let (operands, _) = color_operands(c, svg.color_space).unwrap_or_default();
+        resources += &format!(
+            " /Spot{} [/Separation /{} {} << /FunctionType 2 /Domain [0 1] /C0 [{}] /C1 [{}] /N 1 >>]",
+            i,
+            name(ink),
+            alternate,
+            white,
+            operands
+        );
+    }
+    resources + " >> "
+}
+
+fn line_cap(cap: LineCap) -> u8 {
+    match cap {
+        LineCap::Butt => 0,
+        LineCap::Round => 1,
+        LineCap::Square => 2,
+    }
+}
+
+fn line_join(join: LineJoin) -> u8 {
+    match join {
+        LineJoin::Miter => 0,
+        LineJoin::Round => 1,
+        LineJoin::Bevel => 2,
+    }
+}
+
+/// A PDF name made of the given key, replacing the characters that would need escaping.
+fn name(key: &str) -> String {
+    key.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// A PDF text string as UTF-16BE hex, so that any text can be stored without escaping.
+fn text_string(s: &str) -> String {
+    let mut hex = "<FEFF".to_string();
+    for u in s.encode_utf16() {
+        hex += &format!("{:04X}", u);
+    }
+    hex + ">"
+}
+
+/// The objects of a PDF file, numbered from 1 in order, along with their offsets for the
+/// cross-reference table.
+#[derive(Debug, Default)]
+struct Document {
+    buf: Vec<u8>,
+    offsets: Vec<usize>,
+}
+
+impl Document {
+    fn object(&mut self, body: &[u8]) -> io::Result<()> {
+        if self.buf.is_empty() {
+            // the binary comment tells transfer tools the file is not plain text
+            self.buf.extend(b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n");
+        }
+
+        self.offsets.push(self.buf.len());
+        writeln!(self.buf, "{} 0 obj", self.offsets.len())?;
+        self.buf.extend(body);
+        writeln!(self.buf, "\nendobj")
+    }
+
+    fn finish(self, mut f: impl Write) -> io::Result<()> {
+        let Document { mut buf, offsets } = self;
+
+        let xref = buf.len();
+        writeln!(buf, "xref\n0 {}", offsets.len() + 1)?;
+        write!(buf, "0000000000 65535 f\r\n")?;
+        for o in &offsets {
+            write!(buf, "{:010} 00000 n\r\n", o)?;
+        }
+
+        // the catalog is the first object and the information dictionary the last one
+        writeln!(
+            buf,
+            "trailer\n<< /Size {} /Root 1 0 R /Info {} 0 R >>\nstartxref\n{}\n%%EOF",
+            offsets.len() + 1,
+            offsets.len(),
+            xref
+        )?;
+
+        f.write_all(&buf)
+    }
+}
+
+/// The ISO 216 name, optionally followed by `-landscape`, or `<width>x<height>` in millimeters,
+/// e.g. `a4`, `a3-landscape` or `500x700mm`.
+impl FromStr for PageSize {
+    type Err = ParsePageSizeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        let err = || ParsePageSizeError(format!("invalid page size '{}'", s));
+
+        let (base, landscape) = match s.strip_suffix("-landscape") {
+            Some(base) => (base, true),
+            None => (s.as_str(), false),
+        };
+        if let Some((_, page)) = Self::NAMED.iter().find(|(n, _)| *n == base) {
+            return Ok(if landscape { page.landscape() } else { *page });
+        }
+
+        let (w, h) = s
+            .strip_suffix("mm")
+            .unwrap_or(&s)
+            .split_once('x')
+            .ok_or_else(err)?;
+        let parse = |v: &str| {
+            v.trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|v| v.is_finite() && *v > 0.0)
+                .ok_or_else(err)
+        };
+        Ok(PageSize::mm(parse(w)?, parse(h)?))
+    }
+}
+
+impl fmt::Display for PageSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (n, page) in Self::NAMED {
+            if page == *self {
+                return f.write_str(n);
+            }
+            if page.landscape() == *self {
+                return write!(f, "{}-landscape", n);
+            }
+        }
+
+        write!(f, "{}x{}mm", self.width, self.height)
+    }
+}
+
+/// Serialized as its name or size, e.g. `a4` or `500x700mm`.
+impl Serialize for PageSize {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for PageSize {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        String::deserialize(d)?.parse().map_err(de::Error::custom)
+    }
+}
+
+impl fmt::Display for ParsePageSizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ParsePageSizeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::style::Style;
+
+    fn layers(paths: Vec<Vec<(f64, f64)>>) -> Vec<StyledPaths> {
+        vec![StyledPaths {
+            style: Style::new(Color::Rgb(0, 0, 0), 1.0),
+            paths,
+        }]
+    }
+
+    /// The page coordinates of the given canvas points, in millimeters.
+    fn to_page(
+        options: &PdfOptions,
+        layers: &[StyledPaths],
+        points: &[(f64, f64)],
+    ) -> Vec<(f64, f64)> {
+        let content = String::from_utf8(page_content(layers, options).unwrap()).unwrap();
+        let cm = content
+            .lines()
+            .next()
+            .unwrap()
+            .split_whitespace()
+            .take(6)
+            .map(|v| v.parse::<f64>().unwrap())
+            .collect::<Vec<_>>();
+
+        points
+            .iter()
+            .map(|&(x, y)| {
+                let x = cm[0] * x + cm[2] * y + cm[4];
+                let y = cm[1] * x + cm[3] * y + cm[5];
+                (
+                    (x / PT_PER_MM * 100.0).round() / 100.0,
+                    (y / PT_PER_MM * 100.0).round() / 100.0,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fit_in_the_margins_upside_down() {
+        // a drawing twice as wide as tall on a square page
+        let options = PdfOptions::new(PageSize::mm(100.0, 100.0), SvgOptions::new(200.0, 100.0));
+        let layers = layers(vec![vec![(0.0, 0.0), (2.0, 1.0)]]);
+        let viewport = svg::viewport(&options.svg.canvas(), &layers);
+        let (vx, vy) = viewport.min_corner();
+        let (w, h) = viewport.size();
+
+        // the width fills the page within its margin, the height is centered and the top left
+        // corner of the canvas ends up at the top left of the page, whose y axis grows upwards
+        assert_eq!(
+            to_page(&options, &layers, &[(vx, vy), (vx + w, vy + h)]),
+            vec![(10.0, 70.0), (90.0, 30.0)]
+        );
+
+        let options = PdfOptions {
+            margin: 0.0,
+            ..options
+        };
+        assert_eq!(
+            to_page(&options, &layers, &[(vx, vy), (vx + w, vy + h)]),
+            vec![(0.0, 75.0), (100.0, 25.0)]
+        );
+    }
+
+    #[test]
+    fn page_size() {
+        assert_eq!(
+            PageSize::A4.to_points(),
+            (210.0 * PT_PER_MM, 297.0 * PT_PER_MM)
+        );
+        assert_eq!(PageSize::A4.landscape(), PageSize::mm(297.0, 210.0));
+        assert_eq!(
+            "a3-landscape".parse::<PageSize>().unwrap(),
+            PageSize::A3.landscape()
+        );
+        assert_eq!(
+            "500x700mm".parse::<PageSize>().unwrap(),
+            PageSize::mm(500.0, 700.0)
+        );
+        assert_eq!(PageSize::mm(500.0, 700.0).to_string(), "500x700mm");
+
+        let mut out = vec![];
+        write(layers(vec![]), &PdfOptions::default(), &mut out).unwrap();
+        let out = String::from_utf8_lossy(&out);
+        assert!(out.contains("/MediaBox [0 0 595.28 841.89]"));
+    }
+
+    #[test]
+    fn title() {
+        let pdf = |title: Option<&str>| {
+            let options = PdfOptions {
+                title: title.map(str::to_string),
+                ..PdfOptions::default()
+            };
+            let mut out = vec![];
+            write(layers(vec![]), &options, &mut out).unwrap();
+            String::from_utf8_lossy(&out).into_owned()
+        };
+
+        assert!(pdf(Some("Città")).contains("/Title <FEFF004300690074007400E0>"));
+        assert!(!pdf(None).contains("/Title"));
+    }
+}
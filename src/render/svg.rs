@@ -0,0 +1,516 @@
+//! Export roads to [SVG](https://www.w3.org/TR/SVG2/).
+
+use std::{
+    fmt,
+    io::{self, Write},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    color::{Color, ColorSpace, SpotColors},
+    marks::PrintMarks,
+    style::{group_by_style, Marker, Style, StyledPaths},
+    text::Caption,
+    viewport::{Canvas, Origin, Viewport, YAxis},
+    Road,
+};
+
+/// XML namespace of the metadata written by roads.
+pub const NAMESPACE: &str = "https://github.com/danieledapo/roads";
+
+/// How [`write`] lays out the drawing.
+///
+/// The metadata, the print marks and the caption depend on the single drawing and they're not
+/// (de)serialized.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SvgOptions {
+    /// Size of the canvas the drawing is fit in.
+    pub width: f64,
+    pub height: f64,
+
+    pub origin: Origin,
+    pub y_axis: YAxis,
+
+    /// Empty space around the drawing, in canvas units. The print marks, if any, are drawn in
+    /// additional space.
+    pub margin: f64,
+
+    pub background: Color,
+
+    /// How the ends and the corners of all the lines are drawn.
+    pub stroke: StrokeOptions,
+
+    /// Number of decimals of the coordinates.
+    pub precision: usize,
+
+    /// The color space of the paints for print, the sRGB colors are always written too for the
+    /// viewers that don't support the others.
+    pub color_space: ColorSpace,
+
+    /// The colors printed with spot inks, which are named in the paints.
+    pub spots: SpotColors,
+
+    /// Location of the ICC profile of the CMYK paints, relative to the svg or as an url.
+    pub icc_profile: String,
+
+    /// Key value pairs stored as elements of the roads namespace inside `<metadata>`.
+    #[serde(skip)]
+    pub metadata: Vec<(String, String)>,
+
+    #[serde(skip)]
+    pub marks: Option<PrintMarks>,
+
+    #[serde(skip)]
+    pub caption: Option<Caption>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StrokeOptions {
+    pub cap: LineCap,
+    pub join: LineJoin,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineCap {
+    #[default]
+    Butt,
+    Round,
+    Square,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineJoin {
+    #[default]
+    Miter,
+    Round,
+    Bevel,
+}
+
+impl SvgOptions {
+    /// The ICC profile of the CMYK paints unless another is set, the one of the coated paper of
+    /// most offset presses in Europe.
+    pub const DEFAULT_ICC_PROFILE: &'static str = "CoatedFOGRA39.icc";
+
+    /// Options following the SVG conventions: origin in the top left corner and y axis growing
+    /// downwards.
+    pub fn new(width: f64, height: f64) -> Self {
+        Self::with_canvas(&Canvas::new(width, height))
+    }
+
+    pub fn with_canvas(canvas: &Canvas) -> Self {
+        SvgOptions {
+            width: canvas.width,
+            height: canvas.height,
+            origin: canvas.origin,
+            y_axis: canvas.y_axis,
+            margin: 0.0,
+            background: Color::None,
+            stroke: StrokeOptions::default(),
+            precision: 2,
+            color_space: ColorSpace::default(),
+            spots: SpotColors::default(),
+            icc_profile: Self::DEFAULT_ICC_PROFILE.to_string(),
+            metadata: vec![],
+            marks: None,
+            caption: None,
+        }
+    }
+
+    pub fn canvas(&self) -> Canvas {
+        Canvas {
+            origin: self.origin,
+            y_axis: self.y_axis,
+            ..Canvas::new(self.width, self.height)
+        }
+    }
+
+    /// Total space around the drawing, print marks included.
+    pub fn total_margin(&self) -> f64 {
+        self.margin + self.marks.as_ref().map_or(0.0, PrintMarks::margin)
+    }
+
+    /// The svg paint of the given color: the sRGB color followed by its spot color or its CMYK
+    /// approximation as an `icc-color`, if any.
+    pub fn paint(&self, c: Color) -> String {
+        if let Some(name) = self.spots.get(c) {
+            return format!("{} icc-color({}, 1)", c, name);
+        }
+
+        match c.cmyk().filter(|_| self.color_space == ColorSpace::Cmyk) {
+            Some((c_, m, y, k)) => format!(
+                "{} icc-color(cmyk, {:.3}, {:.3}, {:.3}, {:.3})",
+                c, c_, m, y, k
+            ),
+            None => c.to_string(),
+        }
+    }
+
+    /// The name and the location of each ICC profile the paints refer to. A spot ink refers to
+    /// the profile named after it, next to the svg.
+    fn color_profiles(&self) -> Vec<(String, String)> {
+        let mut profiles = vec![];
+        if self.color_space == ColorSpace::Cmyk {
+            profiles.push(("cmyk".to_string(), self.icc_profile.clone()));
+        }
+        for (_, name) in self.spots.iter() {
+            profiles.push((name.to_string(), format!("{}.icc", name)));
+        }
+        profiles
+    }
+
+    /// The metadata about the colors, for the print shops.
+    fn color_metadata(&self) -> Vec<(String, String)> {
+        let mut metadata = vec![];
+        if self.color_space != ColorSpace::default() {
+            metadata.push(("color-space".to_string(), self.color_space.to_string()));
+        }
+        for (c, name) in self.spots.iter() {
+            metadata.push(("spot-color".to_string(), format!("{}={}", c, name)));
+        }
+        metadata
+    }
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self::new(1920.0, 1080.0)
+    }
+}
+
+/// Write the given layers as an svg that fits in the canvas while preserving the aspect ratio.
+///
+/// The layers are drawn in order, each one in its own group. Colors are written as sRGB, along
+/// with their print color if the options ask for one, see [`SvgOptions::paint`], whose ICC
+/// profiles are declared as `<color-profile>`s. Paths made of a single point are drawn as
+/// markers defined once per layer as a `<symbol>` and instanced with `<use>`, while the closed
+/// paths of filled layers are drawn as polygons. Open paths are always drawn as lines. Layers
+/// with a label are listed in a legend in the top left corner. The transform of each layer is
+/// applied on top of the fitting. The caption, if any, is engraved as polylines in the bottom
+/// left corner. When print marks are given the drawing is surrounded by crop marks and
+/// registration crosses.
+///
+/// Note that svg viewers expect the y axis to grow downwards, any other convention results in an
+/// upside down drawing.
+pub fn write(layers: Vec<StyledPaths>, options: &SvgOptions, mut f: impl Write) -> io::Result<()> {
+    let viewport = viewport(&options.canvas(), &layers);
+    let size = viewport.size();
+    let (vx, vy) = viewport.min_corner();
+    let tr = |p| viewport.to_canvas(p);
+    let n = options.precision;
+
+    let margin = options.total_margin();
+    writeln!(
+        f,
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" xmlns:roads="{ns}" viewBox="{x:.n$} {y:.n$} {w:.n$} {h:.n$}">"#,
+        ns = NAMESPACE,
+        x = vx - margin,
+        y = vy - margin,
+        w = size.0 + 2.0 * margin,
+        h = size.1 + 2.0 * margin,
+    )?;
+
+    let metadata = options
+        .metadata
+        .iter()
+        .cloned()
+        .chain(options.color_metadata())
+        .collect::<Vec<_>>();
+    if !metadata.is_empty() {
+        writeln!(f, "<metadata>")?;
+        for (k, v) in &metadata {
+            writeln!(f, "<roads:{k}>{}</roads:{k}>", escape(v))?;
+        }
+        writeln!(f, "</metadata>")?;
+    }
+
+    writeln!(
+        f,
+        r#"<rect x="{vx:.n$}" y="{vy:.n$}" width="{w:.n$}" height="{h:.n$}" fill="{background}" stroke="none"/>"#,
+        w = size.0,
+        h = size.1,
+        background = options.paint(options.background),
+    )?;
+
+    let legend = layers
+        .iter()
+        .filter_map(|l| Some((l.style.label.clone()?, l.style.clone())))
+        .collect::<Vec<_>>();
+
+    let has_markers = |l: &StyledPaths| l.paths.iter().any(|p| p.len() == 1);
+    let profiles = options.color_profiles();
+    if layers.iter().any(has_markers) || !profiles.is_empty() {
+        writeln!(f, "<defs>")?;
+        for (name, href) in &profiles {
+            writeln!(
+                f,
+                r#"<color-profile xmlns:xlink="http://www.w3.org/1999/xlink" name="{}" xlink:href="{}"/>"#,
+                name,
+                escape(href)
+            )?;
+        }
+        for (i, l) in layers.iter().enumerate().filter(|(_, l)| has_markers(l)) {
+            write_marker_symbol(&mut f, i, &l.style, &options.paint(l.style.color))?;
+        }
+        writeln!(f, "</defs>")?;
+    }
+
+    for (i, l) in layers.into_iter().enumerate() {
+        write!(
+            f,
+            r#"<g stroke="{}" stroke-width="{}" fill="none""#,
+            options.paint(l.style.color),
+            l.style.width
+        )?;
+        if !l.style.transform.is_identity() {
+            write!(f, r#" transform="{}""#, l.style.transform)?;
+        }
+        if !l.style.dash.is_empty() {
+            let dash = l.style.dash.iter().map(f64::to_string).collect::<Vec<_>>();
+            write!(f, r#" stroke-dasharray="{}""#, dash.join(" "))?;
+        }
+        if options.stroke.cap != LineCap::default() {
+            write!(f, r#" stroke-linecap="{}""#, options.stroke.cap)?;
+        }
+        if options.stroke.join != LineJoin::default() {
+            write!(f, r#" stroke-linejoin="{}""#, options.stroke.join)?;
+        }
+        writeln!(f, " >")?;
+
+        // all the rings of a filled layer are written in a single path so that the even-odd rule
+        // can carve out the holes of the polygons, open paths are never closed and they're drawn
+        // as lines instead
+        let filled = l.style.fill != Color::None;
+        if filled {
+            write!(
+                f,
+                r#"<path fill="{}" fill-rule="evenodd" d=""#,
+                options.paint(l.style.fill)
+            )?;
+            for p in l.paths.iter().filter(|p| is_ring(p)) {
+                for (i, p) in p.iter().enumerate() {
+                    let (x, y) = tr(*p);
+                    let cmd = if i == 0 { 'M' } else { 'L' };
+                    write!(f, "{}{:.n$},{:.n$} ", cmd, x, y)?;
+                }
+                write!(f, "Z ")?;
+            }
+            writeln!(f, r#"" />"#)?;
+        }
+
+        for p in l.paths {
+            if let [p] = p[..] {
+                let (x, y) = tr(p);
+                writeln!(
+                    f,
+                    r##"<use href="#marker-{}" x="{:.n$}" y="{:.n$}" />"##,
+                    i, x, y
+                )?;
+                continue;
+            }
+
+            if filled && is_ring(&p) {
+                continue;
+            }
+
+            write_polylines(&mut f, [p.into_iter().map(tr).collect()], n)?;
+        }
+
+        writeln!(f, "</g>")?;
+    }
+
+    if !legend.is_empty() {
+        write_legend(&mut f, (vx, vy), size, &legend, options)?;
+    }
+
+    if let Some(caption) = &options.caption {
+        writeln!(
+            f,
+            r#"<g stroke="{}" stroke-width="{}" stroke-linecap="round" stroke-linejoin="round" fill="none">"#,
+            options.paint(caption.color),
+            caption.stroke_width()
+        )?;
+        write_polylines(&mut f, caption.paths((vx, vy + size.1)), n)?;
+        writeln!(f, "</g>")?;
+    }
+
+    if let Some(marks) = &options.marks {
+        writeln!(f, r#"<g stroke="black" stroke-width="0.5" fill="none">"#)?;
+        let paths = marks
+            .crop_marks((vx, vy), size)
+            .into_iter()
+            .chain(marks.registration_marks((vx, vy), size));
+        write_polylines(&mut f, paths, n)?;
+        writeln!(f, "</g>")?;
+    }
+
+    writeln!(f, "</svg>")?;
+
+    Ok(())
+}
+
+/// The viewport [`write`] uses to fit the given layers in the canvas.
+pub fn viewport(canvas: &Canvas, layers: &[StyledPaths]) -> Viewport {
+    Viewport::fit(*canvas, layers.iter().flat_map(|l| &l.paths).flatten())
+}
+
+/// Like [`write`], but style each road with the given function. Roads for which the function
+/// returns `None` are skipped.
+pub fn write_styled(
+    roads: Vec<Road>,
+    style: impl Fn(&Road) -> Option<Style>,
+    options: &SvgOptions,
+    f: impl Write,
+) -> io::Result<()> {
+    write(group_by_style(roads, style), options, f)
+}
+
+pub(crate) fn is_ring(path: &[(f64, f64)]) -> bool {
+    path.len() > 3 && path.first() == path.last()
+}
+
+/// Write paths already in canvas coordinates.
+fn write_polylines(
+    mut f: impl Write,
+    paths: impl IntoIterator<Item = Vec<(f64, f64)>>,
+    n: usize,
+) -> io::Result<()> {
+    for p in paths {
+        write!(f, r#"<polyline points=""#)?;
+        for (x, y) in p {
+            write!(f, "{:.n$},{:.n$} ", x, y)?;
+        }
+        writeln!(f, r#"" />"#)?;
+    }
+
+    Ok(())
+}
+
+fn write_marker_symbol(mut f: impl Write, id: usize, style: &Style, paint: &str) -> io::Result<()> {
+    let r = style.radius;
+
+    write!(f, r#"<symbol id="marker-{}" overflow="visible">"#, id)?;
+    match style.marker {
+        Marker::Circle => write!(f, r#"<circle r="{}" fill="{}" stroke="none" />"#, r, paint)?,
+        Marker::Square => write!(
+            f,
+            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}" stroke="none" />"#,
+            -r,
+            -r,
+            2.0 * r,
+            2.0 * r,
+            paint
+        )?,
+        Marker::Triangle => write!(
+            f,
+            r#"<polygon points="0,{:.2} {:.2},{:.2} {:.2},{:.2}" fill="{}" stroke="none" />"#,
+            -r,
+            r * 0.866,
+            r / 2.0,
+            -r * 0.866,
+            r / 2.0,
+            paint
+        )?,
+    }
+    writeln!(f, "</symbol>")
+}
+
+fn write_legend(
+    mut f: impl Write,
+    (x, y): (f64, f64),
+    (w, h): (f64, f64),
+    entries: &[(String, Style)],
+    options: &SvgOptions,
+) -> io::Result<()> {
+    let font_size = w.min(h) / 50.0;
+
+    writeln!(
+        f,
+        r#"<g font-family="sans-serif" font-size="{:.2}">"#,
+        font_size
+    )?;
+
+    for (i, (label, style)) in entries.iter().enumerate() {
+        let x0 = x + font_size;
+        let y0 = y + font_size * (1.5 * i as f64 + 2.0);
+
+        writeln!(
+            f,
+            r#"<line x1="{:.2}" y1="{:.2}" x2="{:.2}" y2="{:.2}" stroke="{}" stroke-width="{}" />"#,
+            x0,
+            y0 - font_size / 3.0,
+            x0 + font_size * 2.0,
+            y0 - font_size / 3.0,
+            options.paint(style.color),
+            style.width,
+        )?;
+        writeln!(
+            f,
+            r#"<text x="{:.2}" y="{:.2}">{}</text>"#,
+            x0 + font_size * 2.5,
+            y0,
+            escape(label)
+        )?;
+    }
+
+    writeln!(f, "</g>")
+}
+
+impl fmt::Display for LineCap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LineCap::Butt => "butt",
+            LineCap::Round => "round",
+            LineCap::Square => "square",
+        })
+    }
+}
+
+impl FromStr for LineCap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "butt" => Ok(LineCap::Butt),
+            "round" => Ok(LineCap::Round),
+            "square" => Ok(LineCap::Square),
+            _ => Err(format!("invalid line cap '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for LineJoin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LineJoin::Miter => "miter",
+            LineJoin::Round => "round",
+            LineJoin::Bevel => "bevel",
+        })
+    }
+}
+
+impl FromStr for LineJoin {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "miter" => Ok(LineJoin::Miter),
+            "round" => Ok(LineJoin::Round),
+            "bevel" => Ok(LineJoin::Bevel),
+            _ => Err(format!("invalid line join '{}'", s)),
+        }
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
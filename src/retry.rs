@@ -0,0 +1,191 @@
+//! Retries with exponential backoff for the requests that fail because a server is overloaded.
+
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use reqwest::{header, RequestBuilder, Response, StatusCode};
+
+/// How many times and how long to wait before retrying a request.
+///
+/// The delay doubles at every attempt, up to `max_delay`, and some jitter is added so that many
+/// clients don't retry all at once. A `Retry-After` header sent by the server takes precedence if
+/// it asks to wait longer, but never past `max_delay`.
+#[derive(Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    on_retry: Option<RetryObserver>,
+}
+
+type RetryObserver = Arc<dyn Fn(&RetryAttempt) + Send + Sync>;
+
+/// A retry that is about to happen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryAttempt {
+    /// 1 for the first retry.
+    pub attempt: u32,
+    pub max_retries: u32,
+    pub delay: Duration,
+
+    /// Why the previous attempt failed.
+    pub reason: String,
+}
+
+impl RetryPolicy {
+    /// Never retry.
+    pub fn none() -> Self {
+        RetryPolicy {
+            max_retries: 0,
+            ..Self::default()
+        }
+    }
+
+    /// Call the given function before every retry, e.g. to tell the user what's going on.
+    pub fn with_observer(mut self, f: impl Fn(&RetryAttempt) + Send + Sync + 'static) -> Self {
+        self.on_retry = Some(Arc::new(f));
+        self
+    }
+
+    /// Wait before the given retry, notifying the observer if any.
+    pub(crate) async fn wait(&self, attempt: u32, retry_after: Option<Duration>, reason: String) {
+        let delay = self.delay(attempt, retry_after);
+
+        if let Some(f) = &self.on_retry {
+            f(&RetryAttempt {
+                attempt,
+                max_retries: self.max_retries,
+                delay,
+                reason,
+            });
+        }
+
+        tokio::time::sleep(delay).await;
+    }
+
+    /// How long to wait before the given retry, the server can't stall it for longer than
+    /// `max_delay`.
+    fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        let backoff = self
+            .base_delay
+            .saturating_mul(2_u32.saturating_pow(attempt - 1))
+            .min(self.max_delay);
+        let retry_after = retry_after.unwrap_or_default().min(self.max_delay);
+        (backoff + jitter(backoff / 4)).max(retry_after)
+    }
+
+    /// Send the request built by the given function, retrying while the server is overloaded.
+    pub(crate) async fn send(
+        &self,
+        request: impl Fn() -> RequestBuilder,
+    ) -> reqwest::Result<Response> {
+        let mut attempt = 0;
+
+        loop {
+            let (reason, retry_after) = match request().send().await {
+                Ok(r) if is_overloaded(r.status()) && attempt < self.max_retries => {
+                    (r.status().to_string(), retry_after(&r))
+                }
+                Err(e) if is_unreachable(&e) && attempt < self.max_retries => (e.to_string(), None),
+                r => return r,
+            };
+
+            attempt += 1;
+            self.wait(attempt, retry_after, reason).await;
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_secs(2),
+            max_delay: Duration::from_secs(60),
+            on_retry: None,
+        }
+    }
+}
+
+impl fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_retries", &self.max_retries)
+            .field("base_delay", &self.base_delay)
+            .field("max_delay", &self.max_delay)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The observer is not taken into account.
+impl PartialEq for RetryPolicy {
+    fn eq(&self, other: &Self) -> bool {
+        self.max_retries == other.max_retries
+            && self.base_delay == other.base_delay
+            && self.max_delay == other.max_delay
+    }
+}
+
+impl Eq for RetryPolicy {}
+
+impl fmt::Display for RetryAttempt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "retry {}/{} in {}s ({})",
+            self.attempt,
+            self.max_retries,
+            self.delay.as_secs(),
+            self.reason
+        )
+    }
+}
+
+/// Whether the server is asking to slow down or it's too busy to answer.
+pub(crate) fn is_overloaded(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+pub(crate) fn is_unreachable(e: &reqwest::Error) -> bool {
+    e.is_connect() || e.is_timeout()
+}
+
+/// The delay asked by the server, only the number of seconds form is supported.
+pub(crate) fn retry_after(r: &Response) -> Option<Duration> {
+    let secs = r.headers().get(header::RETRY_AFTER)?.to_str().ok()?;
+    secs.trim().parse().ok().map(Duration::from_secs)
+}
+
+/// A pseudo random duration up to the given one, good enough to spread retries around.
+fn jitter(max: Duration) -> Duration {
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos();
+
+    max.mul_f64(f64::from(nanos % 1000) / 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_after_is_capped() {
+        let policy = RetryPolicy::default();
+
+        let hours = Some(Duration::from_secs(6 * 3600));
+        assert_eq!(policy.delay(1, hours), policy.max_delay);
+
+        let delay = policy.delay(1, Some(Duration::from_secs(10)));
+        assert_eq!(delay, Duration::from_secs(10));
+    }
+}
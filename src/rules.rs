@@ -0,0 +1,379 @@
+//! A tiny subset of [MapCSS] to style roads depending on their tags.
+//!
+//! A rule is made of one or more selectors followed by a block of declarations:
+//!
+//! ```text
+//! // motorways are thick and dark
+//! [highway=motorway] { width: 3; color: #222 }
+//! [tunnel=yes] { dash: 4 2 }
+//! [natural=tree] { radius: 2; color: green }
+//! [highway=street_lamp] { marker: square }
+//! [natural=water] { fill: #a0c8f0 }
+//! [highway=primary][bridge] { layer: 1 }
+//! [waterway] { transform: translate(2, 2) }
+//! [access!=yes][highway=service] { display: none }
+//! ```
+//!
+//! A rule matches a road when all its selectors match. Rules are applied in order, so later
+//! declarations override the earlier ones.
+//!
+//! [MapCSS]: https://wiki.openstreetmap.org/wiki/MapCSS
+
+use std::{fmt, str::FromStr};
+
+use crate::{
+    color::Color,
+    style::{Marker, Style},
+    transform::Affine,
+    Road,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct StyleRules {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    selectors: Vec<Selector>,
+    declarations: Vec<Declaration>,
+}
+
+#[derive(Debug, Clone)]
+enum Selector {
+    Has(String),
+    Eq(String, String),
+    NotEq(String, String),
+}
+
+#[derive(Debug, Clone)]
+enum Declaration {
+    Color(Color),
+    Width(f64),
+    Layer(i32),
+    Dash(Vec<f64>),
+    Radius(f64),
+    Marker(Marker),
+    Fill(Color),
+    Transform(Affine),
+    Display(bool),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub msg: String,
+}
+
+impl StyleRules {
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Compute the style of the given road starting from the default style, `None` means the
+    /// road should be skipped.
+    pub fn style(&self, road: &Road, default: &Style) -> Option<Style> {
+        let mut style = default.clone();
+        let mut display = true;
+
+        for rule in &self.rules {
+            if !rule.selectors.iter().all(|s| s.matches(road)) {
+                continue;
+            }
+
+            for d in &rule.declarations {
+                match d {
+                    Declaration::Color(c) => style.color = *c,
+                    Declaration::Width(w) => style.width = *w,
+                    Declaration::Layer(l) => style.layer = *l,
+                    Declaration::Dash(d) => style.dash = d.clone(),
+                    Declaration::Radius(r) => style.radius = *r,
+                    Declaration::Marker(m) => style.marker = *m,
+                    Declaration::Fill(c) => style.fill = *c,
+                    Declaration::Transform(t) => style.transform = *t,
+                    Declaration::Display(d) => display = *d,
+                }
+            }
+        }
+
+        if display {
+            Some(style)
+        } else {
+            None
+        }
+    }
+}
+
+impl Selector {
+    fn matches(&self, road: &Road) -> bool {
+        match self {
+            Selector::Has(k) => road.tags.contains_key(k),
+            Selector::Eq(k, v) => road.tags.get(k) == Some(v),
+            Selector::NotEq(k, v) => road.tags.get(k) != Some(v),
+        }
+    }
+}
+
+impl FromStr for StyleRules {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rules = vec![];
+
+        let mut parser = Parser { src: s, pos: 0 };
+        loop {
+            parser.skip_whitespace();
+            if parser.pos == s.len() {
+                break;
+            }
+
+            rules.push(parser.rule()?);
+        }
+
+        Ok(StyleRules { rules })
+    }
+}
+
+struct Parser<'s> {
+    src: &'s str,
+    pos: usize,
+}
+
+impl<'s> Parser<'s> {
+    fn rest(&self) -> &'s str {
+        &self.src[self.pos..]
+    }
+
+    fn err<T>(&self, msg: impl Into<String>) -> Result<T, ParseError> {
+        Err(ParseError {
+            line: self.src[..self.pos].matches('\n').count() + 1,
+            msg: msg.into(),
+        })
+    }
+
+    fn skip_whitespace(&mut self) {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+
+            if !trimmed.starts_with("//") {
+                break;
+            }
+            self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+        }
+    }
+
+    fn take_until(&mut self, c: char) -> Result<&'s str, ParseError> {
+        match self.rest().find(c) {
+            None => self.err(format!("expected '{}'", c)),
+            Some(i) => {
+                let s = &self.rest()[..i];
+                self.pos += i + c.len_utf8();
+                Ok(s)
+            }
+        }
+    }
+
+    fn rule(&mut self) -> Result<Rule, ParseError> {
+        let mut selectors = vec![];
+
+        loop {
+            self.skip_whitespace();
+
+            if self.rest().starts_with('{') {
+                self.pos += 1;
+                break;
+            }
+            if !self.rest().starts_with('[') {
+                return self.err("expected '[' or '{'");
+            }
+            self.pos += 1;
+
+            let sel = self.take_until(']')?;
+            let sel = if let Some((k, v)) = sel.split_once("!=") {
+                Selector::NotEq(k.trim().to_string(), v.trim().to_string())
+            } else if let Some((k, v)) = sel.split_once('=') {
+                Selector::Eq(k.trim().to_string(), v.trim().to_string())
+            } else {
+                Selector::Has(sel.trim().to_string())
+            };
+            selectors.push(sel);
+        }
+
+        if selectors.is_empty() {
+            return self.err("rule without selectors");
+        }
+
+        let body = self.take_until('}')?;
+
+        // values can't contain '}', so whatever follows the block must be another rule
+        self.skip_whitespace();
+        if !self.rest().is_empty() && !self.rest().starts_with('[') {
+            return self.err("unexpected text after '}', values can't contain '}'");
+        }
+
+        let mut declarations = vec![];
+        for decl in body.split(';') {
+            let decl = decl.trim();
+            if decl.is_empty() {
+                continue;
+            }
+
+            let (name, value) = match decl.split_once(':') {
+                Some((n, v)) => (n.trim(), v.trim()),
+                None => return self.err(format!("invalid declaration '{}'", decl)),
+            };
+
+            let invalid = || format!("invalid value '{}' for '{}'", value, name);
+            let d = match name {
+                "color" => match value.parse() {
+                    Ok(c) => Declaration::Color(c),
+                    Err(_) => return self.err(invalid()),
+                },
+                "width" => match value.parse() {
+                    Ok(w) => Declaration::Width(w),
+                    Err(_) => return self.err(invalid()),
+                },
+                "layer" => match value.parse() {
+                    Ok(l) => Declaration::Layer(l),
+                    Err(_) => return self.err(invalid()),
+                },
+                "fill" => match value.parse() {
+                    Ok(c) => Declaration::Fill(c),
+                    Err(_) => return self.err(invalid()),
+                },
+                "radius" => match value.parse() {
+                    Ok(r) => Declaration::Radius(r),
+                    Err(_) => return self.err(invalid()),
+                },
+                "marker" => match value.parse() {
+                    Ok(m) => Declaration::Marker(m),
+                    Err(_) => return self.err(invalid()),
+                },
+                "transform" => match value.parse() {
+                    Ok(t) => Declaration::Transform(t),
+                    Err(_) => return self.err(invalid()),
+                },
+                "dash" => match value.split_whitespace().map(str::parse).collect() {
+                    Ok(d) => Declaration::Dash(d),
+                    Err(_) => return self.err(invalid()),
+                },
+                "display" => match value {
+                    "none" => Declaration::Display(false),
+                    "visible" => Declaration::Display(true),
+                    _ => return self.err(invalid()),
+                },
+                _ => return self.err(format!("unknown property '{}'", name)),
+            };
+            declarations.push(d);
+        }
+
+        Ok(Rule {
+            selectors,
+            declarations,
+        })
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.msg)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn road(tags: &[(&str, &str)]) -> Road {
+        Road {
+            id: 1,
+            tags: tags
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            points: vec![(0.0, 0.0), (1.0, 1.0)],
+            holes: vec![],
+        }
+    }
+
+    fn style(rules: &str, tags: &[(&str, &str)]) -> Option<Style> {
+        let rules = rules.parse::<StyleRules>().unwrap();
+        rules.style(&road(tags), &Style::new(Color::Rgb(0, 0, 0), 1.0))
+    }
+
+    #[test]
+    fn selectors() {
+        let rules = "[highway=motorway] { width: 3 }
+                     [access!=yes] { layer: 2 }
+                     [bridge] { color: red }";
+
+        let s = style(rules, &[("highway", "motorway"), ("access", "yes")]).unwrap();
+        assert_eq!((s.width, s.layer, s.color), (3.0, 0, Color::Rgb(0, 0, 0)));
+
+        let s = style(rules, &[("highway", "primary"), ("bridge", "yes")]).unwrap();
+        assert_eq!((s.width, s.layer), (1.0, 2));
+        assert_eq!(s.color, "red".parse().unwrap());
+    }
+
+    #[test]
+    fn all_selectors_must_match() {
+        let rules = "[highway=primary][bridge] { layer: 1 } [highway=primary] { width: 2 }";
+
+        let s = style(rules, &[("highway", "primary")]).unwrap();
+        assert_eq!((s.layer, s.width), (0, 2.0));
+
+        let s = style(rules, &[("highway", "primary"), ("bridge", "yes")]).unwrap();
+        assert_eq!((s.layer, s.width), (1, 2.0));
+    }
+
+    #[test]
+    fn later_rules_override() {
+        let rules = "[highway] { width: 2; dash: 4 2 }
+                     // no service roads at all
+                     [highway=service] { display: none }";
+
+        let s = style(rules, &[("highway", "residential")]).unwrap();
+        assert_eq!((s.width, s.dash), (2.0, vec![4.0, 2.0]));
+        assert!(style(rules, &[("highway", "service")]).is_none());
+    }
+
+    #[test]
+    fn comments() {
+        let rules = "// only comments\n  // and blanks\n";
+        assert!(rules.parse::<StyleRules>().unwrap().is_empty());
+
+        let s = style(
+            "// thick\n[waterway] { width: 5 } // trailing",
+            &[("waterway", "river")],
+        );
+        assert_eq!(s.unwrap().width, 5.0);
+    }
+
+    #[test]
+    fn malformed() {
+        let err = |rules: &str| rules.parse::<StyleRules>().unwrap_err();
+
+        assert_eq!(err("highway { width: 1 }").msg, "expected '[' or '{'");
+        assert_eq!(err("[highway { width: 1 }").msg, "expected ']'");
+        assert_eq!(err("[highway] { width: 1").msg, "expected '}'");
+        assert_eq!(err("{ width: 1 }").msg, "rule without selectors");
+        assert_eq!(err("[a] { width }").msg, "invalid declaration 'width'");
+        assert_eq!(err("[a] { size: 1 }").msg, "unknown property 'size'");
+        assert_eq!(
+            err("[a] { dash: 4 x }").msg,
+            "invalid value '4 x' for 'dash'"
+        );
+        assert_eq!(err("[a] { display: hidden }").line, 1);
+        assert_eq!(err("[a] { width: 1 }\n\n[b] { width: x }").line, 3);
+    }
+
+    #[test]
+    fn brace_in_value() {
+        let e = "[a] { color: #f}00 }".parse::<StyleRules>().unwrap_err();
+        assert_eq!(e.msg, "unexpected text after '}', values can't contain '}'");
+    }
+}
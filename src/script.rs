@@ -0,0 +1,116 @@
+//! Style roads with a [rhai] script.
+//!
+//! The script must define a `style(tags)` function that receives the tags of a way as a map and
+//! returns either `()` to skip the way or a map with any of the `color`, `width`, `layer`, `dash`,
+//! `radius`, `marker`, `fill`, `transform` and `skip` keys. Missing keys fall back to the default style.
+//!
+//! ```rhai
+//! fn style(tags) {
+//!     if tags.highway == "motorway" {
+//!         #{ width: 2.0, color: "#222" }
+//!     } else if tags.highway == "footway" {
+//!         ()
+//!     } else {
+//!         #{}
+//!     }
+//! }
+//! ```
+//!
+//! [rhai]: https://rhai.rs
+
+use std::path::Path;
+
+use rhai::{Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+
+use crate::{color::ParseColorError, style::Style, Road};
+
+pub struct StyleScript {
+    engine: Engine,
+    ast: AST,
+}
+
+impl StyleScript {
+    pub fn compile(src: &str) -> Result<Self, Box<EvalAltResult>> {
+        let engine = Engine::new();
+        let ast = engine.compile(src)?;
+        Ok(StyleScript { engine, ast })
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, Box<EvalAltResult>> {
+        let engine = Engine::new();
+        let ast = engine.compile_file(path.as_ref().to_path_buf())?;
+        Ok(StyleScript { engine, ast })
+    }
+
+    /// Compute the style of the given road, `None` means the road should be skipped.
+    pub fn style(&self, road: &Road, default: &Style) -> Result<Option<Style>, Box<EvalAltResult>> {
+        let tags: Map = road
+            .tags
+            .iter()
+            .map(|(k, v)| (k.into(), Dynamic::from(v.clone())))
+            .collect();
+
+        let r: Dynamic = self
+            .engine
+            .call_fn(&mut Scope::new(), &self.ast, "style", (tags,))?;
+
+        if r.is_unit() {
+            return Ok(None);
+        }
+
+        let r = r
+            .try_cast::<Map>()
+            .ok_or("style() must return a map or ()")?;
+
+        if let Some(skip) = r.get("skip") {
+            if skip.as_bool()? {
+                return Ok(None);
+            }
+        }
+
+        let mut style = default.clone();
+        if let Some(color) = r.get("color") {
+            style.color = color
+                .clone()
+                .into_string()?
+                .parse()
+                .map_err(|e: ParseColorError| e.to_string())?;
+        }
+        if let Some(width) = r.get("width") {
+            style.width = width
+                .as_float()
+                .or_else(|_| width.as_int().map(|w| w as f64))?;
+        }
+        if let Some(layer) = r.get("layer") {
+            style.layer = layer.as_int()? as i32;
+        }
+        if let Some(fill) = r.get("fill") {
+            style.fill = fill
+                .clone()
+                .into_string()?
+                .parse()
+                .map_err(|e: ParseColorError| e.to_string())?;
+        }
+        if let Some(radius) = r.get("radius") {
+            style.radius = radius
+                .as_float()
+                .or_else(|_| radius.as_int().map(|r| r as f64))?;
+        }
+        if let Some(transform) = r.get("transform") {
+            style.transform = transform.clone().into_string()?.parse()?;
+        }
+        if let Some(marker) = r.get("marker") {
+            style.marker = marker.clone().into_string()?.parse()?;
+        }
+        if let Some(dash) = r.get("dash") {
+            style.dash = dash
+                .clone()
+                .into_array()?
+                .into_iter()
+                .map(|d| d.as_float().or_else(|_| d.as_int().map(|d| d as f64)))
+                .collect::<Result<_, _>>()?;
+        }
+
+        Ok(Some(style))
+    }
+}
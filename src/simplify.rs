@@ -1,9 +1,148 @@
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    fmt,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use crate::pipeline::PathTransform;
+
 /// Simplify a given polyline by reducing the amount of points that do not actually contribute a
 /// lot of details to the overall shape.
 pub fn simplify(poly: &[(f64, f64)]) -> Vec<(f64, f64)> {
     simplify_with_eps(poly, 1e-9)
 }
 
+/// [`PathTransform`] that simplifies every path with the given epsilon.
+///
+/// It keeps track of the [`SimplifyStats`] of the paths it simplified, clones share the same
+/// stats so that a copy can be kept around to inspect them after the pipeline ran.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Simplify {
+    pub eps: f64,
+    pub algorithm: Algorithm,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    stats: Arc<Mutex<SimplifyStats>>,
+}
+
+/// How points are picked for removal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum Algorithm {
+    /// [Ramer–Douglas–Peucker], which keeps the points farther than epsilon from the simplified
+    /// path. Fast and faithful, but it turns curves into few long segments at big tolerances.
+    ///
+    /// [Ramer–Douglas–Peucker]: https://en.wikipedia.org/wiki/Ramer%E2%80%93Douglas%E2%80%93Peucker_algorithm
+    #[default]
+    RamerDouglasPeucker,
+
+    /// [Visvalingam–Whyatt], which removes the points making the smallest triangles with their
+    /// neighbors until they're all bigger than epsilon squared. It keeps the character of curvy
+    /// roads at big tolerances, which matters for small plots.
+    ///
+    /// [Visvalingam–Whyatt]: https://en.wikipedia.org/wiki/Visvalingam%E2%80%93Whyatt_algorithm
+    VisvalingamWhyatt,
+}
+
+/// How much a simplification changed one or more paths.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SimplifyStats {
+    pub paths: usize,
+    pub points_before: usize,
+    pub points_after: usize,
+
+    /// Maximum distance between a removed point and the simplified path, in world units.
+    pub max_error: f64,
+}
+
+impl Simplify {
+    pub fn new(eps: f64) -> Self {
+        Simplify {
+            eps,
+            algorithm: Algorithm::RamerDouglasPeucker,
+            stats: Arc::default(),
+        }
+    }
+
+    pub fn with_algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// The stats accumulated since the last [`Simplify::reset_stats`].
+    pub fn stats(&self) -> SimplifyStats {
+        *self.stats.lock().unwrap()
+    }
+
+    pub fn reset_stats(&self) {
+        *self.stats.lock().unwrap() = SimplifyStats::default();
+    }
+}
+
+impl Default for Simplify {
+    fn default() -> Self {
+        Simplify::new(1e-9)
+    }
+}
+
+impl PathTransform for Simplify {
+    fn apply(&self, paths: Vec<Vec<(f64, f64)>>) -> Vec<Vec<(f64, f64)>> {
+        let mut stats = SimplifyStats::default();
+
+        let paths = paths
+            .iter()
+            .map(|p| {
+                let (p, s) = match self.algorithm {
+                    Algorithm::RamerDouglasPeucker => simplify_with_stats(p, self.eps),
+                    Algorithm::VisvalingamWhyatt => visvalingam_with_stats(p, self.eps),
+                };
+                stats.merge(&s);
+                p
+            })
+            .collect();
+
+        self.stats.lock().unwrap().merge(&stats);
+        paths
+    }
+}
+
+impl SimplifyStats {
+    pub fn merge(&mut self, other: &SimplifyStats) {
+        self.paths += other.paths;
+        self.points_before += other.points_before;
+        self.points_after += other.points_after;
+        self.max_error = self.max_error.max(other.max_error);
+    }
+
+    /// Fraction of the points that were removed, between 0 and 1.
+    pub fn removed_ratio(&self) -> f64 {
+        if self.points_before == 0 {
+            return 0.0;
+        }
+
+        1.0 - self.points_after as f64 / self.points_before as f64
+    }
+}
+
+/// Like [`simplify_with_eps`], but also report how much the path changed.
+pub fn simplify_with_stats(poly: &[(f64, f64)], eps: f64) -> (Vec<(f64, f64)>, SimplifyStats) {
+    let mut r = vec![];
+    let mut max_error = 0.0;
+    simplify_into(&mut r, poly, eps, &mut max_error);
+
+    let stats = SimplifyStats {
+        paths: 1,
+        points_before: poly.len(),
+        points_after: r.len(),
+        max_error,
+    };
+
+    (r, stats)
+}
+
 pub fn simplify_with_eps(poly: &[(f64, f64)], eps: f64) -> Vec<(f64, f64)> {
     let mut r = vec![];
     _simplify_with_eps(&mut r, poly, eps);
@@ -11,20 +150,24 @@ pub fn simplify_with_eps(poly: &[(f64, f64)], eps: f64) -> Vec<(f64, f64)> {
 }
 
 pub fn _simplify_with_eps(r: &mut Vec<(f64, f64)>, poly: &[(f64, f64)], eps: f64) {
+    simplify_into(r, poly, eps, &mut 0.0);
+}
+
+fn simplify_into(r: &mut Vec<(f64, f64)>, poly: &[(f64, f64)], eps: f64, max_error: &mut f64) {
     // Ramer Douglas Peucker doesn't work with closed paths, thus simplify the open path and then
     // close it manually
     if !poly.is_empty() && poly[0] == poly[poly.len() - 1] {
-        rdp(r, &poly[..poly.len() - 1], eps);
+        rdp(r, &poly[..poly.len() - 1], eps, max_error);
         r.push(poly[poly.len() - 1]);
     } else {
-        rdp(r, poly, eps);
+        rdp(r, poly, eps, max_error);
     }
 }
 
 /// Implementation of the [Ramer–Douglas–Peucker algorithm] to simplify an open path.
 ///
 /// [0]: https://en.wikipedia.org/wiki/Ramer%E2%80%93Douglas%E2%80%93Peucker_algorithm
-fn rdp(r: &mut Vec<(f64, f64)>, poly: &[(f64, f64)], eps: f64) {
+fn rdp(r: &mut Vec<(f64, f64)>, poly: &[(f64, f64)], eps: f64, max_error: &mut f64) {
     if poly.len() < 3 {
         r.extend_from_slice(poly);
         return;
@@ -34,7 +177,7 @@ fn rdp(r: &mut Vec<(f64, f64)>, poly: &[(f64, f64)], eps: f64) {
     let ep = *poly.last().unwrap();
 
     let mut farthest_i = 0;
-    let mut max_dist = std::f64::NEG_INFINITY;
+    let mut max_dist = f64::NEG_INFINITY;
     for (i, p) in poly.iter().enumerate().take(poly.len() - 1).skip(1) {
         let d = perpendicular_dist(*p, (sp, ep));
         if d > max_dist {
@@ -44,21 +187,248 @@ fn rdp(r: &mut Vec<(f64, f64)>, poly: &[(f64, f64)], eps: f64) {
     }
 
     if max_dist > eps {
-        rdp(r, &poly[..=farthest_i], eps);
+        rdp(r, &poly[..=farthest_i], eps, max_error);
 
         // remove point with max dist, it will be added with the right vec
         r.pop();
 
-        _simplify_with_eps(r, &poly[farthest_i..], eps);
+        simplify_into(r, &poly[farthest_i..], eps, max_error);
     } else {
+        *max_error = max_error.max(max_dist);
         r.push(sp);
         r.push(ep);
     }
 }
 
+/// Like [`simplify_with_stats`], but with the [Visvalingam–Whyatt algorithm], removing the points
+/// whose triangle with their neighbors is smaller than `eps * eps`.
+///
+/// The max error is the farthest a removed point was from the segment joining its neighbors when
+/// it was removed.
+///
+/// [Visvalingam–Whyatt algorithm]: Algorithm::VisvalingamWhyatt
+pub fn visvalingam_with_stats(poly: &[(f64, f64)], eps: f64) -> (Vec<(f64, f64)>, SimplifyStats) {
+    let mut max_error = 0.0;
+
+    // like for rdp the closing point is kept apart, so that it's never removed
+    let r = if !poly.is_empty() && poly[0] == poly[poly.len() - 1] {
+        let mut r = visvalingam(&poly[..poly.len() - 1], eps * eps, &mut max_error);
+        r.push(poly[poly.len() - 1]);
+        r
+    } else {
+        visvalingam(poly, eps * eps, &mut max_error)
+    };
+
+    let stats = SimplifyStats {
+        paths: 1,
+        points_before: poly.len(),
+        points_after: r.len(),
+        max_error,
+    };
+
+    (r, stats)
+}
+
+/// A point that can be removed, ordered so that the smallest area comes first out of a
+/// [`BinaryHeap`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Candidate {
+    area: f64,
+    i: usize,
+
+    /// Areas are recomputed when a neighbor is removed, old candidates are skipped.
+    version: u32,
+}
+
+impl Eq for Candidate {}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .area
+            .total_cmp(&self.area)
+            .then_with(|| other.i.cmp(&self.i))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Implementation of the Visvalingam–Whyatt algorithm on an open path, the first and the last
+/// points are always kept.
+fn visvalingam(poly: &[(f64, f64)], min_area: f64, max_error: &mut f64) -> Vec<(f64, f64)> {
+    let n = poly.len();
+    if n < 3 {
+        return poly.to_vec();
+    }
+
+    let mut prev = (0..n).map(|i| i.saturating_sub(1)).collect::<Vec<_>>();
+    let mut next = (0..n).map(|i| (i + 1).min(n - 1)).collect::<Vec<_>>();
+    let mut removed = vec![false; n];
+    let mut versions = vec![0; n];
+
+    let area = |prev: &[usize], next: &[usize], i: usize| {
+        triangle_area(poly[prev[i]], poly[i], poly[next[i]])
+    };
+
+    let mut heap = (1..n - 1)
+        .map(|i| Candidate {
+            area: area(&prev, &next, i),
+            i,
+            version: 0,
+        })
+        .collect::<BinaryHeap<_>>();
+
+    while let Some(c) = heap.pop() {
+        if removed[c.i] || c.version != versions[c.i] {
+            continue;
+        }
+        if c.area >= min_area {
+            break;
+        }
+
+        let (p, q) = (prev[c.i], next[c.i]);
+        *max_error = max_error.max(perpendicular_dist(poly[c.i], (poly[p], poly[q])));
+        removed[c.i] = true;
+        next[p] = q;
+        prev[q] = p;
+
+        for j in [p, q] {
+            if j == 0 || j == n - 1 {
+                continue;
+            }
+
+            // a point never becomes cheaper to remove than the points removed before it, so
+            // that removing it cannot be worse than what's been done already
+            versions[j] += 1;
+            heap.push(Candidate {
+                area: area(&prev, &next, j).max(c.area),
+                i: j,
+                version: versions[j],
+            });
+        }
+    }
+
+    poly.iter()
+        .zip(removed)
+        .filter(|(_, removed)| !removed)
+        .map(|(p, _)| *p)
+        .collect()
+}
+
+fn triangle_area(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> f64 {
+    ((b.0 - a.0) * (c.1 - a.1) - (c.0 - a.0) * (b.1 - a.1)).abs() / 2.0
+}
+
 fn perpendicular_dist(p: (f64, f64), (s, e): ((f64, f64), (f64, f64))) -> f64 {
     let num = ((e.1 - s.1) * p.0 - (e.0 - s.0) * p.1 + e.0 * s.1 - e.1 * s.0).abs();
     let den = ((e.0 - s.0).powi(2) + (e.1 - s.1).powi(2)).sqrt();
 
     num / den
 }
+
+/// Human friendly summary like `simplified 1.2M → 180k points, max error 2.1 m`, taking the world
+/// units as meters on the ground, see [`Projection::scale`](crate::projection::Projection::scale).
+impl fmt::Display for SimplifyStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn count(n: usize) -> String {
+            match n {
+                0..=999 => n.to_string(),
+                1_000..=999_999 => format!("{:.0}k", n as f64 / 1e3),
+                _ => format!("{:.1}M", n as f64 / 1e6),
+            }
+        }
+
+        write!(
+            f,
+            "simplified {} → {} points, max error {:.1} m",
+            count(self.points_before),
+            count(self.points_after),
+            self.max_error
+        )
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "rdp" => Ok(Algorithm::RamerDouglasPeucker),
+            "vw" => Ok(Algorithm::VisvalingamWhyatt),
+            _ => Err(format!(
+                "invalid simplification algorithm '{}', expected rdp or vw",
+                s.trim()
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Algorithm::RamerDouglasPeucker => "rdp",
+            Algorithm::VisvalingamWhyatt => "vw",
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+crate::util::serde_via_str!(Algorithm);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn visvalingam_keeps_the_endpoints() {
+        let path = [(0.0, 0.0), (1.0, 1.0), (2.0, -1.0), (3.0, 0.0)];
+        assert_eq!(
+            visvalingam(&path, 1e9, &mut 0.0),
+            vec![(0.0, 0.0), (3.0, 0.0)]
+        );
+
+        let (r, _) = visvalingam_with_stats(&[(0.0, 0.0), (1.0, 0.0)], 10.0);
+        assert_eq!(r, vec![(0.0, 0.0), (1.0, 0.0)]);
+
+        // the closing point stays even though it's collinear with its neighbors
+        let ring = [
+            (0.0, 0.0),
+            (1.0, 0.0),
+            (2.0, 0.0),
+            (2.0, 2.0),
+            (0.0, 2.0),
+            (0.0, 0.0),
+        ];
+        let (r, stats) = visvalingam_with_stats(&ring, 0.5);
+        assert_eq!(
+            r,
+            vec![(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0), (0.0, 0.0)]
+        );
+        assert_eq!((stats.points_before, stats.points_after), (6, 5));
+    }
+
+    #[test]
+    fn visvalingam_drops_collinear_points() {
+        let mut max_error = 0.0;
+        let r = visvalingam(&[(0.0, 0.0), (1.0, 0.0), (2.0, 0.0)], 1e-6, &mut max_error);
+        assert_eq!(r, vec![(0.0, 0.0), (2.0, 0.0)]);
+        assert_eq!(max_error, 0.0);
+    }
+
+    #[test]
+    fn visvalingam_keeps_spikes() {
+        let path = [(0.0, 0.0), (1.0, 10.0), (2.0, 0.0), (3.0, 0.1), (4.0, 0.0)];
+        let (r, stats) = visvalingam_with_stats(&path, 1.0);
+
+        assert_eq!(r, vec![(0.0, 0.0), (1.0, 10.0), (2.0, 0.0), (4.0, 0.0)]);
+        assert_eq!(
+            (stats.paths, stats.points_before, stats.points_after),
+            (1, 5, 4)
+        );
+        assert!((stats.max_error - 0.1).abs() < 1e-9);
+    }
+}
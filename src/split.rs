@@ -0,0 +1,59 @@
+//! Splitting of paths at sharp turns, so that pen plotters lift the pen at hairpins instead of
+//! rounding them off at high speed.
+
+use crate::pipeline::PathTransform;
+
+/// [`PathTransform`] that splits paths wherever they turn by more than the given angle.
+///
+/// Closed rings are kept whole so that areas can still be filled.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SplitAtTurns {
+    /// The sharpest turn allowed within a path, in degrees. 0 means the path keeps going straight
+    /// and 180 that it goes back on itself.
+    pub max_turn: f64,
+}
+
+impl SplitAtTurns {
+    pub fn new(max_turn: f64) -> Self {
+        SplitAtTurns { max_turn }
+    }
+
+    /// The pieces of the given path, consecutive pieces share the point of the turn.
+    pub fn split(&self, path: &[(f64, f64)]) -> Vec<Vec<(f64, f64)>> {
+        let is_ring = path.len() > 3 && path.first() == path.last();
+        if path.len() < 3 || is_ring {
+            return vec![path.to_vec()];
+        }
+
+        let mut pieces = vec![];
+        let mut current = vec![path[0]];
+        for w in path.windows(3) {
+            current.push(w[1]);
+
+            if turn(w[0], w[1], w[2]) > self.max_turn {
+                pieces.push(std::mem::replace(&mut current, vec![w[1]]));
+            }
+        }
+        current.push(path[path.len() - 1]);
+        pieces.push(current);
+
+        pieces
+    }
+}
+
+impl PathTransform for SplitAtTurns {
+    fn apply(&self, paths: Vec<Vec<(f64, f64)>>) -> Vec<Vec<(f64, f64)>> {
+        paths.iter().flat_map(|p| self.split(p)).collect()
+    }
+}
+
+/// How much the direction changes at `b` going from `a` to `c`, in degrees.
+fn turn(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> f64 {
+    let (ux, uy) = (b.0 - a.0, b.1 - a.1);
+    let (vx, vy) = (c.0 - b.0, c.1 - b.1);
+
+    f64::atan2(ux * vy - uy * vx, ux * vx + uy * vy)
+        .abs()
+        .to_degrees()
+}
@@ -0,0 +1,133 @@
+use std::{convert::Infallible, fmt, str::FromStr};
+
+use crate::{color::Color, transform::Affine, Road};
+
+/// How a path is drawn.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Style {
+    pub color: Color,
+    pub width: f64,
+
+    /// Paths on higher layers are drawn on top of the ones on lower layers.
+    pub layer: i32,
+
+    /// Dash pattern as alternating lengths of dashes and gaps, empty for a solid line.
+    pub dash: Vec<f64>,
+
+    /// Radius of the marker drawn for paths made of a single point.
+    pub radius: f64,
+
+    /// Shape of the marker drawn for paths made of a single point.
+    pub marker: Marker,
+
+    /// Color used to fill the closed paths, holes are handled with the even-odd rule.
+    pub fill: Color,
+
+    /// Transform applied to the whole layer in canvas coordinates, after it has been fit in the
+    /// canvas.
+    pub transform: Affine,
+
+    /// Text shown in the legend for the paths drawn with this style, if any.
+    pub label: Option<String>,
+}
+
+/// Shape of the point markers, centered on the point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Marker {
+    #[default]
+    Circle,
+    Square,
+    Triangle,
+}
+
+/// A set of paths that share the same style.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StyledPaths {
+    pub style: Style,
+    pub paths: Vec<Vec<(f64, f64)>>,
+}
+
+impl Style {
+    pub fn new(color: Color, width: f64) -> Self {
+        Style {
+            color,
+            width,
+            layer: 0,
+            dash: vec![],
+            radius: width,
+            marker: Marker::Circle,
+            fill: Color::None,
+            transform: Affine::IDENTITY,
+            label: None,
+        }
+    }
+}
+
+/// Style every road with the given function and group together the ones that share the same
+/// style. Roads for which the function returns `None` are skipped.
+///
+/// The groups are sorted by layer, so they can be drawn in order.
+pub fn group_by_style(
+    roads: Vec<Road>,
+    mut f: impl FnMut(&Road) -> Option<Style>,
+) -> Vec<StyledPaths> {
+    match try_group_by_style(roads, |r| Ok::<_, Infallible>(f(r))) {
+        Ok(groups) => groups,
+        Err(e) => match e {},
+    }
+}
+
+/// Like [`group_by_style`], but the styling function can fail.
+pub fn try_group_by_style<E>(
+    roads: Vec<Road>,
+    mut f: impl FnMut(&Road) -> Result<Option<Style>, E>,
+) -> Result<Vec<StyledPaths>, E> {
+    let mut groups: Vec<StyledPaths> = vec![];
+
+    for road in roads {
+        let style = match f(&road)? {
+            None => continue,
+            Some(s) => s,
+        };
+
+        let mut paths = vec![road.points];
+        paths.extend(road.holes);
+
+        match groups.iter_mut().find(|g| g.style == style) {
+            Some(g) => g.paths.extend(paths),
+            None => groups.push(StyledPaths { style, paths }),
+        }
+    }
+
+    groups.sort_by_key(|g| g.style.layer);
+
+    Ok(groups)
+}
+
+impl FromStr for Marker {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "circle" => Ok(Marker::Circle),
+            "square" => Ok(Marker::Square),
+            "triangle" => Ok(Marker::Triangle),
+            _ => Err(format!("invalid marker '{}'", s.trim())),
+        }
+    }
+}
+
+impl fmt::Display for Marker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Marker::Circle => "circle",
+            Marker::Square => "square",
+            Marker::Triangle => "triangle",
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+crate::util::serde_via_str!(Marker);
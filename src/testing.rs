@@ -0,0 +1,435 @@
+//! Fixtures to exercise the whole pipeline hermetically, without network access: a local server
+//! answering like Nominatim and Overpass with canned json, and builders of synthetic places and
+//! roads to fill it with.
+//!
+//! ```no_run
+//! use roads::{prelude::*, testing::{self, Fixtures}};
+//!
+//! # async fn run() -> std::io::Result<()> {
+//! let center = LatLon::new(45.07, 7.68);
+//! let server = Fixtures::new()
+//!     .nominatim(testing::nominatim_json(&[testing::entry("Testville", 42, center, 0.01)]))
+//!     .overpass(testing::overpass_json(&testing::grid(center, 5, 100.0)))
+//!     .serve()
+//!     .await?;
+//!
+//! let endpoints = server.endpoints();
+//! let places = roads::search_with(&endpoints, "Testville").await.unwrap();
+//! let query = roads::roads_query(&places[0], &RoadFilter::all());
+//! let roads = roads::fetch_query_with(&endpoints, &query).await.unwrap();
+//! assert_eq!(roads.value.len(), 10);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+
+use crate::{
+    endpoints::Endpoints, error::Service, retry::RetryPolicy, LatLon, NominatimEntry,
+    OverpassElement, OverpassMember, OverpassResponse, Road,
+};
+
+/// The canned responses of a [`FixtureServer`].
+#[derive(Debug, Clone, Default)]
+pub struct Fixtures {
+    routes: Vec<Route>,
+}
+
+/// A local server answering the requests sent to the [`FixtureServer::endpoints`] with the
+/// [`Fixtures`] it was started with, recording them. It stops once dropped.
+#[derive(Debug)]
+pub struct FixtureServer {
+    addr: SocketAddr,
+    requests: Arc<Mutex<Vec<FixtureRequest>>>,
+    task: JoinHandle<()>,
+}
+
+/// A request received by a [`FixtureServer`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixtureRequest {
+    pub service: Service,
+
+    /// The path and the query string of the Nominatim requests, e.g. `/search?q=Turin&...`, or
+    /// the query sent to Overpass.
+    pub body: String,
+}
+
+/// The response to the requests sent to a service, or only to the ones containing some text.
+#[derive(Debug, Clone)]
+struct Route {
+    service: Service,
+    matching: Option<String>,
+    status: u16,
+    body: Vec<u8>,
+}
+
+/// Paths the services are served at, since they share the same address.
+const NOMINATIM_PATH: &str = "/nominatim";
+const OVERPASS_PATH: &str = "/overpass/api/interpreter";
+
+impl Fixtures {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer the Nominatim searches with the given json, see [`nominatim_json`].
+    pub fn nominatim(self, body: impl Into<Vec<u8>>) -> Self {
+        self.route(Service::Nominatim, None, 200, body.into())
+    }
+
+    /// Answer the Overpass queries with the given json, see [`overpass_json`].
+    pub fn overpass(self, body: impl Into<Vec<u8>>) -> Self {
+        self.route(Service::Overpass, None, 200, body.into())
+    }
+
+    /// Answer the Overpass queries containing the given text, e.g. `out count`, with the given
+    /// json. The queries matching more than one response get the first one added.
+    pub fn overpass_matching(self, text: impl Into<String>, body: impl Into<Vec<u8>>) -> Self {
+        self.route(Service::Overpass, Some(text.into()), 200, body.into())
+    }
+
+    /// Answer the requests to the given service with the given status and an empty body, e.g.
+    /// 429 to check how an overloaded server is handled.
+    pub fn status(self, service: Service, status: u16) -> Self {
+        self.route(service, None, status, vec![])
+    }
+
+    fn route(
+        mut self,
+        service: Service,
+        matching: Option<String>,
+        status: u16,
+        body: Vec<u8>,
+    ) -> Self {
+        self.routes.push(Route {
+            service,
+            matching,
+            status,
+            body,
+        });
+        self
+    }
+
+    /// Start answering on a free port of the loopback interface.
+    pub async fn serve(self) -> io::Result<FixtureServer> {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+        let addr = listener.local_addr()?;
+        let requests = Arc::new(Mutex::new(vec![]));
+
+        let routes = Arc::new(self.routes);
+        let received = Arc::clone(&requests);
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let routes = Arc::clone(&routes);
+                let received = Arc::clone(&received);
+                tokio::spawn(async move {
+                    // a client hanging up midway is none of the server's business
+                    let _ = answer(stream, &routes, &received).await;
+                });
+            }
+        });
+
+        Ok(FixtureServer {
+            addr,
+            requests,
+            task,
+        })
+    }
+}
+
+impl FixtureServer {
+    /// Endpoints pointing to this server, without cache nor retries so that every request
+    /// reaches it exactly once.
+    pub fn endpoints(&self) -> Endpoints {
+        Endpoints::self_hosted(self.nominatim_url(), self.overpass_url())
+            .with_cache(None)
+            .with_retry(RetryPolicy::none())
+    }
+
+    pub fn nominatim_url(&self) -> String {
+        format!("http://{}{}", self.addr, NOMINATIM_PATH)
+    }
+
+    pub fn overpass_url(&self) -> String {
+        format!("http://{}{}", self.addr, OVERPASS_PATH)
+    }
+
+    /// The requests received so far, oldest first.
+    pub fn requests(&self) -> Vec<FixtureRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Drop for FixtureServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Read a single request from the given connection and answer it.
+async fn answer(
+    mut stream: TcpStream,
+    routes: &[Route],
+    received: &Mutex<Vec<FixtureRequest>>,
+) -> io::Result<()> {
+    let mut buf = vec![];
+    let header_end = loop {
+        let mut chunk = [0; 4096];
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+
+        if let Some(i) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break i + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).into_owned();
+    let content_length = head
+        .lines()
+        .filter_map(|l| l.split_once(':'))
+        .find(|(k, _)| k.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, v)| v.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+    while buf.len() < header_end + content_length {
+        let mut chunk = [0; 4096];
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+
+    let target = head.split_whitespace().nth(1).unwrap_or_default();
+    let request = if let Some(search) = target.strip_prefix(NOMINATIM_PATH) {
+        Some(FixtureRequest {
+            service: Service::Nominatim,
+            body: search.to_string(),
+        })
+    } else if target.starts_with(OVERPASS_PATH) {
+        let form = String::from_utf8_lossy(&buf[header_end..]).into_owned();
+        let query = form
+            .split('&')
+            .find_map(|p| p.strip_prefix("data="))
+            .map_or(form.clone(), url_decode);
+        Some(FixtureRequest {
+            service: Service::Overpass,
+            body: query,
+        })
+    } else {
+        None
+    };
+
+    let route = request.as_ref().and_then(|r| {
+        routes.iter().find(|route| {
+            route.service == r.service && route.matching.as_ref().is_none_or(|m| r.body.contains(m))
+        })
+    });
+    if let Some(request) = request {
+        received.lock().unwrap().push(request);
+    }
+
+    let (status, body) = route.map_or((404, &[][..]), |r| (r.status, &r.body[..]));
+    let head = format!(
+        "HTTP/1.1 {} Fixture\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body).await?;
+    stream.shutdown().await
+}
+
+/// Decode a value of a form sent as `application/x-www-form-urlencoded`.
+fn url_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => match (hex_digit(bytes.get(i + 1)), hex_digit(bytes.get(i + 2))) {
+                (Some(hi), Some(lo)) => {
+                    decoded.push(hi << 4 | lo);
+                    i += 2;
+                }
+                _ => decoded.push(b'%'),
+            },
+            b => decoded.push(b),
+        }
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn hex_digit(b: Option<&u8>) -> Option<u8> {
+    char::from(*b?).to_digit(16).map(|d| d as u8)
+}
+
+/// A relation found by Nominatim with the given name and id, spanning `size` degrees around the
+/// given center.
+pub fn entry(name: &str, osm_id: i64, center: LatLon, size: f64) -> NominatimEntry {
+    let half = size / 2.0;
+    let coord = |c: f64| c.to_string();
+    NominatimEntry {
+        place_id: osm_id,
+        osm_type: "relation".to_string(),
+        osm_id,
+        display_name: name.to_string(),
+        importance: 0.5,
+        boundingbox: [
+            coord(center.lat - half),
+            coord(center.lat + half),
+            coord(center.lon - half),
+            coord(center.lon + half),
+        ],
+        class: "boundary".to_string(),
+        r#type: "administrative".to_string(),
+    }
+}
+
+/// The json Nominatim answers a search with, finding the given places.
+pub fn nominatim_json(entries: &[NominatimEntry]) -> String {
+    serde_json::to_string(entries).expect("places are always serializable")
+}
+
+/// A way with the given id, tags and points on the globe, projected like the fetched ones.
+pub fn way(id: i64, tags: &[(&str, &str)], points: &[LatLon]) -> Road {
+    Road {
+        id,
+        tags: tags
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect(),
+        points: points.iter().map(|p| p.to_xy()).collect(),
+        holes: vec![],
+    }
+}
+
+/// A grid of `n` residential streets running east and `n` running north, `spacing` meters apart,
+/// with the given south west corner. The ids of the ways go from 1 to `2 * n`.
+pub fn grid(corner: LatLon, n: usize, spacing: f64) -> Vec<Road> {
+    // meters per degree, close enough for a neighborhood
+    let dlat = spacing / 111_320.0;
+    let dlon = dlat / corner.lat.to_radians().cos();
+    let len = n.saturating_sub(1) as f64;
+    let at = |i: f64, j: f64| LatLon::new(corner.lat + i * dlat, corner.lon + j * dlon);
+
+    let mut roads = vec![];
+    for i in 0..n {
+        let i = i as f64;
+        let tags = [("highway", "residential")];
+        roads.push(way(
+            roads.len() as i64 + 1,
+            &tags,
+            &[at(i, 0.0), at(i, len)],
+        ));
+        roads.push(way(
+            roads.len() as i64 + 1,
+            &tags,
+            &[at(0.0, i), at(len, i)],
+        ));
+    }
+    roads
+}
+
+/// The json Overpass answers a query with, finding the given roads. The roads with holes are
+/// sent as multipolygons.
+pub fn overpass_json(roads: &[Road]) -> String {
+    let geometry = |points: &[(f64, f64)]| points.iter().map(|p| LatLon::from_xy(*p)).collect();
+    let elements = roads
+        .iter()
+        .map(|r| {
+            let mut element = OverpassElement {
+                kind: "way".to_string(),
+                id: r.id,
+                tags: r.tags.clone(),
+                geometry: geometry(&r.points),
+                lat: None,
+                lon: None,
+                nodes: vec![],
+                members: vec![],
+            };
+            if r.points.len() == 1 {
+                let p = LatLon::from_xy(r.points[0]);
+                element.kind = "node".to_string();
+                element.geometry = vec![];
+                (element.lat, element.lon) = (Some(p.lat), Some(p.lon));
+            } else if !r.holes.is_empty() {
+                let member = |role: &str, points: &[(f64, f64)]| OverpassMember {
+                    kind: "way".to_string(),
+                    id: 0,
+                    role: role.to_string(),
+                    geometry: geometry(points),
+                };
+                element.kind = "relation".to_string();
+                element.geometry = vec![];
+                element
+                    .tags
+                    .insert("type".to_string(), "multipolygon".to_string());
+                element.members = std::iter::once(member("outer", &r.points))
+                    .chain(r.holes.iter().map(|h| member("inner", h)))
+                    .collect();
+            }
+            element
+        })
+        .collect();
+
+    let response = OverpassResponse {
+        elements,
+        remark: None,
+    };
+    serde_json::to_string(&response).expect("roads are always serializable")
+}
+
+/// The json Overpass answers a query ending in `out count` with, counting the given number of
+/// elements.
+pub fn count_json(nodes: u64, ways: u64, relations: u64) -> String {
+    let tags = BTreeMap::from([
+        ("nodes".to_string(), nodes.to_string()),
+        ("ways".to_string(), ways.to_string()),
+        ("relations".to_string(), relations.to_string()),
+        ("total".to_string(), (nodes + ways + relations).to_string()),
+    ]);
+    let response = OverpassResponse {
+        elements: vec![OverpassElement {
+            kind: "count".to_string(),
+            id: 0,
+            tags,
+            geometry: vec![],
+            lat: None,
+            lon: None,
+            nodes: vec![],
+            members: vec![],
+        }],
+        remark: None,
+    };
+    serde_json::to_string(&response).expect("counts are always serializable")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn url_decode_bytes() {
+        assert_eq!(url_decode("way%5Bhighway%5D%3B+out"), "way[highway]; out");
+        assert_eq!(url_decode("%é%4"), "%é%4");
+        assert_eq!(url_decode("caf%C3%A9%"), "café%");
+    }
+}
@@ -0,0 +1,188 @@
+//! Single-stroke text rendered as polylines, so that titles and captions can be plotted with the
+//! same pen as the map instead of relying on the fonts of an SVG viewer.
+//!
+//! The font is a small uppercase stroke font in the spirit of the Hershey simplex font: lowercase
+//! letters are drawn as uppercase ones, common accented latin letters lose their accent and any
+//! other unknown character is drawn as `?`.
+
+use crate::color::Color;
+
+/// Glyph height in font units, from the baseline to the top of the capital letters.
+const CAP_HEIGHT: f64 = 8.0;
+
+/// Horizontal space between two glyphs, in font units.
+const SPACING: f64 = 2.0;
+
+/// Vertical distance between two baselines, relative to the size.
+const LINE_HEIGHT: f64 = 1.6;
+
+/// Each glyph is made of strokes separated by `|`, each stroke is a list of `xy` digit pairs on a
+/// grid where the baseline is at y = 1 and the top of the capital letters is at y = 9.
+const GLYPHS: &[(char, u8, &str)] = &[
+    (' ', 4, ""),
+    ('A', 6, "01 39 61|14 54"),
+    ('B', 6, "01 09 49 58 56 45 05|45 64 62 51 01"),
+    ('C', 6, "68 59 19 08 02 11 51 62"),
+    ('D', 6, "01 09 49 67 63 41 01"),
+    ('E', 6, "61 01 09 69|05 45"),
+    ('F', 6, "01 09 69|05 45"),
+    ('G', 6, "68 59 19 08 02 11 51 62 64 34"),
+    ('H', 6, "01 09|61 69|05 65"),
+    ('I', 0, "01 09"),
+    ('J', 5, "59 52 41 11 02 03"),
+    ('K', 6, "01 09|69 03|25 61"),
+    ('L', 5, "09 01 51"),
+    ('M', 6, "01 09 34 69 61"),
+    ('N', 6, "01 09 61 69"),
+    ('O', 6, "19 08 02 11 51 62 68 59 19"),
+    ('P', 5, "01 09 49 58 56 45 05"),
+    ('Q', 6, "19 08 02 11 51 62 68 59 19|43 60"),
+    ('R', 6, "01 09 49 58 56 45 05|35 61"),
+    ('S', 6, "68 59 19 08 06 15 55 64 62 51 11 02"),
+    ('T', 6, "09 69|39 31"),
+    ('U', 6, "09 02 11 51 62 69"),
+    ('V', 6, "09 31 69"),
+    ('W', 6, "09 11 36 51 69"),
+    ('X', 6, "09 61|01 69"),
+    ('Y', 6, "09 35 69|35 31"),
+    ('Z', 6, "09 69 01 61"),
+    ('0', 5, "19 08 02 11 41 52 58 49 19"),
+    ('1', 5, "17 39 31|11 51"),
+    ('2', 5, "08 19 49 58 56 01 51"),
+    ('3', 5, "08 19 49 58 56 45 25|45 54 52 41 11 02"),
+    ('4', 5, "41 49 03 53"),
+    ('5', 5, "59 09 05 45 54 52 41 11 02"),
+    ('6', 5, "58 49 19 08 02 11 41 52 54 45 05"),
+    ('7', 5, "09 59 21"),
+    ('8', 5, "15 06 08 19 49 58 56 45 15 04 02 11 41 52 54 45"),
+    ('9', 5, "02 11 41 52 58 49 19 08 06 15 55"),
+    ('.', 0, "01 02"),
+    (',', 1, "12 00"),
+    (':', 0, "01 02|05 06"),
+    (';', 1, "12 00|15 16"),
+    ('-', 4, "05 45"),
+    ('+', 4, "05 45|23 27"),
+    ('=', 4, "04 44|06 46"),
+    ('/', 4, "01 49"),
+    ('(', 2, "29 07 03 21"),
+    (')', 2, "09 27 23 01"),
+    ('!', 0, "09 03|01 02"),
+    ('?', 4, "08 19 39 48 46 24 23|21 22"),
+    ('\'', 0, "09 07"),
+    ('"', 2, "09 07|29 27"),
+    ('°', 2, "08 19 28 17 08"),
+    ('_', 6, "00 60"),
+];
+
+/// Lines of text drawn in the bottom left corner of the map.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Caption {
+    pub lines: Vec<String>,
+
+    /// Height of the capital letters, in canvas units.
+    pub size: f64,
+
+    pub color: Color,
+}
+
+impl Caption {
+    pub fn new(lines: Vec<String>, size: f64) -> Self {
+        Caption {
+            lines,
+            size,
+            color: Color::BLACK,
+        }
+    }
+
+    /// Width of the stroke that keeps the text legible at this size.
+    pub fn stroke_width(&self) -> f64 {
+        self.size / 10.0
+    }
+
+    /// The polylines of the caption with the bottom left corner at the given point, leaving a
+    /// margin as big as the text size. The y axis grows downwards like in SVG.
+    pub fn paths(&self, (x, y): (f64, f64)) -> Vec<Vec<(f64, f64)>> {
+        let x = x + self.size;
+        let last_baseline = y - self.size;
+
+        self.lines
+            .iter()
+            .rev()
+            .enumerate()
+            .flat_map(|(i, l)| {
+                let baseline = last_baseline - i as f64 * self.size * LINE_HEIGHT;
+                engrave(l, (x, baseline), self.size)
+            })
+            .collect()
+    }
+}
+
+/// The polylines of the given text starting at the given point on the baseline, with capital
+/// letters as tall as `size`. The y axis grows downwards like in SVG.
+pub fn engrave(text: &str, (x, y): (f64, f64), size: f64) -> Vec<Vec<(f64, f64)>> {
+    let scale = size / CAP_HEIGHT;
+
+    let mut paths = vec![];
+    let mut cursor = 0.0;
+    for c in text.chars().flat_map(normalize) {
+        let (width, strokes) = glyph(c);
+
+        for stroke in strokes.split('|').filter(|s| !s.is_empty()) {
+            let path = stroke
+                .split_whitespace()
+                .map(|p| {
+                    let p = p.as_bytes();
+                    let gx = f64::from(p[0] - b'0');
+                    let gy = f64::from(p[1] - b'0') - 1.0;
+                    (x + (cursor + gx) * scale, y - gy * scale)
+                })
+                .collect();
+            paths.push(path);
+        }
+
+        cursor += f64::from(width) + SPACING;
+    }
+
+    paths
+}
+
+/// Width of the given text with capital letters as tall as `size`.
+pub fn width(text: &str, size: f64) -> f64 {
+    let units = text
+        .chars()
+        .flat_map(normalize)
+        .map(|c| f64::from(glyph(c).0) + SPACING)
+        .sum::<f64>();
+
+    (units - SPACING).max(0.0) * size / CAP_HEIGHT
+}
+
+/// The given text as drawn by the font, i.e. uppercase and without accents. Handy to sort text
+/// the way it's read on the map.
+pub fn fold(text: &str) -> String {
+    text.chars().flat_map(normalize).collect()
+}
+
+fn glyph(c: char) -> (u8, &'static str) {
+    let find = |c| GLYPHS.iter().find(|(g, _, _)| *g == c);
+
+    let (_, w, s) = find(c)
+        .or_else(|| find('?'))
+        .expect("the font has a glyph for ?");
+    (*w, *s)
+}
+
+/// The characters of the font to draw for the given character.
+fn normalize(c: char) -> impl Iterator<Item = char> {
+    c.to_uppercase().map(|c| match c {
+        'À' | 'Á' | 'Â' | 'Ã' | 'Ä' | 'Å' => 'A',
+        'Ç' => 'C',
+        'È' | 'É' | 'Ê' | 'Ë' => 'E',
+        'Ì' | 'Í' | 'Î' | 'Ï' => 'I',
+        'Ñ' => 'N',
+        'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ö' | 'Ø' => 'O',
+        'Ù' | 'Ú' | 'Û' | 'Ü' => 'U',
+        'Ý' => 'Y',
+        c => c,
+    })
+}
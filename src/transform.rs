@@ -0,0 +1,155 @@
+//! Affine transforms applied to whole layers while composing the output.
+
+use std::{fmt, str::FromStr};
+
+/// A 2D affine transform `(x, y) -> (a * x + c * y + e, b * x + d * y + f)`, using the same
+/// conventions as the SVG `matrix(a b c d e f)` transform.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Affine {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+    pub d: f64,
+    pub e: f64,
+    pub f: f64,
+}
+
+impl Affine {
+    pub const IDENTITY: Affine = Affine {
+        a: 1.0,
+        b: 0.0,
+        c: 0.0,
+        d: 1.0,
+        e: 0.0,
+        f: 0.0,
+    };
+
+    pub fn translate(dx: f64, dy: f64) -> Self {
+        Affine {
+            e: dx,
+            f: dy,
+            ..Self::IDENTITY
+        }
+    }
+
+    pub fn scale(sx: f64, sy: f64) -> Self {
+        Affine {
+            a: sx,
+            d: sy,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// Counterclockwise rotation by the given angle in degrees, clockwise when the y axis grows
+    /// downwards.
+    pub fn rotate(degrees: f64) -> Self {
+        let (s, c) = degrees.to_radians().sin_cos();
+        Affine {
+            a: c,
+            b: s,
+            c: -s,
+            d: c,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// The transform that applies `self` first and then `other`.
+    pub fn then(&self, other: &Affine) -> Self {
+        Affine {
+            a: other.a * self.a + other.c * self.b,
+            b: other.b * self.a + other.d * self.b,
+            c: other.a * self.c + other.c * self.d,
+            d: other.b * self.c + other.d * self.d,
+            e: other.a * self.e + other.c * self.f + other.e,
+            f: other.b * self.e + other.d * self.f + other.f,
+        }
+    }
+
+    pub fn apply(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        (
+            self.a * x + self.c * y + self.e,
+            self.b * x + self.d * y + self.f,
+        )
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Self::IDENTITY
+    }
+}
+
+impl Default for Affine {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// Parse a list of SVG transforms, e.g. `translate(2, 2) scale(1.1)`. Like in SVG the rightmost
+/// transform is applied first. `none` is the identity.
+impl FromStr for Affine {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut transform = Affine::IDENTITY;
+
+        let mut rest = s.trim();
+        if rest == "none" {
+            return Ok(transform);
+        }
+
+        while !rest.is_empty() {
+            let (name, args) = rest
+                .split_once('(')
+                .ok_or_else(|| format!("invalid transform '{}'", s))?;
+            let (args, tail) = args
+                .split_once(')')
+                .ok_or_else(|| format!("missing ')' in transform '{}'", s))?;
+
+            let args = args
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|a| !a.is_empty())
+                .map(str::parse)
+                .collect::<Result<Vec<f64>, _>>()
+                .map_err(|_| format!("invalid arguments in transform '{}'", s))?;
+
+            let t = match (name.trim(), &args[..]) {
+                ("translate", [dx]) => Affine::translate(*dx, 0.0),
+                ("translate", [dx, dy]) => Affine::translate(*dx, *dy),
+                ("scale", [s]) => Affine::scale(*s, *s),
+                ("scale", [sx, sy]) => Affine::scale(*sx, *sy),
+                ("rotate", [deg]) => Affine::rotate(*deg),
+                ("matrix", [a, b, c, d, e, f]) => Affine {
+                    a: *a,
+                    b: *b,
+                    c: *c,
+                    d: *d,
+                    e: *e,
+                    f: *f,
+                },
+                (name, _) => return Err(format!("invalid transform '{}'", name)),
+            };
+
+            transform = t.then(&transform);
+            rest = tail.trim_start();
+        }
+
+        Ok(transform)
+    }
+}
+
+/// Format as an SVG `matrix()`, or `none` for the identity.
+impl fmt::Display for Affine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_identity() {
+            return f.write_str("none");
+        }
+
+        write!(
+            f,
+            "matrix({} {} {} {} {} {})",
+            self.a, self.b, self.c, self.d, self.e, self.f
+        )
+    }
+}
+
+#[cfg(feature = "serde")]
+crate::util::serde_via_str!(Affine);
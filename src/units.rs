@@ -0,0 +1,359 @@
+//! Values with units of measurement, so that parameters like `210mm` or `2km` can be parsed,
+//! displayed and converted as expected.
+//!
+//! A bare number without unit is interpreted in the default unit of its type.
+
+use std::{fmt, str::FromStr};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// A length on the output canvas.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Length {
+    pub value: f64,
+    pub unit: LengthUnit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LengthUnit {
+    Px,
+    Mm,
+    Cm,
+    In,
+    Pt,
+}
+
+/// A distance on the ground.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Distance {
+    pub value: f64,
+    pub unit: DistanceUnit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DistanceUnit {
+    M,
+    Km,
+}
+
+/// How much to grow an area by, or to shrink it by when negative, e.g. `10%` of its size or `2km`
+/// on every side.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Padding {
+    Percent(f64),
+    Distance(Distance),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseUnitError(String);
+
+impl Length {
+    /// Pixels per inch, as in CSS and SVG.
+    pub const DPI: f64 = 96.0;
+
+    pub fn px(value: f64) -> Self {
+        Length {
+            value,
+            unit: LengthUnit::Px,
+        }
+    }
+
+    pub fn mm(value: f64) -> Self {
+        Length {
+            value,
+            unit: LengthUnit::Mm,
+        }
+    }
+
+    pub fn to_px(self) -> f64 {
+        self.value * self.unit.px_per_unit()
+    }
+
+    pub fn to_mm(self) -> f64 {
+        self.to_px() / LengthUnit::Mm.px_per_unit()
+    }
+
+    /// Convert this length to the given unit.
+    pub fn to(self, unit: LengthUnit) -> Self {
+        Length {
+            value: self.to_px() / unit.px_per_unit(),
+            unit,
+        }
+    }
+}
+
+impl LengthUnit {
+    const ALL: [(LengthUnit, &'static str); 5] = [
+        (LengthUnit::Px, "px"),
+        (LengthUnit::Mm, "mm"),
+        (LengthUnit::Cm, "cm"),
+        (LengthUnit::In, "in"),
+        (LengthUnit::Pt, "pt"),
+    ];
+
+    pub fn px_per_unit(self) -> f64 {
+        match self {
+            LengthUnit::Px => 1.0,
+            LengthUnit::Mm => Length::DPI / 25.4,
+            LengthUnit::Cm => Length::DPI / 2.54,
+            LengthUnit::In => Length::DPI,
+            LengthUnit::Pt => Length::DPI / 72.0,
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        Self::ALL.iter().find(|(u, _)| *u == self).unwrap().1
+    }
+}
+
+impl Distance {
+    pub fn meters(value: f64) -> Self {
+        Distance {
+            value,
+            unit: DistanceUnit::M,
+        }
+    }
+
+    pub fn to_meters(self) -> f64 {
+        match self.unit {
+            DistanceUnit::M => self.value,
+            DistanceUnit::Km => self.value * 1000.0,
+        }
+    }
+}
+
+impl DistanceUnit {
+    const ALL: [(DistanceUnit, &'static str); 2] =
+        [(DistanceUnit::M, "m"), (DistanceUnit::Km, "km")];
+
+    pub fn symbol(self) -> &'static str {
+        Self::ALL.iter().find(|(u, _)| *u == self).unwrap().1
+    }
+}
+
+impl Padding {
+    pub fn none() -> Self {
+        Padding::Percent(0.0)
+    }
+
+    pub fn is_none(&self) -> bool {
+        match self {
+            Padding::Percent(p) => *p == 0.0,
+            Padding::Distance(d) => d.value == 0.0,
+        }
+    }
+}
+
+impl Default for Padding {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+/// Split a string like `21.5mm` into its value and its unit, if any.
+fn split_unit(s: &str) -> Result<(f64, &str), ParseUnitError> {
+    let s = s.trim();
+    let unit_start = s
+        .rfind(|c: char| c.is_ascii_digit() || c == '.')
+        .map_or(0, |i| i + 1);
+
+    let (value, unit) = s.split_at(unit_start);
+    let value = value
+        .trim()
+        .parse()
+        .map_err(|_| ParseUnitError(format!("invalid number '{}'", value.trim())))?;
+
+    Ok((value, unit.trim()))
+}
+
+impl FromStr for Length {
+    type Err = ParseUnitError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (value, unit) = split_unit(s)?;
+        if unit.is_empty() {
+            return Ok(Length::px(value));
+        }
+
+        LengthUnit::ALL
+            .iter()
+            .find(|(_, sym)| *sym == unit)
+            .map(|(unit, _)| Length { value, unit: *unit })
+            .ok_or_else(|| ParseUnitError(format!("unknown unit '{}'", unit)))
+    }
+}
+
+impl FromStr for LengthUnit {
+    type Err = ParseUnitError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|(_, sym)| *sym == s.trim())
+            .map(|(unit, _)| *unit)
+            .ok_or_else(|| ParseUnitError(format!("unknown unit '{}'", s.trim())))
+    }
+}
+
+impl FromStr for Distance {
+    type Err = ParseUnitError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (value, unit) = split_unit(s)?;
+        if unit.is_empty() {
+            return Ok(Distance::meters(value));
+        }
+
+        DistanceUnit::ALL
+            .iter()
+            .find(|(_, sym)| *sym == unit)
+            .map(|(unit, _)| Distance { value, unit: *unit })
+            .ok_or_else(|| ParseUnitError(format!("unknown unit '{}'", unit)))
+    }
+}
+
+impl FromStr for Padding {
+    type Err = ParseUnitError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().strip_suffix('%') {
+            Some(p) => p
+                .trim()
+                .parse()
+                .map(Padding::Percent)
+                .map_err(|_| ParseUnitError(format!("invalid percentage '{}'", s.trim()))),
+            None => s.parse().map(Padding::Distance),
+        }
+    }
+}
+
+impl fmt::Display for Length {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.value, self.unit.symbol())
+    }
+}
+
+impl fmt::Display for LengthUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.symbol())
+    }
+}
+
+/// Serialized as its symbol, e.g. `mm`.
+impl Serialize for LengthUnit {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for LengthUnit {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        String::deserialize(d)?.parse().map_err(de::Error::custom)
+    }
+}
+
+impl fmt::Display for Distance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.value, self.unit.symbol())
+    }
+}
+
+impl fmt::Display for Padding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Padding::Percent(p) => write!(f, "{}%", p),
+            Padding::Distance(d) => d.fmt(f),
+        }
+    }
+}
+
+impl fmt::Display for ParseUnitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ParseUnitError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_length() {
+        assert_eq!("210mm".parse(), Ok(Length::mm(210.0)));
+        assert_eq!(" 2.5 in ".parse::<Length>().unwrap().unit, LengthUnit::In);
+        assert_eq!("1920".parse(), Ok(Length::px(1920.0)));
+        assert_eq!(
+            "3furlongs".parse::<Length>(),
+            Err(ParseUnitError("unknown unit 'furlongs'".to_string()))
+        );
+        assert_eq!(
+            "mm".parse::<Length>(),
+            Err(ParseUnitError("invalid number ''".to_string()))
+        );
+        assert_eq!("pt".parse(), Ok(LengthUnit::Pt));
+    }
+
+    #[test]
+    fn parse_distance() {
+        assert_eq!(
+            "2km".parse(),
+            Ok(Distance {
+                value: 2.0,
+                unit: DistanceUnit::Km
+            })
+        );
+        assert_eq!("250".parse(), Ok(Distance::meters(250.0)));
+        assert_eq!("2km".parse::<Distance>().unwrap().to_meters(), 2000.0);
+        assert!("2mi".parse::<Distance>().is_err());
+    }
+
+    #[test]
+    fn parse_padding() {
+        assert_eq!("10%".parse(), Ok(Padding::Percent(10.0)));
+        assert_eq!("-5%".parse(), Ok(Padding::Percent(-5.0)));
+        assert_eq!(
+            "500".parse(),
+            Ok(Padding::Distance(Distance::meters(500.0)))
+        );
+        assert_eq!(
+            "-1km".parse(),
+            Ok(Padding::Distance(Distance {
+                value: -1.0,
+                unit: DistanceUnit::Km
+            }))
+        );
+        assert_eq!(
+            "ten%".parse::<Padding>(),
+            Err(ParseUnitError("invalid percentage 'ten%'".to_string()))
+        );
+        assert!("3mm".parse::<Padding>().is_err());
+        assert!("0km".parse::<Padding>().unwrap().is_none());
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(Length::px(96.0).to_mm(), 25.4);
+        assert_eq!(Length::mm(25.4).to_px(), 96.0);
+        assert_eq!("1in".parse::<Length>().unwrap().to_px(), 96.0);
+        assert_eq!("72pt".parse::<Length>().unwrap().to_px(), 96.0);
+        let cm = Length::mm(10.0).to(LengthUnit::Cm);
+        assert_eq!(cm.unit, LengthUnit::Cm);
+        assert!((cm.value - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for s in ["210mm", "1.5in", "800px", "12pt", "3cm"] {
+            assert_eq!(s.parse::<Length>().unwrap().to_string(), s);
+        }
+        for s in ["2km", "250m"] {
+            assert_eq!(s.parse::<Distance>().unwrap().to_string(), s);
+        }
+        for s in ["10%", "-5%", "2km", "-300m"] {
+            assert_eq!(s.parse::<Padding>().unwrap().to_string(), s);
+        }
+        assert_eq!(Length::px(1920.0).to_string(), "1920px");
+    }
+}
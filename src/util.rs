@@ -1,4 +1,9 @@
-use std::time;
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time,
+};
 
 use tui::widgets;
 
@@ -21,10 +26,22 @@ impl<T> WrappingList<T> {
         l
     }
 
+    /// Add the given items at the end, keeping the selection.
+    pub fn extend(&mut self, items: impl IntoIterator<Item = T>) {
+        self.data.extend(items);
+        if self.state.selected().is_none() && !self.data.is_empty() {
+            self.state.select(Some(0));
+        }
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = &T> {
         self.data.iter()
     }
 
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.data.iter_mut()
+    }
+
     pub fn state(&mut self) -> &mut widgets::ListState {
         &mut self.state
     }
@@ -61,11 +78,67 @@ impl<T> WrappingList<T> {
     }
 }
 
+/// A small map that forgets the least recently used entry once full.
+///
+/// Lookups are linear, it's meant for a handful of big values rather than many small ones.
+#[derive(Debug, Clone)]
+pub struct Lru<K, V> {
+    capacity: usize,
+
+    /// The most recently used entry is the last one.
+    entries: Vec<(K, V)>,
+}
+
+impl<K: PartialEq, V> Lru<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        Lru {
+            capacity,
+            entries: vec![],
+        }
+    }
+
+    /// The value of the given key, marking it as the most recently used.
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        let i = self.entries.iter().position(|(k, _)| k == key)?;
+        let entry = self.entries.remove(i);
+        self.entries.push(entry);
+        self.entries.last().map(|(_, v)| v)
+    }
+
+    pub fn put(&mut self, key: K, value: V) {
+        self.entries.retain(|(k, _)| *k != key);
+        if self.entries.len() >= self.capacity && !self.entries.is_empty() {
+            self.entries.remove(0);
+        }
+        if self.capacity > 0 {
+            self.entries.push((key, value));
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
 pub struct DotsSpinner {
     state: usize,
     last_tick: Option<time::Instant>,
 }
 
+impl Default for DotsSpinner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl DotsSpinner {
     pub const PATTERN: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
 
@@ -94,3 +167,96 @@ impl DotsSpinner {
         Self::PATTERN[self.state]
     }
 }
+
+/// 64 bits [FNV-1a] hash of the given bytes.
+///
+/// Unlike the hashers in std it's stable across releases, so it can be persisted.
+///
+/// [FNV-1a]: https://en.wikipedia.org/wiki/Fowler%E2%80%93Noll%E2%80%93Vo_hash_function
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |h, b| {
+        (h ^ u64::from(*b)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Write the file at the given path as a whole or not at all.
+///
+/// The content is written to a temporary file in the same directory, synced to disk and only
+/// then renamed over the destination, so that an interrupted or failed write never leaves a
+/// truncated file behind.
+pub fn write_atomic(
+    path: impl AsRef<Path>,
+    write: impl FnOnce(&mut io::BufWriter<fs::File>) -> io::Result<()>,
+) -> io::Result<()> {
+    let path = path.as_ref();
+    let tmp = temporary_path(path);
+
+    let res = (|| {
+        let mut f = io::BufWriter::new(fs::File::create(&tmp)?);
+        write(&mut f)?;
+        f.flush()?;
+        f.get_ref().sync_all()?;
+        drop(f);
+
+        fs::rename(&tmp, path)?;
+
+        // make the rename itself durable
+        #[cfg(unix)]
+        if let Some(dir) = path.parent() {
+            let dir = if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir
+            };
+            fs::File::open(dir)?.sync_all()?;
+        }
+
+        Ok(())
+    })();
+
+    if res.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+
+    res
+}
+
+/// Like [`fs::write`], but atomic, see [`write_atomic`].
+pub fn write_file_atomic(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    write_atomic(path, |f| f.write_all(contents.as_ref()))
+}
+
+/// A hidden file next to the given one, unique to this process.
+fn temporary_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()))
+}
+
+/// Implement `Serialize` and `Deserialize` through `Display` and `FromStr`, so that the values
+/// are persisted with the same syntax the options accept, like [`Color`](crate::color::Color).
+#[cfg(feature = "serde")]
+macro_rules! serde_via_str {
+    ($($t:ty),+ $(,)?) => {
+        $(
+            impl serde::Serialize for $t {
+                fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                    s.collect_str(self)
+                }
+            }
+
+            impl<'de> serde::Deserialize<'de> for $t {
+                fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+                    let s = <String as serde::Deserialize>::deserialize(d)?;
+                    s.parse().map_err(serde::de::Error::custom)
+                }
+            }
+        )+
+    };
+}
+
+#[cfg(feature = "serde")]
+pub(crate) use serde_via_str;
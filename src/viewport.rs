@@ -0,0 +1,256 @@
+//! Mapping of the projected world coordinates onto the output canvas.
+
+use std::{fmt, str::FromStr};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// The output surface along with its coordinate system conventions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Canvas {
+    pub width: f64,
+    pub height: f64,
+
+    /// Where the point `(0, 0)` is on the canvas.
+    pub origin: Origin,
+
+    /// Which way the y axis grows.
+    pub y_axis: YAxis,
+}
+
+/// Scale-to-fit mapping between world coordinates (projected, y growing upwards) and the
+/// coordinates of a [`Canvas`].
+///
+/// The drawing keeps the aspect ratio of the world bounds, thus it can be smaller than the canvas
+/// on one of the axes, see [`Viewport::size`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub canvas: Canvas,
+    pub min: (f64, f64),
+    pub max: (f64, f64),
+
+    /// Canvas units per world unit.
+    pub scale: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Origin {
+    TopLeft,
+    BottomLeft,
+    Center,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum YAxis {
+    /// The y axis grows downwards like in SVG and most raster formats.
+    Down,
+
+    /// The y axis grows upwards like in G-code and DXF.
+    Up,
+}
+
+impl Canvas {
+    /// A canvas following the SVG conventions: origin in the top left corner and y axis growing
+    /// downwards.
+    pub fn new(width: f64, height: f64) -> Self {
+        Canvas {
+            width,
+            height,
+            origin: Origin::TopLeft,
+            y_axis: YAxis::Down,
+        }
+    }
+
+    /// A canvas following the usual plotting and CAD conventions: origin in the bottom left
+    /// corner and y axis growing upwards.
+    pub fn cartesian(width: f64, height: f64) -> Self {
+        Canvas {
+            width,
+            height,
+            origin: Origin::BottomLeft,
+            y_axis: YAxis::Up,
+        }
+    }
+
+    /// Convert a point expressed as an offset from the top left corner of a drawing of the given
+    /// size to the coordinate system of this canvas.
+    pub fn place(&self, (u, v): (f64, f64), (w, h): (f64, f64)) -> (f64, f64) {
+        let (ox, oy) = self.origin_offset((w, h));
+        let x = u - ox;
+        let y = v - oy;
+
+        match self.y_axis {
+            YAxis::Down => (x, y),
+            YAxis::Up => (x, -y),
+        }
+    }
+
+    /// The smallest coordinates of a drawing of the given size in this canvas coordinate system,
+    /// useful for things like the SVG `viewBox`.
+    pub fn min_corner(&self, (w, h): (f64, f64)) -> (f64, f64) {
+        let (x0, y0) = self.place((0.0, 0.0), (w, h));
+        let (x1, y1) = self.place((w, h), (w, h));
+
+        (x0.min(x1), y0.min(y1))
+    }
+
+    /// Inverse of [`Canvas::place`].
+    pub fn unplace(&self, (x, y): (f64, f64), (w, h): (f64, f64)) -> (f64, f64) {
+        let (ox, oy) = self.origin_offset((w, h));
+        let y = match self.y_axis {
+            YAxis::Down => y,
+            YAxis::Up => -y,
+        };
+
+        (x + ox, y + oy)
+    }
+
+    fn origin_offset(&self, (w, h): (f64, f64)) -> (f64, f64) {
+        match self.origin {
+            Origin::TopLeft => (0.0, 0.0),
+            Origin::BottomLeft => (0.0, h),
+            Origin::Center => (w / 2.0, h / 2.0),
+        }
+    }
+}
+
+impl Viewport {
+    /// Fit the bounds of the given points in the canvas. If there are no points the world is
+    /// assumed to be the canvas itself.
+    pub fn fit<'a>(canvas: Canvas, points: impl IntoIterator<Item = &'a (f64, f64)>) -> Self {
+        let (min, max) = bounds(points).unwrap_or(((0.0, 0.0), (canvas.width, canvas.height)));
+
+        Self::with_bounds(canvas, min, max)
+    }
+
+    /// Fit the given world bounds in the canvas.
+    pub fn with_bounds(canvas: Canvas, min: (f64, f64), max: (f64, f64)) -> Self {
+        let scale = f64::min(
+            canvas.width / (max.0 - min.0),
+            canvas.height / (max.1 - min.1),
+        );
+
+        // degenerate bounds, e.g. a single point
+        let scale = if scale.is_finite() { scale } else { 1.0 };
+
+        Viewport {
+            canvas,
+            min,
+            max,
+            scale,
+        }
+    }
+
+    /// Size of the drawing on the canvas.
+    pub fn size(&self) -> (f64, f64) {
+        (
+            (self.max.0 - self.min.0) * self.scale,
+            (self.max.1 - self.min.1) * self.scale,
+        )
+    }
+
+    /// The smallest canvas coordinates of the drawing.
+    pub fn min_corner(&self) -> (f64, f64) {
+        self.canvas.min_corner(self.size())
+    }
+
+    pub fn to_canvas(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        self.canvas.place(
+            ((x - self.min.0) * self.scale, (self.max.1 - y) * self.scale),
+            self.size(),
+        )
+    }
+
+    pub fn to_world(&self, p: (f64, f64)) -> (f64, f64) {
+        let (u, v) = self.canvas.unplace(p, self.size());
+        (self.min.0 + u / self.scale, self.max.1 - v / self.scale)
+    }
+}
+
+/// Bounding box of the given points as `(min, max)`, if any.
+pub fn bounds<'a>(
+    points: impl IntoIterator<Item = &'a (f64, f64)>,
+) -> Option<((f64, f64), (f64, f64))> {
+    let mut min = (f64::INFINITY, f64::INFINITY);
+    let mut max = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+
+    for (x, y) in points {
+        min = (x.min(min.0), y.min(min.1));
+        max = (x.max(max.0), y.max(max.1));
+    }
+
+    if min.0 > max.0 || min.1 > max.1 {
+        None
+    } else {
+        Some((min, max))
+    }
+}
+
+impl FromStr for Origin {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "top-left" => Ok(Origin::TopLeft),
+            "bottom-left" => Ok(Origin::BottomLeft),
+            "center" => Ok(Origin::Center),
+            _ => Err(format!("invalid origin '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Origin::TopLeft => "top-left",
+            Origin::BottomLeft => "bottom-left",
+            Origin::Center => "center",
+        })
+    }
+}
+
+impl FromStr for YAxis {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "down" => Ok(YAxis::Down),
+            "up" => Ok(YAxis::Up),
+            _ => Err(format!("invalid y axis direction '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for YAxis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            YAxis::Down => "down",
+            YAxis::Up => "up",
+        })
+    }
+}
+
+/// Serialized as its name, e.g. `top-left`.
+impl Serialize for Origin {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Origin {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        String::deserialize(d)?.parse().map_err(de::Error::custom)
+    }
+}
+
+/// Serialized as its direction, i.e. `down` or `up`.
+impl Serialize for YAxis {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for YAxis {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        String::deserialize(d)?.parse().map_err(de::Error::custom)
+    }
+}
@@ -0,0 +1,445 @@
+use std::{sync::Arc, time::Duration};
+
+use roads::{
+    cache::Cache,
+    color::{Color, ColorSpace},
+    error::Service,
+    overpass,
+    pipeline::Pipeline,
+    polite::{PoliteMode, Throttle},
+    prelude::*,
+    progress::{ProgressObserver, Stage as ProgressStage},
+    render::{
+        pdf::{self, PageSize, PdfOptions},
+        svg::{self, SvgOptions},
+    },
+    testing::{self, Fixtures},
+    Error, SearchOptions,
+};
+
+const CENTER: LatLon = LatLon {
+    lat: 45.07,
+    lon: 7.68,
+};
+
+#[tokio::test]
+async fn search_fetch_and_draw() {
+    let mut roads = testing::grid(CENTER, 4, 100.0);
+    roads.push(testing::way(
+        100,
+        &[("highway", "primary"), ("name", "Corso Francia")],
+        &[LatLon::new(45.069, 7.679), LatLon::new(45.073, 7.684)],
+    ));
+
+    let server = Fixtures::new()
+        .nominatim(testing::nominatim_json(&[testing::entry(
+            "Testville",
+            42,
+            CENTER,
+            0.01,
+        )]))
+        .overpass_matching("out count", testing::count_json(0, 9, 0))
+        .overpass(testing::overpass_json(&roads))
+        .serve()
+        .await
+        .unwrap();
+    let endpoints = server.endpoints();
+
+    let places = roads::search_with(&endpoints, "Testville").await.unwrap();
+    let place = best_entry(&places).unwrap();
+    assert_eq!(place.display_name, "Testville");
+
+    let query = roads::roads_query(place, &RoadFilter::all());
+    let count = endpoints.overpass_client().count(&query).await.unwrap();
+    assert_eq!(count.ways, 9);
+
+    let fetched = roads::fetch_query_with(&endpoints, &query).await.unwrap();
+    assert!(fetched.warnings.is_empty());
+    assert_eq!(fetched.value.len(), 9);
+    let primary = fetched.value.iter().find(|r| r.id == 100).unwrap();
+    assert_eq!(primary.tags["name"], "Corso Francia");
+    assert_eq!(primary.points, roads[8].points);
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 3);
+    assert_eq!(requests[0].service, Service::Nominatim);
+    assert!(requests[0].body.contains("Testville"));
+    assert_eq!(requests[2].body, query);
+
+    let layers = vec![StyledPaths {
+        style: Style::new(Color::BLACK, 1.0),
+        paths: fetched.value.iter().map(|r| r.points.clone()).collect(),
+    }];
+    let mut out = vec![];
+    svg::write(layers, &SvgOptions::new(800.0, 800.0), &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert_eq!(out.matches("<polyline").count(), 9);
+}
+
+#[tokio::test]
+async fn search_next_page() {
+    let found = [
+        testing::entry("Testville", 42, CENTER, 0.01),
+        testing::entry("Testville", 43, CENTER, 0.02),
+    ];
+    let server = Fixtures::new()
+        .nominatim(testing::nominatim_json(&[]))
+        .serve()
+        .await
+        .unwrap();
+
+    let options = SearchOptions {
+        limit: Some(100),
+        ..SearchOptions::default()
+    };
+    roads::search_with_options(&server.endpoints(), "Testville", &options.after(&found))
+        .await
+        .unwrap();
+
+    let body = &server.requests()[0].body;
+    assert!(body.contains("limit=40"));
+    assert!(body.contains("exclude_place_ids=42%2C43"));
+}
+
+#[test]
+fn bbox_corners() {
+    let filter = RoadFilter::all();
+    assert!(roads::bbox_roads_query(45.0, 7.6, 45.1, 7.7, &filter).is_ok());
+
+    for (s, w, n, e) in [
+        (45.1, 7.6, 45.0, 7.7),
+        (45.0, 7.7, 45.1, 7.6),
+        (45.0, 7.6, 45.0, 7.7),
+        (45.0, 7.6, f64::NAN, 7.7),
+        (45.0, 7.6, 95.0, 7.7),
+    ] {
+        let err = roads::bbox_roads_query(s, w, n, e, &filter).unwrap_err();
+        assert!(matches!(err, Error::InvalidBbox { .. }));
+    }
+}
+
+#[test]
+fn print_colors() {
+    let layers = vec![
+        StyledPaths {
+            style: Style::new(Color::Rgb(34, 34, 34), 1.0),
+            paths: vec![vec![(0.0, 0.0), (10.0, 5.0)]],
+        },
+        StyledPaths {
+            style: Style::new(Color::Rgb(200, 30, 30), 1.0),
+            paths: vec![vec![(0.0, 5.0), (10.0, 0.0)]],
+        },
+    ];
+    let mut options = SvgOptions::new(100.0, 100.0);
+    options.color_space = ColorSpace::Cmyk;
+    options.spots = "#222=PANTONE-Black-C".parse().unwrap();
+
+    let mut out = vec![];
+    svg::write(layers.clone(), &options, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains(r#"name="cmyk" xlink:href="CoatedFOGRA39.icc""#));
+    assert!(out.contains(r#"name="PANTONE-Black-C" xlink:href="PANTONE-Black-C.icc""#));
+    assert!(out.contains("icc-color(PANTONE-Black-C, 1)"));
+
+    let mut out = vec![];
+    pdf::write(layers, &PdfOptions::new(PageSize::A4, options), &mut out).unwrap();
+    let out = String::from_utf8_lossy(&out);
+    assert!(out.contains("/Spot0 [/Separation /PANTONE-Black-C /DeviceCMYK"));
+    assert!(out.contains("/Spot0 CS 1 SCN"));
+    assert!(out.contains("0.000 0.850 0.850 0.216 K"));
+}
+
+#[tokio::test]
+async fn server_errors() {
+    let server = Fixtures::new()
+        .status(Service::Overpass, 429)
+        .serve()
+        .await
+        .unwrap();
+    let endpoints = server.endpoints();
+
+    let err = roads::search_with(&endpoints, "Testville")
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        Error::Status {
+            service: Service::Nominatim,
+            ..
+        }
+    ));
+
+    let err = roads::fetch_query_with(&endpoints, "way;out geom;")
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        Error::RateLimited {
+            service: Service::Overpass,
+            ..
+        }
+    ));
+}
+
+#[tokio::test]
+async fn budget_counts_failovers() {
+    let overloaded = Fixtures::new()
+        .status(Service::Overpass, 429)
+        .serve()
+        .await
+        .unwrap();
+    let mirror = Fixtures::new()
+        .overpass(testing::overpass_json(&testing::grid(CENTER, 2, 100.0)))
+        .serve()
+        .await
+        .unwrap();
+    let endpoints = overloaded
+        .endpoints()
+        .with_overpass_mirrors([mirror.overpass_url()]);
+    let client = |max_requests| {
+        let mode = PoliteMode {
+            max_requests: Some(max_requests),
+            per_minute: None,
+        };
+        endpoints
+            .overpass_client()
+            .with_throttle(Arc::new(Throttle::new()), mode)
+    };
+
+    let err = client(1)
+        .fetch_query("way[highway];out geom;")
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::BudgetExceeded(_)));
+    assert_eq!(overloaded.requests().len(), 1);
+    assert!(mirror.requests().is_empty());
+
+    let roads = client(2)
+        .fetch_query("way[highway];out geom;")
+        .await
+        .unwrap();
+    assert_eq!(roads.value.len(), 4);
+    assert_eq!(mirror.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn cache_per_server() {
+    let dir = std::env::temp_dir().join(format!("roads-cache-{}", std::process::id()));
+    let cache = Cache::new(&dir, Duration::from_secs(60));
+
+    let mut fetched = vec![];
+    for roads in [
+        testing::grid(CENTER, 1, 100.0),
+        testing::grid(CENTER, 2, 100.0),
+    ] {
+        let server = Fixtures::new()
+            .overpass(testing::overpass_json(&roads))
+            .serve()
+            .await
+            .unwrap();
+        let endpoints = server.endpoints().with_cache(Some(cache.clone()));
+
+        let roads = roads::fetch_query_with(&endpoints, "way;out geom;")
+            .await
+            .unwrap();
+        fetched.push(roads.value.len());
+    }
+    cache.clear().unwrap();
+
+    assert_eq!(fetched, vec![2, 4]);
+}
+
+#[tokio::test]
+async fn empty_results() {
+    let server = Fixtures::new()
+        .overpass(testing::overpass_json(&[]))
+        .serve()
+        .await
+        .unwrap();
+    let client = server.endpoints().overpass_client();
+
+    let railways = client
+        .fetch_elements("way[railway];out geom;")
+        .await
+        .unwrap();
+    assert!(railways.value.is_empty());
+
+    let entry = testing::entry("Testville", 42, CENTER, 0.01);
+    let waterways = overpass::fetch_waterways_with(&server.endpoints(), &entry)
+        .await
+        .unwrap();
+    assert!(waterways.value.is_empty());
+    assert_eq!(server.requests().len(), 2);
+
+    let err = client
+        .fetch_query("way[highway];out geom;")
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::Empty { .. }));
+}
+
+#[tokio::test]
+async fn raw_coordinates() {
+    let elements = r#"{"elements": [{"type": "way", "id": 7, "tags": {"highway": "service"},
+        "geometry": [{"lat": 45.0712345678901, "lon": 7.6812345678901},
+                     {"lat": 45.0798765432109, "lon": 7.6898765432109}]}]}"#;
+    let server = Fixtures::new().overpass(elements).serve().await.unwrap();
+    let client = server.endpoints().overpass_client();
+
+    let roads = client
+        .fetch_geo_query("way[highway];out geom;")
+        .await
+        .unwrap();
+    let bits = |points: &[LatLon]| {
+        points
+            .iter()
+            .map(|p| (p.lat.to_bits(), p.lon.to_bits()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        bits(&roads.value[0].points),
+        bits(&[
+            LatLon::new(45.0712345678901, 7.6812345678901),
+            LatLon::new(45.0798765432109, 7.6898765432109),
+        ])
+    );
+
+    let projected = client.fetch_query("way[highway];out geom;").await.unwrap();
+    let road = roads.value[0].clone().project(&WebMercator);
+    assert_eq!(projected.value[0].points, road.points);
+
+    let entry = testing::entry("Testville", 42, CENTER, 0.01);
+    let paths = fetch_roads_with(&server.endpoints(), &entry, &RoadFilter::all())
+        .await
+        .unwrap();
+    assert_eq!(bits(&paths.value[0]), bits(&roads.value[0].points));
+}
+
+#[tokio::test]
+async fn count_every_set() {
+    let counts = r#"{"elements": [
+        {"type": "count", "id": 0, "tags": {"nodes": "0", "ways": "9", "relations": "0"}},
+        {"type": "count", "id": 0, "tags": {"nodes": "0", "ways": "0", "relations": "1"}}
+    ]}"#;
+    let server = Fixtures::new().overpass(counts).serve().await.unwrap();
+
+    let count = server
+        .endpoints()
+        .overpass_client()
+        .count("way[highway];out geom;rel[boundary];out geom;")
+        .await
+        .unwrap();
+    assert_eq!((count.ways, count.relations), (9, 1));
+}
+
+#[tokio::test]
+async fn fit_budget() {
+    let server = Fixtures::new()
+        .overpass_matching("way[one]", testing::count_json(0, 10, 0))
+        .overpass_matching("way[two]", testing::count_json(0, 20, 0))
+        .overpass_matching("way[three]", testing::count_json(0, 30, 0))
+        .overpass_matching("way[four]", testing::count_json(0, 40, 0))
+        .serve()
+        .await
+        .unwrap();
+    let client = server.endpoints().overpass_client();
+    let queries = ["one", "two", "three", "four"].map(|k| format!("way[{}];out geom;", k));
+
+    assert_eq!(client.fit_budget(25, &queries).await.unwrap(), 1);
+    assert_eq!(server.requests().len(), 3);
+    assert_eq!(client.fit_budget(40, &queries).await.unwrap(), 3);
+    assert_eq!(client.fit_budget(5, &queries).await.unwrap(), 0);
+    assert_eq!(client.fit_budget(5, &[]).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn malformed_bounding_box() {
+    let entries = r#"[{"place_id": 1, "osm_type": "node", "osm_id": 2, "display_name": "Nowhere",
+        "importance": 0.5, "boundingbox": ["45.0", "45.1", "seven", "7.1"], "type": "city"}]"#;
+    let server = Fixtures::new().nominatim(entries).serve().await.unwrap();
+
+    let err = roads::search_with(&server.endpoints(), "Nowhere")
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        Error::Decode {
+            service: Service::Nominatim,
+            ..
+        }
+    ));
+}
+
+#[tokio::test]
+async fn empty_body() {
+    let server = Fixtures::new().overpass("").serve().await.unwrap();
+
+    let err = roads::fetch_query_with(&server.endpoints(), "way;out geom;")
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        Error::Decode {
+            service: Service::Overpass,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn pipeline_progress() {
+    let reported = Arc::new(std::sync::Mutex::new(vec![]));
+    let observer = {
+        let reported = Arc::clone(&reported);
+        ProgressObserver::new(move |p| reported.lock().unwrap().push(*p))
+    };
+    let pipeline = Pipeline::new()
+        .filter(|p: &[(f64, f64)]| p.len() > 1)
+        .with_progress(observer);
+
+    let layer = |paths| StyledPaths {
+        style: Style::new(Color::Rgb(0, 0, 0), 1.0),
+        paths,
+    };
+    let layers = pipeline.run_layers(vec![
+        layer(vec![vec![(0.0, 0.0), (1.0, 1.0)], vec![(2.0, 2.0)]]),
+        layer(vec![vec![(0.0, 1.0), (1.0, 0.0)]]),
+    ]);
+    assert_eq!(layers[0].paths.len() + layers[1].paths.len(), 2);
+
+    let reported = reported.lock().unwrap();
+    assert!(reported.iter().all(|p| p.stage == ProgressStage::Process));
+    assert_eq!(reported.last().unwrap().to_string(), "processing 3/3 paths");
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_round_trip() {
+    use roads::{
+        lod::LodProfile,
+        simplify::{Algorithm, Simplify},
+    };
+
+    let mut style = Style::new(Color::Rgb(200, 30, 30), 2.5);
+    style.layer = 3;
+    let layers = vec![StyledPaths {
+        style,
+        paths: vec![vec![(0.0, 0.0), (10.0, 5.0)], vec![(3.0, 4.0)]],
+    }];
+    let json = serde_json::to_string(&layers).unwrap();
+    let back = serde_json::from_str::<Vec<StyledPaths>>(&json).unwrap();
+    assert_eq!(back[0].style, layers[0].style);
+    assert_eq!(back[0].paths, layers[0].paths);
+
+    let profile = LodProfile::a4_plot();
+    let json = serde_json::to_string(&profile).unwrap();
+    assert_eq!(serde_json::from_str::<LodProfile>(&json).unwrap(), profile);
+
+    let simplify = Simplify::new(2.0).with_algorithm(Algorithm::VisvalingamWhyatt);
+    let json = serde_json::to_string(&simplify).unwrap();
+    let back = serde_json::from_str::<Simplify>(&json).unwrap();
+    assert_eq!(
+        (back.eps, back.algorithm),
+        (simplify.eps, simplify.algorithm)
+    );
+}